@@ -0,0 +1,180 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A log of the decisions made by a sequence of consensus instances.
+
+use std::collections::BTreeMap;
+
+use crate::error::InvalidStateError;
+
+/// The index of a consensus instance in a sequence of instances.
+pub type Instance = u64;
+
+/// The decided values of a sequence of consensus instances, keyed by instance.
+///
+/// Processes which fall behind can read past decisions with [`DecisionLog::get`] and
+/// [`DecisionLog::range`] to catch up. Once decisions have been captured by a checkpoint, the
+/// log can be truncated; a process which needs truncated decisions must catch up from the
+/// checkpoint instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecisionLog<V> {
+    decisions: BTreeMap<Instance, V>,
+    first_instance: Instance,
+}
+
+impl<V> DecisionLog<V>
+where
+    V: PartialEq,
+{
+    /// Constructs a new, empty `DecisionLog`.
+    pub fn new() -> Self {
+        DecisionLog {
+            decisions: BTreeMap::new(),
+            first_instance: 0,
+        }
+    }
+
+    /// Records the decision of `instance`.
+    ///
+    /// Recording the same decision again has no effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if the instance has been truncated, or if a different
+    /// value was already recorded for it; a decision can never change.
+    pub fn record(&mut self, instance: Instance, value: V) -> Result<(), InvalidStateError> {
+        if instance < self.first_instance {
+            return Err(InvalidStateError::with_message(format!(
+                "cannot record instance {}, the log is truncated before instance {}",
+                instance, self.first_instance
+            )));
+        }
+
+        match self.decisions.get(&instance) {
+            Some(decided) if decided != &value => Err(InvalidStateError::with_message(format!(
+                "instance {} already has a different decision",
+                instance
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                self.decisions.insert(instance, value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the decision of `instance`, or `None` if it is undecided or has been truncated.
+    pub fn get(&self, instance: Instance) -> Option<&V> {
+        self.decisions.get(&instance)
+    }
+
+    /// Returns the decisions of the instances from `from` up to, but not including, `to`, in
+    /// instance order. Undecided instances are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if any of the requested instances have been truncated.
+    pub fn range(
+        &self,
+        from: Instance,
+        to: Instance,
+    ) -> Result<Vec<(Instance, &V)>, InvalidStateError> {
+        if from < self.first_instance {
+            return Err(InvalidStateError::with_message(format!(
+                "cannot read from instance {}, the log is truncated before instance {}",
+                from, self.first_instance
+            )));
+        }
+
+        if from >= to {
+            return Ok(vec![]);
+        }
+
+        Ok(self
+            .decisions
+            .range(from..to)
+            .map(|(instance, value)| (*instance, value))
+            .collect())
+    }
+
+    /// Returns the first instance which has not been truncated.
+    pub fn first_instance(&self) -> Instance {
+        self.first_instance
+    }
+
+    /// Discards the decisions of every instance before `instance`, such as after a checkpoint.
+    ///
+    /// Truncating to an instance before the current first instance has no effect.
+    pub fn truncate(&mut self, instance: Instance) {
+        if instance <= self.first_instance {
+            return;
+        }
+
+        self.decisions = self.decisions.split_off(&instance);
+        self.first_instance = instance;
+    }
+}
+
+impl<V> Default for DecisionLog<V>
+where
+    V: PartialEq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the decisions of three instances can be read individually and as a range, and
+    /// that truncated instances can no longer be read.
+    #[test]
+    fn test_get_and_range() {
+        let mut log = DecisionLog::new();
+        log.record(0, "a").unwrap();
+        log.record(1, "b").unwrap();
+        log.record(2, "c").unwrap();
+
+        assert_eq!(log.get(1), Some(&"b"));
+        assert_eq!(log.get(3), None);
+        assert_eq!(
+            log.range(0, 3).unwrap(),
+            vec![(0, &"a"), (1, &"b"), (2, &"c")]
+        );
+        assert_eq!(log.range(1, 2).unwrap(), vec![(1, &"b")]);
+        assert!(log.range(2, 1).unwrap().is_empty());
+
+        log.truncate(2);
+
+        assert_eq!(log.first_instance(), 2);
+        assert_eq!(log.get(0), None);
+        assert_eq!(log.get(2), Some(&"c"));
+        assert!(log.range(1, 3).is_err());
+        assert_eq!(log.range(2, 10).unwrap(), vec![(2, &"c")]);
+        assert!(log.record(1, "b").is_err());
+    }
+
+    /// Tests that a decision can be recorded again with the same value, but not changed.
+    #[test]
+    fn test_decision_cannot_change() {
+        let mut log = DecisionLog::new();
+        log.record(0, 1).unwrap();
+        log.record(0, 1).unwrap();
+
+        assert!(log.record(0, 2).is_err());
+        assert_eq!(log.get(0), Some(&1));
+    }
+}
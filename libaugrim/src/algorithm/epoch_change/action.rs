@@ -0,0 +1,36 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::ContextUpdate;
+
+use super::{EpochChangeContext, EpochChangeMessage, Timestamp};
+
+/// An action returned by epoch change, to be performed by the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EpochChangeAction<P> {
+    /// Broadcast the message to all processes, including this one, using best-effort broadcast.
+    Broadcast(EpochChangeMessage),
+    /// Send the message to the process using a perfect link.
+    Send(P, EpochChangeMessage),
+    /// Start the epoch with the timestamp, led by the process.
+    StartEpoch(Timestamp, P),
+    /// Replace the stored context with this one.
+    UpdateContext(EpochChangeContext<P>),
+}
+
+impl<P> ContextUpdate for EpochChangeAction<P> {
+    fn is_context_update(&self) -> bool {
+        matches!(self, EpochChangeAction::UpdateContext(_))
+    }
+}
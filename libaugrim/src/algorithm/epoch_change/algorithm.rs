@@ -0,0 +1,299 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use crate::algorithm::{normalize_actions, Algorithm};
+use crate::error::InternalError;
+use crate::process::Process;
+
+use super::{
+    EpochChangeAction, EpochChangeContext, EpochChangeEvent, EpochChangeMessage, Timestamp,
+};
+
+/// The leader-based epoch change algorithm.
+pub struct EpochChangeAlgorithm<P> {
+    _process: PhantomData<P>,
+}
+
+impl<P> EpochChangeAlgorithm<P>
+where
+    P: Process,
+{
+    /// Constructs a new `EpochChangeAlgorithm`.
+    pub fn new() -> Self {
+        EpochChangeAlgorithm {
+            _process: PhantomData,
+        }
+    }
+
+    fn handle_trust(
+        &self,
+        process: P,
+        mut context: EpochChangeContext<P>,
+    ) -> Result<Vec<EpochChangeAction<P>>, InternalError> {
+        context.set_trusted(process);
+
+        let mut actions = Vec::new();
+        if process == *context.this_process() {
+            actions.push(self.broadcast_new_epoch(&mut context)?);
+        }
+
+        actions.insert(0, EpochChangeAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_deliver_new_epoch(
+        &self,
+        process: P,
+        timestamp: Timestamp,
+        mut context: EpochChangeContext<P>,
+    ) -> Result<Vec<EpochChangeAction<P>>, InternalError> {
+        if process == *context.trusted() && timestamp > context.last_timestamp() {
+            debug!("starting epoch {}", timestamp);
+            context.set_last_timestamp(timestamp);
+            Ok(vec![
+                EpochChangeAction::UpdateContext(context),
+                EpochChangeAction::StartEpoch(timestamp, process),
+            ])
+        } else {
+            Ok(vec![EpochChangeAction::Send(
+                process,
+                EpochChangeMessage::Nack,
+            )])
+        }
+    }
+
+    fn handle_deliver_nack(
+        &self,
+        mut context: EpochChangeContext<P>,
+    ) -> Result<Vec<EpochChangeAction<P>>, InternalError> {
+        if context.trusted() != context.this_process() {
+            return Ok(vec![]);
+        }
+
+        let broadcast = self.broadcast_new_epoch(&mut context)?;
+        Ok(vec![EpochChangeAction::UpdateContext(context), broadcast])
+    }
+
+    /// Moves this process's timestamp past every timestamp it has used, and returns the broadcast
+    /// of a new epoch with it.
+    ///
+    /// Timestamps are increased by the number of processes, so they stay congruent to the rank
+    /// of the process and no two processes use the same timestamp.
+    fn broadcast_new_epoch(
+        &self,
+        context: &mut EpochChangeContext<P>,
+    ) -> Result<EpochChangeAction<P>, InternalError> {
+        let timestamp = context
+            .timestamp()
+            .checked_add(context.processes().len() as Timestamp)
+            .ok_or_else(|| {
+                InternalError::with_message("epoch timestamps have been exhausted".into())
+            })?;
+        context.set_timestamp(timestamp);
+
+        Ok(EpochChangeAction::Broadcast(EpochChangeMessage::NewEpoch(
+            timestamp,
+        )))
+    }
+}
+
+impl<P> Default for EpochChangeAlgorithm<P>
+where
+    P: Process,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> Algorithm<P> for EpochChangeAlgorithm<P>
+where
+    P: Process,
+{
+    type Event = EpochChangeEvent<P>;
+    type Action = EpochChangeAction<P>;
+    type Context = EpochChangeContext<P>;
+
+    fn event(
+        &self,
+        event: Self::Event,
+        context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError> {
+        let actions = match event {
+            EpochChangeEvent::Trust(process) => self.handle_trust(process, context),
+            EpochChangeEvent::Deliver(process, EpochChangeMessage::NewEpoch(timestamp)) => {
+                self.handle_deliver_new_epoch(process, timestamp, context)
+            }
+            EpochChangeEvent::Deliver(_, EpochChangeMessage::Nack) => {
+                self.handle_deliver_nack(context)
+            }
+        }?;
+
+        Ok(normalize_actions(actions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    type TestAlgorithm = EpochChangeAlgorithm<TestProcess>;
+    type TestContext = EpochChangeContext<TestProcess>;
+    type TestAction = EpochChangeAction<TestProcess>;
+
+    fn processes() -> Vec<TestProcess> {
+        (1..=3).map(|id| TestProcess { id }).collect()
+    }
+
+    /// Handles `event`, returning the context from the `UpdateContext` action, if any, and the
+    /// other actions.
+    fn handle(
+        event: EpochChangeEvent<TestProcess>,
+        context: TestContext,
+    ) -> (TestContext, Vec<TestAction>) {
+        let mut actions = TestAlgorithm::new()
+            .event(event, context.clone())
+            .expect("failed event");
+        match actions.first() {
+            Some(EpochChangeAction::UpdateContext(updated)) => {
+                let updated = updated.clone();
+                actions.remove(0);
+                (updated, actions)
+            }
+            _ => (context, actions),
+        }
+    }
+
+    /// Tests that as leadership moves from p2 to p3, every process starts an epoch led by each
+    /// new leader, with increasing timestamps.
+    #[test]
+    fn test_leadership_change_starts_new_epoch() {
+        let [p2, p3] = [processes()[1], processes()[2]];
+        let mut contexts: Vec<TestContext> = processes()
+            .into_iter()
+            .map(|process| TestContext::new(process, processes()).unwrap())
+            .collect();
+
+        let mut started = Vec::new();
+        for leader in [p2, p3] {
+            // Every process trusts the new leader, which broadcasts a new epoch
+            let mut broadcasts = Vec::new();
+            for context in contexts.iter_mut() {
+                let (updated, actions) = handle(EpochChangeEvent::Trust(leader), context.clone());
+                *context = updated;
+                broadcasts.extend(actions);
+            }
+            let timestamp = match broadcasts.as_slice() {
+                [EpochChangeAction::Broadcast(EpochChangeMessage::NewEpoch(timestamp))] => {
+                    *timestamp
+                }
+                _ => panic!("expected one new epoch broadcast: {:?}", broadcasts),
+            };
+
+            for (index, context) in contexts.iter_mut().enumerate() {
+                let (updated, actions) = handle(
+                    EpochChangeEvent::Deliver(leader, EpochChangeMessage::NewEpoch(timestamp)),
+                    context.clone(),
+                );
+                *context = updated;
+                assert_eq!(
+                    actions,
+                    vec![EpochChangeAction::StartEpoch(timestamp, leader)],
+                    "process {} did not start the epoch",
+                    index + 1
+                );
+            }
+            started.push((timestamp, leader));
+        }
+
+        // p2's first timestamp is its rank plus the number of processes, and p3's likewise
+        assert_eq!(started, vec![(5, p2), (6, p3)]);
+        assert!(contexts.iter().all(|context| context.last_timestamp() == 6));
+        assert_eq!(contexts[0].trusted(), &p3);
+        assert_eq!(contexts[0].timestamp(), 1);
+    }
+
+    /// Tests that a new epoch from a process which is not trusted, or with a timestamp which is not
+    /// higher than the last epoch started, is answered with a `Nack` and not started.
+    #[test]
+    fn test_nack_untrusted_or_stale_epoch() {
+        let [p1, p2, p3] = [processes()[0], processes()[1], processes()[2]];
+        let context = TestContext::new(p1, processes()).unwrap();
+
+        let (context, actions) = handle(
+            EpochChangeEvent::Deliver(p2, EpochChangeMessage::NewEpoch(5)),
+            context,
+        );
+        assert_eq!(
+            actions,
+            vec![EpochChangeAction::Send(p2, EpochChangeMessage::Nack)]
+        );
+        assert_eq!(context.last_timestamp(), 0);
+
+        let (context, _) = handle(EpochChangeEvent::Trust(p3), context);
+        let (context, _) = handle(
+            EpochChangeEvent::Deliver(p3, EpochChangeMessage::NewEpoch(6)),
+            context,
+        );
+        let (context, actions) = handle(
+            EpochChangeEvent::Deliver(p3, EpochChangeMessage::NewEpoch(3)),
+            context,
+        );
+        assert_eq!(
+            actions,
+            vec![EpochChangeAction::Send(p3, EpochChangeMessage::Nack)]
+        );
+        assert_eq!(context.last_timestamp(), 6);
+    }
+
+    /// Tests that a leader which receives a `Nack` broadcasts a new epoch with a higher
+    /// timestamp, and that a process which does not trust itself as the leader ignores it.
+    #[test]
+    fn test_leader_retries_after_nack() {
+        let [p1, p2, p3] = [processes()[0], processes()[1], processes()[2]];
+
+        let (context, _) = handle(
+            EpochChangeEvent::Trust(p2),
+            TestContext::new(p2, processes()).unwrap(),
+        );
+        assert_eq!(context.timestamp(), 5);
+
+        let (context, actions) = handle(
+            EpochChangeEvent::Deliver(p1, EpochChangeMessage::Nack),
+            context,
+        );
+        assert_eq!(
+            actions,
+            vec![EpochChangeAction::Broadcast(EpochChangeMessage::NewEpoch(
+                8
+            ))]
+        );
+        assert_eq!(context.timestamp(), 8);
+
+        let (_, actions) = handle(
+            EpochChangeEvent::Deliver(p2, EpochChangeMessage::Nack),
+            TestContext::new(p3, processes()).unwrap(),
+        );
+        assert!(actions.is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::error::InvalidStateError;
+use crate::process::Process;
+
+use super::Timestamp;
+
+/// The state of epoch change at a single process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochChangeContext<P> {
+    processes: Vec<P>,
+    this_process: P,
+    trusted: P,
+    last_timestamp: Timestamp,
+    timestamp: Timestamp,
+}
+
+impl<P> EpochChangeContext<P>
+where
+    P: Process,
+{
+    /// Constructs the initial context for `this_process`, for the given set of processes.
+    ///
+    /// The processes are ranked in the order given, starting at 1; every process must be given
+    /// the same processes in the same order. The first process is trusted as the leader of the
+    /// initial epoch, which has timestamp 0, and this process's timestamps start at its rank.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `this_process` is not in `processes`.
+    pub fn new(this_process: P, processes: Vec<P>) -> Result<Self, InvalidStateError> {
+        let rank = processes
+            .iter()
+            .position(|process| *process == this_process)
+            .ok_or_else(|| {
+                InvalidStateError::with_message(
+                    "this process is not in the set of processes".into(),
+                )
+            })?
+            + 1;
+
+        Ok(EpochChangeContext {
+            trusted: processes[0],
+            processes,
+            this_process,
+            last_timestamp: 0,
+            timestamp: rank as Timestamp,
+        })
+    }
+
+    /// Returns the processes, in the order of their rank.
+    pub fn processes(&self) -> &Vec<P> {
+        &self.processes
+    }
+
+    /// Returns the process this context belongs to.
+    pub fn this_process(&self) -> &P {
+        &self.this_process
+    }
+
+    /// Returns the process currently trusted as the leader.
+    pub fn trusted(&self) -> &P {
+        &self.trusted
+    }
+
+    /// Sets the process trusted as the leader.
+    pub fn set_trusted(&mut self, trusted: P) {
+        self.trusted = trusted
+    }
+
+    /// Returns the timestamp of the last epoch started.
+    pub fn last_timestamp(&self) -> Timestamp {
+        self.last_timestamp
+    }
+
+    /// Sets the timestamp of the last epoch started.
+    pub fn set_last_timestamp(&mut self, last_timestamp: Timestamp) {
+        self.last_timestamp = last_timestamp
+    }
+
+    /// Returns the timestamp of the last epoch this process broadcast as the leader, or its rank
+    /// if it has not broadcast one.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Sets the timestamp of the last epoch this process broadcast as the leader.
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) {
+        self.timestamp = timestamp
+    }
+}
@@ -0,0 +1,25 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::EpochChangeMessage;
+
+/// An event handled by epoch change.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EpochChangeEvent<P> {
+    /// A message from the process was delivered, by the best-effort broadcast for `NewEpoch` or
+    /// by a perfect link for `Nack`.
+    Deliver(P, EpochChangeMessage),
+    /// The process is trusted as the leader by the eventual leader detector.
+    Trust(P),
+}
@@ -0,0 +1,30 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::message::Message;
+
+use super::Timestamp;
+
+/// A message exchanged between processes running epoch change.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EpochChangeMessage {
+    /// The sender, which trusts itself as the leader, starts an epoch with the timestamp.
+    NewEpoch(Timestamp),
+    /// The receiver of a `NewEpoch` did not start the epoch, because it does not trust the sender
+    /// as the leader or has seen a higher timestamp.
+    Nack,
+}
+
+impl Message for EpochChangeMessage {}
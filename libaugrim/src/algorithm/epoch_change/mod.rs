@@ -0,0 +1,40 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Epoch change.
+//!
+//! Implementation of the "Leader-Based Epoch-Change" algorithm, which divides the execution of
+//! an epoch-based consensus algorithm into epochs, each with a single leader. It relies on an
+//! eventual leader detector (Ω), a best-effort broadcast and perfect links.
+//!
+//! Whenever a process is trusted as the leader by the leader detector, it broadcasts a new epoch
+//! with a timestamp higher than any it has used. Every process which also trusts it, and has not
+//! seen a higher timestamp, starts the epoch; the others reply with a `Nack`, which makes the
+//! leader try again with a higher timestamp.
+
+mod action;
+mod algorithm;
+mod context;
+mod event;
+mod message;
+
+pub use action::EpochChangeAction;
+pub use algorithm::EpochChangeAlgorithm;
+pub use context::EpochChangeContext;
+pub use event::EpochChangeEvent;
+pub use message::EpochChangeMessage;
+
+/// The timestamp of an epoch. Epochs started by a process have increasing timestamps, and no two
+/// processes use the same timestamp.
+pub type Timestamp = u64;
@@ -0,0 +1,39 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::ContextUpdate;
+
+use super::{EpochConsensusContext, EpochConsensusMessage, EpochState};
+
+/// An action returned by epoch consensus, to be performed by the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EpochConsensusAction<P, V> {
+    /// The epoch was aborted with the state, which is used to construct the instance of the next
+    /// epoch.
+    Aborted(EpochState<V>),
+    /// Broadcast the message to all processes, including this one, using best-effort broadcast.
+    Broadcast(EpochConsensusMessage<V>),
+    /// Decide the value.
+    Decide(V),
+    /// Send the message to the process using a perfect link.
+    Send(P, EpochConsensusMessage<V>),
+    /// Replace the stored context with this one.
+    UpdateContext(EpochConsensusContext<P, V>),
+}
+
+impl<P, V> ContextUpdate for EpochConsensusAction<P, V> {
+    fn is_context_update(&self) -> bool {
+        matches!(self, EpochConsensusAction::UpdateContext(_))
+    }
+}
@@ -0,0 +1,457 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use crate::algorithm::{normalize_actions, Algorithm, Value};
+use crate::error::InternalError;
+use crate::process::Process;
+
+use super::{
+    EpochConsensusAction, EpochConsensusContext, EpochConsensusEvent, EpochConsensusMessage,
+    EpochState,
+};
+
+/// The read/write epoch consensus algorithm.
+pub struct EpochConsensusAlgorithm<P, V> {
+    _process: PhantomData<P>,
+    _value: PhantomData<V>,
+}
+
+impl<P, V> EpochConsensusAlgorithm<P, V>
+where
+    P: Process,
+    V: Value,
+{
+    /// Constructs a new `EpochConsensusAlgorithm`.
+    pub fn new() -> Self {
+        EpochConsensusAlgorithm {
+            _process: PhantomData,
+            _value: PhantomData,
+        }
+    }
+
+    fn handle_abort(
+        &self,
+        mut context: EpochConsensusContext<P, V>,
+    ) -> Result<Vec<EpochConsensusAction<P, V>>, InternalError> {
+        context.set_aborted(true);
+        let state = context.state().clone();
+
+        Ok(vec![
+            EpochConsensusAction::UpdateContext(context),
+            EpochConsensusAction::Aborted(state),
+        ])
+    }
+
+    fn handle_propose(
+        &self,
+        value: V,
+        mut context: EpochConsensusContext<P, V>,
+    ) -> Result<Vec<EpochConsensusAction<P, V>>, InternalError> {
+        if !context.is_leader() {
+            return Err(InternalError::with_message(
+                "only the leader of the epoch can propose".into(),
+            ));
+        }
+
+        context.set_proposal(Some(value));
+
+        Ok(vec![
+            EpochConsensusAction::UpdateContext(context),
+            EpochConsensusAction::Broadcast(EpochConsensusMessage::Read),
+        ])
+    }
+
+    fn handle_deliver_read(
+        &self,
+        process: P,
+        context: EpochConsensusContext<P, V>,
+    ) -> Result<Vec<EpochConsensusAction<P, V>>, InternalError> {
+        self.check_from_leader(&process, &context)?;
+
+        Ok(vec![EpochConsensusAction::Send(
+            process,
+            EpochConsensusMessage::State(context.state().clone()),
+        )])
+    }
+
+    fn handle_deliver_state(
+        &self,
+        process: P,
+        state: EpochState<V>,
+        mut context: EpochConsensusContext<P, V>,
+    ) -> Result<Vec<EpochConsensusAction<P, V>>, InternalError> {
+        self.check_leader_from(&process, &context)?;
+
+        if !context.states().iter().any(|(p, _)| *p == process) {
+            context.states_mut().push((process, state));
+        }
+
+        let mut actions = Vec::new();
+        if context.is_majority(context.states().len()) {
+            // Adopt the value written in the latest epoch, if any value has been written
+            let highest = context
+                .states_mut()
+                .drain(..)
+                .map(|(_, state)| state)
+                .filter(|state| state.value().is_some())
+                .reduce(|a, b| if b.timestamp() > a.timestamp() { b } else { a });
+            if let Some(state) = highest {
+                context.set_proposal(state.into_value());
+            }
+
+            let proposal = context.proposal().clone().ok_or_else(|| {
+                InternalError::with_message("states read before the leader proposed".into())
+            })?;
+            actions.push(EpochConsensusAction::Broadcast(
+                EpochConsensusMessage::Write(proposal),
+            ));
+        }
+
+        actions.insert(0, EpochConsensusAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_deliver_write(
+        &self,
+        process: P,
+        value: V,
+        mut context: EpochConsensusContext<P, V>,
+    ) -> Result<Vec<EpochConsensusAction<P, V>>, InternalError> {
+        self.check_from_leader(&process, &context)?;
+
+        context.set_state(EpochState::new(context.timestamp(), Some(value)));
+
+        Ok(vec![
+            EpochConsensusAction::UpdateContext(context),
+            EpochConsensusAction::Send(process, EpochConsensusMessage::Accept),
+        ])
+    }
+
+    fn handle_deliver_accept(
+        &self,
+        process: P,
+        mut context: EpochConsensusContext<P, V>,
+    ) -> Result<Vec<EpochConsensusAction<P, V>>, InternalError> {
+        self.check_leader_from(&process, &context)?;
+
+        if !context.accepted().contains(&process) {
+            context.accepted_mut().push(process);
+        }
+
+        let mut actions = Vec::new();
+        if context.is_majority(context.accepted().len()) {
+            context.accepted_mut().clear();
+
+            let proposal = context.proposal().clone().ok_or_else(|| {
+                InternalError::with_message("value accepted before the leader proposed".into())
+            })?;
+            debug!("decided in epoch {}", context.timestamp());
+            actions.push(EpochConsensusAction::Broadcast(
+                EpochConsensusMessage::Decided(proposal),
+            ));
+        }
+
+        actions.insert(0, EpochConsensusAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_deliver_decided(
+        &self,
+        process: P,
+        value: V,
+        context: EpochConsensusContext<P, V>,
+    ) -> Result<Vec<EpochConsensusAction<P, V>>, InternalError> {
+        self.check_from_leader(&process, &context)?;
+
+        Ok(vec![EpochConsensusAction::Decide(value)])
+    }
+
+    /// Checks that a message broadcast by the leader was delivered from the leader.
+    fn check_from_leader(
+        &self,
+        process: &P,
+        context: &EpochConsensusContext<P, V>,
+    ) -> Result<(), InternalError> {
+        if process != context.leader() {
+            return Err(InternalError::with_message(
+                "leader message delivered from a process which is not the leader".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a reply to the leader was delivered to the leader, from one of the processes.
+    fn check_leader_from(
+        &self,
+        process: &P,
+        context: &EpochConsensusContext<P, V>,
+    ) -> Result<(), InternalError> {
+        if !context.is_leader() {
+            return Err(InternalError::with_message(
+                "reply to the leader delivered to a process which is not the leader".into(),
+            ));
+        }
+        if !context.processes().contains(process) {
+            return Err(InternalError::with_message(
+                "reply delivered from an unknown process".into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<P, V> Default for EpochConsensusAlgorithm<P, V>
+where
+    P: Process,
+    V: Value,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, V> Algorithm<P> for EpochConsensusAlgorithm<P, V>
+where
+    P: Process,
+    V: Value,
+{
+    type Event = EpochConsensusEvent<P, V>;
+    type Action = EpochConsensusAction<P, V>;
+    type Context = EpochConsensusContext<P, V>;
+
+    fn event(
+        &self,
+        event: Self::Event,
+        context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError> {
+        if context.aborted() {
+            return Ok(vec![]);
+        }
+
+        let actions = match event {
+            EpochConsensusEvent::Abort => self.handle_abort(context),
+            EpochConsensusEvent::Deliver(process, EpochConsensusMessage::Read) => {
+                self.handle_deliver_read(process, context)
+            }
+            EpochConsensusEvent::Deliver(process, EpochConsensusMessage::State(state)) => {
+                self.handle_deliver_state(process, state, context)
+            }
+            EpochConsensusEvent::Deliver(process, EpochConsensusMessage::Write(value)) => {
+                self.handle_deliver_write(process, value, context)
+            }
+            EpochConsensusEvent::Deliver(process, EpochConsensusMessage::Accept) => {
+                self.handle_deliver_accept(process, context)
+            }
+            EpochConsensusEvent::Deliver(process, EpochConsensusMessage::Decided(value)) => {
+                self.handle_deliver_decided(process, value, context)
+            }
+            EpochConsensusEvent::Propose(value) => self.handle_propose(value, context),
+        }?;
+
+        Ok(normalize_actions(actions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+
+    use crate::algorithm::epoch_change::Timestamp;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct TestValue(u64);
+
+    impl Value for TestValue {}
+
+    type TestContext = EpochConsensusContext<TestProcess, TestValue>;
+    type TestEvent = EpochConsensusEvent<TestProcess, TestValue>;
+    type TestMessage = EpochConsensusMessage<TestValue>;
+
+    fn processes() -> Vec<TestProcess> {
+        (1..=3).map(|id| TestProcess { id }).collect()
+    }
+
+    /// The instances of a single epoch at every process, with the messages in flight between
+    /// them.
+    struct Epoch {
+        contexts: Vec<TestContext>,
+        decisions: Vec<Option<TestValue>>,
+        aborted: Vec<Option<EpochState<TestValue>>>,
+        queue: VecDeque<(TestProcess, TestProcess, TestMessage)>,
+    }
+
+    impl Epoch {
+        fn new(
+            timestamp: Timestamp,
+            leader: TestProcess,
+            states: Vec<EpochState<TestValue>>,
+        ) -> Self {
+            let contexts = processes()
+                .into_iter()
+                .zip(states)
+                .map(|(process, state)| {
+                    TestContext::new(process, processes(), timestamp, leader, state).unwrap()
+                })
+                .collect();
+
+            Epoch {
+                contexts,
+                decisions: vec![None; 3],
+                aborted: vec![None; 3],
+                queue: VecDeque::new(),
+            }
+        }
+
+        /// Handles the event at the process, queueing the messages it sends.
+        fn event(&mut self, process: TestProcess, event: TestEvent) {
+            let index = processes().iter().position(|p| *p == process).unwrap();
+            let actions = EpochConsensusAlgorithm::new()
+                .event(event, self.contexts[index].clone())
+                .expect("failed event");
+
+            for action in actions {
+                match action {
+                    EpochConsensusAction::Aborted(state) => self.aborted[index] = Some(state),
+                    EpochConsensusAction::Broadcast(message) => {
+                        for to in processes() {
+                            self.queue.push_back((process, to, message.clone()));
+                        }
+                    }
+                    EpochConsensusAction::Decide(value) => {
+                        assert_eq!(self.decisions[index], None, "decided twice");
+                        self.decisions[index] = Some(value);
+                    }
+                    EpochConsensusAction::Send(to, message) => {
+                        self.queue.push_back((process, to, message))
+                    }
+                    EpochConsensusAction::UpdateContext(context) => self.contexts[index] = context,
+                }
+            }
+        }
+
+        /// Delivers the queued messages, including those sent as a result, except the messages
+        /// for which `drop` returns true.
+        fn deliver_all<F>(&mut self, drop: F)
+        where
+            F: Fn(&TestProcess, &TestProcess, &TestMessage) -> bool,
+        {
+            while let Some((from, to, message)) = self.queue.pop_front() {
+                if !drop(&from, &to, &message) {
+                    self.event(to, EpochConsensusEvent::Deliver(from, message));
+                }
+            }
+        }
+
+        /// Aborts the epoch at every process, returning their states.
+        fn abort(mut self) -> Vec<EpochState<TestValue>> {
+            for process in processes() {
+                self.event(process, EpochConsensusEvent::Abort);
+            }
+
+            self.aborted
+                .into_iter()
+                .map(|state| state.expect("not aborted"))
+                .collect()
+        }
+    }
+
+    /// Tests that every process decides the leader's proposal when no messages are lost, and
+    /// accepts it with the timestamp of the epoch.
+    #[test]
+    fn test_decide_in_single_epoch() {
+        let p1 = processes()[0];
+        let mut epoch = Epoch::new(1, p1, vec![EpochState::default(); 3]);
+
+        epoch.event(p1, EpochConsensusEvent::Propose(TestValue(1)));
+        epoch.deliver_all(|_, _, _| false);
+
+        assert_eq!(epoch.decisions, vec![Some(TestValue(1)); 3]);
+        assert!(epoch
+            .contexts
+            .iter()
+            .all(|context| context.state() == &EpochState::new(1, Some(TestValue(1)))));
+    }
+
+    /// Tests that a value accepted by a single process in an aborted epoch is carried over in its
+    /// state, and is decided in the next epoch instead of the new leader's proposal.
+    #[test]
+    fn test_state_carried_over_abort() {
+        let [p1, p2, _] = [processes()[0], processes()[1], processes()[2]];
+
+        // Only p2 receives the write, so there is no majority to decide
+        let mut epoch = Epoch::new(1, p1, vec![EpochState::default(); 3]);
+        epoch.event(p1, EpochConsensusEvent::Propose(TestValue(1)));
+        epoch.deliver_all(|_, to, message| {
+            matches!(message, EpochConsensusMessage::Write(_)) && *to != p2
+        });
+        assert_eq!(epoch.decisions, vec![None; 3]);
+
+        let states = epoch.abort();
+        assert_eq!(
+            states,
+            vec![
+                EpochState::default(),
+                EpochState::new(1, Some(TestValue(1))),
+                EpochState::default(),
+            ]
+        );
+
+        let mut epoch = Epoch::new(5, p2, states);
+        epoch.event(p2, EpochConsensusEvent::Propose(TestValue(2)));
+        epoch.deliver_all(|_, _, _| false);
+
+        assert_eq!(epoch.decisions, vec![Some(TestValue(1)); 3]);
+        assert_eq!(
+            epoch.contexts[0].state(),
+            &EpochState::new(5, Some(TestValue(1)))
+        );
+    }
+
+    /// Tests that an aborted instance handles no further events.
+    #[test]
+    fn test_no_events_after_abort() {
+        let p1 = processes()[0];
+        let mut epoch = Epoch::new(1, p1, vec![EpochState::default(); 3]);
+
+        epoch.event(p1, EpochConsensusEvent::Abort);
+        epoch.event(p1, EpochConsensusEvent::Propose(TestValue(1)));
+
+        assert!(epoch.queue.is_empty());
+        assert!(epoch.contexts[0].aborted());
+    }
+
+    /// Tests that only the leader of the epoch can propose.
+    #[test]
+    fn test_propose_not_leader() {
+        let [p1, p2, _] = [processes()[0], processes()[1], processes()[2]];
+        let context = TestContext::new(p2, processes(), 1, p1, EpochState::default()).unwrap();
+
+        assert!(EpochConsensusAlgorithm::new()
+            .event(EpochConsensusEvent::Propose(TestValue(2)), context)
+            .is_err());
+    }
+}
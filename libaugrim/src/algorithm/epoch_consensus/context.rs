@@ -0,0 +1,160 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::epoch_change::Timestamp;
+use crate::error::InvalidStateError;
+use crate::process::Process;
+
+use super::EpochState;
+
+/// The state of an epoch consensus instance at a single process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochConsensusContext<P, V> {
+    processes: Vec<P>,
+    this_process: P,
+    timestamp: Timestamp,
+    leader: P,
+    state: EpochState<V>,
+    proposal: Option<V>,
+    states: Vec<(P, EpochState<V>)>,
+    accepted: Vec<P>,
+    aborted: bool,
+}
+
+impl<P, V> EpochConsensusContext<P, V>
+where
+    P: Process,
+    V: Clone,
+{
+    /// Constructs the initial context for `this_process`, for the epoch with the timestamp and
+    /// leader.
+    ///
+    /// `state` is the state returned when the previous epoch was aborted, or the default state
+    /// for the first epoch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `this_process` or `leader` is not in `processes`.
+    pub fn new(
+        this_process: P,
+        processes: Vec<P>,
+        timestamp: Timestamp,
+        leader: P,
+        state: EpochState<V>,
+    ) -> Result<Self, InvalidStateError> {
+        if !processes.contains(&this_process) {
+            return Err(InvalidStateError::with_message(
+                "this process is not in the set of processes".into(),
+            ));
+        }
+        if !processes.contains(&leader) {
+            return Err(InvalidStateError::with_message(
+                "the leader is not in the set of processes".into(),
+            ));
+        }
+
+        Ok(EpochConsensusContext {
+            processes,
+            this_process,
+            timestamp,
+            leader,
+            state,
+            proposal: None,
+            states: Vec::new(),
+            accepted: Vec::new(),
+            aborted: false,
+        })
+    }
+
+    /// Returns the processes taking part in the epoch.
+    pub fn processes(&self) -> &Vec<P> {
+        &self.processes
+    }
+
+    /// Returns the process this context belongs to.
+    pub fn this_process(&self) -> &P {
+        &self.this_process
+    }
+
+    /// Returns the timestamp of the epoch.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the leader of the epoch.
+    pub fn leader(&self) -> &P {
+        &self.leader
+    }
+
+    /// Returns the value last accepted by this process, with the timestamp of its epoch.
+    pub fn state(&self) -> &EpochState<V> {
+        &self.state
+    }
+
+    /// Sets the value last accepted by this process, with the timestamp of its epoch.
+    pub fn set_state(&mut self, state: EpochState<V>) {
+        self.state = state
+    }
+
+    /// Returns the value the leader will write, which is its proposal until it adopts a value
+    /// read from the other processes.
+    pub fn proposal(&self) -> &Option<V> {
+        &self.proposal
+    }
+
+    /// Sets the value the leader will write.
+    pub fn set_proposal(&mut self, proposal: Option<V>) {
+        self.proposal = proposal
+    }
+
+    /// Returns the states read by the leader.
+    pub fn states(&self) -> &Vec<(P, EpochState<V>)> {
+        &self.states
+    }
+
+    /// Returns the states read by the leader, for updating.
+    pub fn states_mut(&mut self) -> &mut Vec<(P, EpochState<V>)> {
+        &mut self.states
+    }
+
+    /// Returns the processes which have accepted the value written by the leader.
+    pub fn accepted(&self) -> &Vec<P> {
+        &self.accepted
+    }
+
+    /// Returns the processes which have accepted the value written by the leader, for updating.
+    pub fn accepted_mut(&mut self) -> &mut Vec<P> {
+        &mut self.accepted
+    }
+
+    /// Returns whether the epoch has been aborted.
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Sets whether the epoch has been aborted.
+    pub fn set_aborted(&mut self, aborted: bool) {
+        self.aborted = aborted
+    }
+
+    /// Returns whether this process is the leader of the epoch.
+    pub fn is_leader(&self) -> bool {
+        self.this_process == self.leader
+    }
+
+    /// Returns whether `count` processes are a majority of the processes.
+    pub fn is_majority(&self, count: usize) -> bool {
+        count * 2 > self.processes.len()
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::message::Message;
+
+use super::EpochState;
+
+/// A message exchanged between processes running epoch consensus.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EpochConsensusMessage<V> {
+    /// Broadcast by the leader to read the state of every process.
+    Read,
+    /// The state of the sender, sent to the leader in reply to `Read`.
+    State(EpochState<V>),
+    /// Broadcast by the leader to write the value to every process.
+    Write(V),
+    /// Sent to the leader once the sender has accepted the written value.
+    Accept,
+    /// Broadcast by the leader once a majority has accepted the value.
+    Decided(V),
+}
+
+impl<V> Message for EpochConsensusMessage<V> {}
@@ -0,0 +1,41 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Epoch consensus.
+//!
+//! Implementation of the "Read/Write Epoch Consensus" algorithm, the core of Paxos. An instance
+//! runs for a single epoch, as started by [epoch change](super::epoch_change), with the
+//! timestamp and leader of that epoch. It relies on a best-effort broadcast and perfect links.
+//!
+//! The leader first reads the state of a majority of processes and adopts the value written with
+//! the highest timestamp, if any, instead of its own proposal. It then writes the value to every
+//! process, and once a majority has accepted it, the value is decided. When the epoch is aborted,
+//! the instance returns its state, which is used to construct the instance of the next epoch.
+//!
+//! The messages of an instance carry no timestamp, so the caller must only deliver to an instance
+//! the messages sent by the instance of the same epoch.
+
+mod action;
+mod algorithm;
+mod context;
+mod event;
+mod message;
+mod state;
+
+pub use action::EpochConsensusAction;
+pub use algorithm::EpochConsensusAlgorithm;
+pub use context::EpochConsensusContext;
+pub use event::EpochConsensusEvent;
+pub use message::EpochConsensusMessage;
+pub use state::EpochState;
@@ -0,0 +1,56 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::epoch_change::Timestamp;
+
+/// The value last accepted by a process, along with the timestamp of the epoch in which it was
+/// accepted.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EpochState<V> {
+    timestamp: Timestamp,
+    value: Option<V>,
+}
+
+impl<V> EpochState<V> {
+    /// Constructs a new `EpochState`.
+    pub fn new(timestamp: Timestamp, value: Option<V>) -> Self {
+        EpochState { timestamp, value }
+    }
+
+    /// Returns the timestamp of the epoch in which the value was accepted.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the value accepted, or `None` if no value has been accepted.
+    pub fn value(&self) -> &Option<V> {
+        &self.value
+    }
+
+    /// Consumes the state, returning the value accepted.
+    pub fn into_value(self) -> Option<V> {
+        self.value
+    }
+}
+
+impl<V> Default for EpochState<V> {
+    /// Returns the state of a process which has not accepted a value, with timestamp 0.
+    fn default() -> Self {
+        EpochState {
+            timestamp: 0,
+            value: None,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The number of processes needed to tolerate a number of faulty processes.
+
+use crate::error::InvalidStateError;
+use crate::process::Process;
+
+/// The assumptions an algorithm makes about faulty processes, which determine how many processes
+/// it needs to tolerate a given number of faults.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultModel {
+    /// Processes fail by crashing, and crashes are detected by a perfect failure detector, as in
+    /// flooding consensus.
+    CrashStop,
+    /// Processes fail by crashing, and progress relies on a majority of correct processes rather
+    /// than on detecting crashes.
+    Quorum,
+    /// Faulty processes may behave arbitrarily.
+    Byzantine,
+}
+
+/// Returns the minimum number of processes needed to tolerate `f` faulty processes under
+/// `fault_model`: `f + 1` for crash-stop, `2f + 1` for quorum-based and `3f + 1` for Byzantine
+/// algorithms.
+pub fn min_cluster_size(fault_model: FaultModel, f: usize) -> usize {
+    let multiplier = match fault_model {
+        FaultModel::CrashStop => 1,
+        FaultModel::Quorum => 2,
+        FaultModel::Byzantine => 3,
+    };
+
+    f.saturating_mul(multiplier).saturating_add(1)
+}
+
+/// Checks that `processes` are enough to tolerate `f` faulty processes under `fault_model`.
+///
+/// # Errors
+///
+/// Returns an `InvalidStateError` if there are fewer processes than
+/// [`min_cluster_size`] requires.
+pub fn validate_membership<P: Process>(
+    fault_model: FaultModel,
+    f: usize,
+    processes: &[P],
+) -> Result<(), InvalidStateError> {
+    let required = min_cluster_size(fault_model, f);
+    if processes.len() < required {
+        return Err(InvalidStateError::with_message(format!(
+            "{} processes cannot tolerate {} faulty processes under the {:?} fault model, which \
+             requires at least {}",
+            processes.len(),
+            f,
+            fault_model,
+            required
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Tests the minimum cluster size of each fault model.
+    #[test]
+    fn test_min_cluster_size() {
+        for f in 0..4 {
+            assert_eq!(min_cluster_size(FaultModel::CrashStop, f), f + 1);
+            assert_eq!(min_cluster_size(FaultModel::Quorum, f), 2 * f + 1);
+            assert_eq!(min_cluster_size(FaultModel::Byzantine, f), 3 * f + 1);
+        }
+        assert_eq!(
+            min_cluster_size(FaultModel::Byzantine, usize::MAX),
+            usize::MAX
+        );
+    }
+
+    /// Tests that two processes can tolerate one crash-stop fault but not one fault under the
+    /// quorum model, and that a membership of exactly the minimum size is accepted.
+    #[test]
+    fn test_validate_membership() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+
+        assert!(validate_membership(FaultModel::CrashStop, 1, &processes).is_ok());
+
+        let err = validate_membership(FaultModel::Quorum, 1, &processes)
+            .expect_err("undersized membership was accepted");
+        assert_eq!(
+            err.to_string(),
+            "2 processes cannot tolerate 1 faulty processes under the Quorum fault model, which \
+             requires at least 3"
+        );
+
+        let processes: Vec<TestProcess> = (1..=4).map(|id| TestProcess { id }).collect();
+        assert!(validate_membership(FaultModel::Byzantine, 1, &processes).is_ok());
+        assert!(validate_membership(FaultModel::Byzantine, 2, &processes).is_err());
+    }
+}
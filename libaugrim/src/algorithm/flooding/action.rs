@@ -0,0 +1,91 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::{ContextUpdate, TraceId};
+use crate::runtime::{Effect, RuntimeAction};
+
+use super::{FloodingContext, FloodingMessage};
+
+/// An action returned by flooding consensus, to be performed by the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FloodingAction<P, V> {
+    /// Broadcast the message to all processes, including this one, using best-effort broadcast.
+    Broadcast(FloodingMessage<V>),
+    /// The value has been decided, along with the trace id of the consensus.
+    Decide(V, Option<TraceId>),
+    /// Replace the stored context with this one.
+    UpdateContext(FloodingContext<P, V>),
+}
+
+impl<P, V> ContextUpdate for FloodingAction<P, V> {
+    fn is_context_update(&self) -> bool {
+        matches!(self, FloodingAction::UpdateContext(_))
+    }
+}
+
+impl<P, V> RuntimeAction for FloodingAction<P, V> {
+    type Context = FloodingContext<P, V>;
+    type Message = FloodingMessage<V>;
+    type Value = V;
+
+    fn into_effect(self) -> Effect<Self::Context, Self::Message, Self::Value, Self> {
+        match self {
+            FloodingAction::Broadcast(message) => Effect::Broadcast(message),
+            FloodingAction::Decide(value, _) => Effect::Decide(value),
+            FloodingAction::UpdateContext(context) => Effect::UpdateContext(context),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::algorithm::flooding::Round;
+    use crate::algorithm::normalize_actions;
+    use crate::process::Process;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Tests that normalizing actions with two context updates keeps only the latest one and
+    /// moves it to the front, preserving the order of the other actions.
+    #[test]
+    fn test_normalize_coalesces_context_updates() {
+        let p1 = TestProcess { id: 1 };
+        let stale: FloodingContext<TestProcess, u64> = FloodingContext::new(vec![p1]);
+        let mut latest = stale.clone();
+        latest.set_round(Round::new(2));
+
+        let actions = vec![
+            FloodingAction::UpdateContext(stale),
+            FloodingAction::Broadcast(FloodingMessage::Proposal(Round::new(1), vec![1], None)),
+            FloodingAction::UpdateContext(latest.clone()),
+            FloodingAction::Decide(1, None),
+        ];
+
+        assert_eq!(
+            normalize_actions(actions),
+            vec![
+                FloodingAction::UpdateContext(latest),
+                FloodingAction::Broadcast(FloodingMessage::Proposal(Round::new(1), vec![1], None)),
+                FloodingAction::Decide(1, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1394 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use crate::algorithm::{normalize_actions, Algorithm, Value};
+use crate::error::{InternalError, ResourceExhaustedError};
+use crate::process::Process;
+
+#[cfg(feature = "metrics")]
+use super::FloodingMetrics;
+use super::{FloodingAction, FloodingContext, FloodingEvent, FloodingMessage, Round};
+
+/// A function which returns the canonical form of a proposal.
+type Normalizer<V> = Box<dyn Fn(V) -> Result<V, InternalError> + Send + Sync>;
+
+/// The flooding consensus algorithm.
+///
+/// The `select_func` is used to deterministically select the decided value from the set of
+/// proposals known in the deciding round; every process must use the same function.
+///
+/// In strong-validity mode, enabled by [`FloodingAlgorithm::with_strong_validity`], a value is
+/// not decided if it is known to have been proposed only by processes which have crashed.
+///
+/// Proposals may be normalized before they are stored, with
+/// [`FloodingAlgorithm::with_normalizer`].
+///
+/// With the `metrics` feature, an algorithm constructed with
+/// `FloodingAlgorithm::new_with_metrics` reports its progress to a `FloodingMetrics` sink.
+pub struct FloodingAlgorithm<P, V, F> {
+    select_func: F,
+    normalize: Option<Normalizer<V>>,
+    strong_validity: bool,
+    broadcast_limit: Option<u64>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Box<dyn FloodingMetrics>>,
+    _process: PhantomData<P>,
+    _value: PhantomData<V>,
+}
+
+impl<P, V, F> FloodingAlgorithm<P, V, F>
+where
+    P: Process,
+    V: Value + PartialEq,
+    F: Fn(&[V]) -> Result<V, InternalError>,
+{
+    /// Constructs a new `FloodingAlgorithm` which decides with `select_func`.
+    pub fn new(select_func: F) -> Self {
+        FloodingAlgorithm {
+            select_func,
+            normalize: None,
+            strong_validity: false,
+            broadcast_limit: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            _process: PhantomData,
+            _value: PhantomData,
+        }
+    }
+
+    /// Constructs a new `FloodingAlgorithm` which records the rounds advanced, the proposals
+    /// delivered and the decision with `metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn new_with_metrics<M>(select_func: F, metrics: M) -> Self
+    where
+        M: FloodingMetrics + 'static,
+    {
+        let mut algorithm = Self::new(select_func);
+        algorithm.metrics = Some(Box::new(metrics));
+        algorithm
+    }
+
+    /// Records the metrics of an event which was handled successfully, given the round the
+    /// context was in beforehand and, for a delivered proposal, the proposal's round.
+    #[cfg(feature = "metrics")]
+    fn record_metrics(
+        &self,
+        start_round: Round,
+        proposal_round: Option<Round>,
+        actions: &[FloodingAction<P, V>],
+    ) {
+        let metrics = match &self.metrics {
+            Some(metrics) => metrics,
+            None => return,
+        };
+
+        if let Some(round) = proposal_round {
+            metrics.record_proposal(round);
+        }
+
+        if let Some(FloodingAction::UpdateContext(context)) = actions.first() {
+            for round in start_round.value() + 1..=context.round().value() {
+                metrics.record_round_advance(Round::new(round));
+            }
+            if actions
+                .iter()
+                .any(|action| matches!(action, FloodingAction::Decide(_, _)))
+            {
+                metrics.record_decision(context.round());
+            }
+        }
+    }
+
+    /// Enables strong-validity mode, in which the proposals given to `select_func` exclude those
+    /// known to have been proposed only by crashed processes.
+    ///
+    /// To know which process proposed each value, a process broadcasts only its own value in the
+    /// first round, and records the sender of each first-round proposal it is delivered. The
+    /// values whose proposers it has seen crash are flooded along with the proposals, so that
+    /// every process deciding in a round excludes the same values. If every proposal of the
+    /// deciding round is excluded, all of them are given to `select_func` instead. Every process
+    /// must use the same mode.
+    pub fn with_strong_validity(mut self) -> Self {
+        self.strong_validity = true;
+        self
+    }
+
+    /// Normalizes every proposal with `normalize`, both those proposed by this process and those
+    /// delivered from others, before it is stored.
+    ///
+    /// Proposals which are semantically equal should normalize to equal values, so that they are
+    /// stored once and `select_func` sees the same proposals at every process. Every process must
+    /// use the same function. An event with a proposal which fails to normalize fails with the
+    /// error.
+    pub fn with_normalizer<N>(mut self, normalize: N) -> Self
+    where
+        N: Fn(V) -> Result<V, InternalError> + Send + Sync + 'static,
+    {
+        self.normalize = Some(Box::new(normalize));
+        self
+    }
+
+    /// Returns the canonical form of `value`.
+    fn normalize(&self, value: V) -> Result<V, InternalError> {
+        match &self.normalize {
+            Some(normalize) => normalize(value).map_err(|err| {
+                InternalError::from_source_with_prefix(
+                    Box::new(err),
+                    "unable to normalize proposal".into(),
+                )
+            }),
+            None => Ok(value),
+        }
+    }
+
+    /// Limits the number of broadcasts this process may make in a single consensus.
+    ///
+    /// An event which would exceed the limit fails with an `InternalError` whose source is a
+    /// [`ResourceExhaustedError`], guarding against relay storms; see
+    /// [`FloodingContext::broadcasts`].
+    pub fn with_broadcast_limit(mut self, limit: u64) -> Self {
+        self.broadcast_limit = Some(limit);
+        self
+    }
+
+    /// Counts the broadcasts in `actions` in the context they update, enforcing the limit.
+    fn count_broadcasts(&self, actions: &mut [FloodingAction<P, V>]) -> Result<(), InternalError> {
+        let broadcasts = actions
+            .iter()
+            .filter(|action| matches!(action, FloodingAction::Broadcast(_)))
+            .count() as u64;
+
+        if let Some(FloodingAction::UpdateContext(context)) = actions.first_mut() {
+            let total = context.broadcasts() + broadcasts;
+            if let Some(limit) = self.broadcast_limit {
+                if total > limit {
+                    return Err(InternalError::from_source(Box::new(
+                        ResourceExhaustedError::with_message(format!(
+                            "consensus would make {} broadcasts, exceeding the limit of {}",
+                            total, limit
+                        )),
+                    )));
+                }
+            }
+            context.set_broadcasts(total);
+        }
+
+        Ok(())
+    }
+
+    fn handle_crash(
+        &self,
+        process: P,
+        mut context: FloodingContext<P, V>,
+    ) -> Result<Vec<FloodingAction<P, V>>, InternalError> {
+        context.correct_mut().retain(|p| p != &process);
+
+        let mut actions = self.decide_or_next_round(&mut context)?;
+        actions.insert(0, FloodingAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_deliver_proposal(
+        &self,
+        process: P,
+        round: Round,
+        proposals: Vec<V>,
+        excluded: Vec<V>,
+        mut context: FloodingContext<P, V>,
+    ) -> Result<Vec<FloodingAction<P, V>>, InternalError> {
+        if round.prev().is_none() {
+            return Err(InternalError::with_message(format!(
+                "proposal delivered for round {}, which is not a valid round",
+                round
+            )));
+        }
+        let proposals = proposals
+            .into_iter()
+            .map(|proposal| self.normalize(proposal))
+            .collect::<Result<Vec<_>, _>>()?;
+        let excluded = excluded
+            .into_iter()
+            .map(|value| self.normalize(value))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        context.ensure_round(round)?;
+        let index = round.index()?;
+
+        let received_from = &mut context.received_from_mut()[index];
+        if !received_from.contains(&process) {
+            received_from.push(process);
+        }
+
+        if self.strong_validity && round == Round::first() {
+            for proposal in &proposals {
+                let proposer = (process, proposal.clone());
+                if !context.proposers().contains(&proposer) {
+                    context.proposers_mut().push(proposer);
+                }
+            }
+        }
+
+        let round_proposals = &mut context.proposals_mut()[index];
+        for proposal in proposals {
+            if !round_proposals.contains(&proposal) {
+                round_proposals.push(proposal);
+            }
+        }
+
+        let round_excluded = &mut context.excluded_mut()[index];
+        for value in excluded {
+            if !round_excluded.contains(&value) {
+                round_excluded.push(value);
+            }
+        }
+
+        let mut actions = self.decide_or_next_round(&mut context)?;
+        actions.insert(0, FloodingAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_deliver_decided(
+        &self,
+        process: P,
+        value: V,
+        mut context: FloodingContext<P, V>,
+    ) -> Result<Vec<FloodingAction<P, V>>, InternalError> {
+        let mut actions = Vec::new();
+
+        if context.correct().contains(&process) {
+            context.set_decision(Some(value.clone()));
+            actions.push(FloodingAction::Broadcast(FloodingMessage::Decided(
+                value.clone(),
+                context.trace_id().clone(),
+            )));
+            actions.push(FloodingAction::Decide(value, context.trace_id().clone()));
+        }
+
+        actions.insert(0, FloodingAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_propose(
+        &self,
+        value: V,
+        mut context: FloodingContext<P, V>,
+    ) -> Result<Vec<FloodingAction<P, V>>, InternalError> {
+        let value = self.normalize(value)?;
+
+        context.ensure_round(Round::first())?;
+        let index = Round::first().index()?;
+
+        let round_proposals = &mut context.proposals_mut()[index];
+        if !round_proposals.contains(&value) {
+            round_proposals.push(value.clone());
+        }
+
+        // A process alone in its cluster has heard from every process once it has proposed, so
+        // it decides without waiting for its own broadcast. The decision is still broadcast,
+        // since learners are not among the correct processes and only learn it that way
+        if let [process] = context.received_from()[0].as_slice() {
+            let process = *process;
+            if context.correct().as_slice() == [process] {
+                let received_from = &mut context.received_from_mut()[index];
+                if !received_from.contains(&process) {
+                    received_from.push(process);
+                }
+                let mut actions = self.decide_or_next_round(&mut context)?;
+                actions.insert(0, FloodingAction::UpdateContext(context));
+                return Ok(actions);
+            }
+        }
+
+        // In strong-validity mode, the first-round proposal identifies the value of its sender
+        let message = if self.strong_validity {
+            FloodingMessage::StrongProposal(
+                Round::first(),
+                vec![value],
+                vec![],
+                context.trace_id().clone(),
+            )
+        } else {
+            FloodingMessage::Proposal(
+                Round::first(),
+                context.proposals()[index].clone(),
+                context.trace_id().clone(),
+            )
+        };
+
+        Ok(vec![
+            FloodingAction::UpdateContext(context),
+            FloodingAction::Broadcast(message),
+        ])
+    }
+
+    /// Decides or moves to the next round for as long as every correct process has been heard
+    /// from in the current round.
+    ///
+    /// The decision is only recorded in the context once `select_func` has succeeded, so if it
+    /// returns an error, the context has no decision and remains in the deciding round.
+    fn decide_or_next_round(
+        &self,
+        context: &mut FloodingContext<P, V>,
+    ) -> Result<Vec<FloodingAction<P, V>>, InternalError> {
+        let mut actions = Vec::new();
+
+        while context.decision().is_none() {
+            let round = context.round();
+            context.ensure_round(round)?;
+            let index = round.index()?;
+            let prev_index = round
+                .prev()
+                .ok_or_else(|| {
+                    InternalError::with_message(
+                        "context is in round 0, which is not a valid round".into(),
+                    )
+                })?
+                .index()?;
+
+            if !is_subset(context.correct(), &context.received_from()[index]) {
+                break;
+            }
+
+            if is_same_set(
+                &context.received_from()[index],
+                &context.received_from()[prev_index],
+            ) {
+                let candidates = self.candidates(context, index);
+                if candidates.is_empty() {
+                    return Err(InternalError::with_message(format!(
+                        "cannot decide: no proposals in round {}",
+                        round
+                    )));
+                }
+
+                let decision = (self.select_func)(&candidates).map_err(|err| {
+                    InternalError::from_source_with_prefix(
+                        Box::new(err),
+                        format!("unable to decide in round {}", round),
+                    )
+                })?;
+
+                debug!(
+                    "decided in round {} (trace id: {:?})",
+                    round,
+                    context.trace_id()
+                );
+                context.set_decision(Some(decision.clone()));
+                actions.push(FloodingAction::Broadcast(FloodingMessage::Decided(
+                    decision.clone(),
+                    context.trace_id().clone(),
+                )));
+                actions.push(FloodingAction::Decide(decision, context.trace_id().clone()));
+            } else {
+                let next = round.next().ok_or_else(|| {
+                    InternalError::with_message(format!("no round follows round {}", round))
+                })?;
+                context.set_round(next);
+                context.ensure_round(next)?;
+                let message = if self.strong_validity {
+                    FloodingMessage::StrongProposal(
+                        next,
+                        context.proposals()[index].clone(),
+                        self.excluded(context, index),
+                        context.trace_id().clone(),
+                    )
+                } else {
+                    FloodingMessage::Proposal(
+                        next,
+                        context.proposals()[index].clone(),
+                        context.trace_id().clone(),
+                    )
+                };
+                actions.push(FloodingAction::Broadcast(message));
+            }
+        }
+
+        Ok(actions)
+    }
+
+    /// Returns the proposals which may be decided in the round with the given index.
+    ///
+    /// Only the proposals and exclusions flooded in the round are used, and not this process's
+    /// own view of which processes are correct, so that every process deciding in the round
+    /// selects from the same candidates.
+    fn candidates(&self, context: &FloodingContext<P, V>, index: usize) -> Vec<V> {
+        let proposals = &context.proposals()[index];
+        if !self.strong_validity {
+            return proposals.clone();
+        }
+
+        let candidates: Vec<V> = proposals
+            .iter()
+            .filter(|value| !context.excluded()[index].contains(value))
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            proposals.clone()
+        } else {
+            candidates
+        }
+    }
+
+    /// Returns the proposals to be flooded as excluded after the round with the given index:
+    /// those already excluded in the round, along with those which this process knows to have
+    /// been proposed only by processes it has seen crash.
+    fn excluded(&self, context: &FloodingContext<P, V>, index: usize) -> Vec<V> {
+        let mut excluded = context.excluded()[index].clone();
+        for value in &context.proposals()[index] {
+            let mut proposers = context
+                .proposers()
+                .iter()
+                .filter(|(_, proposed)| proposed == value)
+                .map(|(process, _)| process)
+                .peekable();
+            if proposers.peek().is_some()
+                && proposers.all(|process| !context.correct().contains(process))
+                && !excluded.contains(value)
+            {
+                excluded.push(value.clone());
+            }
+        }
+        excluded
+    }
+}
+
+impl<P, V, F> Algorithm<P> for FloodingAlgorithm<P, V, F>
+where
+    P: Process,
+    V: Value + PartialEq,
+    F: Fn(&[V]) -> Result<V, InternalError>,
+{
+    type Event = FloodingEvent<P, V>;
+    type Action = FloodingAction<P, V>;
+    type Context = FloodingContext<P, V>;
+
+    fn event(
+        &self,
+        event: Self::Event,
+        mut context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError> {
+        // Once decided, the consensus has terminated; events are dropped rather than growing the
+        // per-round state, which is no longer needed
+        if context.decision().is_some() {
+            debug!("ignoring event received after the decision");
+            return Ok(vec![]);
+        }
+
+        // The first trace id seen is carried on every message and decision which follows
+        let trace_id = match &event {
+            FloodingEvent::Crash(_) => None,
+            FloodingEvent::Deliver(_, FloodingMessage::Proposal(_, _, trace_id))
+            | FloodingEvent::Deliver(_, FloodingMessage::StrongProposal(_, _, _, trace_id))
+            | FloodingEvent::Deliver(_, FloodingMessage::Decided(_, trace_id))
+            | FloodingEvent::Propose(_, trace_id) => trace_id.as_ref(),
+        };
+        if let (None, Some(trace_id)) = (context.trace_id(), trace_id) {
+            context.set_trace_id(Some(trace_id.clone()));
+        }
+
+        #[cfg(feature = "metrics")]
+        let (start_round, proposal_round) = match &event {
+            FloodingEvent::Deliver(_, FloodingMessage::Proposal(round, _, _))
+            | FloodingEvent::Deliver(_, FloodingMessage::StrongProposal(round, _, _, _)) => {
+                (context.round(), Some(*round))
+            }
+            _ => (context.round(), None),
+        };
+
+        let actions = match event {
+            FloodingEvent::Crash(process) => self.handle_crash(process, context),
+            FloodingEvent::Deliver(process, FloodingMessage::Proposal(round, proposals, _)) => {
+                self.handle_deliver_proposal(process, round, proposals, vec![], context)
+            }
+            FloodingEvent::Deliver(
+                process,
+                FloodingMessage::StrongProposal(round, proposals, excluded, _),
+            ) => self.handle_deliver_proposal(process, round, proposals, excluded, context),
+            FloodingEvent::Deliver(process, FloodingMessage::Decided(value, _)) => {
+                self.handle_deliver_decided(process, value, context)
+            }
+            FloodingEvent::Propose(value, _) => self.handle_propose(value, context),
+        }?;
+
+        let mut actions = normalize_actions(actions);
+        self.count_broadcasts(&mut actions)?;
+
+        #[cfg(feature = "metrics")]
+        self.record_metrics(start_round, proposal_round, &actions);
+
+        Ok(actions)
+    }
+}
+
+fn is_subset<P: PartialEq>(subset: &[P], set: &[P]) -> bool {
+    subset.iter().all(|p| set.contains(p))
+}
+
+fn is_same_set<P: PartialEq>(a: &[P], b: &[P]) -> bool {
+    is_subset(a, b) && is_subset(b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+
+    use crate::algorithm::flooding::FloodingContextBuilder;
+    use crate::algorithm::TraceId;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    fn lowest(values: &[u64]) -> Result<u64, InternalError> {
+        values
+            .iter()
+            .min()
+            .copied()
+            .ok_or_else(|| InternalError::with_message("no values".into()))
+    }
+
+    /// Returns the context from the `UpdateContext` action, which must be the first action.
+    fn updated_context(
+        actions: &[FloodingAction<TestProcess, u64>],
+    ) -> FloodingContext<TestProcess, u64> {
+        match actions.first() {
+            Some(FloodingAction::UpdateContext(context)) => context.clone(),
+            _ => panic!("first action was not UpdateContext: {:?}", actions),
+        }
+    }
+
+    /// Tests that two processes which each propose a value and deliver both proposals in the
+    /// first round decide on the lowest value.
+    #[test]
+    fn test_decide_in_first_round() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+        let context = FloodingContext::new(vec![p1, p2]);
+
+        let actions = algorithm
+            .event(FloodingEvent::Propose(5, None), context)
+            .expect("failed to propose");
+        assert_eq!(
+            actions[1],
+            FloodingAction::Broadcast(FloodingMessage::Proposal(Round::new(1), vec![5], None))
+        );
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(1), vec![5], None)),
+                updated_context(&actions),
+            )
+            .expect("failed to deliver");
+        assert_eq!(actions.len(), 1);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(1), vec![3], None)),
+                updated_context(&actions),
+            )
+            .expect("failed to deliver");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                FloodingAction::Broadcast(FloodingMessage::Decided(3, None)),
+                FloodingAction::Decide(3, None),
+            ]
+        );
+        assert_eq!(updated_context(&actions).decision(), &Some(3));
+    }
+
+    /// Tests that when a process crashes before it is heard from, the remaining process moves to
+    /// the next round rather than deciding.
+    #[test]
+    fn test_crash_moves_to_next_round() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+        let context = FloodingContext::new(vec![p1, p2]);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(1), vec![5], None)),
+                context,
+            )
+            .expect("failed to deliver");
+
+        let actions = algorithm
+            .event(FloodingEvent::Crash(p2), updated_context(&actions))
+            .expect("failed to crash");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![FloodingAction::Broadcast(FloodingMessage::Proposal(
+                Round::new(2),
+                vec![5],
+                None
+            ))]
+        );
+        assert_eq!(updated_context(&actions).round(), Round::new(2));
+    }
+
+    /// Tests that a decision delivered from a correct process is adopted and relayed.
+    #[test]
+    fn test_deliver_decided() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+        let context = FloodingContext::new(vec![p1, p2]);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p2, FloodingMessage::Decided(7, None)),
+                context,
+            )
+            .expect("failed to deliver");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                FloodingAction::Broadcast(FloodingMessage::Decided(7, None)),
+                FloodingAction::Decide(7, None),
+            ]
+        );
+    }
+
+    /// Tests that once a process has decided, later proposals, crashes and decisions are ignored.
+    ///
+    /// Each event returns no actions, and in particular no `UpdateContext`, so the stored context
+    /// and its per-round state do not grow however many rounds of proposals are delivered.
+    #[test]
+    fn test_events_after_decision_ignored() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p2, FloodingMessage::Decided(7, None)),
+                FloodingContext::new(vec![p1, p2]),
+            )
+            .expect("failed to deliver");
+        let context = updated_context(&actions);
+
+        for round in 1..=100 {
+            for process in [p1, p2] {
+                let actions = algorithm
+                    .event(
+                        FloodingEvent::Deliver(
+                            process,
+                            FloodingMessage::Proposal(Round::new(round), vec![round], None),
+                        ),
+                        context.clone(),
+                    )
+                    .expect("failed to deliver");
+                assert_eq!(
+                    actions,
+                    vec![],
+                    "proposal for round {} was not ignored",
+                    round
+                );
+            }
+        }
+        for event in [
+            FloodingEvent::Crash(p1),
+            FloodingEvent::Propose(1, None),
+            FloodingEvent::Deliver(p1, FloodingMessage::Decided(7, None)),
+        ] {
+            let actions = algorithm
+                .event(event.clone(), context.clone())
+                .expect("failed to handle event");
+            assert_eq!(actions, vec![], "{:?} was not ignored", event);
+        }
+    }
+
+    /// Tests that a metrics sink records one round advance per change of membership which stops
+    /// the consensus from deciding, along with every delivered proposal and the decision.
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics() {
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct TestMetrics {
+            records: Arc<Mutex<Vec<(&'static str, Round)>>>,
+        }
+
+        impl TestMetrics {
+            fn count(&self, name: &str) -> usize {
+                self.records
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|(record, _)| *record == name)
+                    .count()
+            }
+        }
+
+        impl FloodingMetrics for TestMetrics {
+            fn record_round_advance(&self, round: Round) {
+                self.records.lock().unwrap().push(("round", round));
+            }
+
+            fn record_decision(&self, round: Round) {
+                self.records.lock().unwrap().push(("decision", round));
+            }
+
+            fn record_proposal(&self, round: Round) {
+                self.records.lock().unwrap().push(("proposal", round));
+            }
+        }
+
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let metrics = TestMetrics::default();
+        let algorithm = FloodingAlgorithm::new_with_metrics(lowest, metrics.clone());
+
+        let events = vec![
+            FloodingEvent::Crash(p3),
+            FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(1), vec![5], None)),
+            FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(1), vec![3], None)),
+            FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(2), vec![5], None)),
+            FloodingEvent::Crash(p2),
+            FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(3), vec![3], None)),
+        ];
+        let membership_changes = events
+            .iter()
+            .filter(|event| matches!(event, FloodingEvent::Crash(_)))
+            .count();
+
+        let mut context = FloodingContext::new(vec![p1, p2, p3]);
+        for event in events {
+            let actions = algorithm
+                .event(event, context)
+                .expect("failed to handle event");
+            context = updated_context(&actions);
+        }
+
+        assert_eq!(context.decision(), &Some(3));
+        assert_eq!(metrics.count("round"), membership_changes);
+        assert_eq!(metrics.count("proposal"), 4);
+        assert_eq!(
+            metrics.records.lock().unwrap().last(),
+            Some(&("decision", Round::new(3)))
+        );
+    }
+
+    /// Tests that when `select_func` returns an error at decision time, the event fails with a
+    /// descriptive error and no partially-updated context is returned, so the caller can retry
+    /// with the context it already holds.
+    #[test]
+    fn test_select_func_error_leaves_context_unchanged() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let failing = FloodingAlgorithm::new(|_: &[u64]| {
+            Err(InternalError::with_message("selection failed".into()))
+        });
+
+        let mut context = FloodingContext::new(vec![p1, p2]);
+        for event in [
+            FloodingEvent::Propose(4, None),
+            FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(1), vec![4], None)),
+        ] {
+            context = updated_context(&failing.event(event, context).expect("failed event"));
+        }
+
+        let event =
+            FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(1), vec![4], None));
+        let err = failing
+            .event(event.clone(), context.clone())
+            .expect_err("select_func error was not returned");
+        assert_eq!(
+            err.to_string(),
+            "unable to decide in round 1: selection failed"
+        );
+
+        assert_eq!(context.decision(), &None);
+        assert_eq!(context.round(), Round::new(1));
+
+        let actions = FloodingAlgorithm::new(lowest)
+            .event(event, context)
+            .expect("failed to retry");
+        assert_eq!(updated_context(&actions).decision(), &Some(4));
+    }
+
+    /// Tests that reaching a decision with no proposals in the round fails with a descriptive
+    /// error before the selector is called, and that no decision is made.
+    #[test]
+    fn test_decide_without_proposals() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(|_: &[u64]| -> Result<u64, InternalError> {
+            panic!("select_func called without proposals")
+        });
+
+        let context = FloodingContext::new(vec![p1, p2]);
+        let context = updated_context(
+            &algorithm
+                .event(
+                    FloodingEvent::Deliver(
+                        p1,
+                        FloodingMessage::Proposal(Round::new(1), vec![], None),
+                    ),
+                    context,
+                )
+                .expect("failed to deliver proposal"),
+        );
+
+        let err = algorithm
+            .event(
+                FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(1), vec![], None)),
+                context.clone(),
+            )
+            .expect_err("empty proposals were decided");
+        assert_eq!(err.to_string(), "cannot decide: no proposals in round 1");
+        assert_eq!(context.decision(), &None);
+    }
+
+    /// Tests that the received-from snapshot reflects the proposals delivered so far, per round.
+    #[test]
+    fn test_received_from_snapshot() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+        let context = FloodingContext::new(vec![p1, p2, p3]);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p3, FloodingMessage::Proposal(Round::new(1), vec![1], None)),
+                context,
+            )
+            .expect("failed to deliver");
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(1), vec![2], None)),
+                updated_context(&actions),
+            )
+            .expect("failed to deliver");
+
+        let snapshot = updated_context(&actions).received_from_snapshot();
+        assert_eq!(snapshot[0], vec![p1, p2, p3]);
+        assert_eq!(snapshot[1], vec![p3, p1]);
+        assert!(snapshot[2..].iter().all(|round| round.is_empty()));
+    }
+
+    /// Tests that a context built in round 2, after a third process crashed in round 1, decides
+    /// once the remaining process is heard from in round 2.
+    #[test]
+    fn test_resume_in_second_round() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+        let context = FloodingContextBuilder::new()
+            .with_correct(vec![p1, p2])
+            .with_round(Round::new(2))
+            .with_received_from(vec![vec![p1, p2, p3], vec![p1, p2], vec![p1], vec![]])
+            .with_proposals(vec![vec![], vec![5, 3], vec![5, 3], vec![]])
+            .build()
+            .expect("failed to build context");
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(
+                    p2,
+                    FloodingMessage::Proposal(Round::new(2), vec![3, 5], None),
+                ),
+                context,
+            )
+            .expect("failed to deliver");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                FloodingAction::Broadcast(FloodingMessage::Decided(3, None)),
+                FloodingAction::Decide(3, None),
+            ]
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.round(), Round::new(2));
+        assert_eq!(context.decision(), &Some(3));
+    }
+
+    /// Tests that a single process decides its own proposal in the first round as soon as it
+    /// proposes, without broadcasting a proposal, and that it broadcasts the decision for any
+    /// learners. Also tests that a context for no processes does not panic.
+    #[test]
+    fn test_single_process() {
+        let p1 = TestProcess { id: 1 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Propose(4, None),
+                FloodingContext::new(vec![p1]),
+            )
+            .expect("failed to propose");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                FloodingAction::Broadcast(FloodingMessage::Decided(4, None)),
+                FloodingAction::Decide(4, None),
+            ]
+        );
+        assert_eq!(updated_context(&actions).round(), Round::new(1));
+        assert_eq!(updated_context(&actions).decision(), &Some(4));
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Propose(4, None),
+                FloodingContext::new(vec![]),
+            )
+            .expect("failed to propose");
+        assert_eq!(updated_context(&actions).proposals()[1], vec![4]);
+    }
+
+    /// Tests that a proposal for a round beyond those allocated by `FloodingContext::new` is
+    /// recorded rather than causing an index panic.
+    #[test]
+    fn test_deliver_proposal_for_later_round() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(5), vec![1], None)),
+                FloodingContext::new(vec![p1, p2]),
+            )
+            .expect("failed to deliver");
+
+        let context = updated_context(&actions);
+        assert_eq!(context.received_from()[5], vec![p2]);
+        assert_eq!(context.round(), Round::new(1));
+    }
+
+    /// Tests that proposals for rounds beyond the process count grow the per-round state as
+    /// needed, storing each proposal and sender in its own round and leaving the rounds between
+    /// empty.
+    #[test]
+    fn test_per_round_state_grows() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(8), vec![3], None)),
+                FloodingContext::new(vec![p1, p2]),
+            )
+            .expect("failed to deliver");
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(4), vec![6], None)),
+                updated_context(&actions),
+            )
+            .expect("failed to deliver");
+
+        let context = updated_context(&actions);
+        assert_eq!(context.proposals().len(), 9);
+        assert_eq!(context.received_from().len(), 9);
+        assert_eq!(context.proposals()[8], vec![3]);
+        assert_eq!(context.received_from()[8], vec![p2]);
+        assert_eq!(context.proposals()[4], vec![6]);
+        assert_eq!(context.received_from()[4], vec![p1]);
+        for round in [3, 5, 6, 7] {
+            assert!(context.proposals()[round].is_empty());
+            assert!(context.received_from()[round].is_empty());
+        }
+    }
+
+    /// Tests that the trace id given with a proposal is carried on the resulting broadcasts and
+    /// the decision, and that a process adopts the trace id of a message it is delivered.
+    #[test]
+    fn test_trace_id() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let trace_id = Some(TraceId::new("trace-1"));
+        let algorithm = FloodingAlgorithm::new(lowest);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Propose(5, trace_id.clone()),
+                FloodingContext::new(vec![p1, p2]),
+            )
+            .expect("failed to propose");
+        assert_eq!(
+            actions[1],
+            FloodingAction::Broadcast(FloodingMessage::Proposal(
+                Round::new(1),
+                vec![5],
+                trace_id.clone()
+            ))
+        );
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(1), vec![5], None)),
+                updated_context(&actions),
+            )
+            .expect("failed to deliver");
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(1), vec![3], None)),
+                updated_context(&actions),
+            )
+            .expect("failed to deliver");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                FloodingAction::Broadcast(FloodingMessage::Decided(3, trace_id.clone())),
+                FloodingAction::Decide(3, trace_id.clone()),
+            ]
+        );
+
+        // Another process adopts the trace id from the message
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(p1, FloodingMessage::Decided(3, trace_id.clone())),
+                FloodingContext::new(vec![p1, p2]),
+            )
+            .expect("failed to deliver");
+        assert_eq!(updated_context(&actions).trace_id(), &trace_id);
+        assert_eq!(actions[2], FloodingAction::Decide(3, trace_id));
+    }
+
+    /// Tests that in strong-validity mode a value proposed only by a process which then crashed
+    /// is excluded from the decision, although it is the value the standard algorithm decides.
+    ///
+    /// The crash of the proposer is seen by p2, which floods the exclusion to p1, so p1 excludes
+    /// the value although it never saw the proposal itself. p4 crashes without proposing, so that
+    /// the processes move to the second round.
+    #[test]
+    fn test_strong_validity_excludes_crashed_proposer() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let p4 = TestProcess { id: 4 };
+
+        let run = |algorithm: &FloodingAlgorithm<TestProcess, u64, _>, events| {
+            let mut context = FloodingContext::new(vec![p1, p2, p3, p4]);
+            let mut actions = Vec::new();
+            for event in events {
+                actions = algorithm.event(event, context).expect("failed event");
+                context = updated_context(&actions);
+            }
+            (context, actions)
+        };
+
+        let (context, _) = run(
+            &FloodingAlgorithm::new(lowest),
+            vec![
+                FloodingEvent::Propose(5, None),
+                FloodingEvent::Deliver(p1, FloodingMessage::Proposal(Round::new(1), vec![5], None)),
+                FloodingEvent::Crash(p3),
+                FloodingEvent::Crash(p4),
+                FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(1), vec![7], None)),
+                FloodingEvent::Deliver(
+                    p1,
+                    FloodingMessage::Proposal(Round::new(2), vec![5, 7], None),
+                ),
+                FloodingEvent::Deliver(
+                    p2,
+                    FloodingMessage::Proposal(Round::new(2), vec![7, 1, 5], None),
+                ),
+            ],
+        );
+        assert_eq!(context.decision(), &Some(1));
+        assert!(context.proposers().is_empty());
+
+        let (context, actions) = run(
+            &FloodingAlgorithm::new(lowest).with_strong_validity(),
+            vec![
+                FloodingEvent::Propose(5, None),
+                FloodingEvent::Deliver(
+                    p1,
+                    FloodingMessage::StrongProposal(Round::new(1), vec![5], vec![], None),
+                ),
+                FloodingEvent::Crash(p3),
+                FloodingEvent::Crash(p4),
+                FloodingEvent::Deliver(
+                    p2,
+                    FloodingMessage::StrongProposal(Round::new(1), vec![7], vec![], None),
+                ),
+                FloodingEvent::Deliver(
+                    p1,
+                    FloodingMessage::StrongProposal(Round::new(2), vec![5, 7], vec![], None),
+                ),
+                FloodingEvent::Deliver(
+                    p2,
+                    FloodingMessage::StrongProposal(Round::new(2), vec![7, 1, 5], vec![1], None),
+                ),
+            ],
+        );
+        assert_eq!(context.decision(), &Some(5));
+        assert_eq!(context.proposers(), &vec![(p1, 5), (p2, 7)]);
+        assert_eq!(context.excluded()[2], vec![1]);
+        assert_eq!(actions.last(), Some(&FloodingAction::Decide(5, None)));
+    }
+
+    /// Tests that in strong-validity mode a process floods as excluded the values it knows to have
+    /// been proposed only by crashed processes when it moves to the next round.
+    #[test]
+    fn test_strong_validity_floods_exclusions() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let p4 = TestProcess { id: 4 };
+        let algorithm = FloodingAlgorithm::new(lowest).with_strong_validity();
+
+        let mut context = FloodingContext::new(vec![p1, p2, p3, p4]);
+        let mut actions = Vec::new();
+        for event in [
+            FloodingEvent::Deliver(
+                p2,
+                FloodingMessage::StrongProposal(Round::new(1), vec![7], vec![], None),
+            ),
+            FloodingEvent::Deliver(
+                p3,
+                FloodingMessage::StrongProposal(Round::new(1), vec![1], vec![], None),
+            ),
+            FloodingEvent::Crash(p3),
+            FloodingEvent::Crash(p4),
+            FloodingEvent::Deliver(
+                p1,
+                FloodingMessage::StrongProposal(Round::new(1), vec![5], vec![], None),
+            ),
+        ] {
+            actions = algorithm.event(event, context).expect("failed event");
+            context = updated_context(&actions);
+        }
+
+        assert_eq!(context.decision(), &None);
+        assert_eq!(
+            actions[1],
+            FloodingAction::Broadcast(FloodingMessage::StrongProposal(
+                Round::new(2),
+                vec![7, 1, 5],
+                vec![1],
+                None
+            ))
+        );
+    }
+
+    /// Tests that in strong-validity mode, if every proposal of the deciding round is excluded,
+    /// the decision is made from all of the proposals rather than failing.
+    #[test]
+    fn test_strong_validity_falls_back_when_all_excluded() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(lowest).with_strong_validity();
+
+        let mut context = FloodingContext::new(vec![p1, p2]);
+        let mut actions = Vec::new();
+        for event in [
+            FloodingEvent::Deliver(
+                p1,
+                FloodingMessage::StrongProposal(Round::new(1), vec![5], vec![5], None),
+            ),
+            FloodingEvent::Deliver(
+                p2,
+                FloodingMessage::StrongProposal(Round::new(1), vec![7], vec![7], None),
+            ),
+        ] {
+            actions = algorithm.event(event, context).expect("failed event");
+            context = updated_context(&actions);
+        }
+
+        assert_eq!(context.decision(), &Some(5));
+        assert_eq!(actions.last(), Some(&FloodingAction::Decide(5, None)));
+    }
+
+    /// Tests that once a process has decided, further decided messages, which every process
+    /// relays, are dropped without any action, not even a context update.
+    #[test]
+    fn test_duplicate_decided_after_decision() {
+        let processes: Vec<TestProcess> = (1..=1000).map(|id| TestProcess { id }).collect();
+        let algorithm = FloodingAlgorithm::new(lowest);
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(processes[0], FloodingMessage::Decided(7, None)),
+                FloodingContext::new(processes.clone()),
+            )
+            .expect("failed to deliver");
+        let context = updated_context(&actions);
+        assert_eq!(context.decision(), &Some(7));
+
+        for process in processes.iter().rev() {
+            let actions = algorithm
+                .event(
+                    FloodingEvent::Deliver(*process, FloodingMessage::Decided(7, None)),
+                    context.clone(),
+                )
+                .expect("failed to deliver");
+            assert!(actions.is_empty());
+        }
+    }
+
+    /// Tests that a proposal for round 0, which is not a valid round, is rejected with an error
+    /// rather than being recorded in the slot for the processes initially heard from.
+    #[test]
+    fn test_deliver_proposal_for_round_zero() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+
+        let err = algorithm
+            .event(
+                FloodingEvent::Deliver(p2, FloodingMessage::Proposal(Round::new(0), vec![1], None)),
+                FloodingContext::new(vec![p1, p2]),
+            )
+            .expect_err("round 0 was accepted");
+        assert_eq!(
+            err.to_string(),
+            "proposal delivered for round 0, which is not a valid round"
+        );
+
+        let mut context = FloodingContext::new(vec![p1, p2]);
+        context.set_round(Round::new(0));
+        assert!(algorithm.event(FloodingEvent::Crash(p2), context).is_err());
+    }
+
+    type SelectFn = fn(&[u64]) -> Result<u64, InternalError>;
+
+    /// Delivers every message broadcast by `processes` to all of them until none remain,
+    /// returning the final context of each process.
+    fn run_to_decision(
+        algorithm: &FloodingAlgorithm<TestProcess, u64, SelectFn>,
+        processes: &[TestProcess],
+    ) -> Result<Vec<FloodingContext<TestProcess, u64>>, InternalError> {
+        let mut contexts = vec![FloodingContext::new(processes.to_vec()); processes.len()];
+        let mut queue = VecDeque::new();
+
+        let mut handle = |index: usize, event, queue: &mut VecDeque<_>| {
+            let actions = algorithm.event(event, contexts[index].clone())?;
+            for action in actions {
+                match action {
+                    FloodingAction::UpdateContext(context) => contexts[index] = context,
+                    FloodingAction::Broadcast(message) => queue.push_back((index, message)),
+                    FloodingAction::Decide(_, _) => (),
+                }
+            }
+            Ok::<_, InternalError>(())
+        };
+
+        for (index, value) in (1..=processes.len() as u64).enumerate() {
+            handle(index, FloodingEvent::Propose(value, None), &mut queue)?;
+        }
+        while let Some((from, message)) = queue.pop_front() {
+            for to in 0..processes.len() {
+                handle(
+                    to,
+                    FloodingEvent::Deliver(processes[from], message.clone()),
+                    &mut queue,
+                )?;
+            }
+        }
+
+        Ok(contexts)
+    }
+
+    /// Tests the broadcasts counted for a four-process consensus without crashes, in which each
+    /// process broadcasts its proposal and then its decision, and that a limit below that count
+    /// aborts the consensus with a `ResourceExhaustedError`.
+    #[test]
+    fn test_broadcast_amplification() {
+        let processes: Vec<TestProcess> = (1..=4).map(|id| TestProcess { id }).collect();
+        let select: SelectFn = lowest;
+
+        let contexts =
+            run_to_decision(&FloodingAlgorithm::new(select), &processes).expect("consensus failed");
+        assert!(contexts
+            .iter()
+            .all(|context| context.decision() == &Some(1)));
+        assert!(contexts.iter().all(|context| context.broadcasts() == 2));
+        let messages: u64 = contexts
+            .iter()
+            .map(|context| context.broadcasts() * processes.len() as u64)
+            .sum();
+        assert_eq!(messages, 32);
+
+        let err = run_to_decision(
+            &FloodingAlgorithm::new(select).with_broadcast_limit(1),
+            &processes,
+        )
+        .expect_err("broadcast limit was not enforced");
+        assert!(std::error::Error::source(&err)
+            .and_then(|source| source.downcast_ref::<ResourceExhaustedError>())
+            .is_some());
+    }
+
+    /// Tests that proposals are normalized before they are stored: differently-formatted but
+    /// equal proposals, one proposed and one delivered, are stored once in canonical form, and a
+    /// proposal which fails to normalize is an error.
+    #[test]
+    fn test_normalize_proposals() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        // Values are equal modulo 100, and canonically less than 100
+        let algorithm = FloodingAlgorithm::new(lowest).with_normalizer(|value: u64| {
+            if value >= 1000 {
+                return Err(InternalError::with_message("value out of range".into()));
+            }
+            Ok(value % 100)
+        });
+        let context = FloodingContext::new(vec![p1, p2]);
+
+        let actions = algorithm
+            .event(FloodingEvent::Propose(142, None), context)
+            .expect("failed to propose");
+        assert_eq!(
+            actions[1],
+            FloodingAction::Broadcast(FloodingMessage::Proposal(Round::first(), vec![42], None))
+        );
+
+        let actions = algorithm
+            .event(
+                FloodingEvent::Deliver(
+                    p2,
+                    FloodingMessage::Proposal(Round::first(), vec![242], None),
+                ),
+                updated_context(&actions),
+            )
+            .expect("failed to deliver");
+        let context = updated_context(&actions);
+        assert_eq!(context.proposals()[1], vec![42]);
+
+        let err = algorithm
+            .event(
+                FloodingEvent::Deliver(
+                    p1,
+                    FloodingMessage::Proposal(Round::first(), vec![1042], None),
+                ),
+                context,
+            )
+            .expect_err("proposal which failed to normalize was accepted");
+        assert_eq!(
+            err.to_string(),
+            "unable to normalize proposal: value out of range"
+        );
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::TraceId;
+use crate::error::{InternalError, InvalidStateError};
+use crate::process::Process;
+
+use super::Round;
+
+/// The state of flooding consensus at a single process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloodingContext<P, V> {
+    correct: Vec<P>,
+    decision: Option<V>,
+    excluded: Vec<Vec<V>>,
+    proposals: Vec<Vec<V>>,
+    proposers: Vec<(P, V)>,
+    received_from: Vec<Vec<P>>,
+    round: Round,
+    trace_id: Option<TraceId>,
+    broadcasts: u64,
+}
+
+impl<P, V> FloodingContext<P, V>
+where
+    P: Process,
+    V: Clone,
+{
+    /// Constructs the initial context for the given set of processes.
+    ///
+    /// All processes are initially considered correct, and the consensus starts in round 1.
+    pub fn new(processes: Vec<P>) -> Self {
+        let rounds = processes.len() + 1;
+
+        let mut received_from = vec![Vec::new(); rounds];
+        received_from[0] = processes.clone();
+
+        FloodingContext {
+            correct: processes,
+            decision: None,
+            excluded: vec![Vec::new(); rounds],
+            proposals: vec![Vec::new(); rounds],
+            proposers: Vec::new(),
+            received_from,
+            round: Round::first(),
+            trace_id: None,
+            broadcasts: 0,
+        }
+    }
+
+    /// Returns the processes which have not been detected as crashed.
+    pub fn correct(&self) -> &Vec<P> {
+        &self.correct
+    }
+
+    /// Returns the processes which have not been detected as crashed, for updating.
+    pub fn correct_mut(&mut self) -> &mut Vec<P> {
+        &mut self.correct
+    }
+
+    /// Returns the decided value, or `None` if this process has not decided.
+    pub fn decision(&self) -> &Option<V> {
+        &self.decision
+    }
+
+    /// Sets the decided value.
+    pub fn set_decision(&mut self, decision: Option<V>) {
+        self.decision = decision
+    }
+
+    /// Returns the proposals known in each round to have been proposed only by crashed processes,
+    /// indexed by round.
+    ///
+    /// This is only tracked by a [`FloodingAlgorithm`](super::FloodingAlgorithm) in
+    /// strong-validity mode.
+    pub fn excluded(&self) -> &Vec<Vec<V>> {
+        &self.excluded
+    }
+
+    /// Returns the proposals known to have been proposed only by crashed processes, for updating.
+    pub fn excluded_mut(&mut self) -> &mut Vec<Vec<V>> {
+        &mut self.excluded
+    }
+
+    /// Returns the proposals known in each round, indexed by round.
+    pub fn proposals(&self) -> &Vec<Vec<V>> {
+        &self.proposals
+    }
+
+    /// Returns the proposals known in each round, for updating.
+    pub fn proposals_mut(&mut self) -> &mut Vec<Vec<V>> {
+        &mut self.proposals
+    }
+
+    /// Returns the processes known to have proposed each value.
+    ///
+    /// This is only tracked by a [`FloodingAlgorithm`](super::FloodingAlgorithm) in
+    /// strong-validity mode.
+    pub fn proposers(&self) -> &Vec<(P, V)> {
+        &self.proposers
+    }
+
+    /// Returns the processes known to have proposed each value, for updating.
+    pub fn proposers_mut(&mut self) -> &mut Vec<(P, V)> {
+        &mut self.proposers
+    }
+
+    /// Returns the processes heard from in each round, indexed by round.
+    pub fn received_from(&self) -> &Vec<Vec<P>> {
+        &self.received_from
+    }
+
+    /// Returns a copy of the processes heard from in each round, indexed by round.
+    ///
+    /// Unlike [`FloodingContext::received_from`], the snapshot is owned, so it can be handed off
+    /// for reporting (for example, to another thread) while the context continues to be used.
+    pub fn received_from_snapshot(&self) -> Vec<Vec<P>> {
+        self.received_from.clone()
+    }
+
+    /// Returns the processes heard from in each round, for updating.
+    pub fn received_from_mut(&mut self) -> &mut Vec<Vec<P>> {
+        &mut self.received_from
+    }
+
+    /// Returns the current round.
+    pub fn round(&self) -> Round {
+        self.round
+    }
+
+    /// Sets the current round.
+    pub fn set_round(&mut self, round: Round) {
+        self.round = round
+    }
+
+    /// Returns the trace id of the consensus, taken from the first event which carried one.
+    pub fn trace_id(&self) -> &Option<TraceId> {
+        &self.trace_id
+    }
+
+    /// Sets the trace id of the consensus.
+    pub fn set_trace_id(&mut self, trace_id: Option<TraceId>) {
+        self.trace_id = trace_id
+    }
+
+    /// Returns the number of broadcasts this process has made in the consensus, each of which
+    /// sends a message to every process.
+    ///
+    /// Summed over every process, this measures the message amplification of the consensus.
+    pub fn broadcasts(&self) -> u64 {
+        self.broadcasts
+    }
+
+    /// Sets the number of broadcasts this process has made in the consensus.
+    pub fn set_broadcasts(&mut self, broadcasts: u64) {
+        self.broadcasts = broadcasts
+    }
+
+    /// Extends the per-round state, if necessary, so that it can be indexed by `round`.
+    ///
+    /// `new` allocates enough rounds for every process but one to crash, but a context for an
+    /// empty set of processes, or a message for a later round, may need more.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the round cannot be used as an index.
+    pub(super) fn ensure_round(&mut self, round: Round) -> Result<(), InternalError> {
+        let index = round.index()?;
+        if self.excluded.len() <= index {
+            self.excluded.resize(index + 1, Vec::new());
+        }
+        if self.proposals.len() <= index {
+            self.proposals.resize(index + 1, Vec::new());
+        }
+        if self.received_from.len() <= index {
+            self.received_from.resize(index + 1, Vec::new());
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [`FloodingContext`], such as to resume a consensus part way through.
+pub struct FloodingContextBuilder<P, V> {
+    correct: Option<Vec<P>>,
+    decision: Option<V>,
+    proposals: Option<Vec<Vec<V>>>,
+    received_from: Option<Vec<Vec<P>>>,
+    round: Option<Round>,
+}
+
+impl<P, V> FloodingContextBuilder<P, V>
+where
+    P: Process,
+    V: Clone,
+{
+    /// Constructs a new `FloodingContextBuilder` with no fields set.
+    pub fn new() -> Self {
+        FloodingContextBuilder {
+            correct: None,
+            decision: None,
+            proposals: None,
+            received_from: None,
+            round: None,
+        }
+    }
+
+    /// Sets the processes which are considered correct.
+    pub fn with_correct(mut self, correct: Vec<P>) -> Self {
+        self.correct = Some(correct);
+        self
+    }
+
+    /// Sets the value already decided.
+    pub fn with_decision(mut self, decision: V) -> Self {
+        self.decision = Some(decision);
+        self
+    }
+
+    /// Sets the proposals known in each round, indexed by round.
+    pub fn with_proposals(mut self, proposals: Vec<Vec<V>>) -> Self {
+        self.proposals = Some(proposals);
+        self
+    }
+
+    /// Sets the processes heard from in each round, indexed by round.
+    pub fn with_received_from(mut self, received_from: Vec<Vec<P>>) -> Self {
+        self.received_from = Some(received_from);
+        self
+    }
+
+    /// Sets the round the consensus is in.
+    pub fn with_round(mut self, round: Round) -> Self {
+        self.round = Some(round);
+        self
+    }
+
+    /// Builds the context.
+    ///
+    /// The round defaults to round 1. The per-round state not given defaults to empty, except
+    /// that every correct process is considered heard from in round 0, as in
+    /// [`FloodingContext::new`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `correct` is missing, if the round is round 0, or if
+    /// `proposals` and `received_from` do not have the same number of rounds or do not include
+    /// the current round.
+    pub fn build(self) -> Result<FloodingContext<P, V>, InvalidStateError> {
+        let correct = self.correct.ok_or_else(|| {
+            InvalidStateError::with_message("unable to build, missing field: `correct`".into())
+        })?;
+
+        let round = self.round.unwrap_or_else(Round::first);
+        if round.prev().is_none() {
+            return Err(InvalidStateError::with_message(
+                "unable to build, round 0 is not a valid round".into(),
+            ));
+        }
+        let index = round
+            .index()
+            .map_err(|err| InvalidStateError::with_message(format!("unable to build, {}", err)))?;
+
+        let rounds = match (&self.proposals, &self.received_from) {
+            (Some(proposals), Some(received_from)) if proposals.len() != received_from.len() => {
+                return Err(InvalidStateError::with_message(format!(
+                    "unable to build, `proposals` has {} rounds but `received_from` has {}",
+                    proposals.len(),
+                    received_from.len()
+                )))
+            }
+            (Some(proposals), _) => proposals.len(),
+            (None, Some(received_from)) => received_from.len(),
+            (None, None) => (correct.len() + 1).max(index + 1),
+        };
+        if rounds <= index {
+            return Err(InvalidStateError::with_message(format!(
+                "unable to build, the per-round state has {} rounds but the context is in round {}",
+                rounds, round
+            )));
+        }
+
+        let proposals = self.proposals.unwrap_or_else(|| vec![Vec::new(); rounds]);
+        let received_from = self.received_from.unwrap_or_else(|| {
+            let mut received_from = vec![Vec::new(); rounds];
+            received_from[0] = correct.clone();
+            received_from
+        });
+
+        Ok(FloodingContext {
+            correct,
+            decision: self.decision,
+            excluded: vec![Vec::new(); rounds],
+            proposals,
+            proposers: Vec::new(),
+            received_from,
+            round,
+            trace_id: None,
+            broadcasts: 0,
+        })
+    }
+}
+
+impl<P, V> Default for FloodingContextBuilder<P, V>
+where
+    P: Process,
+    V: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Tests that a cloned context is independent of the original, so a runner can snapshot the
+    /// context between events and roll back to it.
+    #[test]
+    fn test_clone_context() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let original: FloodingContext<TestProcess, u64> = FloodingContext::new(vec![p1, p2]);
+
+        let mut clone = original.clone();
+        clone.correct_mut().retain(|process| process != &p2);
+        clone.proposals_mut()[1].push(4);
+        clone.set_round(Round::new(2));
+        clone.set_decision(Some(4));
+
+        assert_eq!(original.correct(), &vec![p1, p2]);
+        assert!(original.proposals()[1].is_empty());
+        assert_eq!(original.round(), Round::first());
+        assert_eq!(original.decision(), &None);
+        assert_eq!(original, FloodingContext::new(vec![p1, p2]));
+        assert_ne!(clone, original);
+    }
+
+    /// Tests that a builder given only the correct processes produces the same context as `new`,
+    /// and that per-round state which is inconsistent with the round is rejected.
+    #[test]
+    fn test_builder() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let context: FloodingContext<TestProcess, u64> = FloodingContextBuilder::new()
+            .with_correct(vec![p1, p2])
+            .build()
+            .expect("failed to build context");
+        assert_eq!(context, FloodingContext::new(vec![p1, p2]));
+
+        let err = FloodingContextBuilder::<TestProcess, u64>::new()
+            .with_correct(vec![p1, p2])
+            .with_proposals(vec![vec![], vec![1]])
+            .with_received_from(vec![vec![p1, p2], vec![p1], vec![]])
+            .build()
+            .expect_err("built a context with inconsistent per-round state");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, `proposals` has 2 rounds but `received_from` has 3"
+        );
+
+        let err = FloodingContextBuilder::<TestProcess, u64>::new()
+            .with_correct(vec![p1, p2])
+            .with_round(Round::new(2))
+            .with_proposals(vec![vec![], vec![1]])
+            .build()
+            .expect_err("built a context without state for its round");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, the per-round state has 2 rounds but the context is in round 2"
+        );
+
+        assert!(FloodingContextBuilder::<TestProcess, u64>::new()
+            .with_correct(vec![p1])
+            .with_round(Round::new(0))
+            .build()
+            .is_err());
+    }
+}
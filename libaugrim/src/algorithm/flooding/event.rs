@@ -0,0 +1,29 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::TraceId;
+
+use super::FloodingMessage;
+
+/// An event handled by flooding consensus.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FloodingEvent<P, V> {
+    /// The process was detected as crashed by the failure detector.
+    Crash(P),
+    /// A message from the process was delivered by the best-effort broadcast.
+    Deliver(P, FloodingMessage<V>),
+    /// The value is proposed by this process; the optional trace id is carried on every message
+    /// and decision which follows, so that the consensus can be traced across processes.
+    Propose(V, Option<TraceId>),
+}
@@ -0,0 +1,408 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multiple independent flooding consensus instances over one network.
+//!
+//! A process taking part in several consensus instances at once, such as one per partition,
+//! broadcasts each message wrapped in an [`InstanceMessage`] tagged with its instance. An
+//! [`InstanceManager`] holds the algorithm and context of each instance, and passes each
+//! delivered message to the instance it is tagged with, so the proposals of one instance are
+//! never seen by another.
+
+use std::collections::BTreeMap;
+
+use crate::algorithm::{Algorithm, Instance, TraceId, Value};
+use crate::error::{InternalError, InvalidStateError};
+use crate::message::Message;
+use crate::process::Process;
+
+use super::{FloodingAction, FloodingAlgorithm, FloodingContext, FloodingEvent, FloodingMessage};
+
+/// A flooding consensus message, tagged with the instance it belongs to.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstanceMessage<V> {
+    instance: Instance,
+    message: FloodingMessage<V>,
+}
+
+impl<V> InstanceMessage<V> {
+    /// Constructs the message of the given instance.
+    pub fn new(instance: Instance, message: FloodingMessage<V>) -> Self {
+        InstanceMessage { instance, message }
+    }
+
+    /// Returns the instance the message belongs to.
+    pub fn instance(&self) -> Instance {
+        self.instance
+    }
+
+    /// Returns the flooding consensus message.
+    pub fn message(&self) -> &FloodingMessage<V> {
+        &self.message
+    }
+
+    /// Returns the flooding consensus message, consuming the instance message.
+    pub fn into_message(self) -> FloodingMessage<V> {
+        self.message
+    }
+}
+
+impl<V> Message for InstanceMessage<V> {}
+
+/// An action returned by an [`InstanceManager`], to be performed by the caller.
+///
+/// The manager stores the updated context of each instance itself, so there is no context update
+/// action.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstanceAction<V> {
+    /// Broadcast the message to all processes, including this one, using best-effort broadcast.
+    Broadcast(InstanceMessage<V>),
+    /// The instance decided the value, along with the trace id of the consensus.
+    Decide(Instance, V, Option<TraceId>),
+}
+
+/// The algorithm and current context of an instance.
+type InstanceState<P, V, F> = (FloodingAlgorithm<P, V, F>, FloodingContext<P, V>);
+
+/// Runs independent flooding consensus instances, each with its own algorithm and context.
+pub struct InstanceManager<P, V, F> {
+    instances: BTreeMap<Instance, InstanceState<P, V, F>>,
+}
+
+impl<P, V, F> InstanceManager<P, V, F>
+where
+    P: Process,
+    V: Value + PartialEq,
+    F: Fn(&[V]) -> Result<V, InternalError>,
+{
+    /// Constructs a new `InstanceManager` without any instances.
+    pub fn new() -> Self {
+        InstanceManager {
+            instances: BTreeMap::new(),
+        }
+    }
+
+    /// Adds `instance`, which is run by `algorithm` from the initial `context`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if the instance has already been added.
+    pub fn add_instance(
+        &mut self,
+        instance: Instance,
+        algorithm: FloodingAlgorithm<P, V, F>,
+        context: FloodingContext<P, V>,
+    ) -> Result<(), InvalidStateError> {
+        if self.instances.contains_key(&instance) {
+            return Err(InvalidStateError::with_message(format!(
+                "consensus instance {} has already been added",
+                instance
+            )));
+        }
+
+        self.instances.insert(instance, (algorithm, context));
+        Ok(())
+    }
+
+    /// Removes `instance`, such as once its decision has been recorded, returning its context if
+    /// it was present.
+    pub fn remove_instance(&mut self, instance: Instance) -> Option<FloodingContext<P, V>> {
+        self.instances.remove(&instance).map(|(_, context)| context)
+    }
+
+    /// Returns the current context of `instance`, or `None` if it has not been added.
+    pub fn context(&self, instance: Instance) -> Option<&FloodingContext<P, V>> {
+        self.instances.get(&instance).map(|(_, context)| context)
+    }
+
+    /// Handles `event` for `instance`, returning the actions which should be performed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the instance has not been added, or if its algorithm fails
+    /// to handle the event; in that case its context is unchanged.
+    pub fn event(
+        &mut self,
+        instance: Instance,
+        event: FloodingEvent<P, V>,
+    ) -> Result<Vec<InstanceAction<V>>, InternalError> {
+        let (algorithm, context) = self.instances.get_mut(&instance).ok_or_else(|| {
+            InternalError::with_message(format!("no consensus instance {}", instance))
+        })?;
+
+        let mut instance_actions = Vec::new();
+        for action in algorithm.event(event, context.clone())? {
+            match action {
+                FloodingAction::UpdateContext(updated) => *context = updated,
+                FloodingAction::Broadcast(message) => instance_actions.push(
+                    InstanceAction::Broadcast(InstanceMessage::new(instance, message)),
+                ),
+                FloodingAction::Decide(value, trace_id) => {
+                    instance_actions.push(InstanceAction::Decide(instance, value, trace_id))
+                }
+            }
+        }
+
+        Ok(instance_actions)
+    }
+
+    /// Handles `message`, delivered from `from`, for the instance it is tagged with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` as for [`InstanceManager::event`].
+    pub fn deliver(
+        &mut self,
+        from: P,
+        message: InstanceMessage<V>,
+    ) -> Result<Vec<InstanceAction<V>>, InternalError> {
+        let instance = message.instance();
+        self.event(
+            instance,
+            FloodingEvent::Deliver(from, message.into_message()),
+        )
+    }
+
+    /// Handles the crash of `process` for every instance, in instance order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if an instance fails to handle the crash; the instances before
+    /// it have already handled it, and their actions are lost.
+    pub fn crash(&mut self, process: P) -> Result<Vec<InstanceAction<V>>, InternalError> {
+        let instances: Vec<Instance> = self.instances.keys().copied().collect();
+
+        let mut actions = Vec::new();
+        for instance in instances {
+            actions.extend(self.event(instance, FloodingEvent::Crash(process))?);
+        }
+
+        Ok(actions)
+    }
+}
+
+impl<P, V, F> Default for InstanceManager<P, V, F>
+where
+    P: Process,
+    V: Value + PartialEq,
+    F: Fn(&[V]) -> Result<V, InternalError>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::{channel, Sender};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::broadcast::best_effort::{BestEffortBroadcastSender, BroadcastMessage};
+    use crate::communication::{IntraProcessNetwork, IntraProcessNetworkSender};
+    use crate::links::Receiver;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestValue(u64);
+
+    impl Value for TestValue {}
+
+    type SelectFn = fn(&[TestValue]) -> Result<TestValue, InternalError>;
+
+    type TestManager = Arc<Mutex<InstanceManager<TestProcess, TestValue, SelectFn>>>;
+
+    type TestBroadcastMessage = BroadcastMessage<TestProcess, InstanceMessage<TestValue>>;
+
+    type TestSender = BestEffortBroadcastSender<
+        TestProcess,
+        InstanceMessage<TestValue>,
+        IntraProcessNetworkSender<TestProcess, TestBroadcastMessage>,
+    >;
+
+    fn lowest(values: &[TestValue]) -> Result<TestValue, InternalError> {
+        values
+            .iter()
+            .min_by_key(|value| value.0)
+            .cloned()
+            .ok_or_else(|| InternalError::with_message("no values".into()))
+    }
+
+    /// Performs the actions of a process's manager: broadcasts over the network and reports
+    /// decisions on a channel.
+    struct Node {
+        process: TestProcess,
+        manager: TestManager,
+        broadcast: TestSender,
+        decisions: Sender<(TestProcess, Instance, TestValue)>,
+    }
+
+    impl Node {
+        fn perform(&self, actions: Vec<InstanceAction<TestValue>>) -> Result<(), InternalError> {
+            for action in actions {
+                match action {
+                    InstanceAction::Broadcast(message) => {
+                        self.broadcast.broadcast(message)?;
+                    }
+                    InstanceAction::Decide(instance, value, _) => self
+                        .decisions
+                        .send((self.process, instance, value))
+                        .map_err(|err| InternalError::from_source(Box::new(err)))?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Receiver<TestProcess, TestBroadcastMessage> for Node {
+        fn deliver(
+            &mut self,
+            from: TestProcess,
+            message: TestBroadcastMessage,
+        ) -> Result<(), InternalError> {
+            let actions = self
+                .manager
+                .lock()
+                .unwrap()
+                .deliver(from, message.into_payload())?;
+            self.perform(actions)
+        }
+    }
+
+    /// Tests that two instances run concurrently by three processes over an in-process network
+    /// each decide their own lowest proposal, without seeing the proposals of the other.
+    #[test]
+    fn test_independent_instances_over_intraprocess() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let (decision_sender, decision_receiver) = channel();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        // Each process has a node on the network, which handles deliveries, and a local node
+        // sharing its manager, which is used to propose
+        let local_nodes: Vec<Node> = processes
+            .iter()
+            .map(|process| {
+                let mut manager = InstanceManager::new();
+                for instance in [1, 2] {
+                    manager
+                        .add_instance(
+                            instance,
+                            FloodingAlgorithm::new(lowest as SelectFn),
+                            FloodingContext::new(processes.clone()),
+                        )
+                        .unwrap();
+                }
+                let manager = Arc::new(Mutex::new(manager));
+
+                let node = || Node {
+                    process: *process,
+                    manager: manager.clone(),
+                    broadcast: BestEffortBroadcastSender::over_intraprocess(
+                        *process,
+                        &network,
+                        processes.clone(),
+                    ),
+                    decisions: decision_sender.clone(),
+                };
+                let local_node = node();
+                network.add_process(*process, node());
+                local_node
+            })
+            .collect();
+
+        // Instance 1 proposes 10..=12 and instance 2 proposes 20..=22, so a decision from the
+        // wrong instance's proposals would be detected
+        for (index, node) in local_nodes.iter().enumerate() {
+            for instance in [1, 2] {
+                let value = TestValue(instance * 10 + index as u64);
+                let actions = node
+                    .manager
+                    .lock()
+                    .unwrap()
+                    .event(instance, FloodingEvent::Propose(value, None))
+                    .unwrap();
+                node.perform(actions).unwrap();
+            }
+        }
+
+        let mut decisions: Vec<_> = (0..processes.len() * 2)
+            .map(|_| {
+                decision_receiver
+                    .recv_timeout(Duration::from_secs(10))
+                    .expect("timed out waiting for decisions")
+            })
+            .collect();
+        network.shutdown().unwrap();
+
+        decisions.sort_by_key(|(process, instance, _)| (*instance, process.id));
+        let expected: Vec<_> = [1, 2]
+            .iter()
+            .flat_map(|instance| {
+                processes
+                    .iter()
+                    .map(move |process| (*process, *instance, TestValue(instance * 10)))
+            })
+            .collect();
+        assert_eq!(decisions, expected);
+
+        for node in &local_nodes {
+            let manager = node.manager.lock().unwrap();
+            assert_eq!(manager.context(1).unwrap().decision(), &Some(TestValue(10)));
+            assert_eq!(manager.context(2).unwrap().decision(), &Some(TestValue(20)));
+        }
+    }
+
+    /// Tests that a message for an instance which has not been added is rejected, and that an
+    /// instance cannot be added twice.
+    #[test]
+    fn test_unknown_and_duplicate_instances() {
+        let p1 = TestProcess { id: 1 };
+        let mut manager: InstanceManager<TestProcess, TestValue, SelectFn> = InstanceManager::new();
+        manager
+            .add_instance(
+                1,
+                FloodingAlgorithm::new(lowest),
+                FloodingContext::new(vec![p1]),
+            )
+            .unwrap();
+
+        assert!(manager
+            .add_instance(
+                1,
+                FloodingAlgorithm::new(lowest),
+                FloodingContext::new(vec![p1])
+            )
+            .is_err());
+
+        let err = manager
+            .deliver(
+                p1,
+                InstanceMessage::new(7, FloodingMessage::Decided(TestValue(1), None)),
+            )
+            .expect_err("message for unknown instance was accepted");
+        assert_eq!(err.to_string(), "no consensus instance 7");
+
+        assert!(manager.remove_instance(1).is_some());
+        assert!(manager.context(1).is_none());
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A learner for flooding consensus.
+//!
+//! A learner learns the decided value without taking part in the agreement. It is not included in
+//! the processes given to the acceptors' [`FloodingContext`](super::FloodingContext), so it is
+//! never waited on in a round, but it must be included in the best-effort broadcast so that it
+//! receives the `Decided` messages relayed by the acceptors.
+
+use std::marker::PhantomData;
+
+use crate::algorithm::{normalize_actions, Algorithm, ContextUpdate, TraceId, Value};
+use crate::error::InternalError;
+use crate::process::Process;
+
+use super::FloodingMessage;
+
+/// An event handled by a flooding consensus learner.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LearnerEvent<P, V> {
+    /// A message from the process was delivered by the best-effort broadcast.
+    Deliver(P, FloodingMessage<V>),
+}
+
+/// An action returned by a flooding consensus learner, to be performed by the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LearnerAction<P, V> {
+    /// The value has been decided, along with the trace id of the consensus.
+    Decide(V, Option<TraceId>),
+    /// Replace the stored context with this one.
+    UpdateContext(LearnerContext<P, V>),
+}
+
+impl<P, V> ContextUpdate for LearnerAction<P, V> {
+    fn is_context_update(&self) -> bool {
+        matches!(self, LearnerAction::UpdateContext(_))
+    }
+}
+
+/// The state of a flooding consensus learner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LearnerContext<P, V> {
+    acceptors: Vec<P>,
+    decision: Option<V>,
+}
+
+impl<P, V> LearnerContext<P, V>
+where
+    P: Process,
+{
+    /// Constructs a new `LearnerContext` which learns decisions from `acceptors`.
+    pub fn new(acceptors: Vec<P>) -> Self {
+        LearnerContext {
+            acceptors,
+            decision: None,
+        }
+    }
+
+    /// Returns the acceptors decisions are learned from.
+    pub fn acceptors(&self) -> &[P] {
+        &self.acceptors
+    }
+
+    /// Returns the learned value, or `None` if no decision has been learned.
+    pub fn decision(&self) -> &Option<V> {
+        &self.decision
+    }
+}
+
+/// The flooding consensus learner algorithm.
+///
+/// The first `Decided` message delivered from an acceptor is the decision; an acceptor only sends
+/// one after it has decided, and every acceptor decides the same value. Proposals are ignored.
+pub struct FloodingLearner<P, V> {
+    _process: PhantomData<P>,
+    _value: PhantomData<V>,
+}
+
+impl<P, V> FloodingLearner<P, V>
+where
+    P: Process,
+    V: Value,
+{
+    /// Constructs a new `FloodingLearner`.
+    pub fn new() -> Self {
+        FloodingLearner {
+            _process: PhantomData,
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<P, V> Default for FloodingLearner<P, V>
+where
+    P: Process,
+    V: Value,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, V> Algorithm<P> for FloodingLearner<P, V>
+where
+    P: Process,
+    V: Value,
+{
+    type Event = LearnerEvent<P, V>;
+    type Action = LearnerAction<P, V>;
+    type Context = LearnerContext<P, V>;
+
+    fn event(
+        &self,
+        event: Self::Event,
+        mut context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError> {
+        match event {
+            LearnerEvent::Deliver(process, FloodingMessage::Decided(value, trace_id))
+                if context.decision.is_none() && context.acceptors.contains(&process) =>
+            {
+                context.decision = Some(value.clone());
+                Ok(normalize_actions(vec![
+                    LearnerAction::UpdateContext(context),
+                    LearnerAction::Decide(value, trace_id),
+                ]))
+            }
+            LearnerEvent::Deliver(_, _) => Ok(vec![]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+
+    use crate::algorithm::flooding::{
+        FloodingAction, FloodingAlgorithm, FloodingContext, FloodingEvent, Round,
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestValue(u64);
+
+    impl Value for TestValue {}
+
+    fn lowest(values: &[TestValue]) -> Result<TestValue, InternalError> {
+        values
+            .iter()
+            .min_by_key(|value| value.0)
+            .cloned()
+            .ok_or_else(|| InternalError::with_message("no values".into()))
+    }
+
+    /// Tests that with two acceptors and one learner, where every broadcast also reaches the
+    /// learner, the learner decides the acceptors' value exactly once, and the acceptors decide in
+    /// the first round as they would without a learner.
+    #[test]
+    fn test_learner_observes_decision() {
+        let acceptors = [TestProcess { id: 1 }, TestProcess { id: 2 }];
+        let learner_process = TestProcess { id: 3 };
+
+        let algorithm = FloodingAlgorithm::new(lowest);
+        let learner = FloodingLearner::new();
+
+        let mut contexts: Vec<FloodingContext<TestProcess, TestValue>> = acceptors
+            .iter()
+            .map(|_| FloodingContext::new(acceptors.to_vec()))
+            .collect();
+        let mut learner_context = LearnerContext::new(acceptors.to_vec());
+
+        let mut broadcasts = VecDeque::new();
+        let mut acceptor_decisions = Vec::new();
+        let mut learner_decisions = Vec::new();
+
+        let mut events: VecDeque<(usize, FloodingEvent<TestProcess, TestValue>)> = VecDeque::new();
+        events.push_back((0, FloodingEvent::Propose(TestValue(5), None)));
+        events.push_back((1, FloodingEvent::Propose(TestValue(3), None)));
+
+        loop {
+            while let Some((index, event)) = events.pop_front() {
+                let actions = algorithm
+                    .event(event, contexts[index].clone())
+                    .expect("acceptor failed");
+                for action in actions {
+                    match action {
+                        FloodingAction::UpdateContext(context) => contexts[index] = context,
+                        FloodingAction::Broadcast(message) => {
+                            broadcasts.push_back((acceptors[index], message))
+                        }
+                        FloodingAction::Decide(value, _) => acceptor_decisions.push(value),
+                    }
+                }
+            }
+
+            let (from, message) = match broadcasts.pop_front() {
+                Some(broadcast) => broadcast,
+                None => break,
+            };
+
+            for index in 0..acceptors.len() {
+                events.push_back((index, FloodingEvent::Deliver(from, message.clone())));
+            }
+
+            for action in learner
+                .event(
+                    LearnerEvent::Deliver(from, message),
+                    learner_context.clone(),
+                )
+                .expect("learner failed")
+            {
+                match action {
+                    LearnerAction::UpdateContext(context) => learner_context = context,
+                    LearnerAction::Decide(value, _) => learner_decisions.push(value),
+                }
+            }
+        }
+
+        assert_eq!(acceptor_decisions, vec![TestValue(3), TestValue(3)]);
+        assert_eq!(learner_decisions, vec![TestValue(3)]);
+        assert_eq!(learner_context.decision(), &Some(TestValue(3)));
+        for context in &contexts {
+            assert_eq!(context.round(), Round::new(1));
+            assert_eq!(context.correct(), &acceptors[..]);
+            assert!(!context.received_from()[1].contains(&learner_process));
+        }
+    }
+
+    /// Tests that a learner of a single acceptor learns the decision, which the acceptor reaches
+    /// as it proposes without broadcasting a proposal.
+    #[test]
+    fn test_learner_of_single_acceptor() {
+        let acceptor = TestProcess { id: 1 };
+        let algorithm = FloodingAlgorithm::new(lowest);
+        let learner = FloodingLearner::new();
+
+        let broadcasts: Vec<FloodingMessage<TestValue>> = algorithm
+            .event(
+                FloodingEvent::Propose(TestValue(4), None),
+                FloodingContext::new(vec![acceptor]),
+            )
+            .expect("acceptor failed")
+            .into_iter()
+            .filter_map(|action| match action {
+                FloodingAction::Broadcast(message) => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            broadcasts,
+            vec![FloodingMessage::Decided(TestValue(4), None)]
+        );
+
+        let actions = learner
+            .event(
+                LearnerEvent::Deliver(acceptor, broadcasts[0].clone()),
+                LearnerContext::new(vec![acceptor]),
+            )
+            .expect("learner failed");
+        assert!(actions.contains(&LearnerAction::Decide(TestValue(4), None)));
+    }
+
+    /// Tests that a `Decided` message from a process which is not an acceptor is ignored.
+    #[test]
+    fn test_ignore_decided_from_non_acceptor() {
+        let learner = FloodingLearner::new();
+        let context = LearnerContext::new(vec![TestProcess { id: 1 }]);
+
+        let actions = learner
+            .event(
+                LearnerEvent::Deliver(
+                    TestProcess { id: 9 },
+                    FloodingMessage::Decided(TestValue(1), None),
+                ),
+                context,
+            )
+            .expect("learner failed");
+        assert!(actions.is_empty());
+    }
+}
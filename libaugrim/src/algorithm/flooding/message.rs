@@ -0,0 +1,54 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::TraceId;
+use crate::message::Message;
+
+use super::Round;
+
+/// A message exchanged between processes running flooding consensus.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FloodingMessage<V> {
+    /// The proposals known to the sender at the given round, and the trace id of the consensus.
+    Proposal(Round, Vec<V>, Option<TraceId>),
+    /// The proposal sent in strong-validity mode: the proposals known to the sender at the given
+    /// round, those of them known to have been proposed only by crashed processes, and the trace
+    /// id of the consensus.
+    StrongProposal(Round, Vec<V>, Vec<V>, Option<TraceId>),
+    /// The value decided by the sender, and the trace id of the consensus.
+    Decided(V, Option<TraceId>),
+}
+
+impl<V> Message for FloodingMessage<V> {}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    /// Tests that each variant of `FloodingMessage` survives a round trip through JSON.
+    #[test]
+    fn test_serde_round_trip() {
+        for message in [
+            FloodingMessage::Proposal(Round::new(2), vec![1u64, 2], None),
+            FloodingMessage::StrongProposal(Round::new(2), vec![1, 2], vec![2], None),
+            FloodingMessage::Decided(1, Some(TraceId::new("trace"))),
+        ] {
+            let json = serde_json::to_string(&message).expect("failed to serialize");
+            let decoded: FloodingMessage<u64> =
+                serde_json::from_str(&json).expect("failed to deserialize");
+            assert_eq!(decoded, message);
+        }
+    }
+}
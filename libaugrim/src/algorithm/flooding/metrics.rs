@@ -0,0 +1,32 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics reported by flooding consensus, for monitoring.
+
+use super::Round;
+
+/// A sink for the metrics of a [`FloodingAlgorithm`](super::FloodingAlgorithm).
+///
+/// Metrics are only recorded for events which the algorithm handles successfully, so they
+/// reflect the changes made to the context.
+pub trait FloodingMetrics: Send + Sync {
+    /// Records that the consensus advanced to `round`.
+    fn record_round_advance(&self, round: Round);
+
+    /// Records that the consensus decided in `round`.
+    fn record_decision(&self, round: Round);
+
+    /// Records that a proposal for `round` was delivered.
+    fn record_proposal(&self, round: Round);
+}
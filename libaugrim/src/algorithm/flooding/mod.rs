@@ -0,0 +1,50 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Flooding consensus.
+//!
+//! Implementation of the "Flooding Consensus" algorithm, which is a uniform consensus algorithm
+//! for the fail-stop model. It relies on a best-effort broadcast for communication and a perfect
+//! failure detector to learn of crashed processes.
+//!
+//! Processes exchange their known proposals in rounds. Once a process has heard from every
+//! process it believes to be correct in the current round, and the set of processes it heard
+//! from is unchanged from the previous round, it decides on a value selected from the proposals.
+//!
+//! Processes which only need to learn the decided value can run a [`FloodingLearner`] instead, and
+//! processes taking part in several consensus instances at once can run them with an
+//! [`InstanceManager`].
+
+mod action;
+mod algorithm;
+mod context;
+mod event;
+mod instance;
+mod learner;
+mod message;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod round;
+pub mod selectors;
+
+pub use action::FloodingAction;
+pub use algorithm::FloodingAlgorithm;
+pub use context::{FloodingContext, FloodingContextBuilder};
+pub use event::FloodingEvent;
+pub use instance::{InstanceAction, InstanceManager, InstanceMessage};
+pub use learner::{FloodingLearner, LearnerAction, LearnerContext, LearnerEvent};
+pub use message::FloodingMessage;
+#[cfg(feature = "metrics")]
+pub use metrics::FloodingMetrics;
+pub use round::Round;
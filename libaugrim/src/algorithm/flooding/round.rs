@@ -0,0 +1,85 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::error::InternalError;
+
+/// A round of flooding consensus.
+///
+/// Rounds start at 1. Round 0 is never sent in a message; it stands for the state before the
+/// consensus starts, in which every process is considered heard from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Round(u64);
+
+impl Round {
+    /// Constructs the round with the given number.
+    pub fn new(round: u64) -> Self {
+        Round(round)
+    }
+
+    /// Returns the first round of the consensus.
+    pub fn first() -> Self {
+        Round(1)
+    }
+
+    /// Returns the number of the round.
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the round before this one, or `None` if this is round 0.
+    pub fn prev(&self) -> Option<Round> {
+        self.0.checked_sub(1).map(Round)
+    }
+
+    /// Returns the round after this one, or `None` if it would overflow.
+    pub fn next(&self) -> Option<Round> {
+        self.0.checked_add(1).map(Round)
+    }
+
+    /// Returns the index of this round in the per-round state of a context.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the round cannot be represented as an index.
+    pub fn index(&self) -> Result<usize, InternalError> {
+        usize::try_from(self.0).map_err(|_| {
+            InternalError::with_message(format!("round {} cannot be used as an index", self.0))
+        })
+    }
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that `prev` and `next` are checked at the bounds of the round numbers.
+    #[test]
+    fn test_checked_prev_and_next() {
+        assert_eq!(Round::first().prev(), Some(Round::new(0)));
+        assert_eq!(Round::new(0).prev(), None);
+        assert_eq!(Round::first().next(), Some(Round::new(2)));
+        assert_eq!(Round::new(u64::MAX).next(), None);
+        assert_eq!(Round::new(3).index().unwrap(), 3);
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Common decision rules for use as the `select_func` of a
+//! [`FloodingAlgorithm`](super::FloodingAlgorithm).
+//!
+//! Each function selects a value from the proposals known in the deciding round, and returns an
+//! `InternalError` if there are none.
+
+use crate::error::InternalError;
+
+/// Selects the lowest proposal.
+pub fn lowest<V: Ord + Clone>(proposals: &[V]) -> Result<V, InternalError> {
+    proposals.iter().min().cloned().ok_or_else(no_proposals)
+}
+
+/// Selects the highest proposal.
+pub fn highest<V: Ord + Clone>(proposals: &[V]) -> Result<V, InternalError> {
+    proposals.iter().max().cloned().ok_or_else(no_proposals)
+}
+
+/// Selects the first proposal, in the order the proposals became known.
+///
+/// Processes may learn of the proposals in different orders, so this is only suitable when the
+/// order does not matter, such as when every process proposes the same value.
+pub fn first<V: Clone>(proposals: &[V]) -> Result<V, InternalError> {
+    proposals.first().cloned().ok_or_else(no_proposals)
+}
+
+fn no_proposals() -> InternalError {
+    InternalError::with_message("no proposals to select from".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::algorithm::flooding::{
+        FloodingAction, FloodingAlgorithm, FloodingContext, FloodingEvent, FloodingMessage, Round,
+    };
+    use crate::algorithm::{Algorithm, Value};
+    use crate::process::Process;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct TestValue(u64);
+
+    impl Value for TestValue {}
+
+    /// Tests that each selector picks the expected proposal.
+    #[test]
+    fn test_select() {
+        let proposals = [TestValue(4), TestValue(2), TestValue(9)];
+
+        assert_eq!(lowest(&proposals).unwrap(), TestValue(2));
+        assert_eq!(highest(&proposals).unwrap(), TestValue(9));
+        assert_eq!(first(&proposals).unwrap(), TestValue(4));
+    }
+
+    /// Tests that each selector returns an error when there are no proposals.
+    #[test]
+    fn test_select_empty() {
+        let proposals: [TestValue; 0] = [];
+
+        assert!(lowest(&proposals).is_err());
+        assert!(highest(&proposals).is_err());
+        assert!(first(&proposals).is_err());
+    }
+
+    /// Tests that a selector can be given directly to `FloodingAlgorithm::new`, and is used to
+    /// decide.
+    #[test]
+    fn test_use_with_flooding() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let algorithm = FloodingAlgorithm::new(highest);
+        let mut context = FloodingContext::new(vec![p1, p2]);
+
+        for (process, value) in [(p1, 3), (p2, 8)] {
+            let actions = algorithm
+                .event(
+                    FloodingEvent::Deliver(
+                        process,
+                        FloodingMessage::Proposal(Round::first(), vec![TestValue(value)], None),
+                    ),
+                    context,
+                )
+                .expect("failed to deliver");
+            context = match &actions[0] {
+                FloodingAction::UpdateContext(context) => context.clone(),
+                action => panic!("unexpected action: {:?}", action),
+            };
+        }
+
+        assert_eq!(context.decision(), &Some(TestValue(8)));
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::ContextUpdate;
+use crate::runtime::{Effect, RuntimeAction};
+
+use super::{HierarchicalContext, HierarchicalMessage};
+
+/// An action returned by hierarchical consensus, to be performed by the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HierarchicalAction<P, V> {
+    /// Broadcast the message to all processes, including this one, using best-effort broadcast.
+    Broadcast(HierarchicalMessage<V>),
+    /// The value has been decided.
+    Decide(V),
+    /// Replace the stored context with this one.
+    UpdateContext(HierarchicalContext<P, V>),
+}
+
+impl<P, V> ContextUpdate for HierarchicalAction<P, V> {
+    fn is_context_update(&self) -> bool {
+        matches!(self, HierarchicalAction::UpdateContext(_))
+    }
+}
+
+impl<P, V> RuntimeAction for HierarchicalAction<P, V> {
+    type Context = HierarchicalContext<P, V>;
+    type Message = HierarchicalMessage<V>;
+    type Value = V;
+
+    fn into_effect(self) -> Effect<Self::Context, Self::Message, Self::Value, Self> {
+        match self {
+            HierarchicalAction::Broadcast(message) => Effect::Broadcast(message),
+            HierarchicalAction::Decide(value) => Effect::Decide(value),
+            HierarchicalAction::UpdateContext(context) => Effect::UpdateContext(context),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use crate::algorithm::{normalize_actions, Algorithm, Value};
+use crate::error::InternalError;
+use crate::process::RankedProcess;
+
+use super::{HierarchicalAction, HierarchicalContext, HierarchicalEvent, HierarchicalMessage};
+
+/// The hierarchical consensus algorithm.
+pub struct HierarchicalAlgorithm<P, V> {
+    _process: PhantomData<P>,
+    _value: PhantomData<V>,
+}
+
+impl<P, V> HierarchicalAlgorithm<P, V>
+where
+    P: RankedProcess,
+    V: Value,
+{
+    /// Constructs a new `HierarchicalAlgorithm`.
+    pub fn new() -> Self {
+        HierarchicalAlgorithm {
+            _process: PhantomData,
+            _value: PhantomData,
+        }
+    }
+
+    fn handle_crash(
+        &self,
+        process: P,
+        mut context: HierarchicalContext<P, V>,
+    ) -> Result<Vec<HierarchicalAction<P, V>>, InternalError> {
+        if !context.detected().contains(&process) {
+            context.detected_mut().push(process);
+        }
+
+        let mut actions = self.decide_or_next_round(&mut context);
+        actions.insert(0, HierarchicalAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_deliver_decided(
+        &self,
+        process: P,
+        value: V,
+        mut context: HierarchicalContext<P, V>,
+    ) -> Result<Vec<HierarchicalAction<P, V>>, InternalError> {
+        let rank = context.rank_of(&process).ok_or_else(|| {
+            InternalError::with_message("decision delivered from an unknown process".into())
+        })?;
+
+        // Only the decisions of lower-ranked processes are adopted, and of those, the decision of
+        // the highest-ranked process supersedes the others
+        if rank < context.rank() && rank > context.proposer() {
+            context.set_proposal(Some(value));
+            context.set_proposer(rank);
+        }
+        if rank < context.rank() && !context.delivered().contains(&rank) {
+            context.delivered_mut().push(rank);
+        }
+
+        let mut actions = self.decide_or_next_round(&mut context);
+        actions.insert(0, HierarchicalAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_propose(
+        &self,
+        value: V,
+        mut context: HierarchicalContext<P, V>,
+    ) -> Result<Vec<HierarchicalAction<P, V>>, InternalError> {
+        if context.proposal().is_none() {
+            context.set_proposal(Some(value));
+        }
+
+        let mut actions = self.decide_or_next_round(&mut context);
+        actions.insert(0, HierarchicalAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    /// Moves past every round whose process has crashed or has had its decision delivered, and
+    /// decides once the round of this process is reached, if it has a proposal.
+    fn decide_or_next_round(
+        &self,
+        context: &mut HierarchicalContext<P, V>,
+    ) -> Vec<HierarchicalAction<P, V>> {
+        let mut actions = Vec::new();
+
+        while context.round() < context.rank() {
+            let round = context.round();
+            let detected = context.detected().iter().any(|process| {
+                context
+                    .rank_of(process)
+                    .map(|rank| rank == round)
+                    .unwrap_or(false)
+            });
+
+            if detected || context.delivered().contains(&round) {
+                context.set_round(round + 1);
+            } else {
+                break;
+            }
+        }
+
+        if context.round() == context.rank() && !context.broadcast() {
+            if let Some(proposal) = context.proposal().clone() {
+                debug!("decided in round {}", context.round());
+                context.set_broadcast(true);
+                actions.push(HierarchicalAction::Broadcast(HierarchicalMessage::Decided(
+                    proposal.clone(),
+                )));
+                actions.push(HierarchicalAction::Decide(proposal));
+            }
+        }
+
+        actions
+    }
+}
+
+impl<P, V> Default for HierarchicalAlgorithm<P, V>
+where
+    P: RankedProcess,
+    V: Value,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, V> Algorithm<P> for HierarchicalAlgorithm<P, V>
+where
+    P: RankedProcess,
+    V: Value,
+{
+    type Event = HierarchicalEvent<P, V>;
+    type Action = HierarchicalAction<P, V>;
+    type Context = HierarchicalContext<P, V>;
+
+    fn event(
+        &self,
+        event: Self::Event,
+        context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError> {
+        let actions = match event {
+            HierarchicalEvent::Crash(process) => self.handle_crash(process, context),
+            HierarchicalEvent::Deliver(process, HierarchicalMessage::Decided(value)) => {
+                self.handle_deliver_decided(process, value, context)
+            }
+            HierarchicalEvent::Propose(value) => self.handle_propose(value, context),
+        }?;
+
+        Ok(normalize_actions(actions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::process::Process;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    impl RankedProcess for TestProcess {}
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct TestValue(u64);
+
+    impl Value for TestValue {}
+
+    type TestAlgorithm = HierarchicalAlgorithm<TestProcess, TestValue>;
+    type TestContext = HierarchicalContext<TestProcess, TestValue>;
+    type TestAction = HierarchicalAction<TestProcess, TestValue>;
+
+    fn processes() -> Vec<TestProcess> {
+        (1..=3).map(|id| TestProcess { id }).collect()
+    }
+
+    /// Returns the context from the `UpdateContext` action, which must be the first action.
+    fn updated_context(actions: &[TestAction]) -> TestContext {
+        match actions.first() {
+            Some(HierarchicalAction::UpdateContext(context)) => context.clone(),
+            _ => panic!("first action was not UpdateContext: {:?}", actions),
+        }
+    }
+
+    /// Handles each event in turn, returning the decision, if any, and the final context.
+    fn run(
+        this_process: TestProcess,
+        events: Vec<HierarchicalEvent<TestProcess, TestValue>>,
+    ) -> (Option<TestValue>, TestContext) {
+        let algorithm = TestAlgorithm::new();
+        let mut context = TestContext::new(this_process, processes()).unwrap();
+        let mut decision = None;
+
+        for event in events {
+            let actions = algorithm.event(event, context).expect("failed event");
+            context = updated_context(&actions);
+            for action in actions {
+                if let HierarchicalAction::Decide(value) = action {
+                    assert_eq!(decision, None, "decided twice");
+                    decision = Some(value);
+                }
+            }
+        }
+
+        (decision, context)
+    }
+
+    /// Tests that the lowest-ranked process decides its own proposal as soon as it proposes.
+    #[test]
+    fn test_lowest_rank_decides_own_proposal() {
+        let p1 = processes()[0];
+        let actions = TestAlgorithm::new()
+            .event(
+                HierarchicalEvent::Propose(TestValue(1)),
+                TestContext::new(p1, processes()).unwrap(),
+            )
+            .expect("failed to propose");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                HierarchicalAction::Broadcast(HierarchicalMessage::Decided(TestValue(1))),
+                HierarchicalAction::Decide(TestValue(1)),
+            ]
+        );
+    }
+
+    /// Tests that a higher-ranked process waits for the lower-ranked processes, and decides the
+    /// proposal of the lowest-ranked process rather than its own.
+    #[test]
+    fn test_decide_lowest_ranked_proposal() {
+        let [p1, p2, p3] = [processes()[0], processes()[1], processes()[2]];
+
+        let (decision, context) = run(
+            p3,
+            vec![
+                HierarchicalEvent::Propose(TestValue(3)),
+                HierarchicalEvent::Deliver(p1, HierarchicalMessage::Decided(TestValue(1))),
+            ],
+        );
+        assert_eq!(decision, None);
+        assert_eq!(context.round(), 2);
+
+        let (decision, _) = run(
+            p3,
+            vec![
+                HierarchicalEvent::Propose(TestValue(3)),
+                HierarchicalEvent::Deliver(p1, HierarchicalMessage::Decided(TestValue(1))),
+                HierarchicalEvent::Deliver(p2, HierarchicalMessage::Decided(TestValue(1))),
+            ],
+        );
+        assert_eq!(decision, Some(TestValue(1)));
+    }
+
+    /// Tests that when the lowest-ranked process crashes before its decision is delivered, every
+    /// other process decides the proposal of the lowest-ranked correct process.
+    #[test]
+    fn test_decide_after_crash() {
+        let [p1, p2, p3] = [processes()[0], processes()[1], processes()[2]];
+
+        let (decision, _) = run(
+            p2,
+            vec![
+                HierarchicalEvent::Propose(TestValue(2)),
+                HierarchicalEvent::Crash(p1),
+            ],
+        );
+        assert_eq!(decision, Some(TestValue(2)));
+
+        // p3 learns of the crash after p2's decision is delivered
+        let (decision, context) = run(
+            p3,
+            vec![
+                HierarchicalEvent::Propose(TestValue(3)),
+                HierarchicalEvent::Deliver(p2, HierarchicalMessage::Decided(TestValue(2))),
+                HierarchicalEvent::Crash(p1),
+            ],
+        );
+        assert_eq!(decision, Some(TestValue(2)));
+        assert_eq!(context.proposer(), 2);
+    }
+
+    /// Tests that a process which has not proposed adopts a delivered decision, so it decides
+    /// once its round is reached even without a proposal of its own.
+    #[test]
+    fn test_decide_adopted_proposal_without_proposing() {
+        let [p1, p2, _] = [processes()[0], processes()[1], processes()[2]];
+
+        let (decision, _) = run(
+            p2,
+            vec![HierarchicalEvent::Deliver(
+                p1,
+                HierarchicalMessage::Decided(TestValue(1)),
+            )],
+        );
+        assert_eq!(decision, Some(TestValue(1)));
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::error::InvalidStateError;
+use crate::process::{rank, RankedProcess};
+
+use super::Rank;
+
+/// The state of hierarchical consensus at a single process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HierarchicalContext<P, V> {
+    processes: Vec<P>,
+    rank: Rank,
+    detected: Vec<P>,
+    delivered: Vec<Rank>,
+    proposal: Option<V>,
+    proposer: Rank,
+    round: Rank,
+    broadcast: bool,
+}
+
+impl<P, V> HierarchicalContext<P, V>
+where
+    P: RankedProcess,
+    V: Clone,
+{
+    /// Constructs the initial context for `this_process`, for the given set of processes.
+    ///
+    /// The processes are ranked by their `Ord` implementation, starting at 1 for the least
+    /// process, so every process must be given the same set of processes, in any order. The
+    /// consensus starts in round 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `this_process` is not in `processes`.
+    pub fn new(this_process: P, processes: Vec<P>) -> Result<Self, InvalidStateError> {
+        let processes = rank(processes);
+        let rank = processes
+            .iter()
+            .position(|process| *process == this_process)
+            .ok_or_else(|| {
+                InvalidStateError::with_message(
+                    "this process is not in the set of processes".into(),
+                )
+            })?
+            + 1;
+
+        Ok(HierarchicalContext {
+            processes,
+            rank,
+            detected: Vec::new(),
+            delivered: Vec::new(),
+            proposal: None,
+            proposer: 0,
+            round: 1,
+            broadcast: false,
+        })
+    }
+
+    /// Returns the processes, in the order of their rank.
+    pub fn processes(&self) -> &Vec<P> {
+        &self.processes
+    }
+
+    /// Returns the rank of this process.
+    pub fn rank(&self) -> Rank {
+        self.rank
+    }
+
+    /// Returns the rank of `process`, or `None` if it is not one of the processes.
+    pub fn rank_of(&self, process: &P) -> Option<Rank> {
+        self.processes
+            .iter()
+            .position(|p| p == process)
+            .map(|index| index + 1)
+    }
+
+    /// Returns the processes detected as crashed.
+    pub fn detected(&self) -> &Vec<P> {
+        &self.detected
+    }
+
+    /// Returns the processes detected as crashed, for updating.
+    pub fn detected_mut(&mut self) -> &mut Vec<P> {
+        &mut self.detected
+    }
+
+    /// Returns the ranks of the processes whose decision has been delivered.
+    pub fn delivered(&self) -> &Vec<Rank> {
+        &self.delivered
+    }
+
+    /// Returns the ranks of the processes whose decision has been delivered, for updating.
+    pub fn delivered_mut(&mut self) -> &mut Vec<Rank> {
+        &mut self.delivered
+    }
+
+    /// Returns the value this process will decide: its own proposal, or the decision adopted from
+    /// a lower-ranked process.
+    pub fn proposal(&self) -> &Option<V> {
+        &self.proposal
+    }
+
+    /// Sets the proposal of this process.
+    pub fn set_proposal(&mut self, proposal: Option<V>) {
+        self.proposal = proposal
+    }
+
+    /// Returns the rank of the process whose proposal was adopted, or 0 if none has been.
+    pub fn proposer(&self) -> Rank {
+        self.proposer
+    }
+
+    /// Sets the rank of the process whose proposal was adopted.
+    pub fn set_proposer(&mut self, proposer: Rank) {
+        self.proposer = proposer
+    }
+
+    /// Returns the current round, which is the rank of the process whose turn it is to decide.
+    pub fn round(&self) -> Rank {
+        self.round
+    }
+
+    /// Sets the current round.
+    pub fn set_round(&mut self, round: Rank) {
+        self.round = round
+    }
+
+    /// Returns true if this process has decided and broadcast its decision.
+    pub fn broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    /// Sets whether this process has decided and broadcast its decision.
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        self.broadcast = broadcast
+    }
+}
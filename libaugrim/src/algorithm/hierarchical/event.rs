@@ -0,0 +1,26 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::HierarchicalMessage;
+
+/// An event handled by hierarchical consensus.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HierarchicalEvent<P, V> {
+    /// The process was detected as crashed by the failure detector.
+    Crash(P),
+    /// A message from the process was delivered by the best-effort broadcast.
+    Deliver(P, HierarchicalMessage<V>),
+    /// The value is proposed by this process.
+    Propose(V),
+}
@@ -0,0 +1,41 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hierarchical consensus.
+//!
+//! Implementation of the "Hierarchical Consensus" algorithm, which is a consensus algorithm for
+//! the fail-stop model. It relies on a best-effort broadcast for communication and a perfect
+//! failure detector to learn of crashed processes.
+//!
+//! Processes are ranked by their [`RankedProcess`](crate::process::RankedProcess) order, and the
+//! consensus proceeds in one round per rank. In each round, the process of that rank decides on
+//! its proposal and broadcasts it; every higher-ranked process adopts the proposal in place of its
+//! own. A round ends when its process's decision is delivered or the process is detected as
+//! crashed.
+
+mod action;
+mod algorithm;
+mod context;
+mod event;
+mod message;
+
+pub use action::HierarchicalAction;
+pub use algorithm::HierarchicalAlgorithm;
+pub use context::HierarchicalContext;
+pub use event::HierarchicalEvent;
+pub use message::HierarchicalMessage;
+
+/// The rank of a process in hierarchical consensus, starting at 1; each round of the consensus
+/// is identified by the rank of the process which decides in it.
+pub type Rank = usize;
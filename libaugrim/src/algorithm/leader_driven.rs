@@ -0,0 +1,470 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Leader-driven consensus.
+//!
+//! Implementation of the "Leader-Driven Consensus" algorithm, which composes
+//! [epoch change](super::epoch_change) with one [epoch consensus](super::epoch_consensus)
+//! instance per epoch. Each time epoch change starts an epoch, the instance of the current epoch
+//! is aborted, and the instance of the new epoch is constructed from its state, so a value which
+//! may have been decided in an earlier epoch is carried over to every later one. The leader of
+//! the current epoch proposes once it has a value, and the first decision of any instance is the
+//! decision of the consensus.
+//!
+//! The trusted leader is given to [`LeaderDrivenConsensus::trust`], typically by an eventual
+//! leader detector.
+
+use crate::algorithm::epoch_change::{
+    EpochChangeAction, EpochChangeAlgorithm, EpochChangeContext, EpochChangeEvent,
+    EpochChangeMessage, Timestamp,
+};
+use crate::algorithm::epoch_consensus::{
+    EpochConsensusAction, EpochConsensusAlgorithm, EpochConsensusContext, EpochConsensusEvent,
+    EpochConsensusMessage, EpochState,
+};
+use crate::algorithm::{Algorithm, Value};
+use crate::error::{InternalError, InvalidStateError};
+use crate::failure_detector::EventualLeaderDetectorReceiver;
+use crate::links::{PerfectLink, Receiver, Sender};
+use crate::message::Message;
+use crate::process::Process;
+
+/// A message exchanged between processes running leader-driven consensus.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LeaderDrivenMessage<V> {
+    /// A message of epoch change.
+    EpochChange(EpochChangeMessage),
+    /// A message of the epoch consensus instance of the epoch with the timestamp.
+    EpochConsensus(Timestamp, EpochConsensusMessage<V>),
+}
+
+impl<V> Message for LeaderDrivenMessage<V> {}
+
+/// A message delivered for an epoch which this process has not started yet.
+type PendingMessage<P, V> = (P, Timestamp, EpochConsensusMessage<V>);
+
+/// Runs leader-driven consensus for a single process, sending messages over perfect links and
+/// passing the decided value to a callback.
+///
+/// Messages delivered to this process are given to [`LeaderDrivenConsensus::deliver`], or through
+/// its [`Receiver`] implementation.
+pub struct LeaderDrivenConsensus<P, V, S, D> {
+    epoch_change: EpochChangeAlgorithm<P>,
+    epoch_change_context: EpochChangeContext<P>,
+    epoch_consensus: EpochConsensusAlgorithm<P, V>,
+    epoch_consensus_context: EpochConsensusContext<P, V>,
+    pending: Vec<PendingMessage<P, V>>,
+    value: Option<V>,
+    proposed: bool,
+    decided: bool,
+    sender: S,
+    on_decide: D,
+}
+
+impl<P, V, S, D> LeaderDrivenConsensus<P, V, S, D>
+where
+    P: Process,
+    V: Value,
+    S: Sender<P, LeaderDrivenMessage<V>> + PerfectLink,
+    D: FnMut(V) -> Result<(), InternalError>,
+{
+    /// Constructs a new `LeaderDrivenConsensus` for `this_process`, for the given set of
+    /// processes, sending messages with `sender` and passing the decided value to `on_decide`.
+    ///
+    /// Every process must be given the same processes in the same order. The first process is the
+    /// leader of the initial epoch, which has timestamp 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `this_process` is not in `processes`.
+    pub fn new(
+        this_process: P,
+        processes: Vec<P>,
+        sender: S,
+        on_decide: D,
+    ) -> Result<Self, InvalidStateError> {
+        let epoch_change_context = EpochChangeContext::new(this_process, processes.clone())?;
+        let epoch_consensus_context = EpochConsensusContext::new(
+            this_process,
+            processes,
+            0,
+            *epoch_change_context.trusted(),
+            EpochState::default(),
+        )?;
+
+        Ok(LeaderDrivenConsensus {
+            epoch_change: EpochChangeAlgorithm::new(),
+            epoch_change_context,
+            epoch_consensus: EpochConsensusAlgorithm::new(),
+            epoch_consensus_context,
+            pending: Vec::new(),
+            value: None,
+            proposed: false,
+            decided: false,
+            sender,
+            on_decide,
+        })
+    }
+
+    /// Returns the timestamp of the current epoch.
+    pub fn epoch(&self) -> Timestamp {
+        self.epoch_consensus_context.timestamp()
+    }
+
+    /// Returns the leader of the current epoch.
+    pub fn leader(&self) -> &P {
+        self.epoch_consensus_context.leader()
+    }
+
+    /// Returns whether a value has been decided.
+    pub fn decided(&self) -> bool {
+        self.decided
+    }
+
+    /// Proposes `value`, which is proposed by this process whenever it leads an epoch.
+    ///
+    /// Only the first proposal is kept; later proposals are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the proposal cannot be made by the current epoch.
+    pub fn propose(&mut self, value: V) -> Result<(), InternalError> {
+        if self.value.is_none() {
+            self.value = Some(value);
+        }
+
+        self.propose_if_leader()
+    }
+
+    /// Handles `process` becoming the trusted leader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if epoch change fails to handle the leader, or if a message
+    /// cannot be sent.
+    pub fn trust(&mut self, process: P) -> Result<(), InternalError> {
+        self.epoch_change_event(EpochChangeEvent::Trust(process))
+    }
+
+    /// Handles `message`, delivered from `from`.
+    ///
+    /// A message for an earlier epoch is dropped, and a message for a later epoch is held until
+    /// that epoch starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the message cannot be handled, if a message cannot be sent,
+    /// or if the decide callback fails.
+    pub fn deliver(
+        &mut self,
+        from: P,
+        message: LeaderDrivenMessage<V>,
+    ) -> Result<(), InternalError> {
+        match message {
+            LeaderDrivenMessage::EpochChange(message) => {
+                self.epoch_change_event(EpochChangeEvent::Deliver(from, message))
+            }
+            LeaderDrivenMessage::EpochConsensus(timestamp, message) => {
+                if timestamp == self.epoch() {
+                    self.epoch_consensus_event(EpochConsensusEvent::Deliver(from, message))
+                } else {
+                    if timestamp > self.epoch() {
+                        self.pending.push((from, timestamp, message));
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    fn epoch_change_event(&mut self, event: EpochChangeEvent<P>) -> Result<(), InternalError> {
+        let actions = self
+            .epoch_change
+            .event(event, self.epoch_change_context.clone())?;
+
+        for action in actions {
+            match action {
+                EpochChangeAction::UpdateContext(context) => self.epoch_change_context = context,
+                EpochChangeAction::Broadcast(message) => {
+                    self.broadcast(LeaderDrivenMessage::EpochChange(message))?
+                }
+                EpochChangeAction::Send(to, message) => self
+                    .sender
+                    .send(&to, LeaderDrivenMessage::EpochChange(message))?,
+                EpochChangeAction::StartEpoch(timestamp, leader) => {
+                    self.start_epoch(timestamp, leader)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn epoch_consensus_event(
+        &mut self,
+        event: EpochConsensusEvent<P, V>,
+    ) -> Result<(), InternalError> {
+        let actions = self
+            .epoch_consensus
+            .event(event, self.epoch_consensus_context.clone())?;
+        let timestamp = self.epoch();
+
+        for action in actions {
+            match action {
+                EpochConsensusAction::UpdateContext(context) => {
+                    self.epoch_consensus_context = context
+                }
+                EpochConsensusAction::Broadcast(message) => {
+                    self.broadcast(LeaderDrivenMessage::EpochConsensus(timestamp, message))?
+                }
+                EpochConsensusAction::Send(to, message) => self
+                    .sender
+                    .send(&to, LeaderDrivenMessage::EpochConsensus(timestamp, message))?,
+                EpochConsensusAction::Decide(value) => {
+                    if !self.decided {
+                        self.decided = true;
+                        debug!("decided in epoch {}", timestamp);
+                        (self.on_decide)(value)?;
+                    }
+                }
+                // Aborting is handled by start_epoch, which uses the state directly
+                EpochConsensusAction::Aborted(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Aborts the instance of the current epoch and starts the instance of the new epoch from its
+    /// state, then delivers the messages held for the new epoch.
+    fn start_epoch(&mut self, timestamp: Timestamp, leader: P) -> Result<(), InternalError> {
+        self.epoch_consensus_event(EpochConsensusEvent::Abort)?;
+
+        let context = &self.epoch_consensus_context;
+        self.epoch_consensus_context = EpochConsensusContext::new(
+            *context.this_process(),
+            context.processes().clone(),
+            timestamp,
+            leader,
+            context.state().clone(),
+        )
+        .map_err(|err| InternalError::from_source(Box::new(err)))?;
+        self.proposed = false;
+
+        let (current, pending): (Vec<_>, Vec<_>) = self
+            .pending
+            .drain(..)
+            .filter(|(_, pending_timestamp, _)| *pending_timestamp >= timestamp)
+            .partition(|(_, pending_timestamp, _)| *pending_timestamp == timestamp);
+        self.pending = pending;
+
+        self.propose_if_leader()?;
+        for (from, _, message) in current {
+            self.epoch_consensus_event(EpochConsensusEvent::Deliver(from, message))?;
+        }
+
+        Ok(())
+    }
+
+    /// Proposes the value to the current epoch if this process leads it and has not yet proposed
+    /// in it.
+    fn propose_if_leader(&mut self) -> Result<(), InternalError> {
+        if !self.epoch_consensus_context.is_leader() || self.proposed {
+            return Ok(());
+        }
+
+        match self.value.clone() {
+            Some(value) => {
+                self.proposed = true;
+                self.epoch_consensus_event(EpochConsensusEvent::Propose(value))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn broadcast(&self, message: LeaderDrivenMessage<V>) -> Result<(), InternalError> {
+        for process in self.epoch_consensus_context.processes() {
+            self.sender.send(process, message.clone())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<P, V, S, D> Receiver<P, LeaderDrivenMessage<V>> for LeaderDrivenConsensus<P, V, S, D>
+where
+    P: Process,
+    V: Value,
+    S: Sender<P, LeaderDrivenMessage<V>> + PerfectLink,
+    D: FnMut(V) -> Result<(), InternalError>,
+{
+    fn deliver(&mut self, from: P, message: LeaderDrivenMessage<V>) -> Result<(), InternalError> {
+        LeaderDrivenConsensus::deliver(self, from, message)
+    }
+}
+
+impl<P, V, S, D> EventualLeaderDetectorReceiver<P> for LeaderDrivenConsensus<P, V, S, D>
+where
+    P: Process,
+    V: Value,
+    S: Sender<P, LeaderDrivenMessage<V>> + PerfectLink,
+    D: FnMut(V) -> Result<(), InternalError>,
+{
+    fn trust(&mut self, process: P) -> Result<(), InternalError> {
+        LeaderDrivenConsensus::trust(self, process)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::{channel, Sender as ChannelSender};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::communication::{IntraProcessNetwork, IntraProcessNetworkSender};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct TestValue(u64);
+
+    impl Value for TestValue {}
+
+    type TestMessage = LeaderDrivenMessage<TestValue>;
+
+    type DecideFn = Box<dyn FnMut(TestValue) -> Result<(), InternalError> + Send>;
+
+    type TestConsensus = Arc<
+        Mutex<
+            LeaderDrivenConsensus<
+                TestProcess,
+                TestValue,
+                IntraProcessNetworkSender<TestProcess, TestMessage>,
+                DecideFn,
+            >,
+        >,
+    >;
+
+    /// Delivers messages from the network to a process's consensus, which is shared with the
+    /// test so it can propose and change the trusted leader.
+    struct Node {
+        consensus: TestConsensus,
+    }
+
+    impl Receiver<TestProcess, TestMessage> for Node {
+        fn deliver(
+            &mut self,
+            from: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.consensus.lock().unwrap().deliver(from, message)
+        }
+    }
+
+    fn consensus(
+        process: TestProcess,
+        processes: &[TestProcess],
+        network: &IntraProcessNetwork<TestProcess, TestMessage, Node>,
+        decisions: ChannelSender<(TestProcess, TestValue)>,
+    ) -> TestConsensus {
+        let on_decide: DecideFn = Box::new(move |value| {
+            decisions
+                .send((process, value))
+                .map_err(|err| InternalError::from_source(Box::new(err)))
+        });
+
+        Arc::new(Mutex::new(
+            LeaderDrivenConsensus::new(
+                process,
+                processes.to_vec(),
+                network.sender(process),
+                on_decide,
+            )
+            .unwrap(),
+        ))
+    }
+
+    /// Tests that when the first leader stalls without proposing and leadership moves to p2, every
+    /// process decides p2's proposal exactly once.
+    #[test]
+    fn test_decide_after_leader_change_over_intraprocess() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let (decision_sender, decision_receiver) = channel();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        let nodes: Vec<TestConsensus> = processes
+            .iter()
+            .map(|process| {
+                let consensus = consensus(*process, &processes, &network, decision_sender.clone());
+                network.add_process(
+                    *process,
+                    Node {
+                        consensus: consensus.clone(),
+                    },
+                );
+                consensus
+            })
+            .collect();
+
+        // Every process trusts p1, which never proposes, so no value can be decided
+        for node in &nodes {
+            node.lock().unwrap().trust(processes[0]).unwrap();
+        }
+        for (node, value) in nodes[1..].iter().zip([2, 3]) {
+            node.lock().unwrap().propose(TestValue(value)).unwrap();
+        }
+        assert!(decision_receiver
+            .recv_timeout(Duration::from_millis(100))
+            .is_err());
+
+        // Leadership moves to p2, which proposes in its epoch
+        for node in &nodes {
+            node.lock().unwrap().trust(processes[1]).unwrap();
+        }
+
+        let mut decisions: Vec<_> = (0..processes.len())
+            .map(|_| {
+                decision_receiver
+                    .recv_timeout(Duration::from_secs(10))
+                    .expect("timed out waiting for decisions")
+            })
+            .collect();
+        assert!(decision_receiver
+            .recv_timeout(Duration::from_millis(100))
+            .is_err());
+        network.shutdown().unwrap();
+
+        decisions.sort_by_key(|(process, _)| process.id);
+        assert_eq!(
+            decisions,
+            processes
+                .iter()
+                .map(|process| (*process, TestValue(2)))
+                .collect::<Vec<_>>()
+        );
+        for node in &nodes {
+            let node = node.lock().unwrap();
+            assert!(node.decided());
+            assert_eq!(node.leader(), &processes[1]);
+        }
+    }
+}
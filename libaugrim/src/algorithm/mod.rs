@@ -0,0 +1,183 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Distributed algorithms expressed as event handlers.
+//!
+//! An [`Algorithm`] does not perform any I/O itself. Each time an event occurs (a message is
+//! delivered, a process crashes, etc.), the algorithm is given the event and the current context
+//! and returns a list of actions for the caller to perform, such as sending messages or updating
+//! the stored context.
+
+mod decision_log;
+pub mod epoch_change;
+pub mod epoch_consensus;
+mod fault_model;
+pub mod flooding;
+pub mod hierarchical;
+pub mod leader_driven;
+pub mod randomized;
+mod trace;
+
+use crate::error::InternalError;
+use crate::process::Process;
+
+pub use decision_log::{DecisionLog, Instance};
+pub use fault_model::{min_cluster_size, validate_membership, FaultModel};
+pub use trace::TraceId;
+
+/// An action which may replace the stored context of an algorithm.
+pub trait ContextUpdate {
+    /// Returns true if this action replaces the stored context.
+    fn is_context_update(&self) -> bool;
+}
+
+/// Normalizes the actions returned for a single event so that there is at most one context
+/// update, and that it is the first action.
+///
+/// If there are multiple context updates, only the last is kept since it supersedes the others.
+/// The order of the remaining actions is preserved.
+pub fn normalize_actions<A: ContextUpdate>(actions: Vec<A>) -> Vec<A> {
+    let mut update = None;
+    let mut others = Vec::with_capacity(actions.len());
+
+    for action in actions {
+        if action.is_context_update() {
+            update = Some(action);
+        } else {
+            others.push(action);
+        }
+    }
+
+    match update {
+        Some(update) => {
+            others.insert(0, update);
+            others
+        }
+        None => others,
+    }
+}
+
+/// A value which can be agreed upon by an algorithm.
+///
+/// `Value` is implemented for the primitive types, `String`, and vectors, options and boxes of
+/// values, so those can be agreed upon directly. There is deliberately no blanket implementation
+/// for every `Clone` type: it would prevent a type from opting in with its own implementation,
+/// and every value type outside this crate would have to satisfy it instead. Other types opt in
+/// with `impl Value for MyType {}`.
+pub trait Value: Clone {}
+
+macro_rules! impl_value {
+    ($($value:ty),*) => {
+        $(impl Value for $value {})*
+    };
+}
+
+impl_value!(bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, String);
+
+impl<V: Value> Value for Vec<V> {}
+
+impl<V: Value> Value for Option<V> {}
+
+impl<V: Value> Value for Box<V> {}
+
+/// A distributed algorithm, implemented as a handler of events.
+pub trait Algorithm<P>
+where
+    P: Process,
+{
+    /// The events handled by the algorithm.
+    type Event;
+    /// The actions returned by the algorithm.
+    type Action;
+    /// The state of the algorithm at a single process, passed with each event.
+    type Context;
+
+    /// Handles an event, returning the actions which should be performed as a result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the event could not be handled; in that case no action has
+    /// been performed and the context which was passed in is still the current one.
+    fn event(
+        &self,
+        event: Self::Event,
+        context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flooding::{selectors, FloodingAlgorithm, FloodingContext, FloodingEvent};
+
+    use crate::runtime::Simulator;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Runs flooding consensus with the lowest-value selector among processes proposing `values`
+    /// in turn, and returns the decision of each process.
+    fn decide<V>(values: Vec<V>) -> Vec<V>
+    where
+        V: Value + PartialEq + Ord,
+    {
+        let processes: Vec<TestProcess> = (1..=values.len() as u64)
+            .map(|id| TestProcess { id })
+            .collect();
+        let mut sim = Simulator::new(
+            FloodingAlgorithm::new(selectors::lowest),
+            processes
+                .iter()
+                .map(|process| (*process, FloodingContext::new(processes.clone())))
+                .collect(),
+            FloodingEvent::Deliver,
+        );
+
+        for (process, value) in processes.iter().zip(values) {
+            sim.event(process, FloodingEvent::Propose(value, None))
+                .unwrap();
+        }
+        sim.run_until_quiescent(1000).unwrap();
+
+        sim.decisions()
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    /// Tests that `String` can be agreed upon without implementing `Value` for it.
+    #[test]
+    fn test_string_value() {
+        assert_eq!(
+            decide(vec!["b".to_string(), "a".to_string()]),
+            vec!["a".to_string(), "a".to_string()]
+        );
+    }
+
+    /// Tests that `u64`, and vectors of it, can be agreed upon without implementing `Value` for
+    /// them.
+    #[test]
+    fn test_u64_value() {
+        assert_eq!(decide(vec![7u64, 3, 5]), vec![3, 3, 3]);
+        assert_eq!(
+            decide(vec![vec![2u64], vec![1, 9]]),
+            vec![vec![1, 9], vec![1, 9]]
+        );
+    }
+}
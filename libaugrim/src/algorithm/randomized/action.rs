@@ -0,0 +1,49 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::ContextUpdate;
+use crate::runtime::{Effect, RuntimeAction};
+
+use super::{RandomizedContext, RandomizedMessage};
+
+/// An action returned by randomized binary consensus, to be performed by the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RandomizedAction<P> {
+    /// Broadcast the message to all processes, including this one, using best-effort broadcast.
+    Broadcast(RandomizedMessage),
+    /// The bit has been decided.
+    Decide(bool),
+    /// Replace the stored context with this one.
+    UpdateContext(RandomizedContext<P>),
+}
+
+impl<P> ContextUpdate for RandomizedAction<P> {
+    fn is_context_update(&self) -> bool {
+        matches!(self, RandomizedAction::UpdateContext(_))
+    }
+}
+
+impl<P> RuntimeAction for RandomizedAction<P> {
+    type Context = RandomizedContext<P>;
+    type Message = RandomizedMessage;
+    type Value = bool;
+
+    fn into_effect(self) -> Effect<Self::Context, Self::Message, Self::Value, Self> {
+        match self {
+            RandomizedAction::Broadcast(message) => Effect::Broadcast(message),
+            RandomizedAction::Decide(value) => Effect::Decide(value),
+            RandomizedAction::UpdateContext(context) => Effect::UpdateContext(context),
+        }
+    }
+}
@@ -0,0 +1,460 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use crate::algorithm::{normalize_actions, Algorithm};
+use crate::error::InternalError;
+use crate::process::Process;
+
+use super::{Phase, RandomizedAction, RandomizedContext, RandomizedEvent, RandomizedMessage};
+
+/// The randomized binary consensus algorithm.
+///
+/// The coin is given the round in which it is tossed. A common coin returns the same toss to
+/// every process in a round, for example by deriving it from a seed shared by all processes; a
+/// local coin tossed independently by each process also terminates, but may take many more
+/// rounds.
+pub struct RandomizedAlgorithm<P, C> {
+    coin: C,
+    _process: PhantomData<P>,
+}
+
+impl<P, C> RandomizedAlgorithm<P, C>
+where
+    P: Process,
+    C: Fn(u64) -> bool,
+{
+    /// Constructs a new `RandomizedAlgorithm` which breaks ties with `coin`.
+    pub fn new(coin: C) -> Self {
+        RandomizedAlgorithm {
+            coin,
+            _process: PhantomData,
+        }
+    }
+
+    fn handle_propose(
+        &self,
+        value: bool,
+        mut context: RandomizedContext<P>,
+    ) -> Result<Vec<RandomizedAction<P>>, InternalError> {
+        if context.phase() != Phase::Idle || context.decision().is_some() {
+            return Ok(vec![]);
+        }
+
+        context.set_proposal(Some(value));
+        context.set_round(1);
+        context.set_phase(Phase::One);
+
+        // Proposals delivered before this process proposed count toward the first phase
+        replay_pending(&mut context);
+        let mut actions = vec![RandomizedAction::Broadcast(RandomizedMessage::PhaseOne(
+            1, value,
+        ))];
+        actions.extend(self.advance(&mut context));
+        actions.insert(0, RandomizedAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_deliver(
+        &self,
+        process: P,
+        message: RandomizedMessage,
+        mut context: RandomizedContext<P>,
+    ) -> Result<Vec<RandomizedAction<P>>, InternalError> {
+        if context.decision().is_some() || !context.processes().contains(&process) {
+            return Ok(vec![]);
+        }
+
+        let (round, phase, value) = match message {
+            RandomizedMessage::Decided(value) => {
+                context.set_decision(Some(value));
+                context.set_phase(Phase::Idle);
+                return Ok(vec![
+                    RandomizedAction::UpdateContext(context),
+                    RandomizedAction::Broadcast(RandomizedMessage::Decided(value)),
+                    RandomizedAction::Decide(value),
+                ]);
+            }
+            RandomizedMessage::PhaseOne(round, value) => (round, Phase::One, Some(value)),
+            RandomizedMessage::PhaseTwo(round, value) => (round, Phase::Two, value),
+        };
+
+        if (round, phase) > (context.round(), context.phase()) {
+            context.pending_mut().push((process, message));
+            return Ok(vec![RandomizedAction::UpdateContext(context)]);
+        }
+        if (round, phase) < (context.round(), context.phase()) {
+            // The phase is over, so the message is no longer needed
+            return Ok(vec![]);
+        }
+
+        record(&mut context, process, value);
+        let mut actions = self.advance(&mut context);
+        actions.insert(0, RandomizedAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    /// Ends each phase for which the values of all but `faulty` processes have been received,
+    /// until one has not, or a bit is decided.
+    ///
+    /// Waiting for N - f values, rather than a majority, is what makes agreement hold when fewer
+    /// than half of the processes may crash: a process which decides a bit in the second phase
+    /// has seen it from more than f processes, so every other process sees it at least once
+    /// among the N - f values it waits for, and adopts it.
+    fn advance(&self, context: &mut RandomizedContext<P>) -> Vec<RandomizedAction<P>> {
+        let mut actions = Vec::new();
+        let quorum = context.processes().len().saturating_sub(context.faulty());
+
+        loop {
+            if context.decision().is_some() || context.values().len() < quorum {
+                return actions;
+            }
+
+            let round = context.round();
+            match context.phase() {
+                Phase::One => {
+                    let kept = [true, false]
+                        .iter()
+                        .copied()
+                        .find(|bit| 2 * count(context.values(), *bit) > context.processes().len());
+                    context.set_proposal(kept);
+                    context.set_phase(Phase::Two);
+                    actions.push(RandomizedAction::Broadcast(RandomizedMessage::PhaseTwo(
+                        round, kept,
+                    )));
+                }
+                Phase::Two => {
+                    if let Some(bit) = [true, false]
+                        .iter()
+                        .copied()
+                        .find(|bit| count(context.values(), *bit) > context.faulty())
+                    {
+                        debug!("decided in round {}", round);
+                        context.set_decision(Some(bit));
+                        context.set_phase(Phase::Idle);
+                        context.values_mut().clear();
+                        context.pending_mut().clear();
+                        actions.push(RandomizedAction::Broadcast(RandomizedMessage::Decided(bit)));
+                        actions.push(RandomizedAction::Decide(bit));
+                        return actions;
+                    }
+
+                    let proposal = context
+                        .values()
+                        .iter()
+                        .find_map(|(_, value)| *value)
+                        .unwrap_or_else(|| (self.coin)(round));
+                    context.set_proposal(Some(proposal));
+                    context.set_round(round + 1);
+                    context.set_phase(Phase::One);
+                    actions.push(RandomizedAction::Broadcast(RandomizedMessage::PhaseOne(
+                        round + 1,
+                        proposal,
+                    )));
+                }
+                Phase::Idle => return actions,
+            }
+
+            context.values_mut().clear();
+            replay_pending(context);
+        }
+    }
+}
+
+/// Records the value received from `process` in the current phase, unless it already sent one.
+fn record<P: Process>(context: &mut RandomizedContext<P>, process: P, value: Option<bool>) {
+    if !context.values().iter().any(|(p, _)| *p == process) {
+        context.values_mut().push((process, value));
+    }
+}
+
+/// Records the pending values for the current phase.
+fn replay_pending<P: Process>(context: &mut RandomizedContext<P>) {
+    let current = (context.round(), context.phase());
+    let pending = std::mem::take(context.pending_mut());
+
+    for (process, message) in pending {
+        let (round, phase, value) = match &message {
+            RandomizedMessage::PhaseOne(round, value) => (*round, Phase::One, Some(*value)),
+            RandomizedMessage::PhaseTwo(round, value) => (*round, Phase::Two, *value),
+            RandomizedMessage::Decided(_) => continue,
+        };
+
+        if (round, phase) == current {
+            record(context, process, value);
+        } else if (round, phase) > current {
+            context.pending_mut().push((process, message));
+        }
+    }
+}
+
+/// Returns the number of processes which sent `bit`.
+fn count<P>(values: &[(P, Option<bool>)], bit: bool) -> usize {
+    values
+        .iter()
+        .filter(|(_, value)| *value == Some(bit))
+        .count()
+}
+
+impl<P, C> Algorithm<P> for RandomizedAlgorithm<P, C>
+where
+    P: Process,
+    C: Fn(u64) -> bool,
+{
+    type Event = RandomizedEvent<P>;
+    type Action = RandomizedAction<P>;
+    type Context = RandomizedContext<P>;
+
+    fn event(
+        &self,
+        event: Self::Event,
+        context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError> {
+        let actions = match event {
+            RandomizedEvent::Deliver(process, message) => {
+                self.handle_deliver(process, message, context)
+            }
+            RandomizedEvent::Propose(value) => self.handle_propose(value, context),
+        }?;
+
+        Ok(normalize_actions(actions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::runtime::Simulator;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Simulates the processes proposing `proposals` in turn, with up to one crash tolerated, and
+    /// returns the decision of each process.
+    fn run<C>(coin: C, proposals: &[bool]) -> Vec<(TestProcess, bool)>
+    where
+        C: Fn(u64) -> bool,
+    {
+        let processes: Vec<TestProcess> = (1..=proposals.len() as u64)
+            .map(|id| TestProcess { id })
+            .collect();
+        let mut sim = Simulator::new(
+            RandomizedAlgorithm::new(coin),
+            processes
+                .iter()
+                .map(|process| {
+                    (
+                        *process,
+                        RandomizedContext::new(processes.clone(), 1).unwrap(),
+                    )
+                })
+                .collect(),
+            RandomizedEvent::Deliver,
+        );
+
+        for (process, proposal) in processes.iter().zip(proposals) {
+            sim.event(process, RandomizedEvent::Propose(*proposal))
+                .unwrap();
+        }
+        sim.run_until_quiescent(10_000).unwrap();
+
+        sim.decisions().to_vec()
+    }
+
+    /// Tests that when every process proposes the same bit, that bit is decided in the first
+    /// round, whatever the coin.
+    #[test]
+    fn test_validity() {
+        for bit in [true, false] {
+            let decisions = run(|_| !bit, &[bit; 3]);
+
+            assert_eq!(decisions.len(), 3);
+            assert!(decisions.iter().all(|(_, decided)| *decided == bit));
+        }
+    }
+
+    /// Tests that processes proposing different bits terminate with every process deciding the
+    /// same bit, using a common coin which alternates between rounds.
+    #[test]
+    fn test_agreement_on_mixed_proposals() {
+        for proposals in [
+            [true, false, true],
+            [false, true, false],
+            [true, false, false],
+        ] {
+            let decisions = run(|round| round % 2 == 0, &proposals);
+
+            assert_eq!(decisions.len(), 3, "{:?}", proposals);
+            let bit = decisions[0].1;
+            assert!(decisions.iter().all(|(_, decided)| *decided == bit));
+        }
+
+        let decisions = run(|round| round % 2 == 0, &[true, false, true, false, true]);
+        assert_eq!(decisions.len(), 5);
+        assert!(decisions
+            .iter()
+            .all(|(_, decided)| *decided == decisions[0].1));
+    }
+
+    /// Tests that a message for a later phase is kept until that phase is reached, and then
+    /// counted.
+    #[test]
+    fn test_later_phase_buffered() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let algorithm = RandomizedAlgorithm::new(|_| true);
+        let context = RandomizedContext::new(processes.clone(), 1).unwrap();
+
+        let actions = algorithm
+            .event(
+                RandomizedEvent::Deliver(processes[1], RandomizedMessage::PhaseTwo(1, Some(true))),
+                context,
+            )
+            .unwrap();
+        let context = match &actions[0] {
+            RandomizedAction::UpdateContext(context) => context.clone(),
+            action => panic!("unexpected action: {:?}", action),
+        };
+        assert_eq!(context.pending().len(), 1);
+        assert!(context.values().is_empty());
+
+        let mut context = context;
+        for event in [
+            RandomizedEvent::Propose(true),
+            RandomizedEvent::Deliver(processes[0], RandomizedMessage::PhaseOne(1, true)),
+            RandomizedEvent::Deliver(processes[2], RandomizedMessage::PhaseOne(1, true)),
+        ] {
+            let actions = algorithm.event(event, context.clone()).unwrap();
+            if let Some(RandomizedAction::UpdateContext(updated)) = actions.first() {
+                context = updated.clone();
+            }
+        }
+
+        assert_eq!(context.phase(), Phase::Two);
+        assert_eq!(context.values(), &vec![(processes[1], Some(true))]);
+        assert!(context.pending().is_empty());
+    }
+
+    /// Tests that a proposal delivered before this process proposes counts toward the first
+    /// phase, so that with one of three processes silent, the phase ends once this process's own
+    /// proposal is delivered.
+    #[test]
+    fn test_proposal_delivered_before_propose() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let algorithm = RandomizedAlgorithm::new(|_| true);
+
+        let mut context = RandomizedContext::new(processes.clone(), 1).unwrap();
+        let mut actions = Vec::new();
+        for event in [
+            RandomizedEvent::Deliver(processes[1], RandomizedMessage::PhaseOne(1, true)),
+            RandomizedEvent::Propose(true),
+            RandomizedEvent::Deliver(processes[0], RandomizedMessage::PhaseOne(1, true)),
+        ] {
+            actions = algorithm.event(event, context.clone()).unwrap();
+            if let Some(RandomizedAction::UpdateContext(updated)) = actions.first() {
+                context = updated.clone();
+            }
+        }
+
+        assert_eq!(context.phase(), Phase::Two);
+        assert!(context.pending().is_empty());
+        assert_eq!(
+            actions[1],
+            RandomizedAction::Broadcast(RandomizedMessage::PhaseTwo(1, Some(true)))
+        );
+    }
+
+    /// Tests agreement with five processes tolerating one crash, fewer than the two a majority
+    /// would allow, under an adversary which splits the processes into the overlapping groups
+    /// {p1, p2, p3} and {p3, p4, p5}: each process is delivered the messages from its own group
+    /// first, and decisions last.
+    #[test]
+    fn test_agreement_below_maximum_faulty() {
+        let processes: Vec<TestProcess> = (1..=5).map(|id| TestProcess { id }).collect();
+        let group = |process: &TestProcess| if process.id <= 2 { 1..=3 } else { 3..=5 };
+        let algorithm = RandomizedAlgorithm::new(|_| false);
+        let mut contexts: Vec<RandomizedContext<TestProcess>> = processes
+            .iter()
+            .map(|_| RandomizedContext::new(processes.clone(), 1).unwrap())
+            .collect();
+        let mut queue: Vec<(TestProcess, TestProcess, RandomizedMessage)> = Vec::new();
+        let mut decisions = Vec::new();
+
+        let mut handle =
+            |index: usize,
+             event: RandomizedEvent<TestProcess>,
+             contexts: &mut Vec<RandomizedContext<TestProcess>>,
+             queue: &mut Vec<(TestProcess, TestProcess, RandomizedMessage)>| {
+                for action in algorithm.event(event, contexts[index].clone()).unwrap() {
+                    match action {
+                        RandomizedAction::UpdateContext(context) => contexts[index] = context,
+                        RandomizedAction::Broadcast(message) => {
+                            for to in &processes {
+                                queue.push((processes[index], *to, message.clone()));
+                            }
+                        }
+                        RandomizedAction::Decide(bit) => decisions.push((processes[index], bit)),
+                    }
+                }
+            };
+
+        for (index, proposal) in [true, true, true, false, false].iter().enumerate() {
+            handle(
+                index,
+                RandomizedEvent::Propose(*proposal),
+                &mut contexts,
+                &mut queue,
+            );
+        }
+
+        for _ in 0..10_000 {
+            let next = queue
+                .iter()
+                .position(|(from, to, message)| {
+                    !matches!(message, RandomizedMessage::Decided(_))
+                        && group(to).contains(&from.id)
+                })
+                .or_else(|| {
+                    queue.iter().position(|(_, _, message)| {
+                        !matches!(message, RandomizedMessage::Decided(_))
+                    })
+                })
+                .or(if queue.is_empty() { None } else { Some(0) });
+            let (from, to, message) = match next {
+                Some(index) => queue.remove(index),
+                None => break,
+            };
+            let index = processes.iter().position(|process| *process == to).unwrap();
+            handle(
+                index,
+                RandomizedEvent::Deliver(from, message),
+                &mut contexts,
+                &mut queue,
+            );
+        }
+
+        assert!(queue.is_empty());
+        assert_eq!(decisions.len(), 5);
+        assert!(
+            decisions.iter().all(|(_, bit)| *bit == decisions[0].1),
+            "{:?}",
+            decisions
+        );
+    }
+}
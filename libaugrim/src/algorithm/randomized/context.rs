@@ -0,0 +1,167 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::{validate_membership, FaultModel};
+use crate::error::InvalidStateError;
+use crate::process::Process;
+
+use super::RandomizedMessage;
+
+/// The phase of a round of randomized binary consensus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// This process has not proposed yet, or has decided.
+    Idle,
+    /// Proposals are being collected.
+    One,
+    /// The bits kept from the proposals are being collected.
+    Two,
+}
+
+/// The state of randomized binary consensus at a single process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RandomizedContext<P> {
+    processes: Vec<P>,
+    faulty: usize,
+    round: u64,
+    phase: Phase,
+    proposal: Option<bool>,
+    decision: Option<bool>,
+    values: Vec<(P, Option<bool>)>,
+    pending: Vec<(P, RandomizedMessage)>,
+}
+
+impl<P> RandomizedContext<P>
+where
+    P: Process,
+{
+    /// Constructs the initial context for the given set of processes, of which up to `faulty`
+    /// may crash.
+    ///
+    /// The consensus starts in round 0, and moves to round 1 when this process proposes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if there are not more than `2 * faulty` processes.
+    pub fn new(processes: Vec<P>, faulty: usize) -> Result<Self, InvalidStateError> {
+        validate_membership(FaultModel::Quorum, faulty, &processes)?;
+
+        Ok(RandomizedContext {
+            processes,
+            faulty,
+            round: 0,
+            phase: Phase::Idle,
+            proposal: None,
+            decision: None,
+            values: Vec::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Returns the processes taking part in the consensus.
+    pub fn processes(&self) -> &Vec<P> {
+        &self.processes
+    }
+
+    /// Returns the number of processes which may crash.
+    pub fn faulty(&self) -> usize {
+        self.faulty
+    }
+
+    /// Returns the current round.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// Sets the current round.
+    pub fn set_round(&mut self, round: u64) {
+        self.round = round
+    }
+
+    /// Returns the phase of the current round.
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Sets the phase of the current round.
+    pub fn set_phase(&mut self, phase: Phase) {
+        self.phase = phase
+    }
+
+    /// Returns the proposal of this process in the current phase; in the second phase, it is the
+    /// bit kept from the first, if any.
+    pub fn proposal(&self) -> &Option<bool> {
+        &self.proposal
+    }
+
+    /// Sets the proposal of this process in the current phase.
+    pub fn set_proposal(&mut self, proposal: Option<bool>) {
+        self.proposal = proposal
+    }
+
+    /// Returns the decided value, or `None` if this process has not decided.
+    pub fn decision(&self) -> &Option<bool> {
+        &self.decision
+    }
+
+    /// Sets the decided value.
+    pub fn set_decision(&mut self, decision: Option<bool>) {
+        self.decision = decision
+    }
+
+    /// Returns the bits received in the current phase, along with the process which sent each.
+    pub fn values(&self) -> &Vec<(P, Option<bool>)> {
+        &self.values
+    }
+
+    /// Returns the bits received in the current phase, for updating.
+    pub fn values_mut(&mut self) -> &mut Vec<(P, Option<bool>)> {
+        &mut self.values
+    }
+
+    /// Returns the messages received for a later phase than the current one, which are handled
+    /// once it is reached.
+    pub fn pending(&self) -> &Vec<(P, RandomizedMessage)> {
+        &self.pending
+    }
+
+    /// Returns the messages received for a later phase than the current one, for updating.
+    pub fn pending_mut(&mut self) -> &mut Vec<(P, RandomizedMessage)> {
+        &mut self.pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Tests that a context can only be constructed for a majority of correct processes.
+    #[test]
+    fn test_new_requires_majority() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+
+        let context = RandomizedContext::new(processes.clone(), 1).unwrap();
+        assert_eq!(context.round(), 0);
+        assert_eq!(context.phase(), Phase::Idle);
+
+        assert!(RandomizedContext::new(processes, 2).is_err());
+    }
+}
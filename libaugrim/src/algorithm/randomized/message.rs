@@ -0,0 +1,29 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::message::Message;
+
+/// A message exchanged between processes running randomized binary consensus.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RandomizedMessage {
+    /// The proposal of the sender in the first phase of the given round.
+    PhaseOne(u64, bool),
+    /// The bit kept by the sender at the end of the first phase of the given round, if any.
+    PhaseTwo(u64, Option<bool>),
+    /// The bit decided by the sender.
+    Decided(bool),
+}
+
+impl Message for RandomizedMessage {}
@@ -0,0 +1,40 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Randomized binary consensus.
+//!
+//! Implementation of the "Randomized Binary Consensus" algorithm, which is a consensus algorithm
+//! on a single bit for the fail-silent model: it needs no failure detector, but tolerates only
+//! `f` crashed processes among `N > 2f`. It relies on a best-effort broadcast for communication.
+//!
+//! The consensus proceeds in rounds of two phases. In the first phase, each process broadcasts its
+//! proposal and waits for the proposals of `N - f` processes; if a majority of all processes
+//! proposed the same bit, it keeps that bit, otherwise it keeps no bit. In the second phase, each
+//! process broadcasts the bit it kept and waits for those of `N - f` processes. If more than `f`
+//! processes kept the same bit, that bit is decided. Otherwise a coin is tossed: a process which
+//! saw a kept bit proposes it in the next round, and the others propose the coin. With a common
+//! coin, which gives every process the same toss in a round, the processes eventually propose the
+//! same bit and decide.
+
+mod action;
+mod algorithm;
+mod context;
+mod event;
+mod message;
+
+pub use action::RandomizedAction;
+pub use algorithm::RandomizedAlgorithm;
+pub use context::{Phase, RandomizedContext};
+pub use event::RandomizedEvent;
+pub use message::RandomizedMessage;
@@ -0,0 +1,43 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Trace ids, which correlate the messages and actions of one logical operation.
+
+use std::fmt;
+
+/// Identifies a single logical operation, such as one consensus, across processes.
+///
+/// An algorithm which supports tracing carries the trace id from the event which starts the
+/// operation on every message and action which follows from it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceId(String);
+
+impl TraceId {
+    /// Constructs a trace id from `id`.
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        TraceId(id.into())
+    }
+
+    /// Returns the trace id as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
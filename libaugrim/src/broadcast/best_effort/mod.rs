@@ -0,0 +1,788 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Best-effort broadcast.
+//!
+//! Implementation of the "Basic Broadcast" algorithm: a message is broadcast by sending it to
+//! every process, including the sender. If the sender does not crash, every correct process
+//! eventually delivers the message.
+
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::communication::{IntraProcessNetwork, IntraProcessNetworkSender};
+use crate::error::InternalError;
+use crate::links::Receiver;
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+
+use super::{BroadcastId, BroadcastIdGenerator};
+
+/// A message sent by best-effort broadcast, tagged with the id of the broadcast.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BroadcastMessage<P, M> {
+    id: BroadcastId<P>,
+    payload: M,
+}
+
+impl<P, M> BroadcastMessage<P, M>
+where
+    P: Process,
+{
+    /// Constructs the message of the broadcast with the given id.
+    pub fn new(id: BroadcastId<P>, payload: M) -> Self {
+        BroadcastMessage { id, payload }
+    }
+
+    /// Returns the id of the broadcast.
+    pub fn id(&self) -> &BroadcastId<P> {
+        &self.id
+    }
+
+    /// Returns the payload of the broadcast.
+    pub fn payload(&self) -> &M {
+        &self.payload
+    }
+
+    /// Returns the payload of the broadcast, consuming the message.
+    pub fn into_payload(self) -> M {
+        self.payload
+    }
+}
+
+impl<P, M> Message for BroadcastMessage<P, M> {}
+
+/// The outcome of sending one broadcast to each of its processes.
+///
+/// A broadcast which could not be sent to some processes, for example during a transient network
+/// outage, can be retried for just those processes with [`BestEffortBroadcastSender::retry`].
+#[derive(Debug)]
+pub struct BroadcastReport<P, M> {
+    message: BroadcastMessage<P, M>,
+    sent: Vec<P>,
+    failed: Vec<(P, InternalError)>,
+}
+
+impl<P, M> BroadcastReport<P, M>
+where
+    P: Process,
+{
+    /// Returns the id of the broadcast.
+    pub fn id(&self) -> &BroadcastId<P> {
+        self.message.id()
+    }
+
+    /// Returns the processes the message was sent to.
+    pub fn sent(&self) -> &[P] {
+        &self.sent
+    }
+
+    /// Returns the processes the message could not be sent to, along with the error for each.
+    pub fn failed(&self) -> &[(P, InternalError)] {
+        &self.failed
+    }
+
+    /// Returns true if the message was sent to every process.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Returns the processes the message could not be sent to, which are the targets of a retry.
+    pub fn into_retry_targets(self) -> Vec<P> {
+        self.failed
+            .into_iter()
+            .map(|(process, _)| process)
+            .collect()
+    }
+}
+
+/// An error returned when a broadcast could not be sent to some of its processes.
+///
+/// The broadcast was still sent to every other process.
+#[derive(Debug)]
+pub struct BroadcastError<P> {
+    id: BroadcastId<P>,
+    failed: Vec<(P, InternalError)>,
+}
+
+impl<P> BroadcastError<P>
+where
+    P: Process,
+{
+    /// Returns the id of the broadcast.
+    pub fn id(&self) -> &BroadcastId<P> {
+        &self.id
+    }
+
+    /// Returns the processes the message could not be sent to, along with the error for each.
+    pub fn failed(&self) -> &[(P, InternalError)] {
+        &self.failed
+    }
+}
+
+impl<P> error::Error for BroadcastError<P> where P: fmt::Debug {}
+
+impl<P> fmt::Display for BroadcastError<P>
+where
+    P: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unable to send broadcast to {} process(es): ",
+            self.failed.len()
+        )?;
+        for (index, (process, err)) in self.failed.iter().enumerate() {
+            if index > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{:?}: {}", process, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl<P> From<BroadcastError<P>> for InternalError
+where
+    P: fmt::Debug,
+{
+    fn from(err: BroadcastError<P>) -> Self {
+        InternalError::with_message(err.to_string())
+    }
+}
+
+type Comparator<P> = Box<dyn Fn(&P, &P) -> Ordering + Send + Sync>;
+
+/// The sending side of best-effort broadcast.
+pub struct BestEffortBroadcastSender<P, M, N> {
+    id_generator: BroadcastIdGenerator<P>,
+    network: N,
+    processes: Vec<P>,
+    ordering: Option<Comparator<P>>,
+    _message: PhantomData<M>,
+}
+
+impl<P, M, N> BestEffortBroadcastSender<P, M, N>
+where
+    P: Process,
+    M: Message + Clone,
+    N: NetworkSender<P, BroadcastMessage<P, M>>,
+{
+    /// Constructs a new `BestEffortBroadcastSender` which broadcasts from `this_process` to
+    /// `processes` over `network`.
+    pub fn new(this_process: P, processes: Vec<P>, network: N) -> Self {
+        BestEffortBroadcastSender {
+            id_generator: BroadcastIdGenerator::new(this_process),
+            network,
+            processes,
+            ordering: None,
+            _message: PhantomData,
+        }
+    }
+
+    /// Orders the processes with `compare`, so that every broadcast sends to them in the same
+    /// order regardless of the order in which they were given. Processes added later are kept
+    /// in the same order.
+    pub fn with_ordering<C>(mut self, compare: C) -> Self
+    where
+        C: Fn(&P, &P) -> Ordering + Send + Sync + 'static,
+    {
+        self.processes.sort_by(&compare);
+        self.ordering = Some(Box::new(compare));
+        self
+    }
+
+    /// Orders the processes by their `Ord` implementation; see
+    /// [`BestEffortBroadcastSender::with_ordering`].
+    pub fn sorted(self) -> Self
+    where
+        P: Ord + 'static,
+    {
+        self.with_ordering(Ord::cmp)
+    }
+
+    /// Returns the process broadcasts are sent from.
+    pub fn this_process(&self) -> &P {
+        self.id_generator.origin()
+    }
+
+    /// Returns the processes broadcasts are sent to, in the order they are sent to.
+    pub fn processes(&self) -> &[P] {
+        &self.processes
+    }
+
+    /// Adds `process` to the processes broadcasts are sent to, such as when it joins the
+    /// membership. If the processes are ordered it takes its place in that order, otherwise it
+    /// is sent to after the existing processes; adding a process which is already present has no
+    /// effect.
+    pub fn add_process(&mut self, process: P) {
+        if self.processes.contains(&process) {
+            return;
+        }
+
+        match &self.ordering {
+            Some(compare) => {
+                let index = self
+                    .processes
+                    .partition_point(|p| compare(p, &process) != Ordering::Greater);
+                self.processes.insert(index, process);
+            }
+            None => self.processes.push(process),
+        }
+    }
+
+    /// Removes `process` from the processes broadcasts are sent to, such as when it leaves the
+    /// membership, returning whether it was present.
+    pub fn remove_process(&mut self, process: &P) -> bool {
+        let len = self.processes.len();
+        self.processes.retain(|p| p != process);
+        self.processes.len() != len
+    }
+
+    /// Broadcasts `message` to every process, returning the id assigned to the broadcast.
+    ///
+    /// Messages are sent in the order of the processes given to
+    /// [`BestEffortBroadcastSender::new`], unless they have been reordered.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `BroadcastError` naming the processes the message could not be sent to; it is
+    /// still sent to every other process.
+    pub fn broadcast(&self, message: M) -> Result<BroadcastId<P>, BroadcastError<P>> {
+        let id = self.id_generator.next_id();
+        let report = self.send_to(&self.processes, BroadcastMessage::new(id, message));
+
+        if report.is_complete() {
+            Ok(id)
+        } else {
+            Err(BroadcastError {
+                id,
+                failed: report.failed,
+            })
+        }
+    }
+
+    /// Broadcasts `message` to every process, continuing past processes the message cannot be
+    /// sent to, and reports the processes it was and was not sent to.
+    pub fn broadcast_with_report(&self, message: M) -> BroadcastReport<P, M> {
+        let message = BroadcastMessage::new(self.id_generator.next_id(), message);
+        self.send_to(&self.processes, message)
+    }
+
+    /// Sends the broadcast of `report` again to the processes it could not be sent to, with the
+    /// same id, and reports the outcome for those processes only.
+    pub fn retry(&self, report: BroadcastReport<P, M>) -> BroadcastReport<P, M> {
+        let message = report.message.clone();
+        let targets = report.into_retry_targets();
+        self.send_to(&targets, message)
+    }
+
+    fn send_to(&self, processes: &[P], message: BroadcastMessage<P, M>) -> BroadcastReport<P, M> {
+        let mut sent = Vec::new();
+        let mut failed = Vec::new();
+
+        for process in processes {
+            match self.network.send(process, message.clone()) {
+                Ok(()) => sent.push(*process),
+                Err(err) => failed.push((*process, err)),
+            }
+        }
+
+        BroadcastReport {
+            message,
+            sent,
+            failed,
+        }
+    }
+}
+
+impl<P, M> BestEffortBroadcastSender<P, M, IntraProcessNetworkSender<P, BroadcastMessage<P, M>>>
+where
+    P: Process + Hash + Send + 'static,
+    M: Message + Clone + Send + 'static,
+{
+    /// Constructs a new `BestEffortBroadcastSender` which broadcasts from `this_process` to
+    /// `processes` over an in-process network.
+    ///
+    /// The receivers of the processes are added to `network` separately, with
+    /// [`IntraProcessNetwork::add_process`].
+    pub fn over_intraprocess<R>(
+        this_process: P,
+        network: &IntraProcessNetwork<P, BroadcastMessage<P, M>, R>,
+        processes: Vec<P>,
+    ) -> Self
+    where
+        R: Receiver<P, BroadcastMessage<P, M>> + Send + 'static,
+    {
+        Self::new(this_process, processes, network.sender(this_process))
+    }
+}
+
+/// The receiving side of best-effort broadcast.
+pub trait BestEffortBroadcastReceiver<P, M> {
+    /// Delivers `message`, which was broadcast by `process`.
+    fn deliver(&mut self, process: P, message: M) -> Result<(), InternalError>;
+}
+
+/// A best-effort broadcast receiver which records every message delivered, in delivery order.
+///
+/// Clones share the same record, so a clone may be registered with a network while another is
+/// kept to inspect what was delivered. It may be registered directly with an
+/// [`IntraProcessNetwork`], as it is also a link [`Receiver`].
+pub struct CollectingBestEffortReceiver<P, M> {
+    delivered: Arc<Mutex<Vec<(P, M)>>>,
+}
+
+impl<P, M> CollectingBestEffortReceiver<P, M>
+where
+    P: Process,
+    M: Clone,
+{
+    /// Constructs a new `CollectingBestEffortReceiver` which has not delivered any message.
+    pub fn new() -> Self {
+        CollectingBestEffortReceiver {
+            delivered: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns the messages delivered so far, along with the processes which broadcast them.
+    pub fn delivered(&self) -> Vec<(P, M)> {
+        self.lock().clone()
+    }
+
+    /// Locks the record. Pushing a message cannot leave it inconsistent, so a poisoned lock is
+    /// recovered.
+    fn lock(&self) -> MutexGuard<'_, Vec<(P, M)>> {
+        self.delivered
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<P, M> Clone for CollectingBestEffortReceiver<P, M> {
+    fn clone(&self) -> Self {
+        CollectingBestEffortReceiver {
+            delivered: Arc::clone(&self.delivered),
+        }
+    }
+}
+
+impl<P, M> Default for CollectingBestEffortReceiver<P, M>
+where
+    P: Process,
+    M: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, M> BestEffortBroadcastReceiver<P, M> for CollectingBestEffortReceiver<P, M>
+where
+    P: Process,
+    M: Clone,
+{
+    fn deliver(&mut self, process: P, message: M) -> Result<(), InternalError> {
+        self.lock().push((process, message));
+        Ok(())
+    }
+}
+
+impl<P, M> Receiver<P, M> for CollectingBestEffortReceiver<P, M>
+where
+    P: Process,
+    M: Clone,
+{
+    fn deliver(&mut self, from: P, message: M) -> Result<(), InternalError> {
+        BestEffortBroadcastReceiver::deliver(self, from, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(&'static str);
+
+    impl Message for TestMessage {}
+
+    type TestBroadcastMessage = BroadcastMessage<TestProcess, TestMessage>;
+
+    /// Records every message delivered, in delivery order.
+    #[derive(Default)]
+    struct CollectingReceiver {
+        delivered: Vec<(TestProcess, TestBroadcastMessage)>,
+    }
+
+    impl BestEffortBroadcastReceiver<TestProcess, TestBroadcastMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            process: TestProcess,
+            message: TestBroadcastMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.push((process, message));
+            Ok(())
+        }
+    }
+
+    /// A network which delivers each message sent to `to` directly into the receiver of that
+    /// process.
+    struct LoopbackNetwork {
+        from: TestProcess,
+        receivers: Vec<(TestProcess, RefCell<CollectingReceiver>)>,
+    }
+
+    impl NetworkSender<TestProcess, TestBroadcastMessage> for LoopbackNetwork {
+        fn send(
+            &self,
+            to: &TestProcess,
+            message: TestBroadcastMessage,
+        ) -> Result<(), InternalError> {
+            let (_, receiver) = self
+                .receivers
+                .iter()
+                .find(|(process, _)| process == to)
+                .ok_or_else(|| InternalError::with_message("unknown process".into()))?;
+            receiver.borrow_mut().deliver(self.from, message)
+        }
+    }
+
+    /// Tests that broadcasting the same payload twice assigns distinct ids to the two broadcasts,
+    /// and that both are delivered to every process as distinct messages.
+    #[test]
+    fn test_distinct_broadcast_ids() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let network = LoopbackNetwork {
+            from: p1,
+            receivers: vec![
+                (p1, RefCell::new(CollectingReceiver::default())),
+                (p2, RefCell::new(CollectingReceiver::default())),
+            ],
+        };
+        let sender = BestEffortBroadcastSender::new(p1, vec![p1, p2], network);
+
+        let first = sender.broadcast(TestMessage("value")).unwrap();
+        let second = sender.broadcast(TestMessage("value")).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(first, BroadcastId::new(p1, 0));
+        assert_eq!(second, BroadcastId::new(p1, 1));
+
+        for (_, receiver) in sender.network.receivers.iter() {
+            let delivered = &receiver.borrow().delivered;
+            assert_eq!(
+                delivered,
+                &vec![
+                    (p1, BroadcastMessage::new(first, TestMessage("value"))),
+                    (p1, BroadcastMessage::new(second, TestMessage("value"))),
+                ]
+            );
+        }
+    }
+
+    /// A network which records the process each message is sent to.
+    #[derive(Default)]
+    struct RecordingNetwork {
+        sent_to: RefCell<Vec<TestProcess>>,
+    }
+
+    impl NetworkSender<TestProcess, TestBroadcastMessage> for RecordingNetwork {
+        fn send(
+            &self,
+            to: &TestProcess,
+            _message: TestBroadcastMessage,
+        ) -> Result<(), InternalError> {
+            self.sent_to.borrow_mut().push(*to);
+            Ok(())
+        }
+    }
+
+    /// Tests that two senders built from the same processes, given in different orders, send to
+    /// them in the same order once sorted, and that a custom ordering is respected.
+    #[test]
+    fn test_deterministic_send_order() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+
+        let first =
+            BestEffortBroadcastSender::new(p1, vec![p3, p1, p2], RecordingNetwork::default())
+                .sorted();
+        let second =
+            BestEffortBroadcastSender::new(p1, vec![p2, p3, p1], RecordingNetwork::default())
+                .sorted();
+
+        first.broadcast(TestMessage("value")).unwrap();
+        second.broadcast(TestMessage("value")).unwrap();
+
+        assert_eq!(*first.network.sent_to.borrow(), vec![p1, p2, p3]);
+        assert_eq!(*second.network.sent_to.borrow(), vec![p1, p2, p3]);
+
+        let reversed =
+            BestEffortBroadcastSender::new(p1, vec![p2, p1, p3], RecordingNetwork::default())
+                .with_ordering(|a, b| b.cmp(a));
+        reversed.broadcast(TestMessage("value")).unwrap();
+        assert_eq!(*reversed.network.sent_to.borrow(), vec![p3, p2, p1]);
+    }
+
+    /// Tests that a removed process no longer receives broadcasts, and that an added process
+    /// receives them after the existing processes.
+    #[test]
+    fn test_update_processes() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+
+        let mut sender =
+            BestEffortBroadcastSender::new(p1, vec![p1, p2, p3], RecordingNetwork::default());
+
+        assert!(sender.remove_process(&p2));
+        assert!(!sender.remove_process(&p2));
+        assert_eq!(sender.processes(), &[p1, p3]);
+        sender.broadcast(TestMessage("first")).unwrap();
+        assert_eq!(*sender.network.sent_to.borrow(), vec![p1, p3]);
+
+        sender.add_process(p2);
+        sender.add_process(p2);
+        assert_eq!(sender.processes(), &[p1, p3, p2]);
+        sender.network.sent_to.borrow_mut().clear();
+        sender.broadcast(TestMessage("second")).unwrap();
+        assert_eq!(*sender.network.sent_to.borrow(), vec![p1, p3, p2]);
+    }
+
+    /// Tests that a process added to an ordered sender takes its place in the order.
+    #[test]
+    fn test_add_process_ordered() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let p4 = TestProcess { id: 4 };
+
+        let mut sorted =
+            BestEffortBroadcastSender::new(p1, vec![p3, p1], RecordingNetwork::default()).sorted();
+        sorted.add_process(p2);
+        sorted.add_process(p4);
+        assert_eq!(sorted.processes(), &[p1, p2, p3, p4]);
+        sorted.broadcast(TestMessage("value")).unwrap();
+        assert_eq!(*sorted.network.sent_to.borrow(), vec![p1, p2, p3, p4]);
+
+        let mut reversed =
+            BestEffortBroadcastSender::new(p1, vec![p1, p3], RecordingNetwork::default())
+                .with_ordering(|a, b| b.cmp(a));
+        reversed.add_process(p2);
+        assert_eq!(reversed.processes(), &[p3, p2, p1]);
+    }
+
+    type Delivered = Arc<Mutex<Vec<(TestProcess, TestBroadcastMessage)>>>;
+
+    /// Records every message delivered by an `IntraProcessNetwork`.
+    struct NetworkReceiver {
+        delivered: Delivered,
+    }
+
+    impl Receiver<TestProcess, TestBroadcastMessage> for NetworkReceiver {
+        fn deliver(
+            &mut self,
+            from: TestProcess,
+            message: TestBroadcastMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.lock().unwrap().push((from, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that senders constructed with `over_intraprocess` broadcast over an
+    /// `IntraProcessNetwork` to three in-process receivers, each of which delivers every broadcast,
+    /// including its own.
+    #[test]
+    fn test_over_intraprocess() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        let delivered: Vec<Delivered> = processes
+            .iter()
+            .map(|process| {
+                let delivered = Delivered::default();
+                network.add_process(
+                    *process,
+                    NetworkReceiver {
+                        delivered: delivered.clone(),
+                    },
+                );
+                delivered
+            })
+            .collect();
+
+        let ids: Vec<_> = processes
+            .iter()
+            .map(|process| {
+                BestEffortBroadcastSender::over_intraprocess(*process, &network, processes.clone())
+                    .broadcast(TestMessage("value"))
+                    .unwrap()
+            })
+            .collect();
+
+        network.shutdown().unwrap();
+
+        for delivered in delivered {
+            let mut delivered = delivered.lock().unwrap().clone();
+            delivered.sort_by_key(|(from, _)| from.id);
+            assert_eq!(
+                delivered,
+                processes
+                    .iter()
+                    .zip(ids.iter())
+                    .map(|(process, id)| (
+                        *process,
+                        BroadcastMessage::new(*id, TestMessage("value"))
+                    ))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    /// Tests that a sender and `CollectingBestEffortReceiver`s wired together over an
+    /// `IntraProcessNetwork` observe every broadcast, in the order each sender broadcast them.
+    #[test]
+    fn test_collecting_receiver_over_intraprocess() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        let receivers: Vec<CollectingBestEffortReceiver<TestProcess, TestBroadcastMessage>> =
+            processes
+                .iter()
+                .map(|process| {
+                    let receiver = CollectingBestEffortReceiver::new();
+                    network.add_process(*process, receiver.clone());
+                    receiver
+                })
+                .collect();
+
+        let sender =
+            BestEffortBroadcastSender::over_intraprocess(processes[0], &network, processes.clone());
+        let first = sender.broadcast(TestMessage("first")).unwrap();
+        let second = sender.broadcast(TestMessage("second")).unwrap();
+
+        network.shutdown().unwrap();
+
+        for receiver in receivers {
+            assert_eq!(
+                receiver.delivered(),
+                vec![
+                    (
+                        processes[0],
+                        BroadcastMessage::new(first, TestMessage("first"))
+                    ),
+                    (
+                        processes[0],
+                        BroadcastMessage::new(second, TestMessage("second"))
+                    ),
+                ]
+            );
+        }
+    }
+
+    /// A network which fails to send to the processes which are down, and records the processes
+    /// each message is sent to.
+    #[derive(Default)]
+    struct FlakyNetwork {
+        down: RefCell<Vec<TestProcess>>,
+        sent_to: RefCell<Vec<TestProcess>>,
+    }
+
+    impl NetworkSender<TestProcess, TestBroadcastMessage> for FlakyNetwork {
+        fn send(
+            &self,
+            to: &TestProcess,
+            _message: TestBroadcastMessage,
+        ) -> Result<(), InternalError> {
+            if self.down.borrow().contains(to) {
+                return Err(InternalError::with_message("process unreachable".into()));
+            }
+            self.sent_to.borrow_mut().push(*to);
+            Ok(())
+        }
+    }
+
+    /// Tests that a broadcast which fails to reach one process is still sent to the others, and
+    /// that the error names the process it could not be sent to.
+    #[test]
+    fn test_broadcast_partial_failure() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let network = FlakyNetwork::default();
+        network.down.borrow_mut().push(processes[0]);
+        let sender = BestEffortBroadcastSender::new(processes[0], processes.clone(), network);
+
+        let err = sender
+            .broadcast(TestMessage("value"))
+            .expect_err("partial broadcast was reported as successful");
+
+        assert_eq!(
+            *sender.network.sent_to.borrow(),
+            vec![processes[1], processes[2]]
+        );
+        assert_eq!(err.failed().len(), 1);
+        assert_eq!(err.failed()[0].0, processes[0]);
+        assert_eq!(
+            err.to_string(),
+            "unable to send broadcast to 1 process(es): TestProcess { id: 1 }: process unreachable"
+        );
+        assert_eq!(err.id().sequence(), 0);
+    }
+
+    /// Tests that a broadcast which fails to reach one process reports that process, and that
+    /// once it recovers, a retry sends the broadcast to that process only, with the same id.
+    #[test]
+    fn test_retry_failed_processes() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let network = FlakyNetwork::default();
+        network.down.borrow_mut().push(processes[1]);
+        let sender = BestEffortBroadcastSender::new(processes[0], processes.clone(), network);
+
+        let report = sender.broadcast_with_report(TestMessage("value"));
+        let id = *report.id();
+        assert!(!report.is_complete());
+        assert_eq!(report.sent(), &[processes[0], processes[2]]);
+        assert_eq!(report.failed().len(), 1);
+        assert_eq!(report.failed()[0].0, processes[1]);
+
+        sender.network.down.borrow_mut().clear();
+        sender.network.sent_to.borrow_mut().clear();
+        let report = sender.retry(report);
+
+        assert!(report.is_complete());
+        assert_eq!(report.id(), &id);
+        assert_eq!(report.sent(), &[processes[1]]);
+        assert_eq!(*sender.network.sent_to.borrow(), vec![processes[1]]);
+    }
+}
@@ -0,0 +1,541 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Byzantine reliable broadcast.
+//!
+//! Implementation of the "Authenticated Double-Echo Broadcast" algorithm, which tolerates up to
+//! `f` Byzantine processes among `N > 3f`. The network must authenticate the process each message
+//! is delivered from, so that a Byzantine process cannot impersonate another.
+//!
+//! The origin of a broadcast sends the message to every process. Each process echoes the first
+//! message it receives from the origin to every process; once more than `(N + f) / 2` processes
+//! have echoed the same message, it sends a ready message for it. A process also sends a ready
+//! message once more than `f` processes have, since at least one of them is correct. Once more
+//! than `2f` processes are ready for the same message, it is delivered. Even if the origin sends
+//! conflicting messages to different processes, every correct process which delivers delivers the
+//! same message, and if one does, every correct process does.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::algorithm::{validate_membership, FaultModel};
+use crate::error::{InternalError, InvalidStateError};
+use crate::links::Receiver;
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+
+use super::{BroadcastId, BroadcastIdGenerator};
+
+/// A message exchanged by Byzantine reliable broadcast, tagged with the id of its broadcast.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ByzantineBroadcastMessage<P, M> {
+    /// The message, sent by the origin of the broadcast.
+    Send(BroadcastId<P>, M),
+    /// The message received from the origin, echoed by the sender.
+    Echo(BroadcastId<P>, M),
+    /// The sender is ready to deliver the message.
+    Ready(BroadcastId<P>, M),
+}
+
+impl<P, M> Message for ByzantineBroadcastMessage<P, M> {}
+
+/// Receives messages delivered by Byzantine reliable broadcast.
+pub trait ByzantineBroadcastReceiver<P, M> {
+    /// Delivers `message`, which was broadcast by `origin`.
+    fn deliver(&mut self, origin: P, message: M) -> Result<(), InternalError>;
+}
+
+/// The sending side of Byzantine reliable broadcast.
+pub struct ByzantineBroadcastSender<P, M, N> {
+    id_generator: BroadcastIdGenerator<P>,
+    network: Arc<N>,
+    processes: Vec<P>,
+    faulty: usize,
+    _message: PhantomData<M>,
+}
+
+impl<P, M, N> ByzantineBroadcastSender<P, M, N>
+where
+    P: Process + Hash,
+    M: Message + Clone + PartialEq,
+    N: NetworkSender<P, ByzantineBroadcastMessage<P, M>>,
+{
+    /// Constructs a new `ByzantineBroadcastSender` which broadcasts from `this_process` to
+    /// `processes` over `network`, tolerating up to `faulty` Byzantine processes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if there are not more than `3 * faulty` processes.
+    pub fn new(
+        this_process: P,
+        processes: Vec<P>,
+        faulty: usize,
+        network: N,
+    ) -> Result<Self, InvalidStateError> {
+        validate_membership(FaultModel::Byzantine, faulty, &processes)?;
+
+        Ok(ByzantineBroadcastSender {
+            id_generator: BroadcastIdGenerator::new(this_process),
+            network: Arc::new(network),
+            processes,
+            faulty,
+            _message: PhantomData,
+        })
+    }
+
+    /// Broadcasts `message` to every process, returning the id assigned to the broadcast.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `InternalError` if the message cannot be sent to a process; it is still
+    /// sent to every other process.
+    pub fn broadcast(&self, message: M) -> Result<BroadcastId<P>, InternalError> {
+        let id = self.id_generator.next_id();
+        send_all(
+            &*self.network,
+            &self.processes,
+            ByzantineBroadcastMessage::Send(id, message),
+        )?;
+        Ok(id)
+    }
+
+    /// Returns the handler for messages delivered by the network to this process, which
+    /// delivers each broadcast message once to `receiver`.
+    pub fn delivery_handler<R>(&self, receiver: R) -> ByzantineBroadcastHandler<P, M, N, R>
+    where
+        R: ByzantineBroadcastReceiver<P, M>,
+    {
+        ByzantineBroadcastHandler {
+            network: self.network.clone(),
+            processes: self.processes.clone(),
+            faulty: self.faulty,
+            broadcasts: HashMap::new(),
+            receiver,
+        }
+    }
+}
+
+/// The progress of a single broadcast at this process.
+struct BroadcastState<P, M> {
+    sent_echo: bool,
+    sent_ready: bool,
+    delivered: bool,
+    echos: Vec<(P, M)>,
+    readys: Vec<(P, M)>,
+}
+
+impl<P, M> Default for BroadcastState<P, M> {
+    fn default() -> Self {
+        BroadcastState {
+            sent_echo: false,
+            sent_ready: false,
+            delivered: false,
+            echos: Vec::new(),
+            readys: Vec::new(),
+        }
+    }
+}
+
+/// Handles the messages delivered by the network on behalf of Byzantine reliable broadcast.
+///
+/// It may be registered directly with a network, as it is a link [`Receiver`].
+pub struct ByzantineBroadcastHandler<P, M, N, R> {
+    network: Arc<N>,
+    processes: Vec<P>,
+    faulty: usize,
+    broadcasts: HashMap<BroadcastId<P>, BroadcastState<P, M>>,
+    receiver: R,
+}
+
+impl<P, M, N, R> Receiver<P, ByzantineBroadcastMessage<P, M>>
+    for ByzantineBroadcastHandler<P, M, N, R>
+where
+    P: Process + Hash,
+    M: Message + Clone + PartialEq,
+    N: NetworkSender<P, ByzantineBroadcastMessage<P, M>>,
+    R: ByzantineBroadcastReceiver<P, M>,
+{
+    fn deliver(
+        &mut self,
+        from: P,
+        message: ByzantineBroadcastMessage<P, M>,
+    ) -> Result<(), InternalError> {
+        if !self.processes.contains(&from) {
+            return Ok(());
+        }
+
+        let n = self.processes.len();
+        let f = self.faulty;
+
+        match message {
+            ByzantineBroadcastMessage::Send(id, message) => {
+                let state = self.broadcasts.entry(id).or_default();
+                // Only the origin may send its broadcast, and only its first message is echoed
+                if from != *id.origin() || state.sent_echo {
+                    return Ok(());
+                }
+                state.sent_echo = true;
+                send_all(
+                    &*self.network,
+                    &self.processes,
+                    ByzantineBroadcastMessage::Echo(id, message),
+                )
+            }
+            ByzantineBroadcastMessage::Echo(id, message) => {
+                let state = self.broadcasts.entry(id).or_default();
+                if state.echos.iter().any(|(process, _)| *process == from) {
+                    return Ok(());
+                }
+                state.echos.push((from, message.clone()));
+
+                if !state.sent_ready && 2 * count(&state.echos, &message) > n + f {
+                    state.sent_ready = true;
+                    send_all(
+                        &*self.network,
+                        &self.processes,
+                        ByzantineBroadcastMessage::Ready(id, message),
+                    )?;
+                }
+                Ok(())
+            }
+            ByzantineBroadcastMessage::Ready(id, message) => {
+                let state = self.broadcasts.entry(id).or_default();
+                if state.readys.iter().any(|(process, _)| *process == from) {
+                    return Ok(());
+                }
+                state.readys.push((from, message.clone()));
+                let readys = count(&state.readys, &message);
+
+                if !state.sent_ready && readys > f {
+                    state.sent_ready = true;
+                    send_all(
+                        &*self.network,
+                        &self.processes,
+                        ByzantineBroadcastMessage::Ready(id, message.clone()),
+                    )?;
+                }
+
+                if !state.delivered && readys > 2 * f {
+                    state.delivered = true;
+                    self.receiver.deliver(*id.origin(), message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Returns the number of processes which sent `message`.
+fn count<P, M: PartialEq>(messages: &[(P, M)], message: &M) -> usize {
+    messages.iter().filter(|(_, m)| m == message).count()
+}
+
+/// Sends `message` to every process, returning the first error once it has been sent to the rest.
+fn send_all<P, M, N>(
+    network: &N,
+    processes: &[P],
+    message: ByzantineBroadcastMessage<P, M>,
+) -> Result<(), InternalError>
+where
+    M: Clone,
+    P: Clone,
+    N: NetworkSender<P, ByzantineBroadcastMessage<P, M>>,
+{
+    let mut result = Ok(());
+    for process in processes {
+        result = result.and(network.send(process, message.clone()));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(&'static str);
+
+    impl Message for TestMessage {}
+
+    type TestByzantineMessage = ByzantineBroadcastMessage<TestProcess, TestMessage>;
+
+    type Queue = Rc<RefCell<VecDeque<(TestProcess, TestProcess, TestByzantineMessage)>>>;
+
+    /// A network which queues every message sent, as `(from, to, message)`, so that the test
+    /// decides which messages are delivered.
+    struct QueueNetwork {
+        from: TestProcess,
+        queue: Queue,
+        unreachable: Option<TestProcess>,
+    }
+
+    impl NetworkSender<TestProcess, TestByzantineMessage> for QueueNetwork {
+        fn send(
+            &self,
+            to: &TestProcess,
+            message: TestByzantineMessage,
+        ) -> Result<(), InternalError> {
+            if self.unreachable == Some(*to) {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            self.queue.borrow_mut().push_back((self.from, *to, message));
+            Ok(())
+        }
+    }
+
+    type Delivered = Rc<RefCell<Vec<(TestProcess, TestMessage)>>>;
+
+    struct CollectingReceiver {
+        delivered: Delivered,
+    }
+
+    impl ByzantineBroadcastReceiver<TestProcess, TestMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            origin: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.borrow_mut().push((origin, message));
+            Ok(())
+        }
+    }
+
+    type TestHandler =
+        ByzantineBroadcastHandler<TestProcess, TestMessage, QueueNetwork, CollectingReceiver>;
+
+    /// A cluster of four processes tolerating one Byzantine process, `p4`, which has no handler;
+    /// the test sends its messages directly.
+    struct Cluster {
+        processes: Vec<TestProcess>,
+        queue: Queue,
+        senders: Vec<ByzantineBroadcastSender<TestProcess, TestMessage, QueueNetwork>>,
+        handlers: Vec<(TestProcess, TestHandler)>,
+        delivered: Vec<Delivered>,
+    }
+
+    impl Cluster {
+        fn new() -> Self {
+            let processes: Vec<TestProcess> = (1..=4).map(|id| TestProcess { id }).collect();
+            let queue = Queue::default();
+
+            let mut senders = Vec::new();
+            let mut handlers = Vec::new();
+            let mut delivered = Vec::new();
+            for process in &processes[..3] {
+                let sender = ByzantineBroadcastSender::new(
+                    *process,
+                    processes.clone(),
+                    1,
+                    QueueNetwork {
+                        from: *process,
+                        queue: queue.clone(),
+                        unreachable: None,
+                    },
+                )
+                .unwrap();
+                let process_delivered = Delivered::default();
+                handlers.push((
+                    *process,
+                    sender.delivery_handler(CollectingReceiver {
+                        delivered: process_delivered.clone(),
+                    }),
+                ));
+                delivered.push(process_delivered);
+                senders.push(sender);
+            }
+
+            Cluster {
+                processes,
+                queue,
+                senders,
+                handlers,
+                delivered,
+            }
+        }
+
+        fn byzantine(&self) -> TestProcess {
+            self.processes[3]
+        }
+
+        /// Sends `message` from the Byzantine process to `to`.
+        fn send_byzantine(&self, to: TestProcess, message: TestByzantineMessage) {
+            self.queue
+                .borrow_mut()
+                .push_back((self.byzantine(), to, message));
+        }
+
+        /// Delivers queued messages until the queue is empty, dropping those to the Byzantine
+        /// process.
+        fn deliver_all(&mut self) {
+            loop {
+                let next = self.queue.borrow_mut().pop_front();
+                let (from, to, message) = match next {
+                    Some(entry) => entry,
+                    None => return,
+                };
+                if let Some((_, handler)) =
+                    self.handlers.iter_mut().find(|(process, _)| *process == to)
+                {
+                    handler.deliver(from, message).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Tests that the broadcast of a correct process is delivered by every correct process, even
+    /// though the Byzantine process echoes and readies a conflicting message.
+    #[test]
+    fn test_correct_origin_despite_byzantine_process() {
+        let mut cluster = Cluster::new();
+        let p1 = cluster.processes[0];
+
+        let id = cluster.senders[0].broadcast(TestMessage("value")).unwrap();
+        for to in cluster.processes.clone() {
+            cluster.send_byzantine(
+                to,
+                ByzantineBroadcastMessage::Echo(id, TestMessage("forged")),
+            );
+            cluster.send_byzantine(
+                to,
+                ByzantineBroadcastMessage::Ready(id, TestMessage("forged")),
+            );
+        }
+        cluster.deliver_all();
+
+        for delivered in &cluster.delivered {
+            assert_eq!(*delivered.borrow(), vec![(p1, TestMessage("value"))]);
+        }
+    }
+
+    /// Tests that when the Byzantine process broadcasts conflicting messages to different
+    /// processes and echoes one of them, every correct process delivers that one message.
+    #[test]
+    fn test_conflicting_sends_deliver_one_value() {
+        let mut cluster = Cluster::new();
+        let (p1, p2, p3) = (
+            cluster.processes[0],
+            cluster.processes[1],
+            cluster.processes[2],
+        );
+        let byzantine = cluster.byzantine();
+        let id = BroadcastId::new(byzantine, 0);
+
+        cluster.send_byzantine(p1, ByzantineBroadcastMessage::Send(id, TestMessage("a")));
+        cluster.send_byzantine(p2, ByzantineBroadcastMessage::Send(id, TestMessage("a")));
+        cluster.send_byzantine(p3, ByzantineBroadcastMessage::Send(id, TestMessage("b")));
+        for to in [p1, p2, p3] {
+            cluster.send_byzantine(to, ByzantineBroadcastMessage::Echo(id, TestMessage("a")));
+        }
+        cluster.send_byzantine(p3, ByzantineBroadcastMessage::Ready(id, TestMessage("b")));
+        cluster.deliver_all();
+
+        for delivered in &cluster.delivered {
+            assert_eq!(*delivered.borrow(), vec![(byzantine, TestMessage("a"))]);
+        }
+    }
+
+    /// Tests that when the Byzantine process sends a different message to each process, no
+    /// message gathers enough echoes, so no correct process delivers anything, and that it
+    /// cannot send a broadcast on behalf of another process.
+    #[test]
+    fn test_no_quorum_delivers_nothing() {
+        let mut cluster = Cluster::new();
+        let byzantine = cluster.byzantine();
+        let id = BroadcastId::new(byzantine, 0);
+
+        for (to, message) in cluster.processes[..3].iter().copied().zip(["a", "b", "c"]) {
+            cluster.send_byzantine(
+                to,
+                ByzantineBroadcastMessage::Send(id, TestMessage(message)),
+            );
+            cluster.send_byzantine(
+                to,
+                ByzantineBroadcastMessage::Send(
+                    BroadcastId::new(cluster.processes[0], 0),
+                    TestMessage("impersonated"),
+                ),
+            );
+        }
+        cluster.deliver_all();
+
+        for delivered in &cluster.delivered {
+            assert!(delivered.borrow().is_empty());
+        }
+    }
+
+    /// Tests that a sender cannot be constructed for three processes tolerating one Byzantine
+    /// process.
+    #[test]
+    fn test_too_few_processes() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let result = ByzantineBroadcastSender::<TestProcess, TestMessage, _>::new(
+            processes[0],
+            processes.clone(),
+            1,
+            QueueNetwork {
+                from: processes[0],
+                queue: Queue::default(),
+                unreachable: None,
+            },
+        );
+        assert!(result.is_err());
+    }
+
+    /// Tests that a broadcast which cannot be sent to one process is still sent to every other
+    /// process before the error is returned.
+    #[test]
+    fn test_send_error_reaches_remaining_processes() {
+        let processes: Vec<TestProcess> = (1..=4).map(|id| TestProcess { id }).collect();
+        let queue = Queue::default();
+        let sender = ByzantineBroadcastSender::new(
+            processes[0],
+            processes.clone(),
+            1,
+            QueueNetwork {
+                from: processes[0],
+                queue: queue.clone(),
+                unreachable: Some(processes[1]),
+            },
+        )
+        .unwrap();
+
+        assert!(sender.broadcast(TestMessage("value")).is_err());
+        assert_eq!(
+            queue
+                .borrow()
+                .iter()
+                .map(|(_, to, _)| *to)
+                .collect::<Vec<_>>(),
+            vec![processes[0], processes[2], processes[3]]
+        );
+    }
+}
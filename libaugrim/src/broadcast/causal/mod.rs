@@ -0,0 +1,481 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Causal-order reliable broadcast.
+//!
+//! Implementation of the "Waiting Causal Broadcast" algorithm, built on reliable broadcast. Each
+//! message carries a vector clock which counts, for every process, the messages from that
+//! process delivered by the sender before it broadcast the message. A message is buffered until
+//! the receiver has delivered at least as many messages from every process, so a message is never
+//! delivered before any message which causally precedes it.
+
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::error::{InternalError, InvalidStateError};
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+
+use super::reliable::{
+    ReliableBroadcastHandler, ReliableBroadcastMessage, ReliableBroadcastReceiver,
+    ReliableBroadcastSender,
+};
+use super::BroadcastId;
+
+/// A message broadcast by causal-order broadcast, along with the vector clock of its sender.
+///
+/// The clock has one entry per process, in the order of the process set given to
+/// [`CausalOrderBroadcast::new`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CausalOrderMessage<M> {
+    clock: Vec<u64>,
+    payload: M,
+}
+
+impl<M> CausalOrderMessage<M> {
+    /// Constructs the message with the vector clock of its sender.
+    pub fn new(clock: Vec<u64>, payload: M) -> Self {
+        CausalOrderMessage { clock, payload }
+    }
+
+    /// Returns the vector clock of the sender when the message was broadcast.
+    pub fn clock(&self) -> &[u64] {
+        &self.clock
+    }
+
+    /// Returns the payload of the message.
+    pub fn payload(&self) -> &M {
+        &self.payload
+    }
+
+    /// Returns the payload of the message, consuming the message.
+    pub fn into_payload(self) -> M {
+        self.payload
+    }
+}
+
+impl<M> Message for CausalOrderMessage<M> {}
+
+/// The best-effort broadcast message which carries a causal-order broadcast message.
+pub type CausalOrderBroadcastMessage<P, M> = ReliableBroadcastMessage<P, CausalOrderMessage<M>>;
+
+/// Receives messages delivered by causal-order broadcast.
+pub trait CausalOrderBroadcastReceiver<P, M> {
+    /// Delivers `message`, which was broadcast by `origin`.
+    fn deliver(&mut self, origin: P, message: M) -> Result<(), InternalError>;
+}
+
+/// The number of messages delivered from each process, shared by the sending side and the
+/// delivery handler of a process.
+type DeliveredClock = Arc<Mutex<Vec<u64>>>;
+
+/// The sending side of causal-order broadcast.
+pub struct CausalOrderBroadcast<P, M, N> {
+    processes: Vec<P>,
+    rank: usize,
+    next_sequence: AtomicU64,
+    delivered: DeliveredClock,
+    reliable: ReliableBroadcastSender<P, CausalOrderMessage<M>, N>,
+}
+
+impl<P, M, N> CausalOrderBroadcast<P, M, N>
+where
+    P: Process + Hash + Debug,
+    M: Message + Clone,
+    N: NetworkSender<P, CausalOrderBroadcastMessage<P, M>>,
+{
+    /// Constructs a new `CausalOrderBroadcast` which broadcasts from `this_process` to
+    /// `processes` over `network`.
+    ///
+    /// Every process must be given the same process set, in the same order, since the vector
+    /// clocks carried by messages are indexed by position in it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `this_process` is not in `processes`.
+    pub fn new(this_process: P, processes: Vec<P>, network: N) -> Result<Self, InvalidStateError> {
+        let rank = processes
+            .iter()
+            .position(|process| *process == this_process)
+            .ok_or_else(|| {
+                InvalidStateError::with_message(
+                    "this process is not in the set of processes".into(),
+                )
+            })?;
+
+        Ok(CausalOrderBroadcast {
+            rank,
+            next_sequence: AtomicU64::new(0),
+            delivered: Arc::new(Mutex::new(vec![0; processes.len()])),
+            reliable: ReliableBroadcastSender::new(this_process, processes.clone(), network),
+            processes,
+        })
+    }
+
+    /// Broadcasts `message` to every process, returning the id assigned to the broadcast.
+    ///
+    /// The message causally follows every message this process has delivered, and every
+    /// message it has broadcast before.
+    pub fn broadcast(&self, message: M) -> Result<BroadcastId<P>, InternalError> {
+        let mut clock = self
+            .delivered
+            .lock()
+            .map_err(|_| InternalError::with_message("causal broadcast lock poisoned".into()))?
+            .clone();
+        clock[self.rank] = self.next_sequence.fetch_add(1, Ordering::SeqCst);
+
+        self.reliable
+            .broadcast(CausalOrderMessage::new(clock, message))
+    }
+
+    /// Returns the handler for messages delivered by best-effort broadcast to this process,
+    /// which delivers the messages in causal order to `receiver`.
+    ///
+    /// The handler must also be told of crashed processes, as for reliable broadcast.
+    pub fn delivery_handler<R>(
+        &self,
+        receiver: R,
+    ) -> ReliableBroadcastHandler<P, CausalOrderMessage<M>, N, CausalOrdering<P, M, R>>
+    where
+        R: CausalOrderBroadcastReceiver<P, M>,
+    {
+        self.reliable.delivery_handler(CausalOrdering {
+            processes: self.processes.clone(),
+            delivered: self.delivered.clone(),
+            pending: Vec::new(),
+            receiver,
+        })
+    }
+}
+
+/// Delivers messages in causal order, buffering those whose causal predecessors have not all
+/// been delivered.
+pub struct CausalOrdering<P, M, R> {
+    processes: Vec<P>,
+    delivered: DeliveredClock,
+    pending: Vec<(usize, P, CausalOrderMessage<M>)>,
+    receiver: R,
+}
+
+impl<P, M, R> CausalOrdering<P, M, R> {
+    /// Removes a pending message whose causal predecessors have all been delivered, and counts
+    /// it as delivered.
+    ///
+    /// The lock on the delivered clock is released before returning, so that the receiver may
+    /// broadcast when the message is delivered to it.
+    fn next_deliverable(&mut self) -> Result<Option<(P, CausalOrderMessage<M>)>, InternalError> {
+        let mut delivered = self
+            .delivered
+            .lock()
+            .map_err(|_| InternalError::with_message("causal broadcast lock poisoned".into()))?;
+
+        let index = match self.pending.iter().position(|(_, _, message)| {
+            message
+                .clock()
+                .iter()
+                .zip(delivered.iter())
+                .all(|(needed, delivered)| needed <= delivered)
+        }) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let (rank, origin, message) = self.pending.remove(index);
+        delivered[rank] += 1;
+        Ok(Some((origin, message)))
+    }
+}
+
+impl<P, M, R> ReliableBroadcastReceiver<P, CausalOrderMessage<M>> for CausalOrdering<P, M, R>
+where
+    P: Process + Hash,
+    R: CausalOrderBroadcastReceiver<P, M>,
+{
+    fn deliver(&mut self, origin: P, message: CausalOrderMessage<M>) -> Result<(), InternalError> {
+        let rank = self
+            .processes
+            .iter()
+            .position(|process| *process == origin)
+            .ok_or_else(|| {
+                InternalError::with_message("message delivered from an unknown process".into())
+            })?;
+        if message.clock().len() != self.processes.len() {
+            return Err(InternalError::with_message(format!(
+                "vector clock has {} entries, expected {}",
+                message.clock().len(),
+                self.processes.len()
+            )));
+        }
+
+        self.pending.push((rank, origin, message));
+
+        // Delivering one message may allow others to be delivered, so repeat until none can
+        while let Some((origin, message)) = self.next_deliverable()? {
+            self.receiver.deliver(origin, message.into_payload())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::broadcast::best_effort::BestEffortBroadcastReceiver;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(&'static str);
+
+    impl Message for TestMessage {}
+
+    type TestBestEffortMessage = CausalOrderBroadcastMessage<TestProcess, TestMessage>;
+
+    type Sent = Rc<RefCell<Vec<(TestProcess, TestProcess, TestBestEffortMessage)>>>;
+
+    /// A network which records every message sent, as `(from, to, message)`, so that the test
+    /// decides which messages are delivered and in which order.
+    struct RecordingNetwork {
+        from: TestProcess,
+        sent: Sent,
+    }
+
+    impl NetworkSender<TestProcess, TestBestEffortMessage> for RecordingNetwork {
+        fn send(
+            &self,
+            to: &TestProcess,
+            message: TestBestEffortMessage,
+        ) -> Result<(), InternalError> {
+            self.sent.borrow_mut().push((self.from, *to, message));
+            Ok(())
+        }
+    }
+
+    type Delivered = Rc<RefCell<Vec<(TestProcess, TestMessage)>>>;
+
+    struct CollectingReceiver {
+        delivered: Delivered,
+    }
+
+    impl CausalOrderBroadcastReceiver<TestProcess, TestMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            origin: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.borrow_mut().push((origin, message));
+            Ok(())
+        }
+    }
+
+    /// Removes and returns the message sent to `to` carrying `payload`.
+    fn take_sent(
+        sent: &Sent,
+        to: TestProcess,
+        payload: &str,
+    ) -> (TestProcess, TestBestEffortMessage) {
+        let mut sent = sent.borrow_mut();
+        let index = sent
+            .iter()
+            .position(|(_, sent_to, message)| {
+                *sent_to == to && message.payload().payload().payload().0 == payload
+            })
+            .expect("message not sent");
+        let (from, _, message) = sent.remove(index);
+        (from, message)
+    }
+
+    /// Tests that a message which causally follows another is not delivered first, even when it
+    /// arrives first: p2 broadcasts "b" after delivering p1's "a", and p3 receives "b" before "a".
+    #[test]
+    fn test_causal_order() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let (p1, p2, p3) = (processes[0], processes[1], processes[2]);
+        let sent = Sent::default();
+
+        let mut senders = Vec::new();
+        let mut handlers = Vec::new();
+        let mut delivered = Vec::new();
+        for process in &processes {
+            let sender = CausalOrderBroadcast::new(
+                *process,
+                processes.clone(),
+                RecordingNetwork {
+                    from: *process,
+                    sent: sent.clone(),
+                },
+            )
+            .unwrap();
+            let process_delivered = Delivered::default();
+            handlers.push(sender.delivery_handler(CollectingReceiver {
+                delivered: process_delivered.clone(),
+            }));
+            delivered.push(process_delivered);
+            senders.push(sender);
+        }
+
+        senders[0].broadcast(TestMessage("a")).unwrap();
+        let (from, message) = take_sent(&sent, p2, "a");
+        handlers[1].deliver(from, message.into_payload()).unwrap();
+        senders[1].broadcast(TestMessage("b")).unwrap();
+
+        let (from, message) = take_sent(&sent, p3, "b");
+        handlers[2].deliver(from, message.into_payload()).unwrap();
+        assert!(delivered[2].borrow().is_empty());
+
+        let (from, message) = take_sent(&sent, p3, "a");
+        handlers[2].deliver(from, message.into_payload()).unwrap();
+        assert_eq!(
+            *delivered[2].borrow(),
+            vec![(p1, TestMessage("a")), (p2, TestMessage("b"))]
+        );
+    }
+
+    /// Tests that the messages of one process are delivered in the order they were broadcast,
+    /// and that concurrent messages from another process are not held back by them.
+    #[test]
+    fn test_concurrent_messages() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+        let (p1, p2) = (processes[0], processes[1]);
+        let sent = Sent::default();
+
+        let sender = CausalOrderBroadcast::new(
+            p1,
+            processes.clone(),
+            RecordingNetwork {
+                from: p1,
+                sent: sent.clone(),
+            },
+        )
+        .unwrap();
+        let other = CausalOrderBroadcast::new(
+            p2,
+            processes.clone(),
+            RecordingNetwork {
+                from: p2,
+                sent: sent.clone(),
+            },
+        )
+        .unwrap();
+        let delivered = Delivered::default();
+        let mut handler = other.delivery_handler(CollectingReceiver {
+            delivered: delivered.clone(),
+        });
+
+        sender.broadcast(TestMessage("a")).unwrap();
+        sender.broadcast(TestMessage("b")).unwrap();
+        other.broadcast(TestMessage("x")).unwrap();
+
+        for payload in ["b", "x", "a"] {
+            let (from, message) = take_sent(&sent, p2, payload);
+            handler.deliver(from, message.into_payload()).unwrap();
+        }
+
+        assert_eq!(
+            *delivered.borrow(),
+            vec![
+                (p2, TestMessage("x")),
+                (p1, TestMessage("a")),
+                (p1, TestMessage("b"))
+            ]
+        );
+    }
+
+    /// A receiver which broadcasts "echo" when it is delivered "a".
+    struct EchoingReceiver {
+        sender: Rc<CausalOrderBroadcast<TestProcess, TestMessage, RecordingNetwork>>,
+    }
+
+    impl CausalOrderBroadcastReceiver<TestProcess, TestMessage> for EchoingReceiver {
+        fn deliver(
+            &mut self,
+            _origin: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            if message == TestMessage("a") {
+                self.sender.broadcast(TestMessage("echo"))?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Tests that a receiver may broadcast from `deliver`, and that its message causally follows
+    /// the message delivered.
+    #[test]
+    fn test_broadcast_on_delivery() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+        let (p1, p2) = (processes[0], processes[1]);
+        let sent = Sent::default();
+
+        let sender = CausalOrderBroadcast::new(
+            p1,
+            processes.clone(),
+            RecordingNetwork {
+                from: p1,
+                sent: sent.clone(),
+            },
+        )
+        .unwrap();
+        let other = Rc::new(
+            CausalOrderBroadcast::new(
+                p2,
+                processes.clone(),
+                RecordingNetwork {
+                    from: p2,
+                    sent: sent.clone(),
+                },
+            )
+            .unwrap(),
+        );
+        let mut handler = other.delivery_handler(EchoingReceiver {
+            sender: other.clone(),
+        });
+
+        sender.broadcast(TestMessage("a")).unwrap();
+        let (from, message) = take_sent(&sent, p2, "a");
+        handler.deliver(from, message.into_payload()).unwrap();
+
+        let (from, message) = take_sent(&sent, p1, "echo");
+        assert_eq!(from, p2);
+        assert_eq!(message.payload().payload().clock(), &[1, 0]);
+    }
+
+    /// Tests that a process which is not in the process set cannot broadcast.
+    #[test]
+    fn test_unknown_process() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+        let result = CausalOrderBroadcast::<_, TestMessage, _>::new(
+            TestProcess { id: 3 },
+            processes,
+            RecordingNetwork {
+                from: TestProcess { id: 3 },
+                sent: Sent::default(),
+            },
+        );
+        assert!(result.is_err());
+    }
+}
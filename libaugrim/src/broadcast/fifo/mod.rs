@@ -0,0 +1,305 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! FIFO-order reliable broadcast.
+//!
+//! Implementation of the "Broadcast with Sequence Number" algorithm, built on reliable
+//! broadcast. Each message is tagged with a sequence number assigned by its origin, and messages
+//! delivered out of order are buffered until every earlier message from the same origin has been
+//! delivered, so the messages of each origin are delivered in the order they were broadcast.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::error::InternalError;
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+
+use super::best_effort::BroadcastMessage;
+use super::reliable::{
+    ReliableBroadcastHandler, ReliableBroadcastMessage, ReliableBroadcastReceiver,
+    ReliableBroadcastSender,
+};
+use super::{BroadcastId, BroadcastIdGenerator};
+
+/// The best-effort broadcast message which carries a FIFO-order broadcast message.
+pub type FifoReliableBroadcastMessage<P, M> = ReliableBroadcastMessage<P, BroadcastMessage<P, M>>;
+
+/// Receives messages delivered by FIFO-order reliable broadcast.
+pub trait FifoReliableBroadcastReceiver<P, M> {
+    /// Delivers `message`, which was broadcast by `origin`.
+    fn deliver(&mut self, origin: P, message: M) -> Result<(), InternalError>;
+}
+
+/// The sending side of FIFO-order reliable broadcast.
+pub struct FifoReliableBroadcastSender<P, M, N> {
+    id_generator: BroadcastIdGenerator<P>,
+    reliable: ReliableBroadcastSender<P, BroadcastMessage<P, M>, N>,
+}
+
+impl<P, M, N> FifoReliableBroadcastSender<P, M, N>
+where
+    P: Process + Hash + Debug,
+    M: Message + Clone,
+    N: NetworkSender<P, FifoReliableBroadcastMessage<P, M>>,
+{
+    /// Constructs a new `FifoReliableBroadcastSender` which broadcasts from `this_process` to
+    /// `processes` over `network`.
+    pub fn new(this_process: P, processes: Vec<P>, network: N) -> Self {
+        FifoReliableBroadcastSender {
+            id_generator: BroadcastIdGenerator::new(this_process),
+            reliable: ReliableBroadcastSender::new(this_process, processes, network),
+        }
+    }
+
+    /// Broadcasts `message` to every process, returning the id assigned to the broadcast; the
+    /// sequence number of the id determines the delivery order.
+    pub fn broadcast(&self, message: M) -> Result<BroadcastId<P>, InternalError> {
+        let id = self.id_generator.next_id();
+        self.reliable
+            .broadcast(BroadcastMessage::new(id, message))?;
+        Ok(id)
+    }
+
+    /// Returns the handler for messages delivered by best-effort broadcast to this process,
+    /// which delivers the messages of each origin in order to `receiver`.
+    ///
+    /// The handler must also be told of crashed processes, as for reliable broadcast.
+    pub fn delivery_handler<R>(
+        &self,
+        receiver: R,
+    ) -> ReliableBroadcastHandler<P, BroadcastMessage<P, M>, N, FifoOrdering<P, M, R>>
+    where
+        R: FifoReliableBroadcastReceiver<P, M>,
+    {
+        self.reliable.delivery_handler(FifoOrdering::new(receiver))
+    }
+}
+
+/// Delivers the messages of each origin in sequence order, buffering those which arrive early.
+pub struct FifoOrdering<P, M, R> {
+    next_sequence: HashMap<P, u64>,
+    pending: HashMap<P, BTreeMap<u64, M>>,
+    receiver: R,
+}
+
+impl<P, M, R> FifoOrdering<P, M, R>
+where
+    P: Process + Hash,
+    R: FifoReliableBroadcastReceiver<P, M>,
+{
+    /// Constructs a new `FifoOrdering` which delivers to `receiver`.
+    pub fn new(receiver: R) -> Self {
+        FifoOrdering {
+            next_sequence: HashMap::new(),
+            pending: HashMap::new(),
+            receiver,
+        }
+    }
+}
+
+impl<P, M, R> ReliableBroadcastReceiver<P, BroadcastMessage<P, M>> for FifoOrdering<P, M, R>
+where
+    P: Process + Hash,
+    R: FifoReliableBroadcastReceiver<P, M>,
+{
+    fn deliver(&mut self, origin: P, message: BroadcastMessage<P, M>) -> Result<(), InternalError> {
+        let sequence = message.id().sequence();
+        let next_sequence = self.next_sequence.entry(origin).or_insert(0);
+
+        if sequence < *next_sequence {
+            // Reliable broadcast delivers each message once, so this is a stale duplicate
+            return Ok(());
+        }
+
+        let pending = self.pending.entry(origin).or_default();
+        pending.insert(sequence, message.into_payload());
+
+        while let Some(payload) = pending.remove(next_sequence) {
+            *next_sequence += 1;
+            self.receiver.deliver(origin, payload)?;
+        }
+
+        if pending.is_empty() {
+            self.pending.remove(&origin);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::broadcast::best_effort::BestEffortBroadcastReceiver;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(&'static str);
+
+    impl Message for TestMessage {}
+
+    type Sent = Rc<
+        RefCell<
+            Vec<(
+                TestProcess,
+                FifoReliableBroadcastMessage<TestProcess, TestMessage>,
+            )>,
+        >,
+    >;
+
+    /// A network which records every message sent, along with its destination.
+    struct RecordingNetwork {
+        sent: Sent,
+    }
+
+    impl NetworkSender<TestProcess, FifoReliableBroadcastMessage<TestProcess, TestMessage>>
+        for RecordingNetwork
+    {
+        fn send(
+            &self,
+            to: &TestProcess,
+            message: FifoReliableBroadcastMessage<TestProcess, TestMessage>,
+        ) -> Result<(), InternalError> {
+            self.sent.borrow_mut().push((*to, message));
+            Ok(())
+        }
+    }
+
+    type Delivered = Rc<RefCell<Vec<(TestProcess, TestMessage)>>>;
+
+    struct CollectingReceiver {
+        delivered: Delivered,
+    }
+
+    impl FifoReliableBroadcastReceiver<TestProcess, TestMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            origin: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.borrow_mut().push((origin, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that messages from one origin which arrive out of order are delivered in the order
+    /// they were broadcast, and that a gap in one origin's messages does not hold back another
+    /// origin's.
+    #[test]
+    fn test_fifo_order() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let message = |origin, sequence, payload| {
+            BroadcastMessage::new(BroadcastId::new(origin, sequence), payload)
+        };
+
+        let delivered = Delivered::default();
+        let mut ordering = FifoOrdering::new(CollectingReceiver {
+            delivered: delivered.clone(),
+        });
+
+        ordering
+            .deliver(p1, message(p1, 2, TestMessage("c")))
+            .unwrap();
+        ordering
+            .deliver(p1, message(p1, 1, TestMessage("b")))
+            .unwrap();
+        ordering
+            .deliver(p2, message(p2, 0, TestMessage("x")))
+            .unwrap();
+        assert_eq!(*delivered.borrow(), vec![(p2, TestMessage("x"))]);
+
+        ordering
+            .deliver(p1, message(p1, 0, TestMessage("a")))
+            .unwrap();
+        ordering
+            .deliver(p1, message(p1, 0, TestMessage("a")))
+            .unwrap();
+        ordering
+            .deliver(p1, message(p1, 3, TestMessage("d")))
+            .unwrap();
+
+        assert_eq!(
+            *delivered.borrow(),
+            vec![
+                (p2, TestMessage("x")),
+                (p1, TestMessage("a")),
+                (p1, TestMessage("b")),
+                (p1, TestMessage("c")),
+                (p1, TestMessage("d"))
+            ]
+        );
+        assert!(ordering.pending.is_empty());
+    }
+
+    /// Tests the full stack: messages broadcast by one process and delivered to another by the
+    /// network in reverse order are surfaced to the application in the order they were sent.
+    #[test]
+    fn test_out_of_order_network_delivery() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let sent = Sent::default();
+
+        let sender = FifoReliableBroadcastSender::new(
+            p1,
+            vec![p1, p2],
+            RecordingNetwork { sent: sent.clone() },
+        );
+        let receiver_side = FifoReliableBroadcastSender::new(
+            p2,
+            vec![p1, p2],
+            RecordingNetwork { sent: sent.clone() },
+        );
+        let delivered = Delivered::default();
+        let mut handler = receiver_side.delivery_handler(CollectingReceiver {
+            delivered: delivered.clone(),
+        });
+
+        for payload in [TestMessage("a"), TestMessage("b"), TestMessage("c")] {
+            sender.broadcast(payload).unwrap();
+        }
+
+        let to_p2: Vec<_> = sent
+            .borrow()
+            .iter()
+            .filter(|(to, _)| *to == p2)
+            .map(|(_, message)| message.clone())
+            .collect();
+        for message in to_p2.into_iter().rev() {
+            handler.deliver(p1, message.into_payload()).unwrap();
+        }
+
+        assert_eq!(
+            *delivered.borrow(),
+            vec![
+                (p1, TestMessage("a")),
+                (p1, TestMessage("b")),
+                (p1, TestMessage("c"))
+            ]
+        );
+    }
+}
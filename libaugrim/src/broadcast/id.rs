@@ -0,0 +1,83 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Identifiers for broadcasts.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::process::Process;
+
+/// Uniquely identifies a broadcast by the process which originated it and a sequence number.
+///
+/// Two broadcasts of the same payload by the same process have different identifiers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BroadcastId<P> {
+    origin: P,
+    sequence: u64,
+}
+
+impl<P> BroadcastId<P>
+where
+    P: Process,
+{
+    /// Constructs the id of the broadcast with the given origin and sequence number.
+    pub fn new(origin: P, sequence: u64) -> Self {
+        BroadcastId { origin, sequence }
+    }
+
+    /// Returns the process which originated the broadcast.
+    pub fn origin(&self) -> &P {
+        &self.origin
+    }
+
+    /// Returns the sequence number of the broadcast, which is unique for its origin.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+/// Generates [`BroadcastId`]s for broadcasts originating from a single process.
+///
+/// Sequence numbers start at zero and increase by one with each generated id.
+pub struct BroadcastIdGenerator<P> {
+    origin: P,
+    next_sequence: AtomicU64,
+}
+
+impl<P> BroadcastIdGenerator<P>
+where
+    P: Process,
+{
+    /// Constructs a new `BroadcastIdGenerator` whose first id has sequence number zero.
+    pub fn new(origin: P) -> Self {
+        BroadcastIdGenerator {
+            origin,
+            next_sequence: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the process the generated ids originate from.
+    pub fn origin(&self) -> &P {
+        &self.origin
+    }
+
+    /// Returns the id for the next broadcast.
+    pub fn next_id(&self) -> BroadcastId<P> {
+        BroadcastId::new(
+            self.origin,
+            self.next_sequence.fetch_add(1, Ordering::SeqCst),
+        )
+    }
+}
@@ -0,0 +1,468 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reliable broadcast.
+//!
+//! Implementation of the "Lazy Reliable Broadcast" algorithm, built on best-effort broadcast and
+//! a perfect failure detector. Each message carries the [`BroadcastId`] assigned by its origin,
+//! so it keeps the same identity when it is relayed by another process. Every process remembers
+//! the messages it has delivered from each process; when that process is detected as crashed,
+//! its messages are relayed, so that if any correct process delivers a message, every correct
+//! process delivers it.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use crate::error::InternalError;
+use crate::failure_detector::PerfectFailureDetectorReceiver;
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+
+use super::best_effort::{
+    BestEffortBroadcastReceiver, BestEffortBroadcastSender, BroadcastMessage,
+};
+use super::{BroadcastId, BroadcastIdGenerator};
+
+/// The best-effort broadcast message which carries a reliably broadcast message.
+pub type ReliableBroadcastMessage<P, M> = BroadcastMessage<P, BroadcastMessage<P, M>>;
+
+/// Receives messages delivered by reliable broadcast.
+pub trait ReliableBroadcastReceiver<P, M> {
+    /// Delivers `message`, which was broadcast by `origin`.
+    fn deliver(&mut self, origin: P, message: M) -> Result<(), InternalError>;
+}
+
+/// The sending side of reliable broadcast.
+pub struct ReliableBroadcastSender<P, M, N> {
+    id_generator: BroadcastIdGenerator<P>,
+    best_effort: Arc<BestEffortBroadcastSender<P, BroadcastMessage<P, M>, N>>,
+    processes: Vec<P>,
+}
+
+impl<P, M, N> ReliableBroadcastSender<P, M, N>
+where
+    P: Process + Hash + Debug,
+    M: Message + Clone,
+    N: NetworkSender<P, ReliableBroadcastMessage<P, M>>,
+{
+    /// Constructs a new `ReliableBroadcastSender` which broadcasts from `this_process` to
+    /// `processes` over `network`.
+    pub fn new(this_process: P, processes: Vec<P>, network: N) -> Self {
+        ReliableBroadcastSender {
+            id_generator: BroadcastIdGenerator::new(this_process),
+            best_effort: Arc::new(BestEffortBroadcastSender::new(
+                this_process,
+                processes.clone(),
+                network,
+            )),
+            processes,
+        }
+    }
+
+    /// Broadcasts `message` to every process, returning the id assigned to the broadcast.
+    pub fn broadcast(&self, message: M) -> Result<BroadcastId<P>, InternalError> {
+        let id = self.id_generator.next_id();
+        self.best_effort
+            .broadcast(BroadcastMessage::new(id, message))?;
+        Ok(id)
+    }
+
+    /// Returns the handler for messages delivered by best-effort broadcast to this process,
+    /// which delivers each reliably broadcast message once to `receiver`.
+    pub fn delivery_handler<R>(&self, receiver: R) -> ReliableBroadcastHandler<P, M, N, R>
+    where
+        R: ReliableBroadcastReceiver<P, M>,
+    {
+        ReliableBroadcastHandler {
+            best_effort: self.best_effort.clone(),
+            correct: self.processes.iter().copied().collect(),
+            delivered: HashSet::new(),
+            from: HashMap::new(),
+            receiver,
+        }
+    }
+}
+
+/// Handles the messages delivered by best-effort broadcast on behalf of reliable broadcast.
+///
+/// The handler must also be told of crashed processes, either by calling
+/// [`ReliableBroadcastHandler::crash`] or by registering it with a perfect failure detector.
+pub struct ReliableBroadcastHandler<P, M, N, R> {
+    best_effort: Arc<BestEffortBroadcastSender<P, BroadcastMessage<P, M>, N>>,
+    correct: HashSet<P>,
+    delivered: HashSet<BroadcastId<P>>,
+    from: HashMap<P, Vec<BroadcastMessage<P, M>>>,
+    receiver: R,
+}
+
+impl<P, M, N, R> ReliableBroadcastHandler<P, M, N, R>
+where
+    P: Process + Hash + Debug,
+    M: Message + Clone,
+    N: NetworkSender<P, ReliableBroadcastMessage<P, M>>,
+    R: ReliableBroadcastReceiver<P, M>,
+{
+    /// Handles the crash of `process` by relaying every message delivered from it.
+    ///
+    /// Every message is relayed even if relaying an earlier one fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `InternalError` raised while relaying the messages.
+    pub fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        if !self.correct.remove(&process) {
+            return Ok(());
+        }
+
+        let mut result = Ok(());
+        if let Some(messages) = self.from.get(&process) {
+            for message in messages {
+                result = result.and(
+                    self.best_effort
+                        .broadcast(message.clone())
+                        .map(|_| ())
+                        .map_err(InternalError::from),
+                );
+            }
+        }
+
+        result
+    }
+}
+
+impl<P, M, N, R> BestEffortBroadcastReceiver<P, BroadcastMessage<P, M>>
+    for ReliableBroadcastHandler<P, M, N, R>
+where
+    P: Process + Hash + Debug,
+    M: Message + Clone,
+    N: NetworkSender<P, ReliableBroadcastMessage<P, M>>,
+    R: ReliableBroadcastReceiver<P, M>,
+{
+    fn deliver(
+        &mut self,
+        process: P,
+        message: BroadcastMessage<P, M>,
+    ) -> Result<(), InternalError> {
+        if !self.delivered.insert(*message.id()) {
+            return Ok(());
+        }
+
+        let origin = *message.id().origin();
+        let payload = message.payload().clone();
+
+        // The message is recorded or relayed before it is handed to the receiver, since it is
+        // never delivered again and a receiver error must not keep it from reaching everyone
+        let relayed = if self.correct.contains(&process) {
+            self.from.entry(process).or_default().push(message);
+            Ok(())
+        } else {
+            // The process crashed before its messages could be relied upon to reach everyone
+            self.best_effort
+                .broadcast(message)
+                .map(|_| ())
+                .map_err(InternalError::from)
+        };
+
+        self.receiver.deliver(origin, payload)?;
+
+        relayed
+    }
+}
+
+impl<P, M, N, R> PerfectFailureDetectorReceiver<P> for ReliableBroadcastHandler<P, M, N, R>
+where
+    P: Process + Hash + Debug,
+    M: Message + Clone,
+    N: NetworkSender<P, ReliableBroadcastMessage<P, M>>,
+    R: ReliableBroadcastReceiver<P, M>,
+{
+    fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        ReliableBroadcastHandler::crash(self, process)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(&'static str);
+
+    impl Message for TestMessage {}
+
+    type Queue = Rc<RefCell<VecDeque<(TestProcess, TestProcess, TestBestEffortMessage)>>>;
+
+    type TestBestEffortMessage = ReliableBroadcastMessage<TestProcess, TestMessage>;
+
+    /// A network which queues every message sent, as `(from, to, message)`, so that the test
+    /// decides which messages are delivered. Sends to `unreachable` fail.
+    struct QueueNetwork {
+        from: TestProcess,
+        queue: Queue,
+        unreachable: Option<TestProcess>,
+    }
+
+    impl NetworkSender<TestProcess, TestBestEffortMessage> for QueueNetwork {
+        fn send(
+            &self,
+            to: &TestProcess,
+            message: TestBestEffortMessage,
+        ) -> Result<(), InternalError> {
+            if self.unreachable.as_ref() == Some(to) {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            self.queue.borrow_mut().push_back((self.from, *to, message));
+            Ok(())
+        }
+    }
+
+    type Delivered = Rc<RefCell<Vec<(TestProcess, TestMessage)>>>;
+
+    struct CollectingReceiver {
+        delivered: Delivered,
+    }
+
+    impl ReliableBroadcastReceiver<TestProcess, TestMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            origin: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.borrow_mut().push((origin, message));
+            Ok(())
+        }
+    }
+
+    /// A receiver which fails every delivery.
+    struct FailingReceiver;
+
+    impl ReliableBroadcastReceiver<TestProcess, TestMessage> for FailingReceiver {
+        fn deliver(
+            &mut self,
+            origin: TestProcess,
+            _message: TestMessage,
+        ) -> Result<(), InternalError> {
+            Err(InternalError::with_message(format!(
+                "unable to deliver from {:?}",
+                origin
+            )))
+        }
+    }
+
+    type TestHandler =
+        ReliableBroadcastHandler<TestProcess, TestMessage, QueueNetwork, CollectingReceiver>;
+
+    /// Delivers queued messages until the queue is empty, dropping those to `crashed` processes.
+    fn deliver_all(
+        queue: &Queue,
+        handlers: &mut [(TestProcess, TestHandler)],
+        crashed: &[TestProcess],
+    ) {
+        loop {
+            let next = queue.borrow_mut().pop_front();
+            let (from, to, message) = match next {
+                Some(entry) => entry,
+                None => return,
+            };
+            if crashed.contains(&to) {
+                continue;
+            }
+            let (_, handler) = handlers
+                .iter_mut()
+                .find(|(process, _)| *process == to)
+                .expect("unknown process");
+            handler.deliver(from, message.into_payload()).unwrap();
+        }
+    }
+
+    /// Tests agreement when the sender crashes after its broadcast reached only one correct
+    /// process: the message is not relayed while the sender is believed correct, but once the
+    /// crash is detected it is relayed, and every correct process delivers it exactly once.
+    #[test]
+    fn test_agreement_when_sender_crashes() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let (p1, p2) = (processes[0], processes[1]);
+        let queue = Queue::default();
+
+        let mut senders = Vec::new();
+        let mut handlers = Vec::new();
+        let mut delivered = Vec::new();
+        for process in &processes {
+            let sender = ReliableBroadcastSender::new(
+                *process,
+                processes.clone(),
+                QueueNetwork {
+                    from: *process,
+                    queue: queue.clone(),
+                    unreachable: None,
+                },
+            );
+            let process_delivered = Delivered::default();
+            handlers.push((
+                *process,
+                sender.delivery_handler(CollectingReceiver {
+                    delivered: process_delivered.clone(),
+                }),
+            ));
+            delivered.push(process_delivered);
+            senders.push(sender);
+        }
+
+        let id = senders[0].broadcast(TestMessage("value")).unwrap();
+        assert_eq!(id, BroadcastId::new(p1, 0));
+
+        // p1 crashes after its message has been sent to p2, but before it is sent to p3
+        queue.borrow_mut().retain(|(_, to, _)| *to == p2);
+        deliver_all(&queue, &mut handlers, &[p1]);
+
+        assert_eq!(*delivered[1].borrow(), vec![(p1, TestMessage("value"))]);
+        assert!(delivered[2].borrow().is_empty());
+        assert!(queue.borrow().is_empty());
+
+        for (_, handler) in handlers.iter_mut().skip(1) {
+            handler.crash(p1).unwrap();
+        }
+        deliver_all(&queue, &mut handlers, &[p1]);
+
+        for process_delivered in &delivered[1..] {
+            assert_eq!(
+                *process_delivered.borrow(),
+                vec![(p1, TestMessage("value"))]
+            );
+        }
+    }
+
+    /// Tests that a message delivered from a process which has already been detected as crashed
+    /// is relayed immediately.
+    #[test]
+    fn test_relay_from_crashed_process() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+        let queue = Queue::default();
+        let sender = ReliableBroadcastSender::new(
+            processes[1],
+            processes.clone(),
+            QueueNetwork {
+                from: processes[1],
+                queue: queue.clone(),
+                unreachable: None,
+            },
+        );
+        let delivered = Delivered::default();
+        let mut handler = sender.delivery_handler(CollectingReceiver {
+            delivered: delivered.clone(),
+        });
+
+        handler.crash(processes[0]).unwrap();
+        let message = BroadcastMessage::new(BroadcastId::new(processes[0], 0), TestMessage("m"));
+        handler.deliver(processes[0], message.clone()).unwrap();
+        handler.deliver(processes[0], message.clone()).unwrap();
+
+        assert_eq!(*delivered.borrow(), vec![(processes[0], TestMessage("m"))]);
+        let relayed: Vec<TestBestEffortMessage> = queue
+            .borrow()
+            .iter()
+            .map(|(_, _, message)| message.clone())
+            .collect();
+        assert_eq!(relayed.len(), 2);
+        assert!(relayed.iter().all(|relayed| relayed.payload() == &message));
+    }
+
+    /// Tests that when relaying a message from a crashed process fails, the remaining messages
+    /// are still relayed and the first error is returned.
+    #[test]
+    fn test_crash_relays_past_failure() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let queue = Queue::default();
+        let sender = ReliableBroadcastSender::new(
+            processes[1],
+            processes.clone(),
+            QueueNetwork {
+                from: processes[1],
+                queue: queue.clone(),
+                unreachable: Some(processes[0]),
+            },
+        );
+        let mut handler = sender.delivery_handler(CollectingReceiver {
+            delivered: Delivered::default(),
+        });
+
+        for sequence in 0..2 {
+            let message =
+                BroadcastMessage::new(BroadcastId::new(processes[0], sequence), TestMessage("m"));
+            handler.deliver(processes[0], message).unwrap();
+        }
+        assert!(handler.crash(processes[0]).is_err());
+
+        let relayed: Vec<(TestProcess, u64)> = queue
+            .borrow()
+            .iter()
+            .map(|(_, to, message)| (*to, message.payload().id().sequence()))
+            .collect();
+        assert_eq!(
+            relayed,
+            vec![
+                (processes[1], 0),
+                (processes[2], 0),
+                (processes[1], 1),
+                (processes[2], 1)
+            ]
+        );
+    }
+
+    /// Tests that a message is still relayed on the crash of its sender when the receiver failed
+    /// to deliver it.
+    #[test]
+    fn test_receiver_error_still_relays() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+        let queue = Queue::default();
+        let sender = ReliableBroadcastSender::new(
+            processes[1],
+            processes.clone(),
+            QueueNetwork {
+                from: processes[1],
+                queue: queue.clone(),
+                unreachable: None,
+            },
+        );
+        let mut handler = sender.delivery_handler(FailingReceiver);
+
+        let message = BroadcastMessage::new(BroadcastId::new(processes[0], 0), TestMessage("m"));
+        assert!(handler.deliver(processes[0], message.clone()).is_err());
+        assert!(queue.borrow().is_empty());
+
+        handler.crash(processes[0]).unwrap();
+        assert_eq!(queue.borrow().len(), 2);
+        assert!(queue
+            .borrow()
+            .iter()
+            .all(|(_, _, relayed)| relayed.payload() == &message));
+    }
+}
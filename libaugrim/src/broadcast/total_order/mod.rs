@@ -0,0 +1,579 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Total-order (atomic) broadcast.
+//!
+//! Implementation of the "Consensus-Based Total-Order Broadcast" algorithm, built on reliable
+//! broadcast and flooding consensus. Messages are reliably broadcast and collected, unordered, by
+//! every process. The processes then run a sequence of consensus instances, one per round, each
+//! agreeing on a batch of the collected messages; every process delivers each decided batch in
+//! the order of the ids of its messages, so all processes deliver the same messages in the same
+//! order.
+//!
+//! Each consensus instance is run by a [`Runtime`], and its messages are tagged with the round
+//! they belong to. Messages for a later round are buffered until this process reaches it.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use crate::algorithm::flooding::{
+    FloodingAlgorithm, FloodingContext, FloodingEvent, FloodingMessage,
+};
+use crate::algorithm::{Instance, Value};
+use crate::error::InternalError;
+use crate::failure_detector::PerfectFailureDetectorReceiver;
+use crate::links::Receiver;
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+use crate::runtime::Runtime;
+
+use super::best_effort::{
+    BestEffortBroadcastReceiver, BestEffortBroadcastSender, BroadcastMessage,
+};
+use super::reliable::{
+    ReliableBroadcastHandler, ReliableBroadcastMessage, ReliableBroadcastReceiver,
+    ReliableBroadcastSender,
+};
+use super::{BroadcastId, BroadcastIdGenerator};
+
+/// A batch of messages agreed upon by one consensus instance, ordered by their ids.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Batch<P, M> {
+    messages: Vec<BroadcastMessage<P, M>>,
+}
+
+impl<P, M> Batch<P, M> {
+    /// Returns the messages of the batch, ordered by their ids.
+    pub fn messages(&self) -> &[BroadcastMessage<P, M>] {
+        &self.messages
+    }
+}
+
+impl<P, M> Value for Batch<P, M>
+where
+    P: Clone,
+    M: Clone,
+{
+}
+
+/// A message sent over the network by total-order broadcast.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TotalOrderMessage<P, M> {
+    /// A message being reliably broadcast, which has not yet been ordered.
+    Broadcast(ReliableBroadcastMessage<P, BroadcastMessage<P, M>>),
+    /// A message of the consensus instance which orders the batch of the given round.
+    Consensus(Instance, BroadcastMessage<P, FloodingMessage<Batch<P, M>>>),
+}
+
+impl<P, M> Message for TotalOrderMessage<P, M> {}
+
+/// Receives messages delivered by total-order broadcast.
+pub trait TotalOrderBroadcastReceiver<P, M> {
+    /// Delivers `message`, which was broadcast by `origin`.
+    fn deliver(&mut self, origin: P, message: M) -> Result<(), InternalError>;
+}
+
+/// The sending side of total-order broadcast.
+pub struct TotalOrderBroadcastSender<P, M, N> {
+    id_generator: BroadcastIdGenerator<P>,
+    reliable: ReliableBroadcastSender<P, BroadcastMessage<P, M>, ReliableNetwork<N>>,
+    network: Arc<N>,
+    processes: Vec<P>,
+    this_process: P,
+}
+
+impl<P, M, N> TotalOrderBroadcastSender<P, M, N>
+where
+    P: Process + Hash + Ord + Debug,
+    M: Message + Clone + PartialEq,
+    N: NetworkSender<P, TotalOrderMessage<P, M>>,
+{
+    /// Constructs a new `TotalOrderBroadcastSender` which broadcasts from `this_process` to
+    /// `processes` over `network`.
+    pub fn new(this_process: P, processes: Vec<P>, network: N) -> Self {
+        let network = Arc::new(network);
+
+        TotalOrderBroadcastSender {
+            id_generator: BroadcastIdGenerator::new(this_process),
+            reliable: ReliableBroadcastSender::new(
+                this_process,
+                processes.clone(),
+                ReliableNetwork {
+                    network: network.clone(),
+                },
+            ),
+            network,
+            processes,
+            this_process,
+        }
+    }
+
+    /// Broadcasts `message` to every process, returning the id assigned to the broadcast. The
+    /// message is delivered once it has been ordered by a consensus instance.
+    pub fn broadcast(&self, message: M) -> Result<BroadcastId<P>, InternalError> {
+        let id = self.id_generator.next_id();
+        self.reliable
+            .broadcast(BroadcastMessage::new(id, message))?;
+        Ok(id)
+    }
+
+    /// Returns the handler for the messages delivered by the network to this process, which
+    /// delivers the broadcast messages in total order to `receiver`.
+    ///
+    /// The handler must also be told of crashed processes, as for reliable broadcast.
+    pub fn delivery_handler<R>(&self, receiver: R) -> TotalOrderBroadcastHandler<P, M, N, R>
+    where
+        R: TotalOrderBroadcastReceiver<P, M>,
+    {
+        let unordered = Arc::new(Mutex::new(Vec::new()));
+
+        TotalOrderBroadcastHandler {
+            reliable: self.reliable.delivery_handler(Collector {
+                collected: unordered.clone(),
+            }),
+            collected: unordered,
+            network: self.network.clone(),
+            processes: self.processes.clone(),
+            correct: self.processes.clone(),
+            this_process: self.this_process,
+            round: 0,
+            consensus: None,
+            proposed: false,
+            pending: BTreeMap::new(),
+            unordered: BTreeMap::new(),
+            delivered: HashSet::new(),
+            receiver,
+        }
+    }
+}
+
+type SelectFn<P, M> = fn(&[Batch<P, M>]) -> Result<Batch<P, M>, InternalError>;
+
+type DecideFn<P, M> = fn(Batch<P, M>) -> Result<(), InternalError>;
+
+type ConsensusRuntime<P, M, N> = Runtime<
+    P,
+    FloodingAlgorithm<P, Batch<P, M>, SelectFn<P, M>>,
+    ConsensusNetwork<N>,
+    DecideFn<P, M>,
+>;
+
+/// A consensus message for a round which this process has not reached yet.
+type PendingMessage<P, M> = (P, FloodingMessage<Batch<P, M>>);
+
+/// Handles the messages delivered by the network on behalf of total-order broadcast.
+///
+/// It may be registered directly with a network, as it is a link [`Receiver`].
+pub struct TotalOrderBroadcastHandler<P, M, N, R>
+where
+    P: Process,
+    M: Clone + PartialEq,
+{
+    reliable:
+        ReliableBroadcastHandler<P, BroadcastMessage<P, M>, ReliableNetwork<N>, Collector<P, M>>,
+    collected: Arc<Mutex<Vec<BroadcastMessage<P, M>>>>,
+    network: Arc<N>,
+    processes: Vec<P>,
+    correct: Vec<P>,
+    this_process: P,
+    round: Instance,
+    consensus: Option<ConsensusRuntime<P, M, N>>,
+    proposed: bool,
+    pending: BTreeMap<Instance, Vec<PendingMessage<P, M>>>,
+    unordered: BTreeMap<BroadcastId<P>, M>,
+    delivered: HashSet<BroadcastId<P>>,
+    receiver: R,
+}
+
+impl<P, M, N, R> TotalOrderBroadcastHandler<P, M, N, R>
+where
+    P: Process + Hash + Ord + Debug,
+    M: Message + Clone + PartialEq,
+    N: NetworkSender<P, TotalOrderMessage<P, M>>,
+    R: TotalOrderBroadcastReceiver<P, M>,
+{
+    /// Returns the round of the consensus instance which orders the next batch.
+    pub fn round(&self) -> Instance {
+        self.round
+    }
+
+    /// Handles the crash of `process`, both for reliable broadcast and for the consensus
+    /// instances of this and later rounds.
+    pub fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        self.reliable.crash(process)?;
+        self.correct.retain(|p| *p != process);
+
+        if let Some(consensus) = self.consensus.as_mut() {
+            consensus.event(FloodingEvent::Crash(process))?;
+        }
+        self.decide()
+    }
+
+    /// Collects the messages delivered by reliable broadcast which have not been ordered yet.
+    fn collect(&mut self) {
+        let collected = std::mem::take(
+            &mut *self
+                .collected
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+
+        for message in collected {
+            let id = *message.id();
+            if !self.delivered.contains(&id) {
+                self.unordered.insert(id, message.into_payload());
+            }
+        }
+    }
+
+    /// Returns the consensus instance of the current round, starting it if necessary.
+    fn consensus(&mut self) -> &mut ConsensusRuntime<P, M, N> {
+        let round = self.round;
+        let this_process = self.this_process;
+        let processes = &self.processes;
+        let correct = &self.correct;
+        let network = &self.network;
+
+        self.consensus.get_or_insert_with(|| {
+            Runtime::new(
+                FloodingAlgorithm::new(union as SelectFn<P, M>),
+                FloodingContext::new(correct.clone()),
+                BestEffortBroadcastSender::new(
+                    this_process,
+                    processes.clone(),
+                    ConsensusNetwork {
+                        round,
+                        network: network.clone(),
+                    },
+                ),
+                ignore_decision as DecideFn<P, M>,
+            )
+        })
+    }
+
+    /// Proposes the unordered messages for the current round, unless they have already been
+    /// proposed or there are none.
+    fn propose(&mut self) -> Result<(), InternalError> {
+        if self.proposed || self.unordered.is_empty() {
+            return Ok(());
+        }
+
+        let batch = Batch {
+            messages: self
+                .unordered
+                .iter()
+                .map(|(id, message)| BroadcastMessage::new(*id, message.clone()))
+                .collect(),
+        };
+        self.proposed = true;
+        self.consensus().event(FloodingEvent::Propose(batch, None))
+    }
+
+    /// Delivers the batch decided by the current round, if any, and moves on to the next round
+    /// until a round has not decided.
+    fn decide(&mut self) -> Result<(), InternalError> {
+        loop {
+            let batch = match self
+                .consensus
+                .as_ref()
+                .and_then(|consensus| consensus.context().decision().clone())
+            {
+                Some(batch) => batch,
+                None => return Ok(()),
+            };
+
+            for message in batch.messages {
+                let id = *message.id();
+                if self.delivered.insert(id) {
+                    self.unordered.remove(&id);
+                    self.receiver
+                        .deliver(*id.origin(), message.into_payload())?;
+                }
+            }
+
+            self.round += 1;
+            self.consensus = None;
+            self.proposed = false;
+
+            if let Some(pending) = self.pending.remove(&self.round) {
+                for (from, message) in pending {
+                    self.consensus()
+                        .event(FloodingEvent::Deliver(from, message))?;
+                }
+            }
+
+            self.propose()?;
+        }
+    }
+}
+
+impl<P, M, N, R> Receiver<P, TotalOrderMessage<P, M>> for TotalOrderBroadcastHandler<P, M, N, R>
+where
+    P: Process + Hash + Ord + Debug,
+    M: Message + Clone + PartialEq,
+    N: NetworkSender<P, TotalOrderMessage<P, M>>,
+    R: TotalOrderBroadcastReceiver<P, M>,
+{
+    fn deliver(&mut self, from: P, message: TotalOrderMessage<P, M>) -> Result<(), InternalError> {
+        match message {
+            TotalOrderMessage::Broadcast(message) => {
+                self.reliable.deliver(from, message.into_payload())?;
+                self.collect();
+                self.propose()?;
+                self.decide()
+            }
+            TotalOrderMessage::Consensus(round, message) => {
+                if round < self.round {
+                    // This process has already decided the round
+                    return Ok(());
+                }
+
+                if round > self.round {
+                    self.pending
+                        .entry(round)
+                        .or_default()
+                        .push((from, message.into_payload()));
+                    return Ok(());
+                }
+
+                self.consensus()
+                    .event(FloodingEvent::Deliver(from, message.into_payload()))?;
+                self.decide()
+            }
+        }
+    }
+}
+
+impl<P, M, N, R> PerfectFailureDetectorReceiver<P> for TotalOrderBroadcastHandler<P, M, N, R>
+where
+    P: Process + Hash + Ord + Debug,
+    M: Message + Clone + PartialEq,
+    N: NetworkSender<P, TotalOrderMessage<P, M>>,
+    R: TotalOrderBroadcastReceiver<P, M>,
+{
+    fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        TotalOrderBroadcastHandler::crash(self, process)
+    }
+}
+
+/// Collects the messages delivered by reliable broadcast, for the handler to order.
+struct Collector<P, M> {
+    collected: Arc<Mutex<Vec<BroadcastMessage<P, M>>>>,
+}
+
+impl<P, M> ReliableBroadcastReceiver<P, BroadcastMessage<P, M>> for Collector<P, M> {
+    fn deliver(
+        &mut self,
+        _origin: P,
+        message: BroadcastMessage<P, M>,
+    ) -> Result<(), InternalError> {
+        self.collected
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(message);
+        Ok(())
+    }
+}
+
+/// Sends the messages of reliable broadcast over the total-order broadcast network.
+struct ReliableNetwork<N> {
+    network: Arc<N>,
+}
+
+impl<P, M, N> NetworkSender<P, ReliableBroadcastMessage<P, BroadcastMessage<P, M>>>
+    for ReliableNetwork<N>
+where
+    N: NetworkSender<P, TotalOrderMessage<P, M>>,
+{
+    fn send(
+        &self,
+        to: &P,
+        message: ReliableBroadcastMessage<P, BroadcastMessage<P, M>>,
+    ) -> Result<(), InternalError> {
+        self.network.send(to, TotalOrderMessage::Broadcast(message))
+    }
+}
+
+/// Sends the messages of one round's consensus instance over the total-order broadcast network.
+struct ConsensusNetwork<N> {
+    round: Instance,
+    network: Arc<N>,
+}
+
+impl<P, M, N> NetworkSender<P, BroadcastMessage<P, FloodingMessage<Batch<P, M>>>>
+    for ConsensusNetwork<N>
+where
+    N: NetworkSender<P, TotalOrderMessage<P, M>>,
+{
+    fn send(
+        &self,
+        to: &P,
+        message: BroadcastMessage<P, FloodingMessage<Batch<P, M>>>,
+    ) -> Result<(), InternalError> {
+        self.network
+            .send(to, TotalOrderMessage::Consensus(self.round, message))
+    }
+}
+
+/// Selects the union of the proposed batches, ordered by id, so that the decision does not
+/// depend on the order in which the proposals became known.
+fn union<P, M>(proposals: &[Batch<P, M>]) -> Result<Batch<P, M>, InternalError>
+where
+    P: Process + Ord,
+    M: Clone,
+{
+    if proposals.is_empty() {
+        return Err(InternalError::with_message(
+            "no proposals to select from".into(),
+        ));
+    }
+
+    let messages: BTreeMap<BroadcastId<P>, BroadcastMessage<P, M>> = proposals
+        .iter()
+        .flat_map(|batch| batch.messages.iter())
+        .map(|message| (*message.id(), message.clone()))
+        .collect();
+
+    Ok(Batch {
+        messages: messages.into_values().collect(),
+    })
+}
+
+/// The decision is read from the context of the consensus instance instead.
+fn ignore_decision<P, M>(_batch: Batch<P, M>) -> Result<(), InternalError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::{channel, Sender};
+    use std::time::Duration;
+
+    use crate::communication::{IntraProcessNetwork, IntraProcessNetworkSender};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(&'static str);
+
+    impl Message for TestMessage {}
+
+    type TestNetworkSender =
+        IntraProcessNetworkSender<TestProcess, TotalOrderMessage<TestProcess, TestMessage>>;
+
+    /// Reports every delivered message on a channel, along with the process it was delivered to.
+    struct ChannelReceiver {
+        process: TestProcess,
+        delivered: Sender<(TestProcess, TestProcess, TestMessage)>,
+    }
+
+    impl TotalOrderBroadcastReceiver<TestProcess, TestMessage> for ChannelReceiver {
+        fn deliver(
+            &mut self,
+            origin: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered
+                .send((self.process, origin, message))
+                .map_err(|err| InternalError::from_source(Box::new(err)))
+        }
+    }
+
+    /// Tests that the union of proposed batches contains each message once, ordered by id.
+    #[test]
+    fn test_union() {
+        let (p1, p2) = (TestProcess { id: 1 }, TestProcess { id: 2 });
+        let a = BroadcastMessage::new(BroadcastId::new(p2, 0), TestMessage("a"));
+        let b = BroadcastMessage::new(BroadcastId::new(p1, 1), TestMessage("b"));
+        let c = BroadcastMessage::new(BroadcastId::new(p1, 0), TestMessage("c"));
+
+        let selected = union(&[
+            Batch {
+                messages: vec![b.clone(), a.clone()],
+            },
+            Batch {
+                messages: vec![a.clone(), c.clone()],
+            },
+        ])
+        .unwrap();
+        assert_eq!(selected.messages(), &[c, b, a]);
+
+        assert!(union::<TestProcess, TestMessage>(&[]).is_err());
+    }
+
+    /// Tests that when two processes broadcast different messages over an in-process network,
+    /// both processes deliver every message, in the same order.
+    #[test]
+    fn test_same_order_over_intraprocess() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+        let (delivered_sender, delivered_receiver) = channel();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        let senders: Vec<TotalOrderBroadcastSender<TestProcess, TestMessage, TestNetworkSender>> =
+            processes
+                .iter()
+                .map(|process| {
+                    let sender = TotalOrderBroadcastSender::new(
+                        *process,
+                        processes.clone(),
+                        network.sender(*process),
+                    );
+                    network.add_process(
+                        *process,
+                        sender.delivery_handler(ChannelReceiver {
+                            process: *process,
+                            delivered: delivered_sender.clone(),
+                        }),
+                    );
+                    sender
+                })
+                .collect();
+
+        let messages = [["a1", "a2", "a3"], ["b1", "b2", "b3"]];
+        for (sender, messages) in senders.iter().zip(messages) {
+            for message in messages {
+                sender.broadcast(TestMessage(message)).unwrap();
+            }
+        }
+
+        let mut delivered = vec![Vec::new(); processes.len()];
+        for _ in 0..processes.len() * 6 {
+            let (process, origin, message) = delivered_receiver
+                .recv_timeout(Duration::from_secs(10))
+                .expect("timed out waiting for deliveries");
+            delivered[(process.id - 1) as usize].push((origin, message));
+        }
+        network.shutdown().unwrap();
+
+        assert_eq!(delivered[0], delivered[1]);
+        let mut messages: Vec<&str> = delivered[0].iter().map(|(_, message)| message.0).collect();
+        messages.sort_unstable();
+        assert_eq!(messages, ["a1", "a2", "a3", "b1", "b2", "b3"]);
+        for (origin, message) in &delivered[0] {
+            assert_eq!(message.0.starts_with('a'), origin.id == 1);
+        }
+    }
+}
@@ -0,0 +1,349 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Uniform reliable broadcast.
+//!
+//! Implementation of the "Majority-Ack Uniform Reliable Broadcast" algorithm, built on
+//! best-effort broadcast. Every process relays each message the first time it sees it, which
+//! acknowledges it to every other process. A message is delivered once more than half of all
+//! processes have acknowledged it, so if any process delivers a message, even one which later
+//! crashes, every correct process delivers it. This requires a majority of processes to be
+//! correct, but no failure detector.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::error::InternalError;
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+
+use super::best_effort::{
+    BestEffortBroadcastReceiver, BestEffortBroadcastSender, BroadcastMessage,
+};
+use super::{BroadcastId, BroadcastIdGenerator};
+
+/// The best-effort broadcast message which carries a uniformly reliably broadcast message.
+pub type UniformReliableBroadcastMessage<P, M> = BroadcastMessage<P, BroadcastMessage<P, M>>;
+
+/// Receives messages delivered by uniform reliable broadcast.
+pub trait UniformReliableBroadcastReceiver<P, M> {
+    /// Delivers `message`, which was broadcast by `origin`.
+    fn deliver(&mut self, origin: P, message: M) -> Result<(), InternalError>;
+}
+
+/// Uniform reliable broadcast at a single process.
+///
+/// Messages delivered by best-effort broadcast to this process must be passed to the
+/// [`BestEffortBroadcastReceiver`] implementation.
+pub struct UniformReliableBroadcast<P, M, N, R> {
+    acks: HashMap<BroadcastId<P>, HashSet<P>>,
+    best_effort: BestEffortBroadcastSender<P, BroadcastMessage<P, M>, N>,
+    delivered: HashSet<BroadcastId<P>>,
+    id_generator: BroadcastIdGenerator<P>,
+    pending: HashSet<BroadcastId<P>>,
+    quorum: usize,
+    receiver: R,
+}
+
+impl<P, M, N, R> UniformReliableBroadcast<P, M, N, R>
+where
+    P: Process + Hash + Debug,
+    M: Message + Clone,
+    N: NetworkSender<P, UniformReliableBroadcastMessage<P, M>>,
+    R: UniformReliableBroadcastReceiver<P, M>,
+{
+    /// Constructs a new `UniformReliableBroadcast` which broadcasts from `this_process` over
+    /// `network` and delivers to `receiver`.
+    ///
+    /// `processes` must contain every process, including this one; a message is delivered once
+    /// it has been acknowledged by more than half of them.
+    pub fn new(this_process: P, processes: Vec<P>, network: N, receiver: R) -> Self {
+        let quorum = processes.len() / 2 + 1;
+
+        UniformReliableBroadcast {
+            acks: HashMap::new(),
+            best_effort: BestEffortBroadcastSender::new(this_process, processes, network),
+            delivered: HashSet::new(),
+            id_generator: BroadcastIdGenerator::new(this_process),
+            pending: HashSet::new(),
+            quorum,
+            receiver,
+        }
+    }
+
+    /// Returns the number of acknowledgements required to deliver a message.
+    pub fn quorum(&self) -> usize {
+        self.quorum
+    }
+
+    /// Broadcasts `message` to every process, returning the id assigned to the broadcast.
+    pub fn broadcast(&mut self, message: M) -> Result<BroadcastId<P>, InternalError> {
+        let id = self.id_generator.next_id();
+        self.pending.insert(id);
+        self.best_effort
+            .broadcast(BroadcastMessage::new(id, message))?;
+        Ok(id)
+    }
+}
+
+impl<P, M, N, R> BestEffortBroadcastReceiver<P, BroadcastMessage<P, M>>
+    for UniformReliableBroadcast<P, M, N, R>
+where
+    P: Process + Hash + Debug,
+    M: Message + Clone,
+    N: NetworkSender<P, UniformReliableBroadcastMessage<P, M>>,
+    R: UniformReliableBroadcastReceiver<P, M>,
+{
+    fn deliver(
+        &mut self,
+        process: P,
+        message: BroadcastMessage<P, M>,
+    ) -> Result<(), InternalError> {
+        let id = *message.id();
+
+        // An acknowledgement which arrives after delivery must not record the message again
+        if self.delivered.contains(&id) {
+            return Ok(());
+        }
+
+        let acks = self.acks.entry(id).or_default();
+        acks.insert(process);
+        let can_deliver = acks.len() >= self.quorum;
+
+        if self.pending.insert(id) {
+            // A crashed minority must not keep the message from being delivered, and the relay
+            // is not retried, so failures are only logged
+            let report = self.best_effort.broadcast_with_report(message.clone());
+            for (process, err) in report.failed() {
+                warn!(
+                    "Unable to relay broadcast {:?} to {:?}: {}",
+                    id, process, err
+                );
+            }
+        }
+
+        if can_deliver && self.delivered.insert(id) {
+            // Acknowledgements are no longer needed once the message is delivered
+            self.acks.remove(&id);
+            self.receiver
+                .deliver(*id.origin(), message.into_payload())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(&'static str);
+
+    impl Message for TestMessage {}
+
+    type Sent = Rc<RefCell<Vec<UniformReliableBroadcastMessage<TestProcess, TestMessage>>>>;
+
+    /// A network which records every message sent, except those sent to `unreachable`, which
+    /// fail.
+    struct RecordingNetwork {
+        sent: Sent,
+        unreachable: Option<TestProcess>,
+    }
+
+    impl NetworkSender<TestProcess, UniformReliableBroadcastMessage<TestProcess, TestMessage>>
+        for RecordingNetwork
+    {
+        fn send(
+            &self,
+            to: &TestProcess,
+            message: UniformReliableBroadcastMessage<TestProcess, TestMessage>,
+        ) -> Result<(), InternalError> {
+            if self.unreachable == Some(*to) {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            self.sent.borrow_mut().push(message);
+            Ok(())
+        }
+    }
+
+    type Delivered = Rc<RefCell<Vec<(TestProcess, TestMessage)>>>;
+
+    struct CollectingReceiver {
+        delivered: Delivered,
+    }
+
+    impl UniformReliableBroadcastReceiver<TestProcess, TestMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            origin: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.borrow_mut().push((origin, message));
+            Ok(())
+        }
+    }
+
+    /// Delivers a message broadcast by the first process to a uniform reliable broadcast among
+    /// `n` processes, acknowledged by the first `acks` processes (counting one of them twice),
+    /// and returns what was delivered.
+    fn deliver_with_acks(n: u64, acks: u64) -> Vec<(TestProcess, TestMessage)> {
+        let processes: Vec<TestProcess> = (1..=n).map(|id| TestProcess { id }).collect();
+        let delivered = Delivered::default();
+        let sent = Sent::default();
+
+        let mut urb = UniformReliableBroadcast::new(
+            processes[n as usize - 1],
+            processes.clone(),
+            RecordingNetwork {
+                sent: sent.clone(),
+                unreachable: None,
+            },
+            CollectingReceiver {
+                delivered: delivered.clone(),
+            },
+        );
+        assert_eq!(urb.quorum() as u64, n / 2 + 1);
+
+        let message = BroadcastMessage::new(BroadcastId::new(processes[0], 0), TestMessage("m"));
+        for process in processes.iter().take(acks as usize) {
+            urb.deliver(*process, message.clone()).unwrap();
+        }
+        urb.deliver(processes[0], message).unwrap();
+
+        // The message is relayed exactly once, to every process, when first seen
+        assert_eq!(sent.borrow().len(), n as usize);
+
+        let delivered = delivered.borrow().clone();
+        delivered
+    }
+
+    /// Tests that a message acknowledged by exactly half of the processes (rounded down) is not
+    /// delivered, for both an even and an odd number of processes.
+    #[test]
+    fn test_half_acks_not_delivered() {
+        assert!(deliver_with_acks(4, 2).is_empty());
+        assert!(deliver_with_acks(5, 2).is_empty());
+    }
+
+    /// Tests that a message acknowledged by one more than half of the processes (rounded down)
+    /// is delivered exactly once.
+    #[test]
+    fn test_majority_acks_delivered() {
+        let processes = TestProcess { id: 1 };
+        assert_eq!(deliver_with_acks(4, 3), vec![(processes, TestMessage("m"))]);
+        assert_eq!(deliver_with_acks(5, 3), vec![(processes, TestMessage("m"))]);
+    }
+
+    /// Tests that a process's own broadcast is not relayed again when it is delivered back to it.
+    #[test]
+    fn test_own_broadcast_not_relayed() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let delivered = Delivered::default();
+        let sent = Sent::default();
+        let mut urb = UniformReliableBroadcast::new(
+            processes[0],
+            processes.clone(),
+            RecordingNetwork {
+                sent: sent.clone(),
+                unreachable: None,
+            },
+            CollectingReceiver {
+                delivered: delivered.clone(),
+            },
+        );
+
+        urb.broadcast(TestMessage("m")).unwrap();
+        let message = sent.borrow()[0].payload().clone();
+        urb.deliver(processes[0], message.clone()).unwrap();
+        urb.deliver(processes[1], message).unwrap();
+
+        assert_eq!(sent.borrow().len(), 3);
+        assert_eq!(*delivered.borrow(), vec![(processes[0], TestMessage("m"))]);
+    }
+
+    /// Tests that an acknowledgement which arrives after a message was delivered is ignored,
+    /// rather than recording acknowledgements for the message again.
+    #[test]
+    fn test_late_ack_ignored() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let delivered = Delivered::default();
+        let sent = Sent::default();
+        let mut urb = UniformReliableBroadcast::new(
+            processes[2],
+            processes.clone(),
+            RecordingNetwork {
+                sent: sent.clone(),
+                unreachable: None,
+            },
+            CollectingReceiver {
+                delivered: delivered.clone(),
+            },
+        );
+
+        let message = BroadcastMessage::new(BroadcastId::new(processes[0], 0), TestMessage("m"));
+        urb.deliver(processes[0], message.clone()).unwrap();
+        urb.deliver(processes[1], message.clone()).unwrap();
+        assert_eq!(delivered.borrow().len(), 1);
+        assert!(urb.acks.is_empty());
+
+        urb.deliver(processes[2], message).unwrap();
+        assert!(urb.acks.is_empty());
+        assert_eq!(delivered.borrow().len(), 1);
+        assert_eq!(sent.borrow().len(), 3);
+    }
+
+    /// Tests that a message is still relayed to the reachable processes and delivered once a
+    /// majority acknowledges it, while one process cannot be reached.
+    #[test]
+    fn test_unreachable_process_does_not_block_delivery() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let delivered = Delivered::default();
+        let sent = Sent::default();
+        let mut urb = UniformReliableBroadcast::new(
+            processes[2],
+            processes.clone(),
+            RecordingNetwork {
+                sent: sent.clone(),
+                unreachable: Some(processes[1]),
+            },
+            CollectingReceiver {
+                delivered: delivered.clone(),
+            },
+        );
+
+        let message = BroadcastMessage::new(BroadcastId::new(processes[0], 0), TestMessage("m"));
+        urb.deliver(processes[0], message.clone()).unwrap();
+        assert_eq!(sent.borrow().len(), 2);
+
+        // The relay delivered back to this process acknowledges the message
+        urb.deliver(processes[2], message).unwrap();
+        assert_eq!(*delivered.borrow(), vec![(processes[0], TestMessage("m"))]);
+    }
+}
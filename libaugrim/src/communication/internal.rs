@@ -0,0 +1,846 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A network which delivers messages between processes running in the same OS process.
+//!
+//! Every process added to an [`IntraProcessNetwork`] is represented by a [`Receiver`]. Messages
+//! sent with an [`IntraProcessNetworkSender`] are queued and passed by a routing thread to the
+//! delivery thread of the destination process, which delivers them to its receiver in the order
+//! they were sent. Each process has its own delivery thread, so processes run concurrently and a
+//! slow receiver does not hold back the delivery of messages to other processes.
+//!
+//! A process can be given a processing delay with [`IntraProcessNetwork::set_processing_delay`]
+//! to model a slow receiver: after each message is delivered to it, further messages to that
+//! process are held back until the delay has elapsed.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::mpsc::{
+    channel, Receiver as ChannelReceiver, RecvTimeoutError, Sender as ChannelSender,
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::error::InternalError;
+use crate::links::{FairLossLink, PerfectLink, Receiver, Sender};
+use crate::network::NetworkSender;
+use crate::process::Process;
+
+enum ControlMessage<P, M> {
+    Message { from: P, to: P, message: M },
+    SetProcessingDelay { process: P, delay: Duration },
+    Shutdown,
+}
+
+/// The receiver of a process, shared with its delivery thread. The receiver is taken out when
+/// the process is removed.
+type SharedReceiver<R> = Arc<Mutex<Option<R>>>;
+
+type ProcessToReceiver<P, R> = Arc<Mutex<HashMap<P, SharedReceiver<R>>>>;
+
+type RouterResult = Result<(), IntraProcessNetworkError>;
+
+/// A network of processes within a single OS process, for tests and single-machine deployments.
+pub struct IntraProcessNetwork<P, M, R> {
+    process_to_receiver: ProcessToReceiver<P, R>,
+    sender: ChannelSender<ControlMessage<P, M>>,
+    join_handle: Option<JoinHandle<RouterResult>>,
+    stopped: ChannelReceiver<()>,
+}
+
+impl<P, M, R> IntraProcessNetwork<P, M, R>
+where
+    P: Process + Hash + Send + 'static,
+    M: Send + 'static,
+    R: Receiver<P, M> + Send + 'static,
+{
+    /// Constructs a new `IntraProcessNetwork` and starts its routing thread.
+    pub fn new() -> Result<Self, InternalError> {
+        let (sender, receiver) = channel();
+        let process_to_receiver: ProcessToReceiver<P, R> = Arc::new(Mutex::new(HashMap::new()));
+
+        let thread_process_to_receiver = process_to_receiver.clone();
+        let (stopped_sender, stopped) = channel();
+        let join_handle = thread::Builder::new()
+            .name("IntraProcessNetwork".into())
+            .spawn(move || {
+                let result = Router::new(thread_process_to_receiver).run(receiver);
+                let _ = stopped_sender.send(());
+                result
+            })
+            .map_err(|err| InternalError::from_source(Box::new(err)))?;
+
+        Ok(IntraProcessNetwork {
+            process_to_receiver,
+            sender,
+            join_handle: Some(join_handle),
+            stopped,
+        })
+    }
+
+    /// Adds `process` to the network; messages sent to it are delivered to `receiver`.
+    ///
+    /// If the process was already present, its previous receiver is replaced.
+    pub fn add_process(&mut self, process: P, receiver: R) {
+        lock(&self.process_to_receiver).insert(process, Arc::new(Mutex::new(Some(receiver))));
+    }
+
+    /// Removes `process` from the network, returning its receiver if it was present.
+    ///
+    /// Messages to a removed process are dropped, which simulates the process crashing. If a
+    /// message is being delivered to the process, this waits for the delivery to finish.
+    pub fn remove_process(&mut self, process: &P) -> Option<R> {
+        let receiver = lock(&self.process_to_receiver).remove(process)?;
+        let removed = lock(&receiver).take();
+        removed
+    }
+
+    /// Sets the time `process` takes to process each message delivered to it.
+    ///
+    /// After a message is delivered to the process, the next message to it is held back until
+    /// `delay` has elapsed. A zero delay removes the throttling.
+    pub fn set_processing_delay(
+        &mut self,
+        process: P,
+        delay: Duration,
+    ) -> Result<(), InternalError> {
+        self.sender
+            .send(ControlMessage::SetProcessingDelay { process, delay })
+            .map_err(|_| IntraProcessNetworkError::NetworkShutdown.into())
+    }
+
+    /// Returns a sender which sends messages from `process` to other processes on the network.
+    pub fn sender(&self, process: P) -> IntraProcessNetworkSender<P, M> {
+        IntraProcessNetworkSender {
+            from: process,
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Stops the routing and delivery threads after all previously sent messages have been
+    /// delivered, including those held back by a processing delay.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if a delivery thread panicked.
+    pub fn shutdown(self) -> Result<(), InternalError> {
+        self.stop(None)
+    }
+
+    /// Stops the routing and delivery threads as for [`IntraProcessNetwork::shutdown`], but
+    /// waits at most `timeout` for the previously sent messages to be delivered.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the messages were not all delivered within `timeout`, in
+    /// which case the threads are left to finish delivering them in the background, or if a
+    /// delivery thread panicked.
+    pub fn shutdown_with_timeout(self, timeout: Duration) -> Result<(), InternalError> {
+        self.stop(Some(timeout))
+    }
+
+    fn stop(mut self, timeout: Option<Duration>) -> Result<(), InternalError> {
+        // The thread may have already exited, in which case there is nothing to stop
+        let _ = self.sender.send(ControlMessage::Shutdown);
+
+        let join_handle = match self.join_handle.take() {
+            Some(join_handle) => join_handle,
+            None => return Ok(()),
+        };
+
+        // The routing thread signals once it has stopped; if it panicked the channel is closed
+        // instead, which the join reports
+        if let Some(timeout) = timeout {
+            if let Err(RecvTimeoutError::Timeout) = self.stopped.recv_timeout(timeout) {
+                return Err(IntraProcessNetworkError::ShutdownTimedOut.into());
+            }
+        }
+
+        match join_handle.join() {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(IntraProcessNetworkError::DeliveryThreadPanicked.into()),
+        }
+    }
+}
+
+impl<P, M, R> Drop for IntraProcessNetwork<P, M, R> {
+    fn drop(&mut self) {
+        // Receivers may hold senders, so the thread would otherwise never see the channel close
+        if self.join_handle.is_some() {
+            let _ = self.sender.send(ControlMessage::Shutdown);
+        }
+    }
+}
+
+/// An error returned by an [`IntraProcessNetwork`] or [`IntraProcessNetworkSender`].
+#[derive(Debug, PartialEq)]
+pub enum IntraProcessNetworkError {
+    /// A delivery thread panicked, so messages may have been lost.
+    DeliveryThreadPanicked,
+    /// The network has shut down, so the message cannot be delivered.
+    NetworkShutdown,
+    /// The messages sent before shutdown were not all delivered before the timeout.
+    ShutdownTimedOut,
+}
+
+impl error::Error for IntraProcessNetworkError {}
+
+impl fmt::Display for IntraProcessNetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntraProcessNetworkError::DeliveryThreadPanicked => {
+                f.write_str("IntraProcessNetwork delivery thread panicked")
+            }
+            IntraProcessNetworkError::NetworkShutdown => {
+                f.write_str("IntraProcessNetwork has shut down")
+            }
+            IntraProcessNetworkError::ShutdownTimedOut => {
+                f.write_str("IntraProcessNetwork did not deliver every message before the timeout")
+            }
+        }
+    }
+}
+
+impl From<IntraProcessNetworkError> for InternalError {
+    fn from(err: IntraProcessNetworkError) -> Self {
+        InternalError::from_source(Box::new(err))
+    }
+}
+
+/// Sends messages from one process over an [`IntraProcessNetwork`].
+///
+/// Messages are neither lost nor duplicated while the destination process remains on the
+/// network, so the sender provides the perfect link properties.
+pub struct IntraProcessNetworkSender<P, M> {
+    from: P,
+    sender: ChannelSender<ControlMessage<P, M>>,
+}
+
+impl<P, M> Clone for IntraProcessNetworkSender<P, M>
+where
+    P: Process,
+{
+    fn clone(&self) -> Self {
+        IntraProcessNetworkSender {
+            from: self.from,
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<P, M> IntraProcessNetworkSender<P, M>
+where
+    P: Process,
+{
+    /// Queues `message` for delivery to the process `to`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IntraProcessNetworkError::NetworkShutdown`] if the network has shut down.
+    pub fn send(&self, to: &P, message: M) -> Result<(), IntraProcessNetworkError> {
+        self.sender
+            .send(ControlMessage::Message {
+                from: self.from,
+                to: *to,
+                message,
+            })
+            .map_err(|_| IntraProcessNetworkError::NetworkShutdown)
+    }
+}
+
+impl<P, M> Sender<P, M> for IntraProcessNetworkSender<P, M>
+where
+    P: Process,
+{
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError> {
+        Ok(IntraProcessNetworkSender::send(self, to, message)?)
+    }
+}
+
+impl<P, M> NetworkSender<P, M> for IntraProcessNetworkSender<P, M>
+where
+    P: Process,
+{
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError> {
+        Ok(IntraProcessNetworkSender::send(self, to, message)?)
+    }
+}
+
+impl<P, M> FairLossLink for IntraProcessNetworkSender<P, M> {}
+
+impl<P, M> PerfectLink for IntraProcessNetworkSender<P, M> {}
+
+/// A message to the delivery thread of a single process.
+enum Delivery<P, M> {
+    Message { from: P, message: M },
+    SetProcessingDelay(Duration),
+}
+
+type DeliveryThread<P, M> = (ChannelSender<Delivery<P, M>>, JoinHandle<()>);
+
+/// The state of the routing thread, which passes each message to the delivery thread of its
+/// destination, starting the thread with the first message to the process.
+struct Router<P, M, R> {
+    process_to_receiver: ProcessToReceiver<P, R>,
+    delivery_threads: HashMap<P, DeliveryThread<P, M>>,
+}
+
+impl<P, M, R> Router<P, M, R>
+where
+    P: Process + Hash + Send + 'static,
+    M: Send + 'static,
+    R: Receiver<P, M> + Send + 'static,
+{
+    fn new(process_to_receiver: ProcessToReceiver<P, R>) -> Self {
+        Router {
+            process_to_receiver,
+            delivery_threads: HashMap::new(),
+        }
+    }
+
+    fn run(mut self, receiver: ChannelReceiver<ControlMessage<P, M>>) -> RouterResult {
+        for control in receiver.iter() {
+            match control {
+                ControlMessage::Message { from, to, message } => {
+                    self.route(to, Delivery::Message { from, message })
+                }
+                ControlMessage::SetProcessingDelay { process, delay } => {
+                    self.route(process, Delivery::SetProcessingDelay(delay))
+                }
+                ControlMessage::Shutdown => break,
+            }
+        }
+
+        // Closing the channels stops each delivery thread once it has delivered the messages
+        // already routed to it
+        let (senders, join_handles): (Vec<_>, Vec<_>) = self
+            .delivery_threads
+            .drain()
+            .map(|(_, thread)| thread)
+            .unzip();
+        drop(senders);
+
+        let mut result = Ok(());
+        for join_handle in join_handles {
+            if join_handle.join().is_err() {
+                result = Err(IntraProcessNetworkError::DeliveryThreadPanicked);
+            }
+        }
+        result
+    }
+
+    /// Passes `delivery` to the delivery thread of `process`, starting the thread if needed.
+    fn route(&mut self, process: P, delivery: Delivery<P, M>) {
+        if !self.delivery_threads.contains_key(&process) {
+            let process_to_receiver = self.process_to_receiver.clone();
+            let (sender, receiver) = channel();
+            let spawned = thread::Builder::new()
+                .name("IntraProcessNetwork delivery".into())
+                .spawn(move || run_delivery(process, process_to_receiver, receiver));
+
+            match spawned {
+                Ok(join_handle) => {
+                    self.delivery_threads.insert(process, (sender, join_handle));
+                }
+                Err(err) => {
+                    error!("Unable to start delivery thread: {}", err);
+                    return;
+                }
+            }
+        }
+
+        if let Some((sender, _)) = self.delivery_threads.get(&process) {
+            if sender.send(delivery).is_err() {
+                warn!("Dropping message to a process whose delivery thread has stopped");
+            }
+        }
+    }
+}
+
+/// Delivers the messages routed to the process `to`, in order, until the routing thread closes
+/// the channel.
+fn run_delivery<P, M, R>(
+    to: P,
+    process_to_receiver: ProcessToReceiver<P, R>,
+    receiver: ChannelReceiver<Delivery<P, M>>,
+) where
+    P: Process + Hash,
+    R: Receiver<P, M>,
+{
+    let mut delay = None;
+
+    for delivery in receiver.iter() {
+        match delivery {
+            Delivery::Message { from, message } => {
+                deliver(&process_to_receiver, from, to, message);
+                if let Some(delay) = delay {
+                    thread::sleep(delay);
+                }
+            }
+            Delivery::SetProcessingDelay(processing_delay) => {
+                delay = Some(processing_delay).filter(|delay| *delay != Duration::from_secs(0));
+            }
+        }
+    }
+}
+
+fn deliver<P, M, R>(process_to_receiver: &ProcessToReceiver<P, R>, from: P, to: P, message: M)
+where
+    P: Process + Hash,
+    R: Receiver<P, M>,
+{
+    let receiver = lock(process_to_receiver).get(&to).cloned();
+
+    match receiver {
+        Some(receiver) => match lock(&receiver).as_mut() {
+            Some(receiver) => {
+                if let Err(err) = receiver.deliver(from, message) {
+                    error!("Unable to deliver message: {}", err);
+                }
+            }
+            None => warn!("Dropping message to a process which has been removed"),
+        },
+        None => warn!("Dropping message to a process which is not on the network"),
+    }
+}
+
+/// Locks the map of receivers or a single receiver. A receiver which panicked while delivering
+/// does not leave either in an inconsistent state, so a poisoned lock is recovered.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use crate::process::ProcessId;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    type Delivered = Arc<Mutex<Vec<(TestProcess, u64)>>>;
+
+    /// A receiver which records every delivered message, except `u64::MAX`, which it fails to
+    /// deliver.
+    struct CollectingReceiver {
+        delivered: Delivered,
+    }
+
+    impl Receiver<TestProcess, u64> for CollectingReceiver {
+        fn deliver(&mut self, from: TestProcess, message: u64) -> Result<(), InternalError> {
+            if message == u64::MAX {
+                return Err(InternalError::with_message("unable to deliver".into()));
+            }
+            self.delivered.lock().unwrap().push((from, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that messages sent to a removed process are dropped without disrupting delivery to
+    /// the remaining processes.
+    #[test]
+    fn test_remove_process() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let delivered_to_p2 = Delivered::default();
+        let delivered_to_p3 = Delivered::default();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        network.add_process(
+            p2,
+            CollectingReceiver {
+                delivered: delivered_to_p2.clone(),
+            },
+        );
+        network.add_process(
+            p3,
+            CollectingReceiver {
+                delivered: delivered_to_p3.clone(),
+            },
+        );
+
+        assert!(network.remove_process(&p2).is_some());
+        assert!(network.remove_process(&p2).is_none());
+
+        let sender = network.sender(p1);
+        sender.send(&p2, 1).unwrap();
+        sender.send(&p3, 2).unwrap();
+
+        // Shutting down waits for previously sent messages to be delivered
+        network.shutdown().unwrap();
+
+        assert!(delivered_to_p2.lock().unwrap().is_empty());
+        assert_eq!(*delivered_to_p3.lock().unwrap(), vec![(p1, 2)]);
+    }
+
+    /// Tests that neither a message to a process which was never added nor a receiver which fails
+    /// to deliver stops the delivery thread.
+    #[test]
+    fn test_unknown_process_and_deliver_error() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let delivered = Delivered::default();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        network.add_process(
+            p2,
+            CollectingReceiver {
+                delivered: delivered.clone(),
+            },
+        );
+
+        let sender = network.sender(p1);
+        sender.send(&TestProcess { id: 9 }, 1).unwrap();
+        sender.send(&p2, u64::MAX).unwrap();
+        sender.send(&p2, 2).unwrap();
+
+        network.shutdown().unwrap();
+
+        assert_eq!(*delivered.lock().unwrap(), vec![(p1, 2)]);
+    }
+
+    /// Tests that sending after the network has shut down returns an error.
+    #[test]
+    fn test_send_after_shutdown() {
+        let network: IntraProcessNetwork<TestProcess, u64, CollectingReceiver> =
+            IntraProcessNetwork::new().unwrap();
+        let sender = network.sender(TestProcess { id: 1 });
+
+        network.shutdown().unwrap();
+
+        assert_eq!(
+            sender.send(&TestProcess { id: 2 }, 1),
+            Err(IntraProcessNetworkError::NetworkShutdown)
+        );
+    }
+
+    /// Tests that an `IntraProcessNetworkError` can be converted into an `InternalError` with `?`
+    /// and keeps its message.
+    #[test]
+    fn test_error_into_internal_error() {
+        fn send_after_shutdown() -> Result<(), InternalError> {
+            let network: IntraProcessNetwork<TestProcess, u64, CollectingReceiver> =
+                IntraProcessNetwork::new()?;
+            let sender = network.sender(TestProcess { id: 1 });
+            network.shutdown()?;
+            sender.send(&TestProcess { id: 2 }, 1)?;
+            Ok(())
+        }
+
+        let err = send_after_shutdown().expect_err("send after shutdown succeeded");
+        assert_eq!(err.to_string(), "IntraProcessNetwork has shut down");
+    }
+
+    type Timestamps = Arc<Mutex<Vec<Instant>>>;
+
+    /// A receiver which records the time at which each message is delivered.
+    struct TimestampReceiver {
+        timestamps: Timestamps,
+    }
+
+    impl Receiver<TestProcess, u64> for TimestampReceiver {
+        fn deliver(&mut self, _from: TestProcess, _message: u64) -> Result<(), InternalError> {
+            self.timestamps.lock().unwrap().push(Instant::now());
+            Ok(())
+        }
+    }
+
+    /// Tests that messages to a process with a processing delay are delivered spaced by at least
+    /// the delay, while messages to another process are delivered without waiting.
+    #[test]
+    fn test_processing_delay() {
+        let delay = Duration::from_millis(50);
+        let p1 = TestProcess { id: 1 };
+        let slow = TestProcess { id: 2 };
+        let fast = TestProcess { id: 3 };
+        let slow_timestamps = Timestamps::default();
+        let fast_timestamps = Timestamps::default();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        network.add_process(
+            slow,
+            TimestampReceiver {
+                timestamps: slow_timestamps.clone(),
+            },
+        );
+        network.add_process(
+            fast,
+            TimestampReceiver {
+                timestamps: fast_timestamps.clone(),
+            },
+        );
+        network.set_processing_delay(slow, delay).unwrap();
+
+        let sender = network.sender(p1);
+        for i in 0..3 {
+            sender.send(&slow, i).unwrap();
+            sender.send(&fast, i).unwrap();
+        }
+
+        network.shutdown().unwrap();
+
+        let slow_timestamps = slow_timestamps.lock().unwrap();
+        let fast_timestamps = fast_timestamps.lock().unwrap();
+        assert_eq!(slow_timestamps.len(), 3);
+        assert_eq!(fast_timestamps.len(), 3);
+
+        for pair in slow_timestamps.windows(2) {
+            assert!(pair[1].duration_since(pair[0]) >= delay);
+        }
+        assert!(fast_timestamps[2] < slow_timestamps[1]);
+    }
+
+    /// A receiver which sleeps before recording each delivered message.
+    struct SleepingReceiver {
+        sleep: Duration,
+        delivered: Delivered,
+    }
+
+    impl Receiver<TestProcess, u64> for SleepingReceiver {
+        fn deliver(&mut self, from: TestProcess, message: u64) -> Result<(), InternalError> {
+            thread::sleep(self.sleep);
+            self.delivered.lock().unwrap().push((from, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that while one receiver sleeps in the middle of a delivery, messages sent to another
+    /// process after it are still delivered.
+    #[test]
+    fn test_slow_receiver_does_not_block_others() {
+        let p1 = TestProcess { id: 1 };
+        let slow = TestProcess { id: 2 };
+        let fast = TestProcess { id: 3 };
+        let delivered_to_slow = Delivered::default();
+        let delivered_to_fast = Delivered::default();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        network.add_process(
+            slow,
+            SleepingReceiver {
+                sleep: Duration::from_millis(500),
+                delivered: delivered_to_slow.clone(),
+            },
+        );
+        network.add_process(
+            fast,
+            SleepingReceiver {
+                sleep: Duration::from_secs(0),
+                delivered: delivered_to_fast.clone(),
+            },
+        );
+
+        let sender = network.sender(p1);
+        sender.send(&slow, 1).unwrap();
+        for message in 0..3 {
+            sender.send(&fast, message).unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(400);
+        while delivered_to_fast.lock().unwrap().len() < 3 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            *delivered_to_fast.lock().unwrap(),
+            vec![(p1, 0), (p1, 1), (p1, 2)]
+        );
+        assert!(delivered_to_slow.lock().unwrap().is_empty());
+
+        network.shutdown().unwrap();
+        assert_eq!(*delivered_to_slow.lock().unwrap(), vec![(p1, 1)]);
+    }
+
+    /// Tests that shutting down with a timeout waits for every queued message to be delivered
+    /// before the delivery threads exit.
+    #[test]
+    fn test_shutdown_with_timeout_drains() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let delivered = Delivered::default();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        network.add_process(
+            p2,
+            CollectingReceiver {
+                delivered: delivered.clone(),
+            },
+        );
+
+        let sender = network.sender(p1);
+        for message in 0..10_000 {
+            sender.send(&p2, message).unwrap();
+        }
+
+        network
+            .shutdown_with_timeout(Duration::from_secs(30))
+            .unwrap();
+
+        let delivered = delivered.lock().unwrap();
+        assert_eq!(delivered.len(), 10_000);
+        assert!(delivered
+            .iter()
+            .enumerate()
+            .all(|(index, (_, message))| *message == index as u64));
+    }
+
+    /// Tests that shutting down with a timeout returns an error if the queued messages cannot be
+    /// delivered in time.
+    #[test]
+    fn test_shutdown_timed_out() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        network.add_process(
+            p2,
+            SleepingReceiver {
+                sleep: Duration::from_millis(200),
+                delivered: Delivered::default(),
+            },
+        );
+
+        let sender = network.sender(p1);
+        for message in 0..3 {
+            sender.send(&p2, message).unwrap();
+        }
+
+        let err = network
+            .shutdown_with_timeout(Duration::from_millis(50))
+            .expect_err("shutdown did not time out");
+        assert_eq!(
+            err.to_string(),
+            IntraProcessNetworkError::ShutdownTimedOut.to_string()
+        );
+    }
+
+    /// A receiver which records every delivered message, except `panic_on`, on which it panics.
+    struct PanickingReceiver {
+        panic_on: u64,
+        delivered: Delivered,
+    }
+
+    impl Receiver<TestProcess, u64> for PanickingReceiver {
+        fn deliver(&mut self, from: TestProcess, message: u64) -> Result<(), InternalError> {
+            if message == self.panic_on {
+                panic!("receiver panicked");
+            }
+            self.delivered.lock().unwrap().push((from, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that a receiver panicking on a delivery thread is reported by shutdown, and does not
+    /// stop delivery to other processes.
+    #[test]
+    fn test_shutdown_reports_panic() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let delivered = Delivered::default();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        network.add_process(
+            p2,
+            PanickingReceiver {
+                panic_on: 1,
+                delivered: Delivered::default(),
+            },
+        );
+        network.add_process(
+            p3,
+            PanickingReceiver {
+                panic_on: 1,
+                delivered: delivered.clone(),
+            },
+        );
+
+        let sender = network.sender(p1);
+        sender.send(&p2, 1).unwrap();
+        sender.send(&p2, 2).unwrap();
+        sender.send(&p3, 3).unwrap();
+
+        let err = network.shutdown().expect_err("panic was not reported");
+        assert_eq!(
+            err.to_string(),
+            IntraProcessNetworkError::DeliveryThreadPanicked.to_string()
+        );
+        assert_eq!(*delivered.lock().unwrap(), vec![(p1, 3)]);
+    }
+
+    type DeliveredById = Arc<Mutex<Vec<(ProcessId, u64)>>>;
+
+    /// A receiver for processes identified by `ProcessId`, which records every delivered message.
+    struct ProcessIdReceiver {
+        delivered: DeliveredById,
+    }
+
+    impl Receiver<ProcessId, u64> for ProcessIdReceiver {
+        fn deliver(&mut self, from: ProcessId, message: u64) -> Result<(), InternalError> {
+            self.delivered.lock().unwrap().push((from, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that `ProcessId` can be used as the process type of the network without defining a
+    /// process type, and that messages between such processes are delivered to the right one.
+    #[test]
+    fn test_process_id() {
+        let processes: Vec<ProcessId> = (1..=3).map(ProcessId::new).collect();
+        let delivered: Vec<DeliveredById> = processes.iter().map(|_| Arc::default()).collect();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        for (process, delivered) in processes.iter().zip(&delivered) {
+            network.add_process(
+                *process,
+                ProcessIdReceiver {
+                    delivered: delivered.clone(),
+                },
+            );
+        }
+
+        for from in &processes {
+            let sender = network.sender(*from);
+            for to in &processes {
+                if to != from {
+                    sender.send(to, from.id() * 10 + to.id()).unwrap();
+                }
+            }
+        }
+        network.shutdown().unwrap();
+
+        for (to, delivered) in processes.iter().zip(&delivered) {
+            let mut delivered = delivered.lock().unwrap().clone();
+            delivered.sort();
+            let expected: Vec<(ProcessId, u64)> = processes
+                .iter()
+                .filter(|from| *from != to)
+                .map(|from| (*from, from.id() * 10 + to.id()))
+                .collect();
+            assert_eq!(delivered, expected);
+        }
+    }
+}
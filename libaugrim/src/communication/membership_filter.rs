@@ -0,0 +1,192 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Filtering of inbound messages by the membership of the sending process.
+
+use crate::error::InternalError;
+use crate::links::Receiver;
+use crate::process::Process;
+
+/// What a [`MembershipFilter`] does with a message from a process which is not a member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnknownProcessPolicy {
+    /// The message is discarded.
+    Drop,
+    /// The message is discarded and an error is returned to the transport.
+    Error,
+    /// The process is added to the membership and the message is delivered, for settings in
+    /// which any process may join.
+    AutoLearn,
+}
+
+/// A receiver which only delivers messages from member processes to the inner receiver, and
+/// applies an [`UnknownProcessPolicy`] to messages from any other process, such as a process
+/// which has not joined yet or a stale peer.
+///
+/// The default policy is [`UnknownProcessPolicy::Error`].
+pub struct MembershipFilter<P, R> {
+    processes: Vec<P>,
+    policy: UnknownProcessPolicy,
+    inner: R,
+}
+
+impl<P, R> MembershipFilter<P, R>
+where
+    P: Process,
+{
+    /// Constructs a new `MembershipFilter` which delivers the messages of `processes` to `inner`.
+    pub fn new(processes: Vec<P>, inner: R) -> Self {
+        MembershipFilter {
+            processes,
+            policy: UnknownProcessPolicy::Error,
+            inner,
+        }
+    }
+
+    /// Sets the policy applied to messages from processes which are not members.
+    pub fn with_policy(mut self, policy: UnknownProcessPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns the member processes, including those learned with
+    /// [`UnknownProcessPolicy::AutoLearn`], in the order they became members.
+    pub fn processes(&self) -> &[P] {
+        &self.processes
+    }
+
+    /// Returns the policy applied to messages from processes which are not members.
+    pub fn policy(&self) -> UnknownProcessPolicy {
+        self.policy
+    }
+
+    /// Returns the receiver messages from member processes are delivered to.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<P, M, R> Receiver<P, M> for MembershipFilter<P, R>
+where
+    P: Process,
+    R: Receiver<P, M>,
+{
+    /// Delivers `message` to the inner receiver if `from` is a member, and otherwise applies the
+    /// policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if `from` is not a member and the policy is
+    /// [`UnknownProcessPolicy::Error`], or if the inner receiver returns an error.
+    fn deliver(&mut self, from: P, message: M) -> Result<(), InternalError> {
+        if !self.processes.contains(&from) {
+            match self.policy {
+                UnknownProcessPolicy::Drop => {
+                    debug!("dropping message from a process which is not a member");
+                    return Ok(());
+                }
+                UnknownProcessPolicy::Error => {
+                    return Err(InternalError::with_message(
+                        "message delivered from a process which is not a member".into(),
+                    ))
+                }
+                UnknownProcessPolicy::AutoLearn => {
+                    info!("adding process to the membership on its first message");
+                    self.processes.push(from);
+                }
+            }
+        }
+
+        self.inner.deliver(from, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Default)]
+    struct CollectingReceiver {
+        delivered: Vec<(TestProcess, u64)>,
+    }
+
+    impl Receiver<TestProcess, u64> for CollectingReceiver {
+        fn deliver(&mut self, from: TestProcess, message: u64) -> Result<(), InternalError> {
+            self.delivered.push((from, message));
+            Ok(())
+        }
+    }
+
+    fn filter(policy: UnknownProcessPolicy) -> MembershipFilter<TestProcess, CollectingReceiver> {
+        MembershipFilter::new(vec![TestProcess { id: 1 }], CollectingReceiver::default())
+            .with_policy(policy)
+    }
+
+    /// Tests that with the `Drop` policy a message from an unknown process is discarded without
+    /// an error, while a member's message is delivered.
+    #[test]
+    fn test_drop_policy() {
+        let mut filter = filter(UnknownProcessPolicy::Drop);
+
+        filter.deliver(TestProcess { id: 9 }, 1).unwrap();
+        filter.deliver(TestProcess { id: 1 }, 2).unwrap();
+
+        assert_eq!(filter.inner().delivered, vec![(TestProcess { id: 1 }, 2)]);
+        assert_eq!(filter.processes(), &[TestProcess { id: 1 }]);
+    }
+
+    /// Tests that with the `Error` policy, the default, a message from an unknown process is
+    /// discarded and an error is returned.
+    #[test]
+    fn test_error_policy() {
+        let mut filter =
+            MembershipFilter::new(vec![TestProcess { id: 1 }], CollectingReceiver::default());
+        assert_eq!(filter.policy(), UnknownProcessPolicy::Error);
+
+        let err = filter
+            .deliver(TestProcess { id: 9 }, 1)
+            .expect_err("message from unknown process was accepted");
+        assert_eq!(
+            err.to_string(),
+            "message delivered from a process which is not a member"
+        );
+        assert!(filter.inner().delivered.is_empty());
+    }
+
+    /// Tests that with the `AutoLearn` policy an unknown process becomes a member on its first
+    /// message, which is delivered, and is not added again.
+    #[test]
+    fn test_auto_learn_policy() {
+        let mut filter = filter(UnknownProcessPolicy::AutoLearn);
+
+        filter.deliver(TestProcess { id: 9 }, 1).unwrap();
+        filter.deliver(TestProcess { id: 9 }, 2).unwrap();
+
+        assert_eq!(
+            filter.inner().delivered,
+            vec![(TestProcess { id: 9 }, 1), (TestProcess { id: 9 }, 2)]
+        );
+        assert_eq!(
+            filter.processes(),
+            &[TestProcess { id: 1 }, TestProcess { id: 9 }]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transports which carry messages between processes.
+
+mod internal;
+mod membership_filter;
+mod request_response;
+mod router;
+
+pub use internal::{IntraProcessNetwork, IntraProcessNetworkError, IntraProcessNetworkSender};
+pub use membership_filter::{MembershipFilter, UnknownProcessPolicy};
+pub use request_response::{ReplySender, Request, RequestResponse};
+pub use router::{RouteHandler, Router};
@@ -0,0 +1,355 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sending a request to a set of processes and waiting for their replies.
+//!
+//! A [`RequestResponse`] sends a request over a [`NetworkSender`] and blocks until every process
+//! has replied or a timeout has elapsed, as in a round of heartbeats. Each request is tagged with
+//! a sequence number, which the process replying must send back. Replies are not matched by the
+//! network; whatever handles the reply messages delivered to this process passes the sender and
+//! sequence number of each to the [`ReplySender`] of the `RequestResponse`.
+
+use std::marker::PhantomData;
+use std::sync::mpsc::{
+    channel, Receiver as ChannelReceiver, RecvTimeoutError, Sender as ChannelSender,
+};
+use std::time::Duration;
+
+use crate::error::InternalError;
+use crate::links::Receiver;
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+use crate::time::{Time, TimeSource};
+
+/// The longest a request waits in real time for a reply before checking its time source again.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A request sent by a [`RequestResponse`], tagged with the sequence number its replies must
+/// carry.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Request<M> {
+    sequence: u64,
+    payload: M,
+}
+
+impl<M> Request<M> {
+    /// Constructs the request with the given sequence number and payload.
+    pub fn new(sequence: u64, payload: M) -> Self {
+        Request { sequence, payload }
+    }
+
+    /// Returns the sequence number which replies to the request must carry.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the payload of the request.
+    pub fn payload(&self) -> &M {
+        &self.payload
+    }
+
+    /// Returns the payload of the request, consuming the request.
+    pub fn into_payload(self) -> M {
+        self.payload
+    }
+}
+
+impl<M> Message for Request<M> {}
+
+/// Sends requests to processes and waits for their replies, up to a timeout measured by a
+/// [`TimeSource`].
+pub struct RequestResponse<P, M, N, T> {
+    network: N,
+    time_source: T,
+    next_sequence: u64,
+    replies: ChannelReceiver<(P, u64)>,
+    reply_sender: ChannelSender<(P, u64)>,
+    _message: PhantomData<M>,
+}
+
+impl<P, M, N, T> RequestResponse<P, M, N, T>
+where
+    P: Process,
+    M: Clone,
+    N: NetworkSender<P, Request<M>>,
+    T: TimeSource,
+{
+    /// Constructs a new `RequestResponse` which sends requests over `network` and measures
+    /// timeouts with `time_source`.
+    pub fn new(network: N, time_source: T) -> Self {
+        let (reply_sender, replies) = channel();
+
+        RequestResponse {
+            network,
+            time_source,
+            next_sequence: 0,
+            replies,
+            reply_sender,
+            _message: PhantomData,
+        }
+    }
+
+    /// Returns the sender to which the replies delivered to this process must be passed.
+    pub fn reply_sender(&self) -> ReplySender<P> {
+        ReplySender {
+            sender: self.reply_sender.clone(),
+        }
+    }
+
+    /// Sends `request` to each of `processes`, and returns those which replied before `timeout`
+    /// elapsed, in the order they were given.
+    ///
+    /// Returns as soon as every process has replied. Replies which carry the sequence number of
+    /// another request are discarded, such as late replies to an earlier request which timed
+    /// out, as are replies from processes which were not sent the request. The time
+    /// source is checked whenever a reply arrives, and otherwise at least every 10 milliseconds
+    /// of real time, so that a time source which does not follow real time is honoured.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `InternalError` if the request cannot be sent to one of the processes,
+    /// once the request has been sent to every other process.
+    pub fn request(
+        &mut self,
+        processes: &[P],
+        request: M,
+        timeout: Duration,
+    ) -> Result<Vec<P>, InternalError> {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        let request = Request::new(sequence, request);
+
+        let deadline = self.time_source.now().add(timeout);
+        let mut result = Ok(());
+        for process in processes {
+            result = result.and(self.network.send(process, request.clone()));
+        }
+        result?;
+
+        let mut replied = vec![false; processes.len()];
+        while replied.iter().any(|replied| !replied) {
+            let now = self.time_source.now();
+            if now >= deadline {
+                // Replies which arrived before the deadline was noticed still count
+                while let Ok(reply) = self.replies.try_recv() {
+                    record_reply(processes, &mut replied, sequence, reply);
+                }
+                break;
+            }
+
+            let wait = deadline.duration_since(&now).min(POLL_INTERVAL);
+            match self.replies.recv_timeout(wait) {
+                Ok(reply) => record_reply(processes, &mut replied, sequence, reply),
+                Err(RecvTimeoutError::Timeout) => (),
+                // The reply sender held by this struct keeps the channel open
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        Ok(processes
+            .iter()
+            .zip(replied)
+            .filter(|(_, replied)| *replied)
+            .map(|(process, _)| *process)
+            .collect())
+    }
+}
+
+/// Marks the sender of `reply` as having replied, if it is one of `processes` and the reply
+/// carries `sequence`.
+fn record_reply<P: PartialEq>(
+    processes: &[P],
+    replied: &mut [bool],
+    sequence: u64,
+    reply: (P, u64),
+) {
+    let (from, reply_sequence) = reply;
+    if reply_sequence != sequence {
+        return;
+    }
+    if let Some(index) = processes.iter().position(|process| *process == from) {
+        replied[index] = true;
+    }
+}
+
+/// Passes the replies delivered to this process to a [`RequestResponse`].
+///
+/// As a [`Receiver`], every message delivered is treated as a reply from its sender, and the
+/// message is the sequence number of the request replied to.
+pub struct ReplySender<P> {
+    sender: ChannelSender<(P, u64)>,
+}
+
+impl<P> ReplySender<P> {
+    /// Records a reply from `from` to the request with the given sequence number.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the `RequestResponse` has been dropped.
+    pub fn reply(&self, from: P, sequence: u64) -> Result<(), InternalError> {
+        self.sender
+            .send((from, sequence))
+            .map_err(|_| InternalError::with_message("request-response has been dropped".into()))
+    }
+}
+
+impl<P> Clone for ReplySender<P> {
+    fn clone(&self) -> Self {
+        ReplySender {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<P> Receiver<P, u64> for ReplySender<P> {
+    fn deliver(&mut self, from: P, sequence: u64) -> Result<(), InternalError> {
+        self.reply(from, sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use crate::time::MockClock;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// A network on which every process except `silent` replies to a request as soon as it is
+    /// sent. Sending to `silent` advances the clock by an hour instead, as though the request
+    /// had been waiting on it, and `silent` replies late to the previous request. Sending to
+    /// `unreachable` fails. Every process sent a request is recorded in `sent`.
+    ///
+    /// The reply sender is set once the `RequestResponse` using the network has been constructed.
+    struct ReplyingNetwork {
+        silent: Option<TestProcess>,
+        unreachable: Option<TestProcess>,
+        sent: Arc<Mutex<Vec<TestProcess>>>,
+        clock: MockClock,
+        replies: Arc<Mutex<Option<ReplySender<TestProcess>>>>,
+    }
+
+    impl NetworkSender<TestProcess, Request<()>> for ReplyingNetwork {
+        fn send(&self, to: &TestProcess, message: Request<()>) -> Result<(), InternalError> {
+            if Some(*to) == self.unreachable {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            self.sent.lock().unwrap().push(*to);
+            let sequence = if Some(*to) == self.silent {
+                self.clock.advance(Duration::from_secs(3600));
+                message.sequence().wrapping_sub(1)
+            } else {
+                message.sequence()
+            };
+            if let Some(replies) = self.replies.lock().unwrap().as_ref() {
+                replies.reply(*to, sequence)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Constructs a `RequestResponse` over a `ReplyingNetwork` with the silent process.
+    fn request_response(
+        silent: Option<TestProcess>,
+        clock: MockClock,
+    ) -> RequestResponse<TestProcess, (), ReplyingNetwork, MockClock> {
+        let replies = Arc::new(Mutex::new(None));
+        let request_response = RequestResponse::new(
+            ReplyingNetwork {
+                silent,
+                unreachable: None,
+                sent: Arc::default(),
+                clock: clock.clone(),
+                replies: replies.clone(),
+            },
+            clock,
+        );
+        *replies.lock().unwrap() = Some(request_response.reply_sender());
+        request_response
+    }
+
+    /// Tests that when one of three processes never replies, the request returns once the
+    /// timeout has elapsed on the clock, with only the processes which replied.
+    #[test]
+    fn test_silent_process_excluded() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let mut request_response = request_response(Some(processes[1]), MockClock::new());
+
+        let replied = request_response
+            .request(&processes, (), Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(replied, vec![processes[0], processes[2]]);
+    }
+
+    /// Tests that the request returns as soon as every process has replied, without the clock
+    /// moving, and that stale replies and replies from other processes are ignored.
+    #[test]
+    fn test_all_replied() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let mut request_response = request_response(None, MockClock::new());
+
+        let mut stale = request_response.reply_sender();
+        Receiver::<TestProcess, u64>::deliver(&mut stale, TestProcess { id: 9 }, 0).unwrap();
+
+        let replied = request_response
+            .request(&processes, (), Duration::from_secs(3600))
+            .unwrap();
+        assert_eq!(replied, processes);
+    }
+
+    /// Tests that a late reply to an earlier request which timed out is not counted as a reply to
+    /// the next request.
+    #[test]
+    fn test_late_reply_ignored() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let mut request_response = request_response(Some(processes[1]), MockClock::new());
+
+        for _ in 0..2 {
+            let replied = request_response
+                .request(&processes, (), Duration::from_secs(60))
+                .unwrap();
+            assert_eq!(replied, vec![processes[0], processes[2]]);
+        }
+    }
+
+    /// Tests that when the request cannot be sent to the first process, it is still sent to the
+    /// others before the error is returned.
+    #[test]
+    fn test_send_error_reaches_remaining_processes() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let mut request_response = request_response(None, MockClock::new());
+        request_response.network.unreachable = Some(processes[0]);
+
+        assert!(request_response
+            .request(&processes, (), Duration::from_secs(60))
+            .is_err());
+        assert_eq!(
+            *request_response.network.sent.lock().unwrap(),
+            processes[1..].to_vec()
+        );
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Routing of inbound messages to the handler registered for them.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::error::InternalError;
+use crate::links::Receiver;
+
+/// A handler registered with a [`Router`].
+pub type RouteHandler<P, M> = Box<dyn Receiver<P, M> + Send>;
+
+/// Dispatches each message delivered to it to the handler registered for the message's
+/// discriminant.
+///
+/// This allows a single transport to carry the messages of several algorithms: the router is
+/// given to the transport as the receiver of a process, and each algorithm registers a handler
+/// for its own discriminant, which is extracted from each message by `discriminant`.
+pub struct Router<P, M, K, F> {
+    discriminant: F,
+    handlers: HashMap<K, RouteHandler<P, M>>,
+}
+
+impl<P, M, K, F> Router<P, M, K, F>
+where
+    K: Eq + Hash,
+    F: Fn(&M) -> K,
+{
+    /// Constructs a new `Router` with no handlers, which routes each message by the key returned
+    /// by `discriminant`.
+    pub fn new(discriminant: F) -> Self {
+        Router {
+            discriminant,
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers `handler` for the messages with discriminant `key`, returning the handler it
+    /// replaces, if any.
+    pub fn register<H>(&mut self, key: K, handler: H) -> Option<RouteHandler<P, M>>
+    where
+        H: Receiver<P, M> + Send + 'static,
+    {
+        self.handlers.insert(key, Box::new(handler))
+    }
+
+    /// Removes and returns the handler registered for `key`, if any.
+    pub fn unregister(&mut self, key: &K) -> Option<RouteHandler<P, M>> {
+        self.handlers.remove(key)
+    }
+}
+
+impl<P, M, K, F> Receiver<P, M> for Router<P, M, K, F>
+where
+    K: Eq + Hash,
+    F: Fn(&M) -> K,
+{
+    /// Delivers `message` to the handler registered for its discriminant.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if no handler is registered for the discriminant, or if the
+    /// handler returns an error.
+    fn deliver(&mut self, from: P, message: M) -> Result<(), InternalError> {
+        let key = (self.discriminant)(&message);
+        match self.handlers.get_mut(&key) {
+            Some(handler) => handler.deliver(from, message),
+            None => Err(InternalError::with_message(
+                "no handler registered for message".into(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum TestMessage {
+        Consensus(u64),
+        Heartbeat,
+        Commit,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    enum Route {
+        Consensus,
+        Heartbeat,
+        Commit,
+    }
+
+    fn route(message: &TestMessage) -> Route {
+        match message {
+            TestMessage::Consensus(_) => Route::Consensus,
+            TestMessage::Heartbeat => Route::Heartbeat,
+            TestMessage::Commit => Route::Commit,
+        }
+    }
+
+    type Delivered = Arc<Mutex<Vec<(TestProcess, TestMessage)>>>;
+
+    /// Records every message delivered to it, standing in for an algorithm's event queue.
+    struct QueueHandler {
+        delivered: Delivered,
+    }
+
+    impl Receiver<TestProcess, TestMessage> for QueueHandler {
+        fn deliver(
+            &mut self,
+            from: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.lock().unwrap().push((from, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that messages are routed to the handler registered for their discriminant, and that
+    /// a message with no registered handler is rejected.
+    #[test]
+    fn test_route_by_discriminant() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let consensus = Delivered::default();
+        let heartbeats = Delivered::default();
+
+        let mut router = Router::new(route);
+        router.register(
+            Route::Consensus,
+            QueueHandler {
+                delivered: consensus.clone(),
+            },
+        );
+        router.register(
+            Route::Heartbeat,
+            QueueHandler {
+                delivered: heartbeats.clone(),
+            },
+        );
+
+        router.deliver(p1, TestMessage::Consensus(1)).unwrap();
+        router.deliver(p2, TestMessage::Heartbeat).unwrap();
+        router.deliver(p2, TestMessage::Consensus(2)).unwrap();
+        assert!(router.deliver(p1, TestMessage::Commit).is_err());
+
+        assert_eq!(
+            *consensus.lock().unwrap(),
+            vec![
+                (p1, TestMessage::Consensus(1)),
+                (p2, TestMessage::Consensus(2))
+            ]
+        );
+        assert_eq!(
+            *heartbeats.lock().unwrap(),
+            vec![(p2, TestMessage::Heartbeat)]
+        );
+
+        assert!(router.unregister(&Route::Heartbeat).is_some());
+        assert!(router.deliver(p1, TestMessage::Heartbeat).is_err());
+    }
+}
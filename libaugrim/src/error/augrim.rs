@@ -0,0 +1,139 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Module containing AugrimError implementation.
+
+use std::error;
+use std::fmt;
+
+use crate::communication::IntraProcessNetworkError;
+
+use super::{InternalError, InvalidStateError, ResourceExhaustedError};
+
+/// An error returned by the library, for callers which handle the errors of several operations
+/// in one place.
+///
+/// Each of the library's errors converts into the matching variant, so a caller can distinguish
+/// an internal failure from an invalid state transition or a network failure.
+#[derive(Debug)]
+pub enum AugrimError {
+    /// An operation failed because of an internal implementation detail.
+    Internal(InternalError),
+    /// An operation was called in a way which results in an inconsistent state.
+    InvalidState(InvalidStateError),
+    /// An operation would have exceeded a configured limit on some resource.
+    ResourceExhausted(ResourceExhaustedError),
+    /// A message could not be carried between processes.
+    ///
+    /// The source is `Send + Sync`, so that it can be passed to or shared with another thread,
+    /// such as the one running a network.
+    Network(Box<dyn error::Error + Send + Sync>),
+}
+
+impl error::Error for AugrimError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            AugrimError::Internal(err) => Some(err),
+            AugrimError::InvalidState(err) => Some(err),
+            AugrimError::ResourceExhausted(err) => Some(err),
+            AugrimError::Network(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl fmt::Display for AugrimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AugrimError::Internal(err) => write!(f, "{}", err),
+            AugrimError::InvalidState(err) => write!(f, "{}", err),
+            AugrimError::ResourceExhausted(err) => write!(f, "{}", err),
+            AugrimError::Network(err) => write!(f, "network error: {}", err),
+        }
+    }
+}
+
+impl From<InternalError> for AugrimError {
+    fn from(err: InternalError) -> Self {
+        AugrimError::Internal(err)
+    }
+}
+
+impl From<InvalidStateError> for AugrimError {
+    fn from(err: InvalidStateError) -> Self {
+        AugrimError::InvalidState(err)
+    }
+}
+
+impl From<ResourceExhaustedError> for AugrimError {
+    fn from(err: ResourceExhaustedError) -> Self {
+        AugrimError::ResourceExhausted(err)
+    }
+}
+
+impl From<IntraProcessNetworkError> for AugrimError {
+    fn from(err: IntraProcessNetworkError) -> Self {
+        AugrimError::Network(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    /// Fails to compile unless the argument is `Send + Sync`.
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    /// Tests that each of the library's errors converts into the matching variant, with its
+    /// display string passed through.
+    #[test]
+    fn test_from_source_errors() {
+        let err = AugrimError::from(InternalError::with_message("internal".to_string()));
+        assert!(matches!(err, AugrimError::Internal(_)));
+        assert_eq!(err.to_string(), "internal");
+
+        let err = AugrimError::from(InvalidStateError::with_message("invalid".to_string()));
+        assert!(matches!(err, AugrimError::InvalidState(_)));
+        assert_eq!(err.to_string(), "invalid");
+
+        let err = AugrimError::from(ResourceExhaustedError::with_message(
+            "exhausted".to_string(),
+        ));
+        assert!(matches!(err, AugrimError::ResourceExhausted(_)));
+        assert_eq!(err.to_string(), "exhausted");
+
+        let err = AugrimError::from(IntraProcessNetworkError::NetworkShutdown);
+        match &err {
+            AugrimError::Network(source) => assert_send_sync(source),
+            _ => panic!("not a network error: {:?}", err),
+        }
+        assert_eq!(
+            err.to_string(),
+            "network error: IntraProcessNetwork has shut down"
+        );
+        assert!(err.source().is_some());
+    }
+
+    /// Tests that `?` converts an error into an `AugrimError` in a function returning one.
+    #[test]
+    fn test_question_mark_conversion() {
+        fn transition() -> Result<(), AugrimError> {
+            Err(InvalidStateError::with_message("oops".to_string()))?;
+            Ok(())
+        }
+
+        assert!(matches!(transition(), Err(AugrimError::InvalidState(_))));
+    }
+}
@@ -127,6 +127,25 @@ impl InternalError {
         }
     }
 
+    /// Wraps this error as the source of a new `InternalError` with a specified prefix string, to
+    /// add context as the error is returned up the stack.
+    ///
+    /// The implementation of `std::fmt::Display` for the new error will be constructed as for
+    /// [`InternalError::from_source_with_prefix`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use augrim::error::InternalError;
+    ///
+    /// let internal_error = InternalError::with_message("oops".to_string())
+    ///     .with_prefix("Could not select value".to_string());
+    /// assert_eq!(format!("{}", internal_error), "Could not select value: oops");
+    /// ```
+    pub fn with_prefix(self, prefix: String) -> Self {
+        Self::from_source_with_prefix(Box::new(self), prefix)
+    }
+
     /// Reduces the `InternalError` to the display string
     ///
     /// If the error includes a source, the debug format will be logged to provide
@@ -281,4 +300,23 @@ pub mod tests {
         let err = InternalError::with_message(msg.to_string());
         assert_eq!(format!("{}", err), msg);
     }
+
+    /// Tests that error constructed with `InternalError::with_prefix` return a display string of
+    /// the form `format!("{}: {}", prefix, message)`.
+    #[test]
+    fn test_display_with_prefix() {
+        let err = InternalError::with_message("test message".to_string())
+            .with_prefix("test prefix".to_string());
+        assert_eq!(format!("{}", err), "test prefix: test message");
+    }
+
+    /// Tests that error constructed with `InternalError::with_prefix` keep the original error as
+    /// the source.
+    #[test]
+    fn test_source_with_prefix() {
+        let err = InternalError::with_message("test message".to_string())
+            .with_prefix("test prefix".to_string());
+        let source = error::Error::source(&err).expect("no source");
+        assert_eq!(format!("{}", source), "test message");
+    }
 }
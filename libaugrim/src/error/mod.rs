@@ -74,6 +74,12 @@
 //! }
 //! ```
 
+mod augrim;
 mod internal;
+mod invalid_state;
+mod resource_exhausted;
 
+pub use augrim::AugrimError;
 pub use internal::InternalError;
+pub use invalid_state::InvalidStateError;
+pub use resource_exhausted::ResourceExhaustedError;
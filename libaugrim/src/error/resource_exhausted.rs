@@ -0,0 +1,68 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Module containing ResourceExhaustedError implementation.
+
+use std::error;
+use std::fmt;
+
+/// An error returned when an operation cannot be completed because it would exceed a configured
+/// limit on some resource.
+///
+/// This usually indicates that the limit is too low, or that the operation is misbehaving.
+#[derive(Debug)]
+pub struct ResourceExhaustedError {
+    message: String,
+}
+
+impl ResourceExhaustedError {
+    /// Constructs a new `ResourceExhaustedError` with a specified message string.
+    ///
+    /// The implementation of `std::fmt::Display` for this error will be the message string
+    /// provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use augrim::error::ResourceExhaustedError;
+    ///
+    /// let resource_exhausted_error = ResourceExhaustedError::with_message("oops".to_string());
+    /// assert_eq!(format!("{}", resource_exhausted_error), "oops");
+    /// ```
+    pub fn with_message(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl error::Error for ResourceExhaustedError {}
+
+impl fmt::Display for ResourceExhaustedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &self.message)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Tests that error constructed with `ResourceExhaustedError::with_message` return message as
+    /// the display string.
+    #[test]
+    fn test_display_with_message() {
+        let msg = "test message";
+        let err = ResourceExhaustedError::with_message(msg.to_string());
+        assert_eq!(format!("{}", err), msg);
+    }
+}
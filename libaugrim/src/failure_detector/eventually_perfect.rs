@@ -0,0 +1,330 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implementation of the "Increasing Timeout" eventually perfect failure detector algorithm.
+//!
+//! Each time the timeout elapses, every process which has not replied to the previous heartbeat
+//! request is suspected, and every suspected process which has since replied is restored. A
+//! process which replies after being suspected was only slow, so the timeout is increased; in a
+//! partially synchronous system the timeout eventually exceeds the delays between correct
+//! processes, after which only crashed processes are suspected.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::InternalError;
+use crate::links::{PerfectLink, Sender};
+use crate::process::Process;
+use crate::time::{Time, TimeSource};
+
+use super::perfect::Shared;
+use super::{HeartbeatMessage, HeartbeatReceiver};
+
+/// Receives the suspicions of an eventually perfect failure detector.
+pub trait EventuallyPerfectFailureDetectorReceiver<P> {
+    /// Called when `process` becomes suspected of having crashed.
+    fn suspect(&mut self, process: P) -> Result<(), InternalError>;
+
+    /// Called when a suspected `process` is found to be alive, and is no longer suspected.
+    fn restore(&mut self, process: P) -> Result<(), InternalError>;
+}
+
+/// An eventually perfect failure detector, which sends heartbeats over a perfect link.
+///
+/// As with [`PerfectFailureDetector`](super::PerfectFailureDetector), the detector is driven by
+/// calling [`EventuallyPerfectFailureDetector::check`] periodically, and replies are handled by
+/// the receiver returned by [`EventuallyPerfectFailureDetector::heartbeat_receiver`].
+pub struct EventuallyPerfectFailureDetector<P, S, T, R>
+where
+    T: TimeSource,
+{
+    shared: Arc<Shared<P, S>>,
+    suspected: HashSet<P>,
+    processes: Vec<P>,
+    time_source: T,
+    increment: Duration,
+    timeout: Duration,
+    deadline: T::Time,
+    receiver: R,
+}
+
+impl<P, S, T, R> EventuallyPerfectFailureDetector<P, S, T, R>
+where
+    P: Process + Hash,
+    S: Sender<P, HeartbeatMessage> + PerfectLink,
+    T: TimeSource,
+    R: EventuallyPerfectFailureDetectorReceiver<P>,
+{
+    /// Constructs a new `EventuallyPerfectFailureDetector` which monitors `processes`, sending
+    /// heartbeats with `sender` and reporting suspicions to `receiver`.
+    ///
+    /// The timeout starts at `timeout`, and grows by the same amount each time a process is
+    /// found to have been suspected wrongly. Every process is initially considered alive; the
+    /// first heartbeats are sent once `timeout` has elapsed.
+    pub fn new(
+        processes: Vec<P>,
+        sender: S,
+        time_source: T,
+        timeout: Duration,
+        receiver: R,
+    ) -> Self {
+        let deadline = time_source.now().add(timeout);
+
+        EventuallyPerfectFailureDetector {
+            shared: Arc::new(Shared {
+                sender,
+                alive: Mutex::new(processes.iter().copied().collect()),
+            }),
+            suspected: HashSet::new(),
+            processes,
+            time_source,
+            increment: timeout,
+            timeout,
+            deadline,
+            receiver,
+        }
+    }
+
+    /// Returns the receiver which handles heartbeat messages delivered to this process.
+    pub fn heartbeat_receiver(&self) -> HeartbeatReceiver<P, S> {
+        HeartbeatReceiver::new(self.shared.clone())
+    }
+
+    /// Returns the processes which are currently suspected.
+    pub fn suspected(&self) -> &HashSet<P> {
+        &self.suspected
+    }
+
+    /// Returns the current timeout.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Handles the timeout if it has elapsed: processes which did not reply since the last
+    /// timeout are suspected, suspected processes which did reply are restored, and new heartbeat
+    /// requests are sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from the receiver or the sender. Every process is still handled
+    /// and the next timeout is still scheduled, since the replies have already been consumed.
+    pub fn check(&mut self) -> Result<(), InternalError> {
+        let now = self.time_source.now();
+        if now < self.deadline {
+            return Ok(());
+        }
+
+        let alive = {
+            let mut alive = self.shared.alive.lock().map_err(|_| {
+                InternalError::with_message("failure detector lock poisoned".into())
+            })?;
+            std::mem::take(&mut *alive)
+        };
+
+        if alive.iter().any(|process| self.suspected.contains(process)) {
+            self.timeout += self.increment;
+        }
+
+        let mut result = Ok(());
+        for process in &self.processes {
+            if !alive.contains(process) && self.suspected.insert(*process) {
+                result = result.and(self.receiver.suspect(*process));
+            } else if alive.contains(process) && self.suspected.remove(process) {
+                result = result.and(self.receiver.restore(*process));
+            }
+            result = result.and(self.shared.sender.send(process, HeartbeatMessage::Request));
+        }
+
+        self.deadline = now.add(self.timeout);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::links::Receiver;
+    use crate::time::MockClock;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Suspicion {
+        Suspect(TestProcess),
+        Restore(TestProcess),
+    }
+
+    type Suspicions = Arc<Mutex<Vec<Suspicion>>>;
+
+    struct SuspicionRecorder {
+        suspicions: Suspicions,
+    }
+
+    impl EventuallyPerfectFailureDetectorReceiver<TestProcess> for SuspicionRecorder {
+        fn suspect(&mut self, process: TestProcess) -> Result<(), InternalError> {
+            self.suspicions
+                .lock()
+                .unwrap()
+                .push(Suspicion::Suspect(process));
+            Ok(())
+        }
+
+        fn restore(&mut self, process: TestProcess) -> Result<(), InternalError> {
+            self.suspicions
+                .lock()
+                .unwrap()
+                .push(Suspicion::Restore(process));
+            Ok(())
+        }
+    }
+
+    type Requests = Arc<Mutex<Vec<TestProcess>>>;
+
+    /// A sender which records the processes heartbeat requests are sent to, so that the test
+    /// decides when each of them replies.
+    #[derive(Clone, Default)]
+    struct RecordingSender {
+        requests: Requests,
+        unreachable: Option<TestProcess>,
+    }
+
+    impl Sender<TestProcess, HeartbeatMessage> for RecordingSender {
+        fn send(&self, to: &TestProcess, message: HeartbeatMessage) -> Result<(), InternalError> {
+            if self.unreachable == Some(*to) {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            if message == HeartbeatMessage::Request {
+                self.requests.lock().unwrap().push(*to);
+            }
+            Ok(())
+        }
+    }
+
+    impl PerfectLink for RecordingSender {}
+
+    /// Tests that a slow process is suspected when it misses a timeout, and restored once its
+    /// late reply arrives, and that the timeout then strictly increases so the next heartbeats
+    /// are sent later than before.
+    #[test]
+    fn test_slow_process_increases_timeout() {
+        let timeout = Duration::from_secs(1);
+        let clock = MockClock::new();
+        let suspicions = Suspicions::default();
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+        let (p1, p2) = (processes[0], processes[1]);
+        let sender = RecordingSender::default();
+
+        let mut detector = EventuallyPerfectFailureDetector::new(
+            processes.clone(),
+            sender.clone(),
+            clock.clone(),
+            timeout,
+            SuspicionRecorder {
+                suspicions: suspicions.clone(),
+            },
+        );
+        let mut heartbeats = detector.heartbeat_receiver();
+
+        // The first heartbeats are sent; only p1 replies within the timeout
+        clock.advance(timeout);
+        detector.check().unwrap();
+        heartbeats.deliver(p1, HeartbeatMessage::Reply).unwrap();
+
+        clock.advance(timeout);
+        detector.check().unwrap();
+        assert_eq!(*suspicions.lock().unwrap(), vec![Suspicion::Suspect(p2)]);
+        assert!(detector.suspected().contains(&p2));
+        assert_eq!(detector.timeout(), timeout);
+
+        // p2's late reply arrives, along with p1's next reply
+        heartbeats.deliver(p2, HeartbeatMessage::Reply).unwrap();
+        heartbeats.deliver(p1, HeartbeatMessage::Reply).unwrap();
+        clock.advance(timeout);
+        detector.check().unwrap();
+        assert_eq!(
+            *suspicions.lock().unwrap(),
+            vec![Suspicion::Suspect(p2), Suspicion::Restore(p2)]
+        );
+        assert!(detector.suspected().is_empty());
+        assert!(detector.timeout() > timeout);
+
+        // The next heartbeats are not sent until the larger timeout elapses
+        sender.requests.lock().unwrap().clear();
+        clock.advance(timeout);
+        detector.check().unwrap();
+        assert!(sender.requests.lock().unwrap().is_empty());
+
+        clock.advance(detector.timeout() - timeout);
+        detector.check().unwrap();
+        assert_eq!(*sender.requests.lock().unwrap(), processes);
+    }
+
+    /// Tests that when a heartbeat request cannot be sent the error is returned only after the
+    /// other processes have been suspected and sent their requests, and the next timeout is
+    /// scheduled.
+    #[test]
+    fn test_send_error_completes_round() {
+        let timeout = Duration::from_secs(1);
+        let clock = MockClock::new();
+        let suspicions = Suspicions::default();
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let sender = RecordingSender {
+            unreachable: Some(processes[0]),
+            ..Default::default()
+        };
+
+        let mut detector = EventuallyPerfectFailureDetector::new(
+            processes.clone(),
+            sender.clone(),
+            clock.clone(),
+            timeout,
+            SuspicionRecorder {
+                suspicions: suspicions.clone(),
+            },
+        );
+
+        // No process replies to the first heartbeats, so every process is suspected
+        clock.advance(timeout);
+        assert!(detector.check().is_err());
+        clock.advance(timeout);
+        assert!(detector.check().is_err());
+        assert_eq!(
+            *suspicions.lock().unwrap(),
+            processes
+                .iter()
+                .map(|process| Suspicion::Suspect(*process))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            *sender.requests.lock().unwrap(),
+            [&processes[1..], &processes[1..]].concat()
+        );
+
+        // The failed round is not repeated before the next timeout
+        detector.check().unwrap();
+        assert_eq!(sender.requests.lock().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implementation of the "Monarchical Eventual Leader Detection" algorithm.
+//!
+//! The leader is the highest-ranked process which is not suspected by an eventually perfect
+//! failure detector. Once the failure detector only suspects crashed processes, every correct
+//! process trusts the same correct leader.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::error::InternalError;
+use crate::process::{rank, RankedProcess};
+
+use super::EventuallyPerfectFailureDetectorReceiver;
+
+/// Receives the leader changes of an eventual leader detector.
+pub trait EventualLeaderDetectorReceiver<P> {
+    /// Called when `process` becomes the trusted leader.
+    fn trust(&mut self, process: P) -> Result<(), InternalError>;
+}
+
+/// An eventual leader detector, driven by the suspicions of an eventually perfect failure
+/// detector.
+///
+/// The detector is registered as the receiver of an
+/// [`EventuallyPerfectFailureDetector`](super::EventuallyPerfectFailureDetector), or is told of
+/// suspicions directly through [`EventuallyPerfectFailureDetectorReceiver`].
+pub struct EventualLeaderDetector<P, R> {
+    processes: Vec<P>,
+    suspected: HashSet<P>,
+    leader: Option<P>,
+    receiver: R,
+}
+
+impl<P, R> EventualLeaderDetector<P, R>
+where
+    P: RankedProcess + Hash,
+    R: EventualLeaderDetectorReceiver<P>,
+{
+    /// Constructs a new `EventualLeaderDetector` for `processes`, which may be given in any order,
+    /// and reports leader changes to `receiver`. The processes are ranked by their `Ord`
+    /// implementation, the least process highest.
+    ///
+    /// No process is initially suspected, so the highest-ranked process is trusted immediately.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if `receiver` fails to handle the initial leader.
+    pub fn new(processes: Vec<P>, receiver: R) -> Result<Self, InternalError> {
+        let mut detector = EventualLeaderDetector {
+            processes: rank(processes),
+            suspected: HashSet::new(),
+            leader: None,
+            receiver,
+        };
+        detector.update_leader()?;
+        Ok(detector)
+    }
+
+    /// Returns the trusted leader, or `None` if every process is suspected.
+    pub fn leader(&self) -> Option<&P> {
+        self.leader.as_ref()
+    }
+
+    /// Trusts the highest-ranked process which is not suspected, if it is not already trusted.
+    fn update_leader(&mut self) -> Result<(), InternalError> {
+        let leader = self
+            .processes
+            .iter()
+            .find(|process| !self.suspected.contains(process))
+            .copied();
+
+        if leader == self.leader {
+            return Ok(());
+        }
+
+        self.leader = leader;
+        match leader {
+            Some(leader) => self.receiver.trust(leader),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<P, R> EventuallyPerfectFailureDetectorReceiver<P> for EventualLeaderDetector<P, R>
+where
+    P: RankedProcess + Hash,
+    R: EventualLeaderDetectorReceiver<P>,
+{
+    fn suspect(&mut self, process: P) -> Result<(), InternalError> {
+        self.suspected.insert(process);
+        self.update_leader()
+    }
+
+    fn restore(&mut self, process: P) -> Result<(), InternalError> {
+        self.suspected.remove(&process);
+        self.update_leader()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::process::Process;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    impl RankedProcess for TestProcess {}
+
+    /// Records every process trusted, in order.
+    #[derive(Default)]
+    struct TrustRecorder {
+        trusted: Vec<TestProcess>,
+    }
+
+    impl EventualLeaderDetectorReceiver<TestProcess> for TrustRecorder {
+        fn trust(&mut self, process: TestProcess) -> Result<(), InternalError> {
+            self.trusted.push(process);
+            Ok(())
+        }
+    }
+
+    /// Tests that the highest-ranked process is trusted first, that leadership moves to the
+    /// next-ranked process when it is suspected, and that suspecting a lower-ranked process
+    /// does not change the leader.
+    #[test]
+    fn test_leader_moves_when_suspected() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let (p1, p2, p3) = (processes[0], processes[1], processes[2]);
+
+        let mut detector =
+            EventualLeaderDetector::new(processes, TrustRecorder::default()).unwrap();
+        assert_eq!(detector.leader(), Some(&p1));
+        assert_eq!(detector.receiver.trusted, vec![p1]);
+
+        detector.suspect(p1).unwrap();
+        assert_eq!(detector.leader(), Some(&p2));
+
+        detector.suspect(p3).unwrap();
+        assert_eq!(detector.leader(), Some(&p2));
+        assert_eq!(detector.receiver.trusted, vec![p1, p2]);
+
+        // Leadership returns to p1 once it is no longer suspected
+        detector.restore(p1).unwrap();
+        assert_eq!(detector.leader(), Some(&p1));
+        assert_eq!(detector.receiver.trusted, vec![p1, p2, p1]);
+    }
+
+    /// Tests that the processes are ranked by their order rather than the order they are given
+    /// in, so detectors given the processes in different orders trust the same leader.
+    #[test]
+    fn test_rank_independent_of_given_order() {
+        let processes: Vec<TestProcess> = (1..=3).rev().map(|id| TestProcess { id }).collect();
+
+        let mut detector =
+            EventualLeaderDetector::new(processes, TrustRecorder::default()).unwrap();
+        assert_eq!(detector.leader(), Some(&TestProcess { id: 1 }));
+
+        detector.suspect(TestProcess { id: 1 }).unwrap();
+        assert_eq!(detector.leader(), Some(&TestProcess { id: 2 }));
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A shared view of which processes are believed to be correct.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::InternalError;
+use crate::process::Process;
+
+use super::{EventuallyPerfectFailureDetectorReceiver, PerfectFailureDetectorReceiver};
+
+/// The membership view of a process: which processes are suspected of having crashed, and which
+/// are believed to be correct.
+///
+/// A `Membership` is registered as the receiver of a failure detector, and is updated by its
+/// crash, suspect and restore events. Clones share the same view, so a clone kept elsewhere, for
+/// example by an operator's status endpoint, sees every update.
+#[derive(Clone)]
+pub struct Membership<P> {
+    view: Arc<Mutex<View<P>>>,
+}
+
+struct View<P> {
+    processes: Vec<P>,
+    suspected: Vec<P>,
+}
+
+impl<P> Membership<P>
+where
+    P: Process,
+{
+    /// Constructs a new `Membership` of `processes`, all of which are initially correct.
+    pub fn new(processes: Vec<P>) -> Self {
+        Membership {
+            view: Arc::new(Mutex::new(View {
+                processes,
+                suspected: Vec::new(),
+            })),
+        }
+    }
+
+    /// Returns the processes suspected of having crashed, in the order the processes were given.
+    pub fn suspected(&self) -> Vec<P> {
+        let view = self.lock();
+        view.processes
+            .iter()
+            .filter(|process| view.suspected.contains(process))
+            .copied()
+            .collect()
+    }
+
+    /// Returns the processes believed to be correct, in the order the processes were given.
+    pub fn correct(&self) -> Vec<P> {
+        let view = self.lock();
+        view.processes
+            .iter()
+            .filter(|process| !view.suspected.contains(process))
+            .copied()
+            .collect()
+    }
+
+    fn suspect_process(&self, process: P) {
+        let mut view = self.lock();
+        if view.processes.contains(&process) && !view.suspected.contains(&process) {
+            view.suspected.push(process);
+        }
+    }
+
+    fn restore_process(&self, process: P) {
+        self.lock().suspected.retain(|p| p != &process);
+    }
+
+    /// Locks the view. Every update leaves the view consistent, so a poisoned lock is recovered.
+    fn lock(&self) -> MutexGuard<'_, View<P>> {
+        self.view
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<P> PerfectFailureDetectorReceiver<P> for Membership<P>
+where
+    P: Process,
+{
+    fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        self.suspect_process(process);
+        Ok(())
+    }
+}
+
+impl<P> EventuallyPerfectFailureDetectorReceiver<P> for Membership<P>
+where
+    P: Process,
+{
+    fn suspect(&mut self, process: P) -> Result<(), InternalError> {
+        self.suspect_process(process);
+        Ok(())
+    }
+
+    fn restore(&mut self, process: P) -> Result<(), InternalError> {
+        self.restore_process(process);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Tests that a crashed process is reported as suspected and not correct, through every
+    /// clone of the membership.
+    #[test]
+    fn test_crash() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let membership = Membership::new(processes.clone());
+        let mut detector_view = membership.clone();
+
+        assert!(membership.suspected().is_empty());
+        assert_eq!(membership.correct(), processes);
+
+        PerfectFailureDetectorReceiver::crash(&mut detector_view, processes[1]).unwrap();
+
+        assert_eq!(membership.suspected(), vec![processes[1]]);
+        assert_eq!(membership.correct(), vec![processes[0], processes[2]]);
+    }
+
+    /// Tests that a suspected process is correct again once restored, and that suspecting a
+    /// process which is not a member has no effect.
+    #[test]
+    fn test_suspect_and_restore() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let mut membership = Membership::new(processes.clone());
+
+        membership.suspect(processes[2]).unwrap();
+        membership.suspect(processes[0]).unwrap();
+        membership.suspect(TestProcess { id: 4 }).unwrap();
+        assert_eq!(membership.suspected(), vec![processes[0], processes[2]]);
+
+        membership.restore(processes[0]).unwrap();
+        assert_eq!(membership.suspected(), vec![processes[2]]);
+        assert_eq!(membership.correct(), vec![processes[0], processes[1]]);
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Failure detectors.
+//!
+//! A failure detector tells a process which other processes have crashed, by exchanging
+//! heartbeats with them and watching for replies. A leader detector builds on a failure detector
+//! to choose a single process which every process trusts.
+
+mod eventually_perfect;
+mod leader;
+mod membership;
+mod perfect;
+
+use crate::message::Message;
+
+pub use eventually_perfect::{
+    EventuallyPerfectFailureDetector, EventuallyPerfectFailureDetectorReceiver,
+};
+pub use leader::{EventualLeaderDetector, EventualLeaderDetectorReceiver};
+pub use membership::Membership;
+pub use perfect::{HeartbeatReceiver, PerfectFailureDetector, PerfectFailureDetectorReceiver};
+
+/// A message exchanged by failure detectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HeartbeatMessage {
+    /// Asks the receiving process to reply, to show that it has not crashed.
+    Request,
+    /// Answers a request.
+    Reply,
+}
+
+impl Message for HeartbeatMessage {}
@@ -0,0 +1,415 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implementation of the "Exclude on Timeout" perfect failure detector algorithm.
+//!
+//! Each time the timeout elapses, every process which has not replied to the previous heartbeat
+//! request is detected as crashed, and a new heartbeat request is sent to every process. This
+//! assumes a synchronous system: a correct process always replies within the timeout.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::InternalError;
+use crate::links::{PerfectLink, Receiver, Sender};
+use crate::process::Process;
+use crate::time::{Time, TimeSource};
+
+use super::HeartbeatMessage;
+
+/// Receives the crash events of a perfect failure detector.
+pub trait PerfectFailureDetectorReceiver<P> {
+    /// Called once when `process` is detected as crashed.
+    fn crash(&mut self, process: P) -> Result<(), InternalError>;
+}
+
+/// State shared between a detector and its [`HeartbeatReceiver`].
+pub(super) struct Shared<P, S> {
+    pub(super) sender: S,
+    pub(super) alive: Mutex<HashSet<P>>,
+}
+
+/// A perfect failure detector, which sends heartbeats over a perfect link.
+///
+/// The detector is driven by calling [`PerfectFailureDetector::check`] periodically; replies are
+/// handled by the [`HeartbeatReceiver`] returned by [`PerfectFailureDetector::heartbeat_receiver`],
+/// which must receive every heartbeat message delivered to this process.
+pub struct PerfectFailureDetector<P, S, T, R>
+where
+    T: TimeSource,
+{
+    shared: Arc<Shared<P, S>>,
+    detected: HashSet<P>,
+    processes: Vec<P>,
+    time_source: T,
+    timeout: Duration,
+    deadline: T::Time,
+    receiver: R,
+}
+
+impl<P, S, T, R> PerfectFailureDetector<P, S, T, R>
+where
+    P: Process + Hash,
+    S: Sender<P, HeartbeatMessage> + PerfectLink,
+    T: TimeSource,
+    R: PerfectFailureDetectorReceiver<P>,
+{
+    /// Constructs a new `PerfectFailureDetector` which monitors `processes`, sending heartbeats
+    /// with `sender` and reporting crashes to `receiver`.
+    ///
+    /// Every process is initially considered alive; the first heartbeats are sent once `timeout`
+    /// has elapsed.
+    pub fn new(
+        processes: Vec<P>,
+        sender: S,
+        time_source: T,
+        timeout: Duration,
+        receiver: R,
+    ) -> Self {
+        let deadline = time_source.now().add(timeout);
+
+        PerfectFailureDetector {
+            shared: Arc::new(Shared {
+                sender,
+                alive: Mutex::new(processes.iter().copied().collect()),
+            }),
+            detected: HashSet::new(),
+            processes,
+            time_source,
+            timeout,
+            deadline,
+            receiver,
+        }
+    }
+
+    /// Returns the receiver which handles heartbeat messages delivered to this process.
+    pub fn heartbeat_receiver(&self) -> HeartbeatReceiver<P, S> {
+        HeartbeatReceiver::new(self.shared.clone())
+    }
+
+    /// Returns the processes which have been detected as crashed.
+    pub fn detected(&self) -> &HashSet<P> {
+        &self.detected
+    }
+
+    /// Handles the timeout if it has elapsed: processes which did not reply since the last
+    /// timeout are detected as crashed, and new heartbeat requests are sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from the receiver or the sender. Every process is still handled
+    /// and the next timeout is still scheduled, since the replies have already been consumed.
+    pub fn check(&mut self) -> Result<(), InternalError> {
+        let now = self.time_source.now();
+        if now < self.deadline {
+            return Ok(());
+        }
+
+        let alive = {
+            let mut alive = self.shared.alive.lock().map_err(|_| {
+                InternalError::with_message("failure detector lock poisoned".into())
+            })?;
+            std::mem::take(&mut *alive)
+        };
+
+        let mut result = Ok(());
+        for process in &self.processes {
+            if !alive.contains(process) && self.detected.insert(*process) {
+                result = result.and(self.receiver.crash(*process));
+            }
+            result = result.and(self.shared.sender.send(process, HeartbeatMessage::Request));
+        }
+
+        self.deadline = now.add(self.timeout);
+
+        result
+    }
+
+    #[cfg(test)]
+    fn alive(&self) -> HashSet<P> {
+        self.shared.alive.lock().expect("lock poisoned").clone()
+    }
+}
+
+/// Handles heartbeat messages on behalf of a failure detector: requests are answered with a
+/// reply, and replies mark the sending process as alive.
+pub struct HeartbeatReceiver<P, S> {
+    shared: Arc<Shared<P, S>>,
+}
+
+impl<P, S> HeartbeatReceiver<P, S> {
+    pub(super) fn new(shared: Arc<Shared<P, S>>) -> Self {
+        HeartbeatReceiver { shared }
+    }
+}
+
+impl<P, S> Receiver<P, HeartbeatMessage> for HeartbeatReceiver<P, S>
+where
+    P: Process + Hash,
+    S: Sender<P, HeartbeatMessage>,
+{
+    fn deliver(&mut self, from: P, message: HeartbeatMessage) -> Result<(), InternalError> {
+        match message {
+            HeartbeatMessage::Request => self.shared.sender.send(&from, HeartbeatMessage::Reply),
+            HeartbeatMessage::Reply => {
+                self.shared
+                    .alive
+                    .lock()
+                    .map_err(|_| {
+                        InternalError::with_message("failure detector lock poisoned".into())
+                    })?
+                    .insert(from);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+    use std::time::{Instant, SystemTime};
+
+    use crate::communication::{IntraProcessNetwork, IntraProcessNetworkSender};
+    use crate::time::MockClock;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    type Crashes = Arc<Mutex<Vec<(TestProcess, SystemTime)>>>;
+
+    /// Records each crash along with the time it was detected.
+    struct CrashRecorder {
+        clock: MockClock,
+        crashes: Crashes,
+    }
+
+    impl PerfectFailureDetectorReceiver<TestProcess> for CrashRecorder {
+        fn crash(&mut self, process: TestProcess) -> Result<(), InternalError> {
+            self.crashes
+                .lock()
+                .unwrap()
+                .push((process, self.clock.now()));
+            Ok(())
+        }
+    }
+
+    type TestDetector = PerfectFailureDetector<
+        TestProcess,
+        IntraProcessNetworkSender<TestProcess, HeartbeatMessage>,
+        MockClock,
+        CrashRecorder,
+    >;
+
+    /// Waits, in real time, until the detector has received replies from exactly `expected`.
+    fn wait_for_alive(detector: &TestDetector, expected: &[TestProcess]) {
+        let expected: HashSet<TestProcess> = expected.iter().copied().collect();
+        let start = Instant::now();
+        while detector.alive() != expected {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "timed out waiting for heartbeat replies"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Tests the detector over an `IntraProcessNetwork`: after a process is removed from the
+    /// network it stops replying to heartbeats, and is detected as crashed exactly one timeout
+    /// after the first unanswered request.
+    #[test]
+    fn test_detect_removed_process() {
+        let timeout = Duration::from_secs(10);
+        let clock = MockClock::new();
+        let start = clock.now();
+        let crashes = Crashes::default();
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+
+        let mut network = IntraProcessNetwork::new().unwrap();
+        let mut detectors: Vec<TestDetector> = processes
+            .iter()
+            .map(|process| {
+                let detector = PerfectFailureDetector::new(
+                    processes.clone(),
+                    network.sender(*process),
+                    clock.clone(),
+                    timeout,
+                    CrashRecorder {
+                        clock: clock.clone(),
+                        crashes: crashes.clone(),
+                    },
+                );
+                network.add_process(*process, detector.heartbeat_receiver());
+                detector
+            })
+            .collect();
+        let detector = &mut detectors[0];
+
+        // The first timeout sends heartbeats to every process, and all of them reply
+        clock.advance(timeout);
+        detector.check().unwrap();
+        wait_for_alive(detector, &processes);
+
+        // Process 3 crashes before the second round of heartbeats
+        network.remove_process(&processes[2]);
+        clock.advance(timeout);
+        detector.check().unwrap();
+        wait_for_alive(detector, &processes[..2]);
+        assert!(crashes.lock().unwrap().is_empty());
+
+        // Nothing happens before the timeout elapses
+        clock.advance(timeout / 2);
+        detector.check().unwrap();
+        assert!(crashes.lock().unwrap().is_empty());
+
+        clock.advance(timeout / 2);
+        detector.check().unwrap();
+        assert_eq!(
+            *crashes.lock().unwrap(),
+            vec![(processes[2], start.add(timeout * 3))]
+        );
+        assert!(detector.detected().contains(&processes[2]));
+
+        // A crash is only reported once
+        wait_for_alive(detector, &processes[..2]);
+        clock.advance(timeout);
+        detector.check().unwrap();
+        assert_eq!(crashes.lock().unwrap().len(), 1);
+
+        network.shutdown().unwrap();
+    }
+
+    type Requests = Arc<Mutex<Vec<TestProcess>>>;
+
+    /// A sender which records the processes heartbeat requests are sent to, so that the test
+    /// decides which of them reply.
+    #[derive(Clone, Default)]
+    struct RecordingSender {
+        requests: Requests,
+        unreachable: Option<TestProcess>,
+    }
+
+    impl Sender<TestProcess, HeartbeatMessage> for RecordingSender {
+        fn send(&self, to: &TestProcess, message: HeartbeatMessage) -> Result<(), InternalError> {
+            if self.unreachable == Some(*to) {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            if message == HeartbeatMessage::Request {
+                self.requests.lock().unwrap().push(*to);
+            }
+            Ok(())
+        }
+    }
+
+    impl PerfectLink for RecordingSender {}
+
+    /// Tests, without a network, that when one process stops replying the detector reports
+    /// exactly that process as crashed, once a full round of heartbeats has gone unanswered.
+    #[test]
+    fn test_detect_only_silent_process() {
+        let timeout = Duration::from_secs(1);
+        let clock = MockClock::new();
+        let crashes = Crashes::default();
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let sender = RecordingSender::default();
+
+        let mut detector = PerfectFailureDetector::new(
+            processes.clone(),
+            sender.clone(),
+            clock.clone(),
+            timeout,
+            CrashRecorder {
+                clock: clock.clone(),
+                crashes: crashes.clone(),
+            },
+        );
+        let mut heartbeats = detector.heartbeat_receiver();
+
+        for round in 0..3 {
+            clock.advance(timeout);
+            detector.check().unwrap();
+
+            let requests = std::mem::take(&mut *sender.requests.lock().unwrap());
+            assert_eq!(requests, processes);
+
+            // Process 2 stops replying after the first round
+            for process in requests {
+                if process != processes[1] || round == 0 {
+                    heartbeats
+                        .deliver(process, HeartbeatMessage::Reply)
+                        .unwrap();
+                }
+            }
+        }
+
+        assert_eq!(
+            crashes
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(process, _)| *process)
+                .collect::<Vec<_>>(),
+            vec![processes[1]]
+        );
+        assert_eq!(
+            detector.detected(),
+            &vec![processes[1]].into_iter().collect::<HashSet<_>>()
+        );
+    }
+
+    /// Tests that when a heartbeat request cannot be sent the error is returned only after the
+    /// remaining processes have been sent their requests and the next timeout is scheduled.
+    #[test]
+    fn test_send_error_completes_round() {
+        let timeout = Duration::from_secs(1);
+        let clock = MockClock::new();
+        let crashes = Crashes::default();
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let sender = RecordingSender {
+            unreachable: Some(processes[0]),
+            ..Default::default()
+        };
+
+        let mut detector = PerfectFailureDetector::new(
+            processes.clone(),
+            sender.clone(),
+            clock.clone(),
+            timeout,
+            CrashRecorder {
+                clock: clock.clone(),
+                crashes: crashes.clone(),
+            },
+        );
+
+        clock.advance(timeout);
+        assert!(detector.check().is_err());
+        assert_eq!(*sender.requests.lock().unwrap(), processes[1..].to_vec());
+
+        // The failed round is not repeated before the next timeout
+        detector.check().unwrap();
+        assert_eq!(sender.requests.lock().unwrap().len(), 2);
+        assert!(crashes.lock().unwrap().is_empty());
+    }
+}
@@ -12,7 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Implementations of distributed algorithms, such as consensus, broadcast and two-phase commit.
+//!
+//! Each algorithm is a handler of events which returns the actions to perform, so it can be
+//! driven by any transport and storage.
+
 #[macro_use]
 extern crate log;
 
+pub mod algorithm;
+pub mod broadcast;
+pub mod communication;
 pub mod error;
+pub mod failure_detector;
+pub mod links;
+pub mod message;
+pub mod network;
+pub mod process;
+pub mod register;
+mod rng;
+pub mod runtime;
+pub mod scheduler;
+pub mod storage;
+pub mod time;
+pub mod two_phase_commit;
+
+pub use error::AugrimError;
@@ -0,0 +1,267 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An in-memory fair-loss link over a channel, which loses messages at random.
+
+use std::sync::mpsc::{channel, Receiver as ChannelReceiver, Sender as ChannelSender};
+use std::sync::Mutex;
+
+use crate::error::InternalError;
+use crate::process::Process;
+use crate::rng::Rng;
+
+use super::{FairLossLink, Receiver, Sender};
+
+/// Constructs a fair-loss link from the process `from` to the process `to`, whose messages are
+/// delivered to `receiver`.
+///
+/// The link initially loses no messages; see [`FairLossChannelSender::with_loss_probability`].
+pub fn fair_loss_channel<P, M, R>(
+    from: P,
+    to: P,
+    receiver: R,
+) -> (
+    FairLossChannelSender<P, M>,
+    FairLossChannelReceiver<P, M, R>,
+)
+where
+    P: Process,
+    R: Receiver<P, M>,
+{
+    let (sender, channel_receiver) = channel();
+
+    (
+        FairLossChannelSender {
+            from,
+            to,
+            sender,
+            loss: Mutex::new((0.0, Rng::new(0))),
+        },
+        FairLossChannelReceiver {
+            receiver: channel_receiver,
+            inner: receiver,
+        },
+    )
+}
+
+/// The sending side of an in-memory fair-loss link.
+///
+/// Each message is either lost, with the configured probability, or queued for the receiving
+/// side. Messages are never duplicated by the link itself.
+pub struct FairLossChannelSender<P, M> {
+    from: P,
+    to: P,
+    sender: ChannelSender<(P, M)>,
+    loss: Mutex<(f64, Rng)>,
+}
+
+impl<P, M> FairLossChannelSender<P, M>
+where
+    P: Process,
+{
+    /// Loses each message with the given probability, which is clamped to `[0, 1)` so that a
+    /// message sent infinitely often is eventually delivered. The messages to lose are chosen
+    /// with a pseudo-random number generator seeded with `seed`.
+    pub fn with_loss_probability(self, probability: f64, seed: u64) -> Self {
+        let probability = probability.clamp(0.0, 1.0 - f64::EPSILON);
+        *self
+            .loss
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = (probability, Rng::new(seed));
+        self
+    }
+}
+
+impl<P, M> Sender<P, M> for FairLossChannelSender<P, M>
+where
+    P: Process,
+{
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError> {
+        if *to != self.to {
+            return Err(InternalError::with_message(
+                "fair-loss link does not reach the process".into(),
+            ));
+        }
+
+        {
+            let mut loss = self
+                .loss
+                .lock()
+                .map_err(|_| InternalError::with_message("fair-loss link lock poisoned".into()))?;
+            let (probability, rng) = &mut *loss;
+            if *probability > 0.0 && rng.next_f64() < *probability {
+                return Ok(());
+            }
+        }
+
+        // A closed receiving side behaves like a process which has crashed
+        let _ = self.sender.send((self.from, message));
+        Ok(())
+    }
+}
+
+impl<P, M> FairLossLink for FairLossChannelSender<P, M> {}
+
+/// The receiving side of an in-memory fair-loss link.
+///
+/// Messages which were not lost are delivered to the inner receiver each time
+/// [`FairLossChannelReceiver::poll`] is called.
+pub struct FairLossChannelReceiver<P, M, R> {
+    receiver: ChannelReceiver<(P, M)>,
+    inner: R,
+}
+
+impl<P, M, R> FairLossChannelReceiver<P, M, R>
+where
+    R: Receiver<P, M>,
+{
+    /// Delivers every message queued on the link to the inner receiver, returning the number
+    /// delivered.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the inner receiver fails to deliver a message; the messages
+    /// after it stay queued.
+    pub fn poll(&mut self) -> Result<usize, InternalError> {
+        let mut delivered = 0;
+        while let Ok((from, message)) = self.receiver.try_recv() {
+            self.inner.deliver(from, message)?;
+            delivered += 1;
+        }
+
+        Ok(delivered)
+    }
+
+    /// Returns the receiver messages are delivered to.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<P, M, R> Receiver<P, M> for FairLossChannelReceiver<P, M, R>
+where
+    R: Receiver<P, M>,
+{
+    fn deliver(&mut self, from: P, message: M) -> Result<(), InternalError> {
+        self.inner.deliver(from, message)
+    }
+}
+
+impl<P, M, R> FairLossLink for FairLossChannelReceiver<P, M, R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::links::{PerfectReceiver, PerfectSender, StubbornReceiver, StubbornSender};
+    use crate::message::Message;
+    use crate::time::SystemTimeSource;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct TestMessage(u64);
+
+    impl Message for TestMessage {}
+
+    /// A receiver which records every delivered message.
+    #[derive(Clone, Default)]
+    struct CollectingReceiver {
+        delivered: Arc<Mutex<Vec<(TestProcess, TestMessage)>>>,
+    }
+
+    impl Receiver<TestProcess, TestMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            from: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.lock().unwrap().push((from, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that a lossy link loses some of the messages sent over it, but delivers the others
+    /// unchanged and in order.
+    #[test]
+    fn test_messages_lost() {
+        let (p1, p2) = (TestProcess { id: 1 }, TestProcess { id: 2 });
+        let (sender, mut receiver) = fair_loss_channel(p1, p2, CollectingReceiver::default());
+        let sender = sender.with_loss_probability(0.5, 42);
+
+        for i in 0..100 {
+            sender.send(&p2, TestMessage(i)).unwrap();
+        }
+        let delivered = receiver.poll().unwrap();
+
+        assert!(delivered > 0 && delivered < 100, "{} delivered", delivered);
+        let messages: Vec<u64> = receiver
+            .inner()
+            .delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, message)| message.0)
+            .collect();
+        assert!(messages.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(sender.send(&TestProcess { id: 3 }, TestMessage(0)).is_err());
+    }
+
+    /// Tests that a message which is lost is eventually delivered when it is retransmitted by a
+    /// stubborn link, and that a perfect link over it delivers it exactly once.
+    #[test]
+    fn test_delivered_if_retried() {
+        let (p1, p2) = (TestProcess { id: 1 }, TestProcess { id: 2 });
+        let collector = CollectingReceiver::default();
+        let (sender, mut receiver) = fair_loss_channel(
+            p1,
+            p2,
+            StubbornReceiver::new(PerfectReceiver::new(collector.clone())),
+        );
+        // The seed loses the first message sent
+        let sender = PerfectSender::new(StubbornSender::new(
+            sender.with_loss_probability(0.9, 1),
+            SystemTimeSource::new(),
+            Duration::from_secs(60),
+        ));
+
+        sender.send(&p2, TestMessage(7)).unwrap();
+        receiver.poll().unwrap();
+        let mut retransmissions = 0;
+        while collector.delivered.lock().unwrap().is_empty() {
+            assert!(retransmissions < 1000, "message was never delivered");
+            sender.inner().retransmit().unwrap();
+            receiver.poll().unwrap();
+            retransmissions += 1;
+        }
+        assert!(retransmissions > 0);
+
+        for _ in 0..20 {
+            sender.inner().retransmit().unwrap();
+        }
+        receiver.poll().unwrap();
+        assert_eq!(
+            *collector.delivered.lock().unwrap(),
+            vec![(p1, TestMessage(7))]
+        );
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Point-to-point link abstractions.
+//!
+//! Links are layered on top of each other, each adding a stronger guarantee:
+//!
+//! - A fair-loss link may lose messages, but a message sent infinitely often is eventually
+//!   delivered. [`fair_loss_channel`] constructs an in-memory fair-loss link which loses
+//!   messages at random.
+//! - A stubborn link ([`StubbornSender`]/[`StubbornReceiver`]) is built over a fair-loss link and
+//!   delivers every sent message infinitely often.
+//! - A perfect link ([`PerfectSender`]/[`PerfectReceiver`]) is built over a stubborn link and
+//!   delivers every sent message exactly once.
+//!
+//! Senders are invoked by the local process to send a message to another process. Receivers are
+//! invoked by the layer below them when a message is delivered.
+
+mod fair_loss;
+mod perfect;
+mod stubborn;
+
+use crate::error::InternalError;
+
+pub use fair_loss::{fair_loss_channel, FairLossChannelReceiver, FairLossChannelSender};
+pub use perfect::{PerfectReceiver, PerfectSender};
+pub use stubborn::{StubbornReceiver, StubbornSender};
+
+/// Sends messages to another process.
+pub trait Sender<P, M> {
+    /// Sends `message` to the process `to`.
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError>;
+}
+
+/// Receives messages delivered from another process.
+pub trait Receiver<P, M> {
+    /// Delivers `message`, which was sent by the process `from`.
+    fn deliver(&mut self, from: P, message: M) -> Result<(), InternalError>;
+}
+
+/// Marker trait for links which provide the fair-loss properties.
+pub trait FairLossLink {}
+
+/// Marker trait for links which provide the stubborn properties.
+pub trait StubbornLink {}
+
+/// Marker trait for links which provide the perfect (reliable) properties.
+pub trait PerfectLink {}
+
+/// A sender over a fair-loss link.
+pub trait FairLossSender<P, M>: Sender<P, M> + FairLossLink {}
+
+impl<P, M, S> FairLossSender<P, M> for S where S: Sender<P, M> + FairLossLink {}
+
+/// A receiver over a fair-loss link.
+pub trait FairLossReceiver<P, M>: Receiver<P, M> + FairLossLink {}
+
+impl<P, M, R> FairLossReceiver<P, M> for R where R: Receiver<P, M> + FairLossLink {}
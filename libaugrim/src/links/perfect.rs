@@ -0,0 +1,256 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implementation of the "Eliminate Duplicates" perfect link algorithm.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::error::InternalError;
+use crate::message::Message;
+use crate::process::Process;
+
+use super::{PerfectLink, Receiver, Sender, StubbornLink};
+
+/// The sending side of a perfect link.
+///
+/// Messages are sent over the underlying stubborn link, which guarantees they are eventually
+/// delivered.
+pub struct PerfectSender<P, M, S> {
+    inner: S,
+    _process: PhantomData<P>,
+    _message: PhantomData<M>,
+}
+
+impl<P, M, S> PerfectSender<P, M, S>
+where
+    P: Process,
+    M: Message,
+    S: Sender<P, M> + StubbornLink,
+{
+    /// Constructs a new `PerfectSender` over the given stubborn link sender.
+    pub fn new(inner: S) -> Self {
+        PerfectSender {
+            inner,
+            _process: PhantomData,
+            _message: PhantomData,
+        }
+    }
+
+    /// Returns the underlying stubborn link sender.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<P, M, S> Sender<P, M> for PerfectSender<P, M, S>
+where
+    P: Process,
+    M: Message,
+    S: Sender<P, M> + StubbornLink,
+{
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError> {
+        self.inner.send(to, message)
+    }
+}
+
+impl<P, M, S> PerfectLink for PerfectSender<P, M, S> {}
+
+/// The receiving side of a perfect link.
+///
+/// Messages delivered by the underlying stubborn link are delivered to the inner receiver only
+/// the first time they are seen; subsequent deliveries of the same message from the same process
+/// are discarded.
+pub struct PerfectReceiver<P, M, R> {
+    inner: R,
+    delivered: HashSet<(P, M)>,
+}
+
+impl<P, M, R> PerfectReceiver<P, M, R>
+where
+    P: Process + Hash,
+    M: Message + Clone + Eq + Hash,
+    R: Receiver<P, M>,
+{
+    /// Constructs a new `PerfectReceiver` which delivers to the given receiver.
+    pub fn new(inner: R) -> Self {
+        PerfectReceiver {
+            inner,
+            delivered: HashSet::new(),
+        }
+    }
+}
+
+impl<P, M, R> Receiver<P, M> for PerfectReceiver<P, M, R>
+where
+    P: Process + Hash,
+    M: Message + Clone + Eq + Hash,
+    R: Receiver<P, M>,
+{
+    fn deliver(&mut self, from: P, message: M) -> Result<(), InternalError> {
+        if self.delivered.insert((from, message.clone())) {
+            self.inner.deliver(from, message)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<P, M, R> PerfectLink for PerfectReceiver<P, M, R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::links::{FairLossLink, StubbornReceiver, StubbornSender};
+    use crate::time::SystemTimeSource;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct TestMessage(u64);
+
+    impl Message for TestMessage {}
+
+    /// A receiver which records every delivered message.
+    struct CollectingReceiver {
+        delivered: Delivered,
+    }
+
+    impl Receiver<TestProcess, TestMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            from: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.lock().unwrap().push((from, message));
+            Ok(())
+        }
+    }
+
+    /// A fair-loss sender which drops every other message it is asked to send, and delivers the
+    /// rest directly into the receiver.
+    struct LossySender<R> {
+        from: TestProcess,
+        receiver: Arc<Mutex<R>>,
+        count: AtomicUsize,
+    }
+
+    impl<R> Sender<TestProcess, TestMessage> for LossySender<R>
+    where
+        R: Receiver<TestProcess, TestMessage>,
+    {
+        fn send(&self, _to: &TestProcess, message: TestMessage) -> Result<(), InternalError> {
+            if self.count.fetch_add(1, Ordering::SeqCst).is_multiple_of(2) {
+                return Ok(());
+            }
+            self.receiver.lock().unwrap().deliver(self.from, message)
+        }
+    }
+
+    impl<R> FairLossLink for LossySender<R> {}
+
+    type TestReceiver = StubbornReceiver<
+        TestProcess,
+        TestMessage,
+        PerfectReceiver<TestProcess, TestMessage, CollectingReceiver>,
+    >;
+
+    type Delivered = Arc<Mutex<Vec<(TestProcess, TestMessage)>>>;
+
+    type TestSender = PerfectSender<
+        TestProcess,
+        TestMessage,
+        StubbornSender<TestProcess, TestMessage, LossySender<TestReceiver>, SystemTimeSource>,
+    >;
+
+    fn setup() -> (TestSender, Delivered) {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let receiver = StubbornReceiver::new(PerfectReceiver::new(CollectingReceiver {
+            delivered: delivered.clone(),
+        }));
+        let sender = PerfectSender::new(StubbornSender::new(
+            LossySender {
+                from: TestProcess { id: 1 },
+                receiver: Arc::new(Mutex::new(receiver)),
+                count: AtomicUsize::new(0),
+            },
+            SystemTimeSource::new(),
+            Duration::from_secs(60),
+        ));
+
+        (sender, delivered)
+    }
+
+    /// Tests that every message sent over a perfect link is eventually delivered, even though the
+    /// underlying fair-loss link drops messages.
+    #[test]
+    fn test_no_loss() {
+        let (sender, delivered) = setup();
+        let to = TestProcess { id: 2 };
+
+        for i in 0..3 {
+            sender.send(&to, TestMessage(i)).unwrap();
+        }
+
+        // The first message was dropped by the fair-loss link
+        assert!(!delivered
+            .lock()
+            .unwrap()
+            .contains(&(TestProcess { id: 1 }, TestMessage(0))));
+
+        sender.inner().retransmit().unwrap();
+        sender.inner().retransmit().unwrap();
+
+        let mut messages: Vec<TestMessage> = delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, message)| message.clone())
+            .collect();
+        messages.sort_by_key(|message| message.0);
+        assert_eq!(
+            messages,
+            vec![TestMessage(0), TestMessage(1), TestMessage(2)]
+        );
+    }
+
+    /// Tests that a message sent over a perfect link is delivered only once, even though the
+    /// underlying stubborn link delivers it many times.
+    #[test]
+    fn test_no_duplication() {
+        let (sender, delivered) = setup();
+        let to = TestProcess { id: 2 };
+
+        sender.send(&to, TestMessage(7)).unwrap();
+        for _ in 0..10 {
+            sender.inner().retransmit().unwrap();
+        }
+
+        assert_eq!(
+            *delivered.lock().unwrap(),
+            vec![(TestProcess { id: 1 }, TestMessage(7))]
+        );
+    }
+}
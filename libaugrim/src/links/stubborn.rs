@@ -0,0 +1,366 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implementation of the "Retransmit Forever" stubborn link algorithm.
+
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::error::InternalError;
+use crate::message::Message;
+use crate::process::Process;
+use crate::time::{Time, TimeSource};
+
+use super::{FairLossSender, Receiver, Sender, StubbornLink};
+
+/// The sending side of a stubborn link.
+///
+/// Every message sent is recorded and sent again over the underlying fair-loss link each time
+/// [`StubbornSender::retransmit`] is called, or each time the retransmit interval elapses if
+/// [`StubbornSender::check`] is called periodically. The interval is measured with a
+/// [`TimeSource`].
+pub struct StubbornSender<P, M, S, T>
+where
+    T: TimeSource,
+{
+    inner: S,
+    sent: Mutex<Vec<(P, M)>>,
+    time_source: T,
+    retransmit_interval: Duration,
+    last_retransmit: Mutex<T::Time>,
+}
+
+impl<P, M, S, T> StubbornSender<P, M, S, T>
+where
+    P: Process,
+    M: Message + Clone,
+    S: FairLossSender<P, M>,
+    T: TimeSource,
+{
+    /// Constructs a new `StubbornSender` over the given fair-loss link sender, which retransmits
+    /// every `retransmit_interval`, as measured by `time_source`, when driven by
+    /// [`StubbornSender::check`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    /// use std::time::Duration;
+    ///
+    /// use augrim::error::InternalError;
+    /// use augrim::links::{FairLossLink, Sender, StubbornSender};
+    /// use augrim::message::Message;
+    /// use augrim::process::Process;
+    /// use augrim::time::SystemTimeSource;
+    ///
+    /// #[derive(Clone, Copy, PartialEq, Eq)]
+    /// struct Node(u64);
+    ///
+    /// impl Process for Node {}
+    ///
+    /// #[derive(Clone)]
+    /// struct Ping;
+    ///
+    /// impl Message for Ping {}
+    ///
+    /// /// A fair-loss link which counts the messages it is asked to send.
+    /// struct CountingLink {
+    ///     sent: Rc<Cell<usize>>,
+    /// }
+    ///
+    /// impl Sender<Node, Ping> for CountingLink {
+    ///     fn send(&self, _to: &Node, _message: Ping) -> Result<(), InternalError> {
+    ///         self.sent.set(self.sent.get() + 1);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// impl FairLossLink for CountingLink {}
+    ///
+    /// let sent = Rc::new(Cell::new(0));
+    /// let sender = StubbornSender::new(
+    ///     CountingLink { sent: sent.clone() },
+    ///     SystemTimeSource::new(),
+    ///     Duration::from_secs(1),
+    /// );
+    ///
+    /// sender.send(&Node(2), Ping)?;
+    /// sender.retransmit()?;
+    /// sender.retransmit()?;
+    /// assert_eq!(sent.get(), 3);
+    /// # Ok::<(), InternalError>(())
+    /// ```
+    pub fn new(inner: S, time_source: T, retransmit_interval: Duration) -> Self {
+        let last_retransmit = Mutex::new(time_source.now());
+
+        StubbornSender {
+            inner,
+            sent: Mutex::new(Vec::new()),
+            time_source,
+            retransmit_interval,
+            last_retransmit,
+        }
+    }
+
+    /// Returns the interval after which [`StubbornSender::check`] retransmits every message.
+    pub fn retransmit_interval(&self) -> Duration {
+        self.retransmit_interval
+    }
+
+    /// Retransmits every previously sent message if the retransmit interval has elapsed since the
+    /// last retransmission, or since the sender was constructed.
+    pub fn check(&self) -> Result<(), InternalError> {
+        let due = {
+            let last_retransmit = self
+                .last_retransmit
+                .lock()
+                .map_err(|_| InternalError::with_message("stubborn sender lock poisoned".into()))?;
+            self.time_source.now().duration_since(&last_retransmit) >= self.retransmit_interval
+        };
+
+        if due {
+            self.retransmit()?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends every previously sent message again over the fair-loss link.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `InternalError` if a message cannot be sent; every other message is still
+    /// sent.
+    pub fn retransmit(&self) -> Result<(), InternalError> {
+        *self
+            .last_retransmit
+            .lock()
+            .map_err(|_| InternalError::with_message("stubborn sender lock poisoned".into()))? =
+            self.time_source.now();
+
+        let sent = self
+            .sent
+            .lock()
+            .map_err(|_| InternalError::with_message("stubborn sender lock poisoned".into()))?;
+
+        let mut result = Ok(());
+        for (to, message) in sent.iter() {
+            result = result.and(self.inner.send(to, message.clone()));
+        }
+
+        result
+    }
+}
+
+impl<P, M, S, T> Sender<P, M> for StubbornSender<P, M, S, T>
+where
+    P: Process,
+    M: Message + Clone,
+    S: FairLossSender<P, M>,
+    T: TimeSource,
+{
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError> {
+        // Recorded first, so that a message lost on its first send is still retransmitted
+        self.sent
+            .lock()
+            .map_err(|_| InternalError::with_message("stubborn sender lock poisoned".into()))?
+            .push((*to, message.clone()));
+        self.inner.send(to, message)
+    }
+}
+
+impl<P, M, S, T> StubbornLink for StubbornSender<P, M, S, T> where T: TimeSource {}
+
+/// The receiving side of a stubborn link.
+///
+/// Every message delivered by the underlying fair-loss link is delivered to the inner receiver.
+pub struct StubbornReceiver<P, M, R> {
+    inner: R,
+    _process: PhantomData<P>,
+    _message: PhantomData<M>,
+}
+
+impl<P, M, R> StubbornReceiver<P, M, R>
+where
+    P: Process,
+    M: Message,
+    R: Receiver<P, M>,
+{
+    /// Constructs a new `StubbornReceiver` which delivers to the given receiver.
+    pub fn new(inner: R) -> Self {
+        StubbornReceiver {
+            inner,
+            _process: PhantomData,
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<P, M, R> Receiver<P, M> for StubbornReceiver<P, M, R>
+where
+    P: Process,
+    M: Message,
+    R: Receiver<P, M>,
+{
+    fn deliver(&mut self, from: P, message: M) -> Result<(), InternalError> {
+        self.inner.deliver(from, message)
+    }
+}
+
+impl<P, M, R> StubbornLink for StubbornReceiver<P, M, R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use crate::links::FairLossLink;
+    use crate::time::MockClock;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(u64);
+
+    impl Message for TestMessage {}
+
+    type Sent = Arc<Mutex<Vec<(TestProcess, TestMessage)>>>;
+
+    /// A fair-loss sender which records every message it is asked to send, after failing the
+    /// first `failures` sends. Sends to `unreachable` always fail.
+    struct RecordingSender {
+        sent: Sent,
+        failures: Mutex<usize>,
+        unreachable: Option<TestProcess>,
+    }
+
+    impl RecordingSender {
+        fn new(sent: Sent) -> Self {
+            RecordingSender::failing(sent, 0)
+        }
+
+        fn failing(sent: Sent, failures: usize) -> Self {
+            RecordingSender {
+                sent,
+                failures: Mutex::new(failures),
+                unreachable: None,
+            }
+        }
+    }
+
+    impl Sender<TestProcess, TestMessage> for RecordingSender {
+        fn send(&self, to: &TestProcess, message: TestMessage) -> Result<(), InternalError> {
+            if self.unreachable == Some(*to) {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(InternalError::with_message("message lost".into()));
+            }
+            self.sent.lock().unwrap().push((*to, message));
+            Ok(())
+        }
+    }
+
+    impl FairLossLink for RecordingSender {}
+
+    /// Tests, with a mock clock, that a sender checked periodically with a 10ms retransmit
+    /// interval retransmits once each interval, and does not retransmit before the interval has
+    /// elapsed since the last retransmission.
+    #[test]
+    fn test_retransmit_interval() {
+        let sent = Sent::default();
+        let clock = MockClock::new();
+        let sender = StubbornSender::new(
+            RecordingSender::new(sent.clone()),
+            clock.clone(),
+            Duration::from_millis(10),
+        );
+        let to = TestProcess { id: 2 };
+
+        sender.send(&to, TestMessage(1)).unwrap();
+        clock.advance(Duration::from_millis(5));
+        sender.retransmit().unwrap();
+        clock.advance(Duration::from_millis(9));
+        sender.check().unwrap();
+        assert_eq!(sent.lock().unwrap().len(), 2);
+
+        for _ in 0..5 {
+            clock.advance(Duration::from_millis(1));
+            sender.check().unwrap();
+            clock.advance(Duration::from_millis(9));
+            sender.check().unwrap();
+        }
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 7);
+        assert!(sent.iter().all(|entry| *entry == (to, TestMessage(1))));
+    }
+
+    /// Tests that a message whose first send fails on the fair-loss link is still retransmitted.
+    #[test]
+    fn test_failed_send_retransmitted() {
+        let sent = Sent::default();
+        let sender = StubbornSender::new(
+            RecordingSender::failing(sent.clone(), 1),
+            MockClock::new(),
+            Duration::from_millis(10),
+        );
+        let to = TestProcess { id: 2 };
+
+        assert!(sender.send(&to, TestMessage(1)).is_err());
+        assert!(sent.lock().unwrap().is_empty());
+
+        sender.retransmit().unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![(to, TestMessage(1))]);
+    }
+
+    /// Tests that a destination which always fails does not stop later messages from being
+    /// retransmitted, and that its error is still returned.
+    #[test]
+    fn test_retransmit_past_unreachable() {
+        let sent = Sent::default();
+        let unreachable = TestProcess { id: 2 };
+        let reachable = TestProcess { id: 3 };
+        let sender = StubbornSender::new(
+            RecordingSender {
+                unreachable: Some(unreachable),
+                ..RecordingSender::new(sent.clone())
+            },
+            MockClock::new(),
+            Duration::from_millis(10),
+        );
+
+        assert!(sender.send(&unreachable, TestMessage(1)).is_err());
+        sender.send(&reachable, TestMessage(2)).unwrap();
+
+        assert!(sender.retransmit().is_err());
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![(reachable, TestMessage(2)), (reachable, TestMessage(2))]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A network sender which injects faults, for testing algorithms over an unreliable network.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::InternalError;
+use crate::rng::Rng;
+
+use super::NetworkSender;
+
+/// The fault policy, shared between a sender and its clones.
+struct Faults<P, M> {
+    dropped: Vec<P>,
+    drop_probability: f64,
+    rng: Rng,
+    buffering: bool,
+    buffered: Vec<(P, M)>,
+}
+
+/// A network sender which wraps another and drops or holds back messages according to a policy.
+///
+/// Messages are dropped if they are sent to a process passed to
+/// [`FaultyNetworkSender::drop_to`], or at random with the probability set by
+/// [`FaultyNetworkSender::with_drop_probability`]. While buffering, the remaining messages are
+/// held back until [`FaultyNetworkSender::release_buffered`] is called. A dropped message is
+/// lost silently, as over a fair-loss link, so `send` still succeeds.
+///
+/// Clones share the same policy, so a test can keep a clone to change the policy of a sender it
+/// has passed to a broadcast or failure detector.
+pub struct FaultyNetworkSender<P, M, N> {
+    inner: N,
+    faults: Arc<Mutex<Faults<P, M>>>,
+}
+
+impl<P, M, N> FaultyNetworkSender<P, M, N>
+where
+    P: PartialEq,
+    N: NetworkSender<P, M>,
+{
+    /// Constructs a new `FaultyNetworkSender` which sends messages with `inner`, initially
+    /// without any faults.
+    pub fn new(inner: N) -> Self {
+        FaultyNetworkSender {
+            inner,
+            faults: Arc::new(Mutex::new(Faults {
+                dropped: Vec::new(),
+                drop_probability: 0.0,
+                rng: Rng::new(0),
+                buffering: false,
+                buffered: Vec::new(),
+            })),
+        }
+    }
+
+    /// Drops each message with the given probability, which is clamped to `[0, 1]`, choosing the
+    /// messages to drop with a pseudo-random number generator seeded with `seed`.
+    pub fn with_drop_probability(self, probability: f64, seed: u64) -> Self {
+        {
+            let mut faults = self.lock();
+            faults.drop_probability = probability.clamp(0.0, 1.0);
+            faults.rng = Rng::new(seed);
+        }
+        self
+    }
+
+    /// Drops every message sent to `process` until it is healed.
+    pub fn drop_to(&self, process: P) {
+        let mut faults = self.lock();
+        if !faults.dropped.contains(&process) {
+            faults.dropped.push(process);
+        }
+    }
+
+    /// Stops dropping every message sent to `process`; messages dropped before are not resent.
+    pub fn heal(&self, process: &P) {
+        self.lock().dropped.retain(|dropped| dropped != process);
+    }
+
+    /// Holds back every message which is not dropped, until the messages are released.
+    pub fn buffer(&self) {
+        self.lock().buffering = true;
+    }
+
+    /// Stops holding back messages, and sends the messages held back in the order they were
+    /// sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `InternalError` raised while sending the messages; the messages after
+    /// it are still sent.
+    pub fn release_buffered(&self) -> Result<(), InternalError> {
+        let buffered = {
+            let mut faults = self.lock();
+            faults.buffering = false;
+            std::mem::take(&mut faults.buffered)
+        };
+
+        let mut result = Ok(());
+        for (to, message) in buffered {
+            result = result.and(self.inner.send(&to, message));
+        }
+
+        result
+    }
+
+    /// Returns the number of messages held back.
+    pub fn buffered_len(&self) -> usize {
+        self.lock().buffered.len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Faults<P, M>> {
+        self.faults
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<P, M, N> Clone for FaultyNetworkSender<P, M, N>
+where
+    N: Clone,
+{
+    fn clone(&self) -> Self {
+        FaultyNetworkSender {
+            inner: self.inner.clone(),
+            faults: self.faults.clone(),
+        }
+    }
+}
+
+impl<P, M, N> NetworkSender<P, M> for FaultyNetworkSender<P, M, N>
+where
+    P: PartialEq + Clone,
+    N: NetworkSender<P, M>,
+{
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError> {
+        {
+            let mut faults = self.lock();
+            if faults.dropped.contains(to) {
+                return Ok(());
+            }
+            if faults.drop_probability > 0.0 && faults.rng.next_f64() < faults.drop_probability {
+                return Ok(());
+            }
+            if faults.buffering {
+                faults.buffered.push((to.clone(), message));
+                return Ok(());
+            }
+        }
+
+        self.inner.send(to, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::network::NullNetworkSender;
+
+    type TestSender = FaultyNetworkSender<u64, u64, NullNetworkSender<u64, u64>>;
+
+    /// Returns the messages recorded by `inner`, in order.
+    fn sent(inner: &NullNetworkSender<u64, u64>) -> Vec<(u64, u64)> {
+        inner.sent().lock().unwrap().clone()
+    }
+
+    /// Tests that a message to a dropped process never arrives, even after the process is healed,
+    /// while messages to other processes and messages sent after healing arrive normally.
+    #[test]
+    fn test_drop_and_heal() {
+        let inner = NullNetworkSender::new();
+        let sender = TestSender::new(inner.clone());
+
+        sender.drop_to(2);
+        sender.send(&2, 1).unwrap();
+        sender.send(&3, 2).unwrap();
+        assert_eq!(sent(&inner), vec![(3, 2)]);
+
+        sender.heal(&2);
+        sender.send(&2, 3).unwrap();
+        assert_eq!(sent(&inner), vec![(3, 2), (2, 3)]);
+    }
+
+    /// Tests that random drops are reproducible from the seed, and that the probabilities 0 and 1
+    /// drop no messages and every message.
+    #[test]
+    fn test_drop_probability() {
+        let run = |probability: f64, seed: u64| {
+            let inner = NullNetworkSender::new();
+            let sender = TestSender::new(inner.clone()).with_drop_probability(probability, seed);
+            for message in 0..100 {
+                sender.send(&1, message).unwrap();
+            }
+            sent(&inner)
+        };
+
+        let delivered = run(0.5, 7);
+        assert!(!delivered.is_empty() && delivered.len() < 100);
+        assert_eq!(delivered, run(0.5, 7));
+        assert_ne!(delivered, run(0.5, 8));
+
+        assert_eq!(run(0.0, 7).len(), 100);
+        assert!(run(1.0, 7).is_empty());
+    }
+
+    /// Tests that buffered messages are held back until released, and are then sent in order
+    /// through a clone which shares the policy.
+    #[test]
+    fn test_buffer_and_release() {
+        let inner = NullNetworkSender::new();
+        let sender = TestSender::new(inner.clone());
+        let control = sender.clone();
+
+        control.buffer();
+        sender.send(&1, 1).unwrap();
+        sender.send(&2, 2).unwrap();
+        assert!(sent(&inner).is_empty());
+        assert_eq!(control.buffered_len(), 2);
+
+        control.release_buffered().unwrap();
+        sender.send(&3, 3).unwrap();
+        assert_eq!(sent(&inner), vec![(1, 1), (2, 2), (3, 3)]);
+    }
+
+    /// A sender which fails to send to one process and records the other messages.
+    struct UnreachableSender {
+        inner: NullNetworkSender<u64, u64>,
+        unreachable: u64,
+    }
+
+    impl NetworkSender<u64, u64> for UnreachableSender {
+        fn send(&self, to: &u64, message: u64) -> Result<(), InternalError> {
+            if *to == self.unreachable {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            self.inner.send(to, message)
+        }
+    }
+
+    /// Tests that releasing buffered messages sends every message even when one cannot be sent,
+    /// and returns the error.
+    #[test]
+    fn test_release_past_failure() {
+        let inner = NullNetworkSender::new();
+        let sender = FaultyNetworkSender::new(UnreachableSender {
+            inner: inner.clone(),
+            unreachable: 2,
+        });
+
+        sender.buffer();
+        sender.send(&1, 1).unwrap();
+        sender.send(&2, 2).unwrap();
+        sender.send(&3, 3).unwrap();
+
+        assert!(sender.release_buffered().is_err());
+        assert_eq!(sent(&inner), vec![(1, 1), (3, 3)]);
+        assert_eq!(sender.buffered_len(), 0);
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Abstractions over the network used to send messages between processes.
+
+mod faulty;
+mod null;
+mod reordering;
+
+use crate::error::InternalError;
+
+pub use faulty::FaultyNetworkSender;
+pub use null::{NullNetworkSender, SentMessages};
+pub use reordering::ReorderingNetworkSender;
+
+/// Sends messages to other processes over a network.
+///
+/// Unlike the links in [`crate::links`], a `NetworkSender` makes no guarantees on its own; the
+/// guarantees depend on the underlying transport.
+pub trait NetworkSender<P, M> {
+    /// Sends `message` to the process `to`.
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError>;
+}
@@ -0,0 +1,117 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A network sender which records messages instead of sending them.
+
+use std::sync::{Arc, Mutex};
+
+use crate::error::InternalError;
+
+use super::NetworkSender;
+
+/// The messages recorded by a [`NullNetworkSender`], along with their destination.
+pub type SentMessages<P, M> = Arc<Mutex<Vec<(P, M)>>>;
+
+/// A network sender which discards every message, for testing algorithms without a transport.
+///
+/// Each message is recorded along with its destination before it is discarded, so tests can
+/// assert on what would have been sent. Clones share the same record.
+pub struct NullNetworkSender<P, M> {
+    sent: SentMessages<P, M>,
+}
+
+impl<P, M> NullNetworkSender<P, M> {
+    /// Constructs a new `NullNetworkSender` which has not recorded any messages.
+    pub fn new() -> Self {
+        NullNetworkSender {
+            sent: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns the record of the messages sent, in the order they were sent.
+    pub fn sent(&self) -> SentMessages<P, M> {
+        self.sent.clone()
+    }
+}
+
+impl<P, M> Clone for NullNetworkSender<P, M> {
+    fn clone(&self) -> Self {
+        NullNetworkSender {
+            sent: self.sent.clone(),
+        }
+    }
+}
+
+impl<P, M> Default for NullNetworkSender<P, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, M> NetworkSender<P, M> for NullNetworkSender<P, M>
+where
+    P: Clone,
+{
+    /// Records `message` and its destination; never fails.
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((to.clone(), message));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::broadcast::best_effort::BestEffortBroadcastSender;
+    use crate::message::Message;
+    use crate::process::Process;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(u64);
+
+    impl Message for TestMessage {}
+
+    /// Tests that a broadcast to three processes over a null sender records one send to each
+    /// process.
+    #[test]
+    fn test_broadcast_records_sends() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let network = NullNetworkSender::new();
+        let sent = network.sent();
+
+        let sender = BestEffortBroadcastSender::new(processes[0], processes.clone(), network);
+        sender.broadcast(TestMessage(1)).unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(
+            sent.iter().map(|(to, _)| *to).collect::<Vec<_>>(),
+            processes
+        );
+        assert!(sent
+            .iter()
+            .all(|(_, message)| message.payload() == &TestMessage(1)));
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A network sender which reorders messages, for testing algorithms which must tolerate
+//! reordering.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::InternalError;
+
+use super::NetworkSender;
+
+/// The messages held back by a [`ReorderingNetworkSender`], along with their destination.
+type Buffered<P, M> = Arc<Mutex<Vec<(P, M)>>>;
+
+/// A network sender which wraps another and holds back every message until it is flushed in a
+/// chosen order.
+///
+/// Clones share the same held back messages, so a test can keep a clone to flush the messages of
+/// a sender it has passed to a broadcast.
+pub struct ReorderingNetworkSender<P, M, N> {
+    inner: N,
+    buffered: Buffered<P, M>,
+}
+
+impl<P, M, N> ReorderingNetworkSender<P, M, N>
+where
+    N: NetworkSender<P, M>,
+{
+    /// Constructs a new `ReorderingNetworkSender` which sends messages with `inner` once they are
+    /// flushed.
+    pub fn new(inner: N) -> Self {
+        ReorderingNetworkSender {
+            inner,
+            buffered: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns the number of messages held back.
+    pub fn buffered_len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Sends the messages held back in the given order, where `order` lists the index of each
+    /// message in the order it was sent to this sender.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if `order` does not contain each index of the held back
+    /// messages exactly once, in which case no message is sent. Otherwise every message is sent,
+    /// and the first `InternalError` raised while sending them is returned.
+    pub fn flush_reordered(&self, order: &[usize]) -> Result<(), InternalError> {
+        let buffered = {
+            let mut buffered = self.lock();
+
+            let mut seen = vec![false; buffered.len()];
+            let is_permutation = order.len() == buffered.len()
+                && order.iter().all(|index| {
+                    *index < seen.len() && !std::mem::replace(&mut seen[*index], true)
+                });
+            if !is_permutation {
+                return Err(InternalError::with_message(format!(
+                    "order is not a permutation of the {} held back messages",
+                    buffered.len()
+                )));
+            }
+
+            std::mem::take(&mut *buffered)
+        };
+
+        let mut buffered: Vec<Option<(P, M)>> = buffered.into_iter().map(Some).collect();
+        let mut result = Ok(());
+        for index in order {
+            if let Some((to, message)) = buffered[*index].take() {
+                result = result.and(self.inner.send(&to, message));
+            }
+        }
+
+        result
+    }
+
+    /// Sends the messages held back in the reverse of the order they were sent to this sender.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if a message cannot be sent, as for
+    /// [`ReorderingNetworkSender::flush_reordered`].
+    pub fn flush_reversed(&self) -> Result<(), InternalError> {
+        let order: Vec<usize> = (0..self.buffered_len()).rev().collect();
+        self.flush_reordered(&order)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<(P, M)>> {
+        self.buffered
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<P, M, N> Clone for ReorderingNetworkSender<P, M, N>
+where
+    N: Clone,
+{
+    fn clone(&self) -> Self {
+        ReorderingNetworkSender {
+            inner: self.inner.clone(),
+            buffered: self.buffered.clone(),
+        }
+    }
+}
+
+impl<P, M, N> NetworkSender<P, M> for ReorderingNetworkSender<P, M, N>
+where
+    P: Clone,
+    N: NetworkSender<P, M>,
+{
+    /// Holds back `message` until it is flushed; never fails.
+    fn send(&self, to: &P, message: M) -> Result<(), InternalError> {
+        self.lock().push((to.clone(), message));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::broadcast::best_effort::BestEffortBroadcastReceiver;
+    use crate::broadcast::fifo::{FifoReliableBroadcastReceiver, FifoReliableBroadcastSender};
+    use crate::message::Message;
+    use crate::network::NullNetworkSender;
+    use crate::process::Process;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestMessage(&'static str);
+
+    impl Message for TestMessage {}
+
+    type Delivered = Rc<RefCell<Vec<(TestProcess, TestMessage)>>>;
+
+    struct CollectingReceiver {
+        delivered: Delivered,
+    }
+
+    impl FifoReliableBroadcastReceiver<TestProcess, TestMessage> for CollectingReceiver {
+        fn deliver(
+            &mut self,
+            origin: TestProcess,
+            message: TestMessage,
+        ) -> Result<(), InternalError> {
+            self.delivered.borrow_mut().push((origin, message));
+            Ok(())
+        }
+    }
+
+    /// Tests that three broadcasts flushed in reverse reach the network in reverse, and that FIFO
+    /// broadcast at the receiving process still delivers them in the order they were broadcast.
+    #[test]
+    fn test_fifo_reorders_reversed_flush() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let network = NullNetworkSender::new();
+        let sent = network.sent();
+        let reordering = ReorderingNetworkSender::new(network);
+        let sender = FifoReliableBroadcastSender::new(p2, vec![p2], reordering.clone());
+
+        for payload in [TestMessage("a"), TestMessage("b"), TestMessage("c")] {
+            sender.broadcast(payload).unwrap();
+        }
+        assert!(sent.lock().unwrap().is_empty());
+        assert_eq!(reordering.buffered_len(), 3);
+
+        reordering.flush_reversed().unwrap();
+        let sent: Vec<_> = sent
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|(_, message)| message)
+            .collect();
+        let sequences: Vec<u64> = sent
+            .iter()
+            .map(|message| message.payload().payload().id().sequence())
+            .collect();
+        assert_eq!(sequences, vec![2, 1, 0]);
+
+        let receiver_side =
+            FifoReliableBroadcastSender::new(p1, vec![p1], NullNetworkSender::new());
+        let delivered = Delivered::default();
+        let mut handler = receiver_side.delivery_handler(CollectingReceiver {
+            delivered: delivered.clone(),
+        });
+        for message in sent {
+            handler.deliver(p2, message.into_payload()).unwrap();
+        }
+
+        assert_eq!(
+            *delivered.borrow(),
+            vec![
+                (p2, TestMessage("a")),
+                (p2, TestMessage("b")),
+                (p2, TestMessage("c"))
+            ]
+        );
+    }
+
+    /// Tests that messages are flushed in a caller-specified order, and that an order which is
+    /// not a permutation of the held back messages is rejected without sending any.
+    #[test]
+    fn test_flush_in_specified_order() {
+        let network = NullNetworkSender::new();
+        let sent = network.sent();
+        let reordering = ReorderingNetworkSender::new(network);
+
+        for message in 0..3 {
+            reordering.send(&1, message).unwrap();
+        }
+
+        assert!(reordering.flush_reordered(&[0, 0, 1]).is_err());
+        assert!(reordering.flush_reordered(&[0, 1]).is_err());
+        assert!(reordering.flush_reordered(&[0, 1, 3]).is_err());
+        assert_eq!(reordering.buffered_len(), 3);
+
+        reordering.flush_reordered(&[1, 2, 0]).unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![(1, 1), (1, 2), (1, 0)]);
+        assert_eq!(reordering.buffered_len(), 0);
+    }
+
+    /// A sender which fails to send to one process and records the other messages.
+    struct UnreachableSender {
+        inner: NullNetworkSender<u64, u64>,
+        unreachable: u64,
+    }
+
+    impl NetworkSender<u64, u64> for UnreachableSender {
+        fn send(&self, to: &u64, message: u64) -> Result<(), InternalError> {
+            if *to == self.unreachable {
+                return Err(InternalError::with_message(format!(
+                    "unable to reach {:?}",
+                    to
+                )));
+            }
+            self.inner.send(to, message)
+        }
+    }
+
+    /// Tests that a flush sends every message even when one cannot be sent, and returns the
+    /// error.
+    #[test]
+    fn test_flush_past_failure() {
+        let network = NullNetworkSender::new();
+        let sent = network.sent();
+        let reordering = ReorderingNetworkSender::new(UnreachableSender {
+            inner: network,
+            unreachable: 2,
+        });
+
+        for to in 1..=3 {
+            reordering.send(&to, to * 10).unwrap();
+        }
+
+        assert!(reordering.flush_reversed().is_err());
+        assert_eq!(*sent.lock().unwrap(), vec![(3, 30), (1, 10)]);
+        assert_eq!(reordering.buffered_len(), 0);
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Definition of a process participating in a distributed algorithm.
+
+/// A process which participates in a distributed algorithm.
+///
+/// A process is used as an identifier; for example, it is the destination of a sent message and
+/// the origin of a delivered message.
+pub trait Process: Copy + Eq + PartialEq {}
+
+/// A process which is ranked against the other processes by its `Ord` implementation.
+///
+/// Algorithms which need every process to agree on a ranking, such as hierarchical consensus and
+/// eventual leader detection, order the processes they are given with [`rank`], so the ranking
+/// does not depend on the order in which each process was given them. The least process is ranked
+/// first.
+pub trait RankedProcess: Process + Ord {}
+
+/// Orders `processes` by rank, removing any duplicates.
+pub fn rank<P: RankedProcess>(mut processes: Vec<P>) -> Vec<P> {
+    processes.sort();
+    processes.dedup();
+    processes
+}
+
+/// A process identified by a number.
+///
+/// Implementations of algorithms are generic over the [`Process`] type; `ProcessId` is a ready-made
+/// one for applications and tests which do not need their own. It is `Hash` and `Ord`, so it may
+/// be used as a map key and sorted into a deterministic order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessId(u64);
+
+impl ProcessId {
+    /// Constructs the process with the given id.
+    pub fn new(id: u64) -> Self {
+        ProcessId(id)
+    }
+
+    /// Returns the id of the process.
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for ProcessId {
+    fn from(id: u64) -> Self {
+        ProcessId(id)
+    }
+}
+
+impl Process for ProcessId {}
+
+impl RankedProcess for ProcessId {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that ranking process ids orders them by id, lowest first, regardless of the order
+    /// they were given in, and removes duplicates.
+    #[test]
+    fn test_rank_process_ids() {
+        let processes = vec![
+            ProcessId::new(3),
+            ProcessId::new(1),
+            ProcessId::new(2),
+            ProcessId::new(1),
+        ];
+
+        assert_eq!(
+            rank(processes),
+            vec![ProcessId::new(1), ProcessId::new(2), ProcessId::new(3)]
+        );
+        assert!(ProcessId::new(1) < ProcessId::new(2));
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shared-memory registers emulated over message passing.
+//!
+//! A register holds a single value which processes write and read. A (1,N) register has a single
+//! writer and any number of readers. A regular register guarantees that a read which is not
+//! concurrent with a write returns the last value written, and that a read concurrent with a
+//! write returns either the last value written or the value being written. An atomic register
+//! further guarantees that once a read has returned a value, no later read returns an older one.
+
+mod read_impose_write_all;
+mod read_one_write_all;
+
+use crate::error::InternalError;
+use crate::message::Message;
+
+pub use read_impose_write_all::{ReadImposeWriteAllReceiver, ReadImposeWriteAllRegister};
+pub use read_one_write_all::{ReadOneWriteAllReceiver, ReadOneWriteAllRegister};
+
+/// A (1,N) regular register.
+pub trait RegularRegister<V> {
+    /// Returns the value of the register, or `None` if no value has been written.
+    fn read(&self) -> Result<Option<V>, InternalError>;
+
+    /// Writes `value` to the register, returning once the write is complete.
+    ///
+    /// Only the single writer of the register may write.
+    fn write(&mut self, value: V) -> Result<(), InternalError>;
+}
+
+/// A (1,N) atomic register.
+///
+/// Unlike a [`RegularRegister`], a read communicates with the other processes, so it requires
+/// exclusive access.
+pub trait AtomicRegister<V> {
+    /// Returns the value of the register, or `None` if no value has been written.
+    fn read(&mut self) -> Result<Option<V>, InternalError>;
+
+    /// Writes `value` to the register, returning once the write is complete.
+    ///
+    /// Only the single writer of the register may write.
+    fn write(&mut self, value: V) -> Result<(), InternalError>;
+}
+
+/// A message exchanged by registers.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RegisterMessage<V> {
+    /// Broadcast by the writer to write the value at every process: the id of the write and the
+    /// value.
+    Write(u64, V),
+    /// Sent to the writer with the id of the write once the sender has written the value.
+    Ack(u64),
+}
+
+impl<V> Message for RegisterMessage<V> {}
+
+/// A message exchanged by atomic registers.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AtomicRegisterMessage<V> {
+    /// Broadcast by the writer, or by a reader writing back the value it read, to impose the
+    /// value at every process: the id of the request, the timestamp and the value.
+    Write(u64, u64, Option<V>),
+    /// Sent to the sender of the request with the id once the value has been imposed.
+    Ack(u64),
+}
+
+impl<V> Message for AtomicRegisterMessage<V> {}
@@ -0,0 +1,364 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implementation of the "Read-Impose Write-All" (1,N) atomic register algorithm.
+//!
+//! Every process keeps a copy of the value, tagged with the timestamp of the write which stored
+//! it, and only replaces it with a value with a higher timestamp. A write sends the value with the
+//! next timestamp to every process, and a read sends the local value with its timestamp, so that
+//! the value read is imposed on every process before the read returns. Either completes once
+//! every process which has not crashed has acknowledged it. As with the "Read-One Write-All"
+//! regular register, this assumes a fail-stop system.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::error::{InternalError, InvalidStateError};
+use crate::failure_detector::PerfectFailureDetectorReceiver;
+use crate::links::{PerfectLink, Receiver, Sender};
+use crate::process::Process;
+
+use super::{AtomicRegister, AtomicRegisterMessage};
+
+/// The state of a register at a single process.
+struct State<P, V> {
+    timestamp: u64,
+    value: Option<V>,
+    correct: HashSet<P>,
+    request: u64,
+    acked: HashSet<P>,
+}
+
+/// State shared between a register and its [`ReadImposeWriteAllReceiver`].
+struct Shared<P, V, S> {
+    sender: S,
+    state: Mutex<State<P, V>>,
+    acks: Condvar,
+}
+
+impl<P, V, S> Shared<P, V, S> {
+    fn lock(&self) -> Result<MutexGuard<'_, State<P, V>>, InternalError> {
+        self.state
+            .lock()
+            .map_err(|_| InternalError::with_message("register lock poisoned".into()))
+    }
+}
+
+/// A (1,N) atomic register which imposes every value written or read on every process.
+///
+/// Messages delivered to this process are handled by the receiver returned by
+/// [`ReadImposeWriteAllRegister::receiver`], which is also told of crashed processes.
+pub struct ReadImposeWriteAllRegister<P, V, S> {
+    shared: Arc<Shared<P, V, S>>,
+    processes: Vec<P>,
+    this_process: P,
+}
+
+impl<P, V, S> ReadImposeWriteAllRegister<P, V, S>
+where
+    P: Process + Hash,
+    V: Clone,
+    S: Sender<P, AtomicRegisterMessage<V>> + PerfectLink,
+{
+    /// Constructs a new `ReadImposeWriteAllRegister` for `this_process`, shared by `processes`,
+    /// which sends messages with `sender`.
+    ///
+    /// The register initially has no value, and every process is considered correct.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `this_process` is not in `processes`.
+    pub fn new(this_process: P, processes: Vec<P>, sender: S) -> Result<Self, InvalidStateError> {
+        if !processes.contains(&this_process) {
+            return Err(InvalidStateError::with_message(
+                "this process is not in the set of processes".into(),
+            ));
+        }
+
+        Ok(ReadImposeWriteAllRegister {
+            shared: Arc::new(Shared {
+                sender,
+                state: Mutex::new(State {
+                    timestamp: 0,
+                    value: None,
+                    correct: processes.iter().copied().collect(),
+                    request: 0,
+                    acked: HashSet::new(),
+                }),
+                acks: Condvar::new(),
+            }),
+            processes,
+            this_process,
+        })
+    }
+
+    /// Returns the process this register belongs to.
+    pub fn this_process(&self) -> &P {
+        &self.this_process
+    }
+
+    /// Returns the receiver which handles register messages delivered to this process.
+    pub fn receiver(&self) -> ReadImposeWriteAllReceiver<P, V, S> {
+        ReadImposeWriteAllReceiver {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Sends the value with the timestamp to every process as a new request, and blocks until
+    /// every correct process has acknowledged it.
+    fn impose(&mut self, timestamp: u64, value: Option<V>) -> Result<(), InternalError> {
+        let request = {
+            let mut state = self.shared.lock()?;
+            state.request += 1;
+            state.acked.clear();
+            state.request
+        };
+
+        for process in &self.processes {
+            self.shared.sender.send(
+                process,
+                AtomicRegisterMessage::Write(request, timestamp, value.clone()),
+            )?;
+        }
+
+        let mut state = self.shared.lock()?;
+        while !state.correct.is_subset(&state.acked) {
+            state = self
+                .shared
+                .acks
+                .wait(state)
+                .map_err(|_| InternalError::with_message("register lock poisoned".into()))?;
+        }
+        state.acked.clear();
+
+        Ok(())
+    }
+}
+
+impl<P, V, S> AtomicRegister<V> for ReadImposeWriteAllRegister<P, V, S>
+where
+    P: Process + Hash,
+    V: Clone,
+    S: Sender<P, AtomicRegisterMessage<V>> + PerfectLink,
+{
+    /// Returns the local value once it has been imposed on every correct process.
+    fn read(&mut self) -> Result<Option<V>, InternalError> {
+        let (timestamp, value) = {
+            let state = self.shared.lock()?;
+            (state.timestamp, state.value.clone())
+        };
+
+        self.impose(timestamp, value.clone())?;
+        Ok(value)
+    }
+
+    /// Writes `value` with the next timestamp to every process, blocking until every correct
+    /// process has acknowledged it.
+    fn write(&mut self, value: V) -> Result<(), InternalError> {
+        let timestamp = self.shared.lock()?.timestamp + 1;
+        self.impose(timestamp, Some(value))
+    }
+}
+
+/// Handles register messages on behalf of a [`ReadImposeWriteAllRegister`]: values with a higher
+/// timestamp are stored, every value is acknowledged, and acknowledgements of the current request
+/// are passed to the waiting read or write.
+pub struct ReadImposeWriteAllReceiver<P, V, S> {
+    shared: Arc<Shared<P, V, S>>,
+}
+
+impl<P, V, S> ReadImposeWriteAllReceiver<P, V, S>
+where
+    P: Process + Hash,
+{
+    /// Handles the crash of `process`, so reads and writes no longer wait for its
+    /// acknowledgement.
+    pub fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        self.shared.lock()?.correct.remove(&process);
+        self.shared.acks.notify_all();
+        Ok(())
+    }
+}
+
+impl<P, V, S> Clone for ReadImposeWriteAllReceiver<P, V, S> {
+    fn clone(&self) -> Self {
+        ReadImposeWriteAllReceiver {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<P, V, S> Receiver<P, AtomicRegisterMessage<V>> for ReadImposeWriteAllReceiver<P, V, S>
+where
+    P: Process + Hash,
+    S: Sender<P, AtomicRegisterMessage<V>>,
+{
+    fn deliver(&mut self, from: P, message: AtomicRegisterMessage<V>) -> Result<(), InternalError> {
+        match message {
+            AtomicRegisterMessage::Write(request, timestamp, value) => {
+                {
+                    let mut state = self.shared.lock()?;
+                    if timestamp > state.timestamp {
+                        state.timestamp = timestamp;
+                        state.value = value;
+                    }
+                }
+                self.shared
+                    .sender
+                    .send(&from, AtomicRegisterMessage::Ack(request))
+            }
+            AtomicRegisterMessage::Ack(request) => {
+                let mut state = self.shared.lock()?;
+                // Acknowledgements of an earlier request may arrive from processes which were
+                // slow to reply before a crash was reported
+                if request == state.request {
+                    state.acked.insert(from);
+                    self.shared.acks.notify_all();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<P, V, S> PerfectFailureDetectorReceiver<P> for ReadImposeWriteAllReceiver<P, V, S>
+where
+    P: Process + Hash,
+{
+    fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        ReadImposeWriteAllReceiver::crash(self, process)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::communication::{IntraProcessNetwork, IntraProcessNetworkSender};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    type TestMessage = AtomicRegisterMessage<u64>;
+
+    type TestSender = IntraProcessNetworkSender<TestProcess, TestMessage>;
+
+    type TestRegister = ReadImposeWriteAllRegister<TestProcess, u64, TestSender>;
+
+    type TestReceiver = ReadImposeWriteAllReceiver<TestProcess, u64, TestSender>;
+
+    type TestNetwork = IntraProcessNetwork<TestProcess, TestMessage, TestReceiver>;
+
+    /// Constructs a network of three processes, each with a register.
+    fn registers() -> (TestNetwork, Vec<TestProcess>, Vec<TestRegister>) {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let mut network = IntraProcessNetwork::new().unwrap();
+
+        let registers = processes
+            .iter()
+            .map(|process| {
+                let register =
+                    TestRegister::new(*process, processes.clone(), network.sender(*process))
+                        .unwrap();
+                network.add_process(*process, register.receiver());
+                register
+            })
+            .collect();
+
+        (network, processes, registers)
+    }
+
+    /// Tests that once a write completes, every process reads the written value.
+    #[test]
+    fn test_read_after_write() {
+        let (network, _, mut registers) = registers();
+        assert_eq!(registers[1].read().unwrap(), None);
+
+        registers[0].write(1).unwrap();
+        registers[0].write(2).unwrap();
+        for register in registers.iter_mut() {
+            assert_eq!(register.read().unwrap(), Some(2));
+        }
+
+        network.shutdown().unwrap();
+    }
+
+    /// Tests that reads at two processes, alternating while a write is in progress, never return
+    /// the new value followed by the old one, even though one of the readers is slow to receive
+    /// the write.
+    #[test]
+    fn test_no_new_old_inversion() {
+        let (mut network, processes, mut registers) = registers();
+        registers[0].write(1).unwrap();
+
+        // p3 is slow, so without the write back from p2's reads it could still hold the old
+        // value after p2 has read the new one
+        network
+            .set_processing_delay(processes[2], Duration::from_millis(20))
+            .unwrap();
+
+        let mut writer = registers.remove(0);
+        let write = thread::spawn(move || {
+            writer.write(2).unwrap();
+            writer
+        });
+
+        let mut reads = Vec::new();
+        while !write.is_finished() {
+            for register in registers.iter_mut() {
+                reads.push(register.read().unwrap());
+            }
+        }
+        write.join().unwrap();
+        for register in registers.iter_mut() {
+            reads.push(register.read().unwrap());
+        }
+
+        assert!(reads
+            .iter()
+            .all(|value| *value == Some(1) || *value == Some(2)));
+        assert!(
+            reads.windows(2).all(|pair| pair[0] <= pair[1]),
+            "new value read before old value: {:?}",
+            reads
+        );
+        assert_eq!(reads.last(), Some(&Some(2)));
+
+        network.shutdown().unwrap();
+    }
+
+    /// Tests that a register cannot be constructed for a process outside the set of processes.
+    #[test]
+    fn test_unknown_process() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let network: TestNetwork = IntraProcessNetwork::new().unwrap();
+
+        assert!(TestRegister::new(
+            TestProcess { id: 4 },
+            processes,
+            network.sender(TestProcess { id: 4 })
+        )
+        .is_err());
+
+        network.shutdown().unwrap();
+    }
+}
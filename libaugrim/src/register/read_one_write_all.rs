@@ -0,0 +1,371 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implementation of the "Read-One Write-All" (1,N) regular register algorithm.
+//!
+//! Every process keeps a copy of the value. A write sends the value to every process and waits
+//! until every process which has not crashed has acknowledged it; a read returns the local copy.
+//! This assumes a fail-stop system, where crashes are detected by a perfect failure detector, so
+//! that a write does not wait on a crashed process forever.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::error::InternalError;
+use crate::failure_detector::PerfectFailureDetectorReceiver;
+use crate::links::{PerfectLink, Receiver, Sender};
+use crate::process::Process;
+
+use super::{RegisterMessage, RegularRegister};
+
+/// The state of a register at a single process.
+struct State<P, V> {
+    value: Option<V>,
+    correct: HashSet<P>,
+    write: u64,
+    acked: HashSet<P>,
+}
+
+/// State shared between a register and its [`ReadOneWriteAllReceiver`].
+struct Shared<P, V, S> {
+    sender: S,
+    state: Mutex<State<P, V>>,
+    acks: Condvar,
+}
+
+impl<P, V, S> Shared<P, V, S> {
+    fn lock(&self) -> Result<MutexGuard<'_, State<P, V>>, InternalError> {
+        self.state
+            .lock()
+            .map_err(|_| InternalError::with_message("register lock poisoned".into()))
+    }
+}
+
+/// A (1,N) regular register which writes to every process and reads the local value.
+///
+/// Writes are sent to every process over a perfect link, which provides a best-effort broadcast.
+/// Messages delivered to this process are handled by the receiver returned by
+/// [`ReadOneWriteAllRegister::receiver`], which is also told of crashed processes.
+pub struct ReadOneWriteAllRegister<P, V, S> {
+    shared: Arc<Shared<P, V, S>>,
+    processes: Vec<P>,
+}
+
+impl<P, V, S> ReadOneWriteAllRegister<P, V, S>
+where
+    P: Process + Hash,
+    V: Clone,
+    S: Sender<P, RegisterMessage<V>> + PerfectLink,
+{
+    /// Constructs a new `ReadOneWriteAllRegister` shared by `processes`, which sends messages with
+    /// `sender`.
+    ///
+    /// The register initially has no value, and every process is considered correct.
+    pub fn new(processes: Vec<P>, sender: S) -> Self {
+        ReadOneWriteAllRegister {
+            shared: Arc::new(Shared {
+                sender,
+                state: Mutex::new(State {
+                    value: None,
+                    correct: processes.iter().copied().collect(),
+                    write: 0,
+                    acked: HashSet::new(),
+                }),
+                acks: Condvar::new(),
+            }),
+            processes,
+        }
+    }
+
+    /// Returns the receiver which handles register messages delivered to this process.
+    pub fn receiver(&self) -> ReadOneWriteAllReceiver<P, V, S> {
+        ReadOneWriteAllReceiver {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<P, V, S> RegularRegister<V> for ReadOneWriteAllRegister<P, V, S>
+where
+    P: Process + Hash,
+    V: Clone,
+    S: Sender<P, RegisterMessage<V>> + PerfectLink,
+{
+    fn read(&self) -> Result<Option<V>, InternalError> {
+        Ok(self.shared.lock()?.value.clone())
+    }
+
+    /// Writes `value` to every process, blocking until every correct process has acknowledged
+    /// it.
+    ///
+    /// A write does not complete while a process which has crashed is still considered correct,
+    /// so crashes must be reported to the receiver.
+    fn write(&mut self, value: V) -> Result<(), InternalError> {
+        let write = {
+            let mut state = self.shared.lock()?;
+            state.write += 1;
+            state.acked.clear();
+            state.write
+        };
+
+        for process in &self.processes {
+            self.shared
+                .sender
+                .send(process, RegisterMessage::Write(write, value.clone()))?;
+        }
+
+        let mut state = self.shared.lock()?;
+        while !state.correct.is_subset(&state.acked) {
+            state = self
+                .shared
+                .acks
+                .wait(state)
+                .map_err(|_| InternalError::with_message("register lock poisoned".into()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Handles register messages on behalf of a [`ReadOneWriteAllRegister`]: writes are stored and
+/// acknowledged, and acknowledgements of the current write are passed to the waiting write.
+pub struct ReadOneWriteAllReceiver<P, V, S> {
+    shared: Arc<Shared<P, V, S>>,
+}
+
+impl<P, V, S> ReadOneWriteAllReceiver<P, V, S>
+where
+    P: Process + Hash,
+{
+    /// Handles the crash of `process`, so writes no longer wait for its acknowledgement.
+    pub fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        self.shared.lock()?.correct.remove(&process);
+        self.shared.acks.notify_all();
+        Ok(())
+    }
+}
+
+impl<P, V, S> Clone for ReadOneWriteAllReceiver<P, V, S> {
+    fn clone(&self) -> Self {
+        ReadOneWriteAllReceiver {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<P, V, S> Receiver<P, RegisterMessage<V>> for ReadOneWriteAllReceiver<P, V, S>
+where
+    P: Process + Hash,
+    S: Sender<P, RegisterMessage<V>>,
+{
+    fn deliver(&mut self, from: P, message: RegisterMessage<V>) -> Result<(), InternalError> {
+        match message {
+            RegisterMessage::Write(write, value) => {
+                self.shared.lock()?.value = Some(value);
+                self.shared.sender.send(&from, RegisterMessage::Ack(write))
+            }
+            RegisterMessage::Ack(write) => {
+                let mut state = self.shared.lock()?;
+                // Acknowledgements of an earlier write may arrive from processes which were slow
+                // to reply before a crash was reported
+                if write == state.write {
+                    state.acked.insert(from);
+                    self.shared.acks.notify_all();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<P, V, S> PerfectFailureDetectorReceiver<P> for ReadOneWriteAllReceiver<P, V, S>
+where
+    P: Process + Hash,
+{
+    fn crash(&mut self, process: P) -> Result<(), InternalError> {
+        ReadOneWriteAllReceiver::crash(self, process)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::communication::{IntraProcessNetwork, IntraProcessNetworkSender};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    type TestMessage = RegisterMessage<u64>;
+
+    type TestRegister = ReadOneWriteAllRegister<
+        TestProcess,
+        u64,
+        IntraProcessNetworkSender<TestProcess, TestMessage>,
+    >;
+
+    type TestReceiver = ReadOneWriteAllReceiver<
+        TestProcess,
+        u64,
+        IntraProcessNetworkSender<TestProcess, TestMessage>,
+    >;
+
+    type TestNetwork = IntraProcessNetwork<TestProcess, TestMessage, TestReceiver>;
+
+    /// Constructs a network of three processes, each with a register.
+    fn registers() -> (TestNetwork, Vec<TestProcess>, Vec<TestRegister>) {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let mut network = IntraProcessNetwork::new().unwrap();
+
+        let registers = processes
+            .iter()
+            .map(|process| {
+                let register = TestRegister::new(processes.clone(), network.sender(*process));
+                network.add_process(*process, register.receiver());
+                register
+            })
+            .collect();
+
+        (network, processes, registers)
+    }
+
+    /// Tests that once a write completes, every process reads the written value.
+    #[test]
+    fn test_read_after_write() {
+        let (network, _, mut registers) = registers();
+        assert_eq!(registers[1].read().unwrap(), None);
+
+        registers[0].write(1).unwrap();
+        for register in &registers {
+            assert_eq!(register.read().unwrap(), Some(1));
+        }
+
+        registers[0].write(2).unwrap();
+        for register in &registers {
+            assert_eq!(register.read().unwrap(), Some(2));
+        }
+
+        network.shutdown().unwrap();
+    }
+
+    /// Tests that reads concurrent with a write return either the previous value or the value
+    /// being written, and never go back to the previous value once the new one has been read.
+    #[test]
+    fn test_read_concurrent_with_write() {
+        let (mut network, processes, mut registers) = registers();
+        registers[0].write(1).unwrap();
+
+        // Slow down p3 so its reads overlap the write
+        network
+            .set_processing_delay(processes[2], Duration::from_millis(20))
+            .unwrap();
+
+        let mut writer = registers.remove(0);
+        let write = thread::spawn(move || {
+            writer.write(2).unwrap();
+            writer
+        });
+
+        let mut reads = Vec::new();
+        while !write.is_finished() {
+            reads.push(registers[1].read().unwrap());
+        }
+        let writer = write.join().unwrap();
+
+        assert!(reads
+            .iter()
+            .all(|value| *value == Some(1) || *value == Some(2)));
+        // A process stores the new value at most once, so the reads change at most once
+        assert!(reads.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(writer.read().unwrap(), Some(2));
+        for register in &registers {
+            assert_eq!(register.read().unwrap(), Some(2));
+        }
+
+        network.shutdown().unwrap();
+    }
+
+    /// Tests that a write waits for a crashed process until its crash is reported, and then
+    /// completes without its acknowledgement.
+    #[test]
+    fn test_write_completes_after_crash() {
+        let (mut network, processes, mut registers) = registers();
+        network.remove_process(&processes[2]);
+
+        let mut receiver = registers[0].receiver();
+        let mut writer = registers.remove(0);
+        let (done_sender, done_receiver) = channel();
+        let write = thread::spawn(move || {
+            writer.write(1).unwrap();
+            done_sender.send(()).unwrap();
+        });
+
+        assert!(done_receiver
+            .recv_timeout(Duration::from_millis(100))
+            .is_err());
+
+        receiver.crash(processes[2]).unwrap();
+        done_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("write did not complete after crash");
+        write.join().unwrap();
+
+        assert_eq!(registers[0].read().unwrap(), Some(1));
+        assert_eq!(registers[1].read().unwrap(), None);
+
+        network.shutdown().unwrap();
+    }
+
+    /// Tests that an acknowledgement of an earlier write which arrives late does not count
+    /// towards the next write.
+    #[test]
+    fn test_earlier_ack_not_counted() {
+        let (mut network, processes, mut registers) = registers();
+        registers[0].write(1).unwrap();
+
+        network.remove_process(&processes[2]);
+        let mut receiver = registers[0].receiver();
+        receiver
+            .deliver(processes[2], RegisterMessage::Ack(1))
+            .unwrap();
+
+        let mut writer = registers.remove(0);
+        let (done_sender, done_receiver) = channel();
+        let write = thread::spawn(move || {
+            writer.write(2).unwrap();
+            done_sender.send(()).unwrap();
+        });
+
+        assert!(done_receiver
+            .recv_timeout(Duration::from_millis(100))
+            .is_err());
+
+        receiver.crash(processes[2]).unwrap();
+        done_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("write did not complete after crash");
+        write.join().unwrap();
+
+        network.shutdown().unwrap();
+    }
+}
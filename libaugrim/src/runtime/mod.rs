@@ -0,0 +1,479 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A runtime which performs the actions returned by an algorithm.
+//!
+//! An [`Algorithm`] only returns the actions to perform for each event. A [`Runtime`] holds the
+//! current context of an algorithm, passes each event to it, and performs the actions it returns:
+//! the context is replaced, messages are broadcast with best-effort broadcast, and decisions are
+//! passed to a callback. A runtime may also be given a [`ContextStore`], to which each updated
+//! context is saved. A [`Simulator`] instead runs an algorithm for a whole cluster of
+//! processes in a single thread, for tests.
+//!
+//! With the `tracing` feature, each event handled by a runtime or simulator is wrapped in an
+//! `event` span. The span records the algorithm's type name, the event's discriminant and the
+//! process, as its index among the processes of the cluster since processes need not implement
+//! `Debug`. A `trace`-level event is emitted for each action performed within the span.
+
+mod simulator;
+
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::algorithm::Algorithm;
+use crate::broadcast::best_effort::{BestEffortBroadcastSender, BroadcastMessage};
+use crate::error::InternalError;
+use crate::message::Message;
+use crate::network::NetworkSender;
+use crate::process::Process;
+use crate::storage::ContextStore;
+
+pub use simulator::Simulator;
+
+/// The effect of an action, as performed by a [`Runtime`].
+pub enum Effect<C, M, V, O> {
+    /// Replace the stored context with this one.
+    UpdateContext(C),
+    /// Broadcast the message to all processes, including this one.
+    Broadcast(M),
+    /// Report the decided value.
+    Decide(V),
+    /// An action which the runtime cannot perform itself.
+    Other(O),
+}
+
+/// An action which can be performed by a [`Runtime`].
+pub trait RuntimeAction: Sized {
+    /// The context replaced by [`Effect::UpdateContext`].
+    type Context;
+    /// The message broadcast by [`Effect::Broadcast`].
+    type Message;
+    /// The value reported by [`Effect::Decide`].
+    type Value;
+
+    /// Returns the effect of this action.
+    fn into_effect(self) -> Effect<Self::Context, Self::Message, Self::Value, Self>;
+}
+
+/// Runs an algorithm for a single process, performing the actions it returns.
+pub struct Runtime<P, A, N, D>
+where
+    A: Algorithm<P>,
+    A::Action: RuntimeAction,
+    P: Process,
+{
+    algorithm: A,
+    context: A::Context,
+    broadcast: BestEffortBroadcastSender<P, <A::Action as RuntimeAction>::Message, N>,
+    on_decide: D,
+    store: Option<Box<dyn ContextStore<A::Context> + Send>>,
+    _process: PhantomData<P>,
+}
+
+impl<P, A, N, D> Runtime<P, A, N, D>
+where
+    P: Process + Hash + Debug,
+    A: Algorithm<P>,
+    A::Action: RuntimeAction<Context = A::Context>,
+    A::Context: Clone,
+    <A::Action as RuntimeAction>::Message: Message + Clone,
+    N: NetworkSender<P, BroadcastMessage<P, <A::Action as RuntimeAction>::Message>>,
+    D: FnMut(<A::Action as RuntimeAction>::Value) -> Result<(), InternalError>,
+{
+    /// Constructs a new `Runtime` which runs `algorithm` from the initial `context`, broadcasting
+    /// messages with `broadcast` and passing each decided value to `on_decide`.
+    pub fn new(
+        algorithm: A,
+        context: A::Context,
+        broadcast: BestEffortBroadcastSender<P, <A::Action as RuntimeAction>::Message, N>,
+        on_decide: D,
+    ) -> Self {
+        Runtime {
+            algorithm,
+            context,
+            broadcast,
+            on_decide,
+            store: None,
+            _process: PhantomData,
+        }
+    }
+
+    /// Saves each updated context to `store`, so the algorithm can be resumed from it after a
+    /// restart.
+    pub fn with_store(mut self, store: Box<dyn ContextStore<A::Context> + Send>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Returns the current context of the algorithm.
+    pub fn context(&self) -> &A::Context {
+        &self.context
+    }
+
+    /// Handles `event` and performs the returned actions in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the algorithm fails to handle the event, or if an action
+    /// fails or cannot be performed by the runtime. If the algorithm fails, no action has been
+    /// performed and the context is unchanged; if an action fails, the actions before it have
+    /// been performed.
+    ///
+    /// If the runtime has a store, each updated context is saved as soon as it is applied. Since
+    /// the context update is the first action, a failed save stops any message from being sent
+    /// based on a context which was not saved.
+    pub fn event(&mut self, event: A::Event) -> Result<(), InternalError> {
+        #[cfg(feature = "tracing")]
+        let span = event_span::<P, A>(
+            &event,
+            self.broadcast
+                .processes()
+                .iter()
+                .position(|process| process == self.broadcast.this_process()),
+        );
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
+        let actions = self.algorithm.event(event, self.context.clone())?;
+
+        for action in actions {
+            let effect = action.into_effect();
+            #[cfg(feature = "tracing")]
+            trace_effect(&effect);
+            match effect {
+                Effect::UpdateContext(context) => {
+                    self.context = context;
+                    if let Some(store) = &self.store {
+                        store.save(&self.context)?;
+                    }
+                }
+                Effect::Broadcast(message) => {
+                    self.broadcast.broadcast(message)?;
+                }
+                Effect::Decide(value) => (self.on_decide)(value)?,
+                Effect::Other(_) => {
+                    return Err(InternalError::with_message(
+                        "action cannot be performed by the runtime".into(),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the span in which `event` is handled by the process with the given index.
+#[cfg(feature = "tracing")]
+fn event_span<P, A>(event: &A::Event, process: Option<usize>) -> tracing::Span
+where
+    P: Process,
+    A: Algorithm<P>,
+{
+    tracing::debug_span!(
+        "event",
+        algorithm = std::any::type_name::<A>(),
+        event = ?std::mem::discriminant(event),
+        process = ?process,
+    )
+}
+
+/// Emits a trace event for an effect which is about to be performed.
+#[cfg(feature = "tracing")]
+fn trace_effect<C, M, V, O>(effect: &Effect<C, M, V, O>) {
+    let effect = match effect {
+        Effect::UpdateContext(_) => "update_context",
+        Effect::Broadcast(_) => "broadcast",
+        Effect::Decide(_) => "decide",
+        Effect::Other(_) => "other",
+    };
+    tracing::trace!(effect, "performing action");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use crate::algorithm::flooding::{
+        FloodingAlgorithm, FloodingContext, FloodingEvent, FloodingMessage,
+    };
+    use crate::algorithm::Value;
+    use crate::storage::MemoryContextStore;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+    struct TestValue(u64);
+
+    impl Value for TestValue {}
+
+    type TestBroadcastMessage = BroadcastMessage<TestProcess, FloodingMessage<TestValue>>;
+
+    type Queue = Rc<RefCell<VecDeque<(TestProcess, TestBroadcastMessage)>>>;
+
+    /// A network which queues every message sent, along with its destination.
+    struct QueueNetwork {
+        queue: Queue,
+    }
+
+    impl NetworkSender<TestProcess, TestBroadcastMessage> for QueueNetwork {
+        fn send(
+            &self,
+            to: &TestProcess,
+            message: TestBroadcastMessage,
+        ) -> Result<(), InternalError> {
+            self.queue.borrow_mut().push_back((*to, message));
+            Ok(())
+        }
+    }
+
+    /// A store which shares a `MemoryContextStore` with the test.
+    struct SharedStore<C>(Arc<MemoryContextStore<C>>);
+
+    impl<C: Clone> ContextStore<C> for SharedStore<C> {
+        fn save(&self, context: &C) -> Result<(), InternalError> {
+            self.0.save(context)
+        }
+
+        fn load(&self) -> Result<Option<C>, InternalError> {
+            self.0.load()
+        }
+    }
+
+    fn lowest(values: &[TestValue]) -> Result<TestValue, InternalError> {
+        values
+            .iter()
+            .copied()
+            .reduce(|a, b| if b < a { b } else { a })
+            .ok_or_else(|| InternalError::with_message("no values".into()))
+    }
+
+    /// Tests that flooding consensus run through a runtime for each of three processes reaches a
+    /// decision at every process, with the messages broadcast by each runtime delivered to the
+    /// others.
+    #[test]
+    fn test_flooding_consensus_to_decision() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let queue = Queue::default();
+        let decisions: Rc<RefCell<Vec<(TestProcess, TestValue)>>> = Rc::default();
+
+        let mut runtimes: Vec<_> = processes
+            .iter()
+            .map(|process| {
+                let process = *process;
+                let decisions = decisions.clone();
+                Runtime::new(
+                    FloodingAlgorithm::new(lowest),
+                    FloodingContext::new(processes.clone()),
+                    BestEffortBroadcastSender::new(
+                        process,
+                        processes.clone(),
+                        QueueNetwork {
+                            queue: queue.clone(),
+                        },
+                    ),
+                    move |value| {
+                        decisions.borrow_mut().push((process, value));
+                        Ok(())
+                    },
+                )
+            })
+            .collect();
+
+        for (runtime, value) in runtimes.iter_mut().zip([5, 3, 4]) {
+            runtime
+                .event(FloodingEvent::Propose(TestValue(value), None))
+                .unwrap();
+        }
+
+        loop {
+            let next = queue.borrow_mut().pop_front();
+            let (to, message) = match next {
+                Some(entry) => entry,
+                None => break,
+            };
+            let from = *message.id().origin();
+            let index = processes.iter().position(|p| *p == to).unwrap();
+            runtimes[index]
+                .event(FloodingEvent::Deliver(from, message.into_payload()))
+                .unwrap();
+        }
+
+        let mut decisions = decisions.borrow().clone();
+        decisions.sort_by_key(|(process, _)| process.id);
+        assert_eq!(
+            decisions,
+            processes
+                .iter()
+                .map(|process| (*process, TestValue(3)))
+                .collect::<Vec<_>>()
+        );
+        for runtime in &runtimes {
+            assert_eq!(runtime.context().decision(), &Some(TestValue(3)));
+        }
+    }
+
+    /// Tests that a runtime with a store saves the context after each update, so that the
+    /// latest context can be loaded back.
+    #[test]
+    fn test_store_saves_updated_context() {
+        let this_process = TestProcess { id: 1 };
+        let processes = vec![this_process, TestProcess { id: 2 }];
+        let store = Arc::new(MemoryContextStore::new());
+
+        let mut runtime = Runtime::new(
+            FloodingAlgorithm::new(lowest),
+            FloodingContext::new(processes.clone()),
+            BestEffortBroadcastSender::new(
+                this_process,
+                processes,
+                QueueNetwork {
+                    queue: Queue::default(),
+                },
+            ),
+            |_| Ok(()),
+        )
+        .with_store(Box::new(SharedStore(store.clone())));
+
+        assert_eq!(store.load().unwrap(), None);
+        runtime
+            .event(FloodingEvent::Propose(TestValue(7), None))
+            .unwrap();
+        assert_eq!(store.load().unwrap().as_ref(), Some(runtime.context()));
+    }
+
+    /// A subscriber which captures the spans created and the events emitted, as text.
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct CapturingSubscriber {
+        spans: Arc<std::sync::Mutex<Vec<String>>>,
+        events: Arc<std::sync::Mutex<Vec<String>>>,
+        next_id: std::sync::atomic::AtomicU64,
+    }
+
+    /// Formats the fields it visits as ` name=value` pairs.
+    #[cfg(feature = "tracing")]
+    struct FieldWriter(String);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for FieldWriter {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for CapturingSubscriber {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut fields = FieldWriter(span.metadata().name().to_string());
+            span.record(&mut fields);
+            self.spans.lock().unwrap().push(fields.0);
+            let id = self
+                .next_id
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tracing::span::Id::from_u64(id + 1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut fields = FieldWriter(String::new());
+            event.record(&mut fields);
+            self.events.lock().unwrap().push(fields.0);
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    /// Tests that a runtime emits one span per `event` call, naming the algorithm and the
+    /// process, and a trace event per action performed.
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_span_per_event() {
+        let this_process = TestProcess { id: 1 };
+        let queue = Queue::default();
+        let subscriber = CapturingSubscriber::default();
+        let spans = subscriber.spans.clone();
+        let events = subscriber.events.clone();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut runtime = Runtime::new(
+                FloodingAlgorithm::new(lowest),
+                FloodingContext::new(vec![this_process]),
+                BestEffortBroadcastSender::new(
+                    this_process,
+                    vec![this_process],
+                    QueueNetwork {
+                        queue: queue.clone(),
+                    },
+                ),
+                |_| Ok(()),
+            );
+
+            runtime
+                .event(FloodingEvent::Propose(TestValue(2), None))
+                .unwrap();
+            let (_, message) = queue.borrow_mut().pop_front().unwrap();
+            runtime
+                .event(FloodingEvent::Deliver(this_process, message.into_payload()))
+                .unwrap();
+        });
+
+        let spans = spans.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        for span in spans.iter() {
+            assert!(span.starts_with("event "), "unexpected span: {}", span);
+            assert!(
+                span.contains("FloodingAlgorithm"),
+                "unexpected span: {}",
+                span
+            );
+            assert!(
+                span.contains("process=Some(0)"),
+                "unexpected span: {}",
+                span
+            );
+        }
+
+        // The single process decides as it proposes; the delivered decision is ignored
+        let events = events.lock().unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event.contains("effect=\"update_context\""))
+                .count(),
+            1
+        );
+        assert_eq!(events.len(), 3);
+    }
+}
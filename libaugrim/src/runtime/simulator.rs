@@ -0,0 +1,377 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A deterministic, single-threaded simulation of an algorithm run by several processes.
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
+
+use crate::algorithm::Algorithm;
+use crate::error::InternalError;
+use crate::process::Process;
+use crate::scheduler::{TimerHandle, TimerWheel};
+
+#[cfg(feature = "tracing")]
+use super::{event_span, trace_effect};
+use super::{Effect, RuntimeAction};
+
+type SimulatedMessage<A> = <A as RuntimeAction>::Message;
+
+/// Simulates a cluster of processes running the same algorithm, with a network which delivers
+/// every broadcast message to every process in the order the messages were sent.
+///
+/// Events are handled one at a time, so a simulation run with the same inputs always produces
+/// the same result.
+///
+/// Events may also be scheduled to be handled by a process after a delay in simulated time. The
+/// simulated time starts at the Unix epoch, and only moves forward when the next scheduled event
+/// is due and no message remains to be delivered.
+pub struct Simulator<P, A, F>
+where
+    A: Algorithm<P>,
+    A::Action: RuntimeAction,
+    P: Process,
+{
+    algorithm: A,
+    processes: Vec<P>,
+    contexts: Vec<A::Context>,
+    decisions: Vec<(P, <A::Action as RuntimeAction>::Value)>,
+    crashed: Vec<P>,
+    queue: VecDeque<(P, P, SimulatedMessage<A::Action>)>,
+    deliver_event: F,
+    now: SystemTime,
+    timers: TimerWheel<SystemTime, (P, A::Event)>,
+    _process: PhantomData<P>,
+}
+
+impl<P, A, F> Simulator<P, A, F>
+where
+    P: Process,
+    A: Algorithm<P>,
+    A::Action: RuntimeAction<Context = A::Context>,
+    A::Context: Clone,
+    SimulatedMessage<A::Action>: Clone,
+    F: Fn(P, SimulatedMessage<A::Action>) -> A::Event,
+{
+    /// Constructs a new `Simulator` of `algorithm`, run by each of the given processes from its
+    /// initial context.
+    ///
+    /// `deliver_event` returns the event which delivers a message from a process.
+    pub fn new(algorithm: A, processes: Vec<(P, A::Context)>, deliver_event: F) -> Self {
+        let (processes, contexts) = processes.into_iter().unzip();
+
+        Simulator {
+            algorithm,
+            processes,
+            contexts,
+            decisions: Vec::new(),
+            crashed: Vec::new(),
+            queue: VecDeque::new(),
+            deliver_event,
+            now: SystemTime::UNIX_EPOCH,
+            timers: TimerWheel::new(),
+            _process: PhantomData,
+        }
+    }
+
+    /// Returns the current context of `process`, or `None` if it is not simulated.
+    pub fn context(&self, process: &P) -> Option<&A::Context> {
+        self.index(process).map(|index| &self.contexts[index])
+    }
+
+    /// Returns the values decided so far, along with the process which decided each, in the
+    /// order they were decided.
+    pub fn decisions(&self) -> &[(P, <A::Action as RuntimeAction>::Value)] {
+        &self.decisions
+    }
+
+    /// Crashes `process`: it handles no further events, and messages sent to it are dropped.
+    ///
+    /// The crash is not reported to the other processes; that is the role of the failure detector,
+    /// whose events are given to them with [`Simulator::event`].
+    pub fn crash(&mut self, process: P) {
+        if !self.crashed.contains(&process) {
+            self.crashed.push(process);
+        }
+    }
+
+    /// Returns the simulated time.
+    pub fn now(&self) -> SystemTime {
+        self.now
+    }
+
+    /// Schedules `event` to be handled by `process` once `delay` has elapsed in simulated time.
+    pub fn schedule(&mut self, delay: Duration, process: P, event: A::Event) -> TimerHandle {
+        self.timers.schedule(self.now + delay, (process, event))
+    }
+
+    /// Cancels the event scheduled with `handle`, returning true if it had not yet been handled.
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        self.timers.cancel(handle).is_some()
+    }
+
+    /// Handles `event` at `process` and performs the returned actions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the process is not simulated, or if the algorithm fails to
+    /// handle the event or returns an action the simulator cannot perform.
+    pub fn event(&mut self, process: &P, event: A::Event) -> Result<(), InternalError> {
+        let index = self.index(process).ok_or_else(|| {
+            InternalError::with_message("event given for a process which is not simulated".into())
+        })?;
+        if self.crashed.contains(process) {
+            return Ok(());
+        }
+
+        #[cfg(feature = "tracing")]
+        let span = event_span::<P, A>(&event, Some(index));
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
+        let actions = self.algorithm.event(event, self.contexts[index].clone())?;
+
+        for action in actions {
+            let effect = action.into_effect();
+            #[cfg(feature = "tracing")]
+            trace_effect(&effect);
+            match effect {
+                Effect::UpdateContext(context) => self.contexts[index] = context,
+                Effect::Broadcast(message) => {
+                    for to in &self.processes {
+                        self.queue.push_back((*process, *to, message.clone()));
+                    }
+                }
+                Effect::Decide(value) => self.decisions.push((*process, value)),
+                Effect::Other(_) => {
+                    return Err(InternalError::with_message(
+                        "action cannot be performed by the simulator".into(),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delivers the next message sent, returning false if there was none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the receiving process fails to handle the message.
+    pub fn step(&mut self) -> Result<bool, InternalError> {
+        match self.queue.pop_front() {
+            Some((from, to, message)) => {
+                let event = (self.deliver_event)(from, message);
+                self.event(&to, event)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Moves the simulated time to the deadline of the earliest scheduled event, and handles
+    /// every event then due, returning false if no event was scheduled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if a process fails to handle one of the events.
+    pub fn fire_next_timers(&mut self) -> Result<bool, InternalError> {
+        let deadline = match self.timers.next_deadline() {
+            Some(deadline) => *deadline,
+            None => return Ok(false),
+        };
+        self.now = self.now.max(deadline);
+
+        for (process, event) in self.timers.poll(&self.now) {
+            self.event(&process, event)?;
+        }
+        Ok(true)
+    }
+
+    /// Delivers messages until none remain, returning the final context of each process in the
+    /// order the processes were given.
+    ///
+    /// Whenever no message remains, the scheduled events which are due next are handled as by
+    /// `fire_next_timers`, until neither messages nor scheduled events remain.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if more than `max_steps` messages would be delivered or timers
+    /// fired, which guards against algorithms which never become quiescent, or if a process fails
+    /// to handle a message or a scheduled event.
+    pub fn run_until_quiescent(
+        &mut self,
+        max_steps: usize,
+    ) -> Result<Vec<(P, A::Context)>, InternalError> {
+        let mut steps = 0;
+        while !self.is_quiescent() {
+            if steps == max_steps {
+                return Err(InternalError::with_message(format!(
+                    "simulation did not become quiescent within {} steps",
+                    max_steps
+                )));
+            }
+            if !self.step()? {
+                self.fire_next_timers()?;
+            }
+            steps += 1;
+        }
+
+        Ok(self
+            .processes
+            .iter()
+            .copied()
+            .zip(self.contexts.iter().cloned())
+            .collect())
+    }
+
+    /// Returns true if no message remains to be delivered and no event remains scheduled.
+    fn is_quiescent(&self) -> bool {
+        self.queue.is_empty() && self.timers.is_empty()
+    }
+
+    fn index(&self, process: &P) -> Option<usize> {
+        self.processes.iter().position(|p| p == process)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::algorithm::flooding::{FloodingAlgorithm, FloodingContext, FloodingEvent};
+    use crate::algorithm::Value;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+    struct TestValue(u64);
+
+    impl Value for TestValue {}
+
+    fn lowest(values: &[TestValue]) -> Result<TestValue, InternalError> {
+        values
+            .iter()
+            .copied()
+            .reduce(|a, b| if b < a { b } else { a })
+            .ok_or_else(|| InternalError::with_message("no values".into()))
+    }
+
+    /// Tests that flooding consensus run to quiescence, with one process crashing before it
+    /// proposes, leaves every correct process with the same decision.
+    #[test]
+    fn test_flooding_to_quiescence() {
+        let processes: Vec<TestProcess> = (1..=4).map(|id| TestProcess { id }).collect();
+        let mut sim = Simulator::new(
+            FloodingAlgorithm::new(lowest),
+            processes
+                .iter()
+                .map(|process| (*process, FloodingContext::new(processes.clone())))
+                .collect(),
+            FloodingEvent::Deliver,
+        );
+
+        sim.crash(processes[0]);
+        for process in &processes[1..] {
+            sim.event(process, FloodingEvent::Crash(processes[0]))
+                .unwrap();
+        }
+        for (process, value) in processes.iter().zip([1, 7, 4, 5]) {
+            sim.event(process, FloodingEvent::Propose(TestValue(value), None))
+                .unwrap();
+        }
+
+        let contexts = sim.run_until_quiescent(1000).unwrap();
+
+        assert_eq!(contexts.len(), 4);
+        for (_, context) in &contexts[1..] {
+            assert_eq!(context.decision(), &Some(TestValue(4)));
+        }
+        assert_eq!(contexts[0].1.decision(), &None);
+        assert_eq!(sim.decisions().len(), 3);
+
+        // A quiescent simulation needs no further steps
+        assert_eq!(sim.run_until_quiescent(0).unwrap(), contexts);
+    }
+
+    /// Tests that events scheduled in simulated time are handled once no message remains: the
+    /// processes wait in the first round for a process which crashed silently, until the failure
+    /// detector's scheduled crash events let them move on and decide.
+    #[test]
+    fn test_timers_fired_to_quiescence() {
+        let processes: Vec<TestProcess> = (1..=3).map(|id| TestProcess { id }).collect();
+        let mut sim = Simulator::new(
+            FloodingAlgorithm::new(lowest),
+            processes
+                .iter()
+                .map(|process| (*process, FloodingContext::new(processes.clone())))
+                .collect(),
+            FloodingEvent::Deliver,
+        );
+
+        sim.crash(processes[2]);
+        for process in &processes[..2] {
+            sim.schedule(
+                Duration::from_secs(5),
+                *process,
+                FloodingEvent::Crash(processes[2]),
+            );
+        }
+        let cancelled = sim.schedule(
+            Duration::from_secs(1),
+            processes[0],
+            FloodingEvent::Crash(processes[1]),
+        );
+        assert!(sim.cancel(cancelled));
+
+        for (process, value) in processes[..2].iter().zip([7, 4]) {
+            sim.event(process, FloodingEvent::Propose(TestValue(value), None))
+                .unwrap();
+        }
+
+        let contexts = sim.run_until_quiescent(1000).unwrap();
+
+        assert_eq!(contexts[0].1.decision(), &Some(TestValue(4)));
+        assert_eq!(contexts[1].1.decision(), &Some(TestValue(4)));
+        assert_eq!(contexts[2].1.decision(), &None);
+        assert_eq!(sim.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(5));
+    }
+
+    /// Tests that a simulation which does not become quiescent within the step cap is an error.
+    #[test]
+    fn test_step_cap() {
+        let processes: Vec<TestProcess> = (1..=2).map(|id| TestProcess { id }).collect();
+        let mut sim = Simulator::new(
+            FloodingAlgorithm::new(lowest),
+            processes
+                .iter()
+                .map(|process| (*process, FloodingContext::new(processes.clone())))
+                .collect(),
+            FloodingEvent::Deliver,
+        );
+        for process in &processes {
+            sim.event(process, FloodingEvent::Propose(TestValue(1), None))
+                .unwrap();
+        }
+
+        assert!(sim.run_until_quiescent(1).is_err());
+    }
+}
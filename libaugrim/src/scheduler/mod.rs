@@ -0,0 +1,184 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scheduling of events at a future time.
+//!
+//! A [`TimerWheel`] holds events keyed by their deadline and returns those which are due when it
+//! is polled with the current time, so it can be driven by any [`TimeSource`], including a
+//! logical clock in tests and simulations. A [`TimerThread`] drives a wheel with the system
+//! clock, and passes each event to a handler when it is due.
+//!
+//! [`TimeSource`]: crate::time::TimeSource
+
+mod thread;
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::time::Time;
+
+pub use thread::TimerThread;
+
+/// Identifies an event scheduled with a [`TimerWheel`], so that it can be cancelled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerHandle(u64);
+
+/// Events scheduled for future points in time.
+///
+/// Events are returned by [`TimerWheel::poll`] in deadline order; events with the same deadline
+/// are returned in the order they were scheduled.
+pub struct TimerWheel<T, E> {
+    timers: BTreeMap<(T, u64), E>,
+    deadlines: HashMap<u64, T>,
+    next_handle: u64,
+}
+
+impl<T, E> TimerWheel<T, E>
+where
+    T: Time,
+{
+    /// Constructs a new `TimerWheel` without any scheduled events.
+    pub fn new() -> Self {
+        TimerWheel {
+            timers: BTreeMap::new(),
+            deadlines: HashMap::new(),
+            next_handle: 0,
+        }
+    }
+
+    /// Schedules `event` to be returned by the first poll at or after `deadline`.
+    pub fn schedule(&mut self, deadline: T, event: E) -> TimerHandle {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+
+        self.deadlines.insert(handle, deadline.clone());
+        self.timers.insert((deadline, handle), event);
+        TimerHandle(handle)
+    }
+
+    /// Cancels the event scheduled with `handle`, returning it, or `None` if it has already been
+    /// returned by a poll or cancelled.
+    pub fn cancel(&mut self, handle: TimerHandle) -> Option<E> {
+        let deadline = self.deadlines.remove(&handle.0)?;
+        self.timers.remove(&(deadline, handle.0))
+    }
+
+    /// Removes and returns every event whose deadline is at or before `now`, in deadline order.
+    pub fn poll(&mut self, now: &T) -> Vec<E> {
+        let mut due = Vec::new();
+
+        while let Some(entry) = self.timers.first_entry() {
+            if &entry.key().0 > now {
+                break;
+            }
+            let ((_, handle), event) = entry.remove_entry();
+            self.deadlines.remove(&handle);
+            due.push(event);
+        }
+
+        due
+    }
+
+    /// Returns the earliest deadline of the scheduled events, if any.
+    pub fn next_deadline(&self) -> Option<&T> {
+        self.timers.keys().next().map(|(deadline, _)| deadline)
+    }
+
+    /// Returns the number of scheduled events.
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Returns true if no event is scheduled.
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+}
+
+impl<T, E> Default for TimerWheel<T, E>
+where
+    T: Time,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, SystemTime};
+
+    use crate::time::{MockClock, SystemTimeSource, TimeSource};
+
+    /// Tests that events polled with a logical clock are returned once due, in deadline order,
+    /// with events sharing a deadline returned in the order they were scheduled.
+    #[test]
+    fn test_poll_order_with_logical_clock() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut wheel = TimerWheel::new();
+        wheel.schedule(at(3), "c");
+        wheel.schedule(at(1), "a");
+        wheel.schedule(at(3), "d");
+        wheel.schedule(at(2), "b");
+
+        assert!(wheel.poll(&clock.now()).is_empty());
+        assert_eq!(wheel.next_deadline(), Some(&at(1)));
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(wheel.poll(&clock.now()), vec!["a", "b"]);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(wheel.poll(&clock.now()), vec!["c", "d"]);
+        assert!(wheel.is_empty());
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    /// Tests that a cancelled event is never returned, and that an event can only be cancelled
+    /// while it is scheduled.
+    #[test]
+    fn test_cancel() {
+        let clock = MockClock::new();
+        let deadline = clock.now() + Duration::from_secs(1);
+
+        let mut wheel = TimerWheel::new();
+        let cancelled = wheel.schedule(deadline, "cancelled");
+        let fired = wheel.schedule(deadline, "fired");
+
+        assert_eq!(wheel.cancel(cancelled), Some("cancelled"));
+        assert_eq!(wheel.cancel(cancelled), None);
+        assert_eq!(wheel.len(), 1);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(wheel.poll(&clock.now()), vec!["fired"]);
+        assert_eq!(wheel.cancel(fired), None);
+    }
+
+    /// Tests that with the system clock only the events whose deadline has passed are returned.
+    #[test]
+    fn test_poll_with_system_clock() {
+        let source = SystemTimeSource::new();
+        let now = source.now();
+
+        let mut wheel: TimerWheel<SystemTime, &str> = TimerWheel::new();
+        wheel.schedule(now + Duration::from_secs(3600), "later");
+        wheel.schedule(now - Duration::from_secs(1), "past");
+
+        assert_eq!(wheel.poll(&source.now()), vec!["past"]);
+        assert_eq!(wheel.len(), 1);
+    }
+}
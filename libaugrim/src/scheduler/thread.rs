@@ -0,0 +1,204 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A thread which delivers scheduled events when they are due.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+use crate::error::InternalError;
+
+use super::{TimerHandle, TimerWheel};
+
+struct State<E> {
+    wheel: TimerWheel<SystemTime, E>,
+    shutdown: bool,
+}
+
+type Shared<E> = Arc<(Mutex<State<E>>, Condvar)>;
+
+/// Runs a [`TimerWheel`] on a thread with the system clock, passing each event to a handler
+/// once its deadline has passed.
+///
+/// Events are passed to the handler in deadline order, on the timer thread. Events which are
+/// still scheduled when the thread is shut down are dropped.
+pub struct TimerThread<E> {
+    shared: Shared<E>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+impl<E> TimerThread<E>
+where
+    E: Send + 'static,
+{
+    /// Starts a timer thread which passes each due event to `handler`.
+    pub fn start<H>(mut handler: H) -> Result<Self, InternalError>
+    where
+        H: FnMut(E) -> Result<(), InternalError> + Send + 'static,
+    {
+        let shared: Shared<E> = Arc::new((
+            Mutex::new(State {
+                wheel: TimerWheel::new(),
+                shutdown: false,
+            }),
+            Condvar::new(),
+        ));
+
+        let thread_shared = shared.clone();
+        let join_handle = thread::Builder::new()
+            .name("TimerThread".into())
+            .spawn(move || run(&thread_shared, &mut handler))
+            .map_err(|err| InternalError::from_source(Box::new(err)))?;
+
+        Ok(TimerThread {
+            shared,
+            join_handle: Some(join_handle),
+        })
+    }
+
+    /// Schedules `event` to be passed to the handler once `deadline` has passed.
+    pub fn schedule(&self, deadline: SystemTime, event: E) -> TimerHandle {
+        let handle = lock(&self.shared).wheel.schedule(deadline, event);
+        // The new event may be due before the one the thread is waiting for
+        self.shared.1.notify_one();
+        handle
+    }
+
+    /// Cancels the event scheduled with `handle`, returning it, or `None` if it has already been
+    /// passed to the handler or cancelled.
+    pub fn cancel(&self, handle: TimerHandle) -> Option<E> {
+        lock(&self.shared).wheel.cancel(handle)
+    }
+
+    /// Stops the timer thread, dropping the events which are still scheduled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the timer thread panicked.
+    pub fn shutdown(mut self) -> Result<(), InternalError> {
+        self.signal_shutdown();
+
+        match self.join_handle.take() {
+            Some(join_handle) => join_handle
+                .join()
+                .map_err(|_| InternalError::with_message("TimerThread thread panicked".into())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E> TimerThread<E> {
+    fn signal_shutdown(&self) {
+        lock(&self.shared).shutdown = true;
+        self.shared.1.notify_one();
+    }
+}
+
+impl<E> Drop for TimerThread<E> {
+    fn drop(&mut self) {
+        // Stop the thread rather than leaking it; it is not joined
+        if self.join_handle.is_some() {
+            self.signal_shutdown();
+        }
+    }
+}
+
+fn run<E, H>(shared: &Shared<E>, handler: &mut H)
+where
+    H: FnMut(E) -> Result<(), InternalError>,
+{
+    let mut state = lock(shared);
+
+    loop {
+        if state.shutdown {
+            break;
+        }
+
+        let due = state.wheel.poll(&SystemTime::now());
+        if !due.is_empty() {
+            // The handler may schedule or cancel events, so it is called without the lock held
+            drop(state);
+            for event in due {
+                if let Err(err) = handler(event) {
+                    error!("Unable to handle timer event: {}", err);
+                }
+            }
+            state = lock(shared);
+            continue;
+        }
+
+        state = match state.wheel.next_deadline() {
+            Some(deadline) => {
+                let timeout = deadline
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default();
+                shared
+                    .1
+                    .wait_timeout(state, timeout)
+                    .map(|(state, _)| state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner().0)
+            }
+            None => shared
+                .1
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        };
+    }
+}
+
+/// Locks the timer state. Every update leaves the state consistent, so a poisoned lock is
+/// recovered.
+fn lock<E>(shared: &Shared<E>) -> MutexGuard<'_, State<E>> {
+    shared
+        .0
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    /// Tests that events are passed to the handler in deadline order once due, that a cancelled
+    /// event is not, and that the thread shuts down with an event still scheduled.
+    #[test]
+    fn test_timer_thread() {
+        let (sender, receiver) = channel();
+        let timers = TimerThread::start(move |event| {
+            sender
+                .send(event)
+                .map_err(|err| InternalError::from_source(Box::new(err)))
+        })
+        .unwrap();
+
+        let now = SystemTime::now();
+        timers.schedule(now + Duration::from_millis(40), "second");
+        let cancelled = timers.schedule(now + Duration::from_millis(20), "cancelled");
+        timers.schedule(now + Duration::from_millis(10), "first");
+        timers.schedule(now + Duration::from_secs(3600), "never");
+        assert_eq!(timers.cancel(cancelled), Some("cancelled"));
+
+        let timeout = Duration::from_secs(10);
+        assert_eq!(receiver.recv_timeout(timeout), Ok("first"));
+        assert_eq!(receiver.recv_timeout(timeout), Ok("second"));
+        assert!(SystemTime::now() >= now + Duration::from_millis(40));
+
+        timers.shutdown().unwrap();
+        assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stores which hold the latest context of an algorithm, so that it can be resumed after a
+//! restart.
+
+#[cfg(feature = "storage-file")]
+use std::fs::{self, File};
+#[cfg(feature = "storage-file")]
+use std::io::{self, ErrorKind, Write};
+use std::marker::PhantomData;
+#[cfg(feature = "storage-file")]
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::error::InternalError;
+
+use super::version::{VersionTracker, Versioned};
+
+/// A store holding the latest saved context of an algorithm.
+///
+/// Each save replaces the previously saved context.
+pub trait ContextStore<C> {
+    /// Saves `context`, replacing the previously saved context.
+    fn save(&self, context: &C) -> Result<(), InternalError>;
+
+    /// Loads the latest saved context, or `None` if no context has been saved.
+    fn load(&self) -> Result<Option<C>, InternalError>;
+}
+
+/// A [`ContextStore`] which holds the context in memory, so it does not survive the process
+/// stopping.
+pub struct MemoryContextStore<C> {
+    context: Mutex<Option<C>>,
+}
+
+impl<C> MemoryContextStore<C> {
+    /// Constructs a new, empty `MemoryContextStore`.
+    pub fn new() -> Self {
+        MemoryContextStore {
+            context: Mutex::new(None),
+        }
+    }
+}
+
+impl<C> Default for MemoryContextStore<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> ContextStore<C> for MemoryContextStore<C>
+where
+    C: Clone,
+{
+    fn save(&self, context: &C) -> Result<(), InternalError> {
+        *self
+            .context
+            .lock()
+            .map_err(|_| InternalError::with_message("context store lock poisoned".into()))? =
+            Some(context.clone());
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<C>, InternalError> {
+        Ok(self
+            .context
+            .lock()
+            .map_err(|_| InternalError::with_message("context store lock poisoned".into()))?
+            .clone())
+    }
+}
+
+/// A [`ContextStore`] which holds the context in a file, serialized as JSON.
+///
+/// Each save writes the context to a temporary file next to the store's file, syncs it to disk
+/// and then renames it over the store's file, syncing the directory so that the rename itself
+/// is durable. A save which was interrupted therefore leaves the previously saved context in
+/// place.
+#[cfg(feature = "storage-file")]
+pub struct FileContextStore<C> {
+    path: PathBuf,
+    _context: PhantomData<C>,
+}
+
+#[cfg(feature = "storage-file")]
+impl<C> FileContextStore<C> {
+    /// Constructs a new `FileContextStore` which stores the context at `path`. The file is not
+    /// created until the first save.
+    pub fn new<T: AsRef<Path>>(path: T) -> Self {
+        FileContextStore {
+            path: path.as_ref().to_path_buf(),
+            _context: PhantomData,
+        }
+    }
+
+    /// Returns the path of the file the context is stored in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        self.path.with_file_name(name)
+    }
+
+    /// Syncs the directory holding the store's file, so that a rename into it survives a crash.
+    #[cfg(unix)]
+    fn sync_dir(&self) -> io::Result<()> {
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()
+    }
+
+    /// Directories cannot be opened with `File::open` on other platforms, so the directory is
+    /// not synced there.
+    #[cfg(not(unix))]
+    fn sync_dir(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "storage-file")]
+impl<C> ContextStore<C> for FileContextStore<C>
+where
+    C: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn save(&self, context: &C) -> Result<(), InternalError> {
+        let bytes = serde_json::to_vec(context).map_err(|err| {
+            InternalError::from_source_with_prefix(
+                Box::new(err),
+                "unable to serialize context".into(),
+            )
+        })?;
+
+        let temp_path = self.temp_path();
+        File::create(&temp_path)
+            .and_then(|mut file| {
+                file.write_all(&bytes)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp_path, &self.path))
+            .and_then(|_| self.sync_dir())
+            .map_err(|err| {
+                InternalError::from_source_with_prefix(
+                    Box::new(err),
+                    format!("unable to save context to {}", self.path.display()),
+                )
+            })
+    }
+
+    fn load(&self) -> Result<Option<C>, InternalError> {
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(InternalError::from_source_with_prefix(
+                    Box::new(err),
+                    format!("unable to load context from {}", self.path.display()),
+                ))
+            }
+        };
+
+        serde_json::from_slice(&bytes).map(Some).map_err(|err| {
+            InternalError::from_source_with_prefix(
+                Box::new(err),
+                format!("unable to deserialize context from {}", self.path.display()),
+            )
+        })
+    }
+}
+
+/// A [`ContextStore`] which stamps each saved context with a version, and refuses to load a
+/// context older than one it has already saved or loaded.
+///
+/// The contexts are held by the wrapped store. After a restart with partial writes, that store
+/// may return a context older than one which was already applied; the highest version, returned
+/// by [`VersionedContextStore::highest`], should be kept durably and passed to
+/// [`VersionedContextStore::with_highest`] on restart so that such a context is rejected.
+pub struct VersionedContextStore<C, S> {
+    store: S,
+    tracker: Mutex<VersionTracker>,
+    _context: PhantomData<C>,
+}
+
+impl<C, S> VersionedContextStore<C, S>
+where
+    S: ContextStore<Versioned<C>>,
+{
+    /// Constructs a new `VersionedContextStore` which holds the contexts in `store`, and has not
+    /// observed any version.
+    pub fn new(store: S) -> Self {
+        Self::with_tracker(store, VersionTracker::new())
+    }
+
+    /// Constructs a new `VersionedContextStore` which holds the contexts in `store`, and has
+    /// already observed version `highest`.
+    pub fn with_highest(store: S, highest: u64) -> Self {
+        Self::with_tracker(store, VersionTracker::with_highest(highest))
+    }
+
+    fn with_tracker(store: S, tracker: VersionTracker) -> Self {
+        VersionedContextStore {
+            store,
+            tracker: Mutex::new(tracker),
+            _context: PhantomData,
+        }
+    }
+
+    /// Returns the highest version saved or loaded, if any.
+    pub fn highest(&self) -> Result<Option<u64>, InternalError> {
+        Ok(self.tracker()?.highest())
+    }
+
+    /// Consumes the `VersionedContextStore`, returning the wrapped store.
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    fn tracker(&self) -> Result<MutexGuard<'_, VersionTracker>, InternalError> {
+        self.tracker
+            .lock()
+            .map_err(|_| InternalError::with_message("context store lock poisoned".into()))
+    }
+}
+
+impl<C, S> ContextStore<C> for VersionedContextStore<C, S>
+where
+    C: Clone,
+    S: ContextStore<Versioned<C>>,
+{
+    /// Saves `context` with the version after the highest observed.
+    ///
+    /// The version is only observed once the wrapped store has saved it, so a failed save does
+    /// not cause the previously saved context to be rejected.
+    fn save(&self, context: &C) -> Result<(), InternalError> {
+        let mut tracker = self.tracker()?;
+        let mut next = tracker.clone();
+        self.store.save(&next.stamp(context.clone()))?;
+        *tracker = next;
+        Ok(())
+    }
+
+    /// Loads the latest saved context, or `None` if no context has been saved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the loaded context is older than a version already observed.
+    fn load(&self) -> Result<Option<C>, InternalError> {
+        let mut tracker = self.tracker()?;
+        match self.store.load()? {
+            Some(versioned) => tracker
+                .load(versioned)
+                .map(Some)
+                .map_err(|err| InternalError::from_source(Box::new(err))),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+    use std::time::SystemTime;
+
+    use crate::algorithm::Algorithm;
+    use crate::process::ProcessId;
+    use crate::time::SystemTimeSource;
+    use crate::two_phase_commit::{
+        CoordinatorAction, CoordinatorAlgorithm, CoordinatorContext, CoordinatorEvent,
+        CoordinatorMessage, CoordinatorState, Participant, TwoPhaseCommitContextBuilder,
+        TwoPhaseCommitMessage,
+    };
+
+    type TestContext = CoordinatorContext<ProcessId, SystemTime>;
+
+    /// Starts voting on a value with a coordinator and two participants, and delivers a vote
+    /// from the first participant, returning the updated context.
+    fn voting_context(
+        algorithm: &CoordinatorAlgorithm<ProcessId, String, SystemTimeSource>,
+    ) -> TestContext {
+        let coordinator = ProcessId::new(0);
+        let context = CoordinatorContext::try_from(
+            TwoPhaseCommitContextBuilder::new()
+                .with_coordinator(coordinator)
+                .with_this_process(coordinator)
+                .with_participants(vec![
+                    Participant::new(ProcessId::new(1)),
+                    Participant::new(ProcessId::new(2)),
+                ])
+                .with_require_coordinator_in_participants(false)
+                .build()
+                .expect("failed to build context"),
+        )
+        .expect("failed to convert context");
+
+        let mut context = context;
+        for event in [
+            CoordinatorEvent::Start("value".to_string(), None),
+            CoordinatorEvent::Deliver(
+                ProcessId::new(1),
+                CoordinatorMessage::VoteResponse(0, true, None),
+            ),
+        ] {
+            let actions = algorithm
+                .event(event, context)
+                .expect("failed to handle event");
+            context = match actions.into_iter().next() {
+                Some(CoordinatorAction::UpdateContext(context)) => context,
+                actions => panic!("first action was not UpdateContext: {:?}", actions),
+            };
+        }
+        context
+    }
+
+    /// Saves a voting coordinator context to `store`, loads it back as a restarted coordinator
+    /// would, and checks that recovering from it requests the missing vote.
+    fn save_load_resume(store: &dyn ContextStore<TestContext>) {
+        let algorithm = CoordinatorAlgorithm::new(SystemTimeSource::new());
+        assert_eq!(store.load().expect("failed to load"), None);
+
+        let context = voting_context(&algorithm);
+        store.save(&context).expect("failed to save");
+
+        let loaded = store
+            .load()
+            .expect("failed to load")
+            .expect("no context saved");
+        assert_eq!(loaded, context);
+        assert_eq!(loaded.state(), &CoordinatorState::Voting);
+
+        let actions = algorithm
+            .event(CoordinatorEvent::Recover("value".to_string()), loaded)
+            .expect("failed to recover");
+        assert_eq!(
+            actions,
+            vec![CoordinatorAction::SendMessage(
+                ProcessId::new(2),
+                TwoPhaseCommitMessage::VoteRequest(0, "value".to_string(), None)
+            )]
+        );
+    }
+
+    /// Tests that a coordinator context saved to a `MemoryContextStore` is loaded back and can be
+    /// resumed from.
+    #[test]
+    fn test_memory_save_load_resume() {
+        save_load_resume(&MemoryContextStore::new());
+    }
+
+    /// Tests that a coordinator context saved to a `VersionedContextStore` is loaded back and can
+    /// be resumed from.
+    #[test]
+    fn test_versioned_save_load_resume() {
+        save_load_resume(&VersionedContextStore::new(MemoryContextStore::new()));
+    }
+
+    /// Tests that after a restart, a `VersionedContextStore` refuses to load a context older than
+    /// the highest version it saved before the restart, and accepts the latest context.
+    #[test]
+    fn test_versioned_reject_regressed_context() {
+        let algorithm = CoordinatorAlgorithm::new(SystemTimeSource::new());
+        let store = VersionedContextStore::new(MemoryContextStore::new());
+
+        let first = voting_context(&algorithm);
+        store.save(&first).expect("failed to save");
+        let mut second = first.clone();
+        second.set_alarm(Some(SystemTime::UNIX_EPOCH));
+        store.save(&second).expect("failed to save");
+        let highest = store
+            .highest()
+            .expect("failed to get highest version")
+            .expect("no version observed");
+        assert_eq!(highest, 1);
+
+        // The wrapped store returns the first context, as if the second save was lost
+        let inner = store.into_inner();
+        inner
+            .save(&Versioned::new(0, first))
+            .expect("failed to save");
+        let restarted = VersionedContextStore::with_highest(inner, highest);
+        let err = restarted.load().expect_err("regressed context was loaded");
+        assert_eq!(
+            err.to_string(),
+            "refusing to load stale version 0, version 1 has already been observed"
+        );
+
+        let inner = restarted.into_inner();
+        inner
+            .save(&Versioned::new(1, second.clone()))
+            .expect("failed to save");
+        let restarted = VersionedContextStore::with_highest(inner, highest);
+        assert_eq!(restarted.load().expect("failed to load"), Some(second));
+    }
+
+    /// Tests that a coordinator context saved to a `FileContextStore` is loaded back by a new
+    /// store for the same file, as it would be after a restart, and can be resumed from.
+    #[cfg(feature = "storage-file")]
+    #[test]
+    fn test_file_save_load_resume() {
+        let path =
+            std::env::temp_dir().join(format!("augrim-{}-context-store", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        save_load_resume(&FileContextStore::new(&path));
+
+        let store: FileContextStore<TestContext> = FileContextStore::new(&path);
+        assert!(store.load().expect("failed to load").is_some());
+        assert!(!store.temp_path().exists());
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A file-backed store of records.
+//!
+//! Each record is appended to the file prefixed with its length, as a little-endian `u64`. A
+//! record which was only partially written when the process stopped is discarded when the store
+//! is opened again.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::InternalError;
+
+const LENGTH_PREFIX_SIZE: usize = 8;
+
+/// How far [`FileStore::append`] pushes a record before returning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DurabilityMode {
+    /// The record is flushed to the operating system, which survives the process crashing but
+    /// not a power loss. [`FileStore::sync`] must be called to make it durable.
+    BufferedFlush,
+    /// The record is flushed and the file is synced to disk before `append` returns.
+    Fsync,
+}
+
+/// A store which appends records to a file.
+pub struct FileStore {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    mode: DurabilityMode,
+}
+
+impl FileStore {
+    /// Opens the store at `path`, creating the file if it does not exist. Records already in the
+    /// file are kept, except for a trailing record which was only partially written.
+    pub fn open<T: AsRef<Path>>(path: T, mode: DurabilityMode) -> Result<Self, InternalError> {
+        let path = path.as_ref().to_path_buf();
+        let open_err = |err| {
+            InternalError::from_source_with_prefix(
+                Box::new(err),
+                format!("unable to open store {}", path.display()),
+            )
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .map_err(open_err)?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(open_err)?;
+        let (_, complete_len) = parse_records(&bytes);
+        if complete_len < bytes.len() {
+            warn!(
+                "discarding partially-written record at the end of store {}",
+                path.display()
+            );
+            file.set_len(complete_len as u64)
+                .and_then(|_| file.sync_data())
+                .map_err(open_err)?;
+        }
+
+        Ok(FileStore {
+            path,
+            writer: BufWriter::new(file),
+            mode,
+        })
+    }
+
+    /// Returns how far each append pushes a record before returning.
+    pub fn mode(&self) -> DurabilityMode {
+        self.mode
+    }
+
+    /// Appends `record` to the store, flushing it and, in [`DurabilityMode::Fsync`] mode,
+    /// syncing it to disk.
+    pub fn append(&mut self, record: &[u8]) -> Result<(), InternalError> {
+        self.writer
+            .write_all(&(record.len() as u64).to_le_bytes())
+            .and_then(|_| self.writer.write_all(record))
+            .and_then(|_| self.writer.flush())
+            .map_err(|err| {
+                InternalError::from_source_with_prefix(
+                    Box::new(err),
+                    "unable to append to store".into(),
+                )
+            })?;
+
+        match self.mode {
+            DurabilityMode::BufferedFlush => Ok(()),
+            DurabilityMode::Fsync => self.sync(),
+        }
+    }
+
+    /// Syncs every record appended so far to disk, so that it survives a power loss.
+    pub fn sync(&mut self) -> Result<(), InternalError> {
+        self.writer
+            .flush()
+            .and_then(|_| self.writer.get_ref().sync_data())
+            .map_err(|err| {
+                InternalError::from_source_with_prefix(Box::new(err), "unable to sync store".into())
+            })
+    }
+
+    /// Reads back every complete record in the store, in the order they were appended.
+    pub fn records(&self) -> Result<Vec<Vec<u8>>, InternalError> {
+        let mut bytes = Vec::new();
+        File::open(&self.path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|err| {
+                InternalError::from_source_with_prefix(
+                    Box::new(err),
+                    format!("unable to read store {}", self.path.display()),
+                )
+            })?;
+
+        Ok(parse_records(&bytes).0)
+    }
+}
+
+/// Parses the complete records in `bytes`, returning them along with the number of bytes they
+/// take up.
+fn parse_records(bytes: &[u8]) -> (Vec<Vec<u8>>, usize) {
+    let mut records = Vec::new();
+    let mut remaining = bytes;
+    while remaining.len() >= LENGTH_PREFIX_SIZE {
+        let (prefix, rest) = remaining.split_at(LENGTH_PREFIX_SIZE);
+        let mut length = [0; LENGTH_PREFIX_SIZE];
+        length.copy_from_slice(prefix);
+        let length = u64::from_le_bytes(length) as usize;
+        if rest.len() < length {
+            break;
+        }
+        let (record, rest) = rest.split_at(length);
+        records.push(record.to_vec());
+        remaining = rest;
+    }
+
+    (records, bytes.len() - remaining.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    /// A path in the temporary directory which is removed when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("augrim-{}-{}", std::process::id(), name));
+            let _ = fs::remove_file(&path);
+            TempPath(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// Tests that records appended and synced are read back by a freshly-opened store, as they
+    /// would be after a crash and restart, in both durability modes.
+    #[test]
+    fn test_records_survive_reopen() {
+        for (name, mode) in [
+            ("buffered", DurabilityMode::BufferedFlush),
+            ("fsync", DurabilityMode::Fsync),
+        ] {
+            let path = TempPath::new(&format!("reopen-{}", name));
+
+            let mut store = FileStore::open(&path.0, mode).unwrap();
+            store.append(b"first").unwrap();
+            store.append(b"").unwrap();
+            store.append(b"second").unwrap();
+            store.sync().unwrap();
+            drop(store);
+
+            let mut store = FileStore::open(&path.0, mode).unwrap();
+            assert_eq!(
+                store.records().unwrap(),
+                vec![b"first".to_vec(), vec![], b"second".to_vec()]
+            );
+
+            store.append(b"third").unwrap();
+            assert_eq!(store.records().unwrap().len(), 4);
+        }
+    }
+
+    /// Tests that a record which was only partially written is discarded when the store is
+    /// reopened, so the records before it are read back and later records are appended after them.
+    #[test]
+    fn test_ignore_partial_record() {
+        let path = TempPath::new("partial");
+
+        let mut store = FileStore::open(&path.0, DurabilityMode::Fsync).unwrap();
+        store.append(b"complete").unwrap();
+        drop(store);
+
+        let mut file = OpenOptions::new().append(true).open(&path.0).unwrap();
+        file.write_all(&10u64.to_le_bytes()).unwrap();
+        file.write_all(b"cut").unwrap();
+        drop(file);
+
+        let mut store = FileStore::open(&path.0, DurabilityMode::Fsync).unwrap();
+        assert_eq!(store.records().unwrap(), vec![b"complete".to_vec()]);
+
+        store.append(b"next").unwrap();
+        assert_eq!(
+            store.records().unwrap(),
+            vec![b"complete".to_vec(), b"next".to_vec()]
+        );
+    }
+}
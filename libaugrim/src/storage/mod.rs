@@ -0,0 +1,28 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Durable storage, which lets a process recover its state after a restart.
+
+mod context;
+#[cfg(feature = "storage-file")]
+mod file;
+mod version;
+
+#[cfg(feature = "storage-file")]
+pub use context::FileContextStore;
+pub use context::{ContextStore, MemoryContextStore, VersionedContextStore};
+
+#[cfg(feature = "storage-file")]
+pub use file::{DurabilityMode, FileStore};
+pub use version::{VersionTracker, Versioned};
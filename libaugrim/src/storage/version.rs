@@ -0,0 +1,145 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Version stamps, which detect stale snapshots when state is loaded.
+
+use crate::error::InvalidStateError;
+
+/// A value stamped with the version it was saved at.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Versioned<T> {
+    version: u64,
+    value: T,
+}
+
+impl<T> Versioned<T> {
+    /// Constructs the value stamped with the given version.
+    pub fn new(version: u64, value: T) -> Self {
+        Versioned { version, value }
+    }
+
+    /// Returns the version the value was saved at.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the value.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns the value, consuming the stamp.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+/// Assigns increasing versions to saved values, and rejects loaded values older than any version
+/// already observed.
+///
+/// After a restart with partial writes, a store may return a snapshot older than state which was
+/// already applied; loading it would silently roll that state back. The highest version observed
+/// should itself be kept durably, for example alongside the process's configuration, and passed
+/// to [`VersionTracker::with_highest`] on restart.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VersionTracker {
+    highest: Option<u64>,
+}
+
+impl VersionTracker {
+    /// Constructs a new `VersionTracker` which has not observed any version.
+    pub fn new() -> Self {
+        VersionTracker { highest: None }
+    }
+
+    /// Constructs a new `VersionTracker` which has already observed `highest`.
+    pub fn with_highest(highest: u64) -> Self {
+        VersionTracker {
+            highest: Some(highest),
+        }
+    }
+
+    /// Returns the highest version observed, if any.
+    pub fn highest(&self) -> Option<u64> {
+        self.highest
+    }
+
+    /// Stamps `value` with the version after the highest observed, which it becomes.
+    pub fn stamp<T>(&mut self, value: T) -> Versioned<T> {
+        let version = self.highest.map(|highest| highest + 1).unwrap_or(0);
+        self.highest = Some(version);
+        Versioned::new(version, value)
+    }
+
+    /// Accepts a loaded value, returning it, if its version is not older than the highest
+    /// observed; its version becomes the highest observed if it is newer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if the value is older than a version already observed.
+    pub fn load<T>(&mut self, versioned: Versioned<T>) -> Result<T, InvalidStateError> {
+        match self.highest {
+            Some(highest) if versioned.version < highest => {
+                Err(InvalidStateError::with_message(format!(
+                    "refusing to load stale version {}, version {} has already been observed",
+                    versioned.version, highest
+                )))
+            }
+            _ => {
+                self.highest = Some(versioned.version);
+                Ok(versioned.into_value())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that after saving v1 and v2 and loading v2, a load which returns v1 is rejected,
+    /// while loading v2 again is accepted.
+    #[test]
+    fn test_reject_stale_version() {
+        let mut tracker = VersionTracker::new();
+        let v1 = tracker.stamp("v1");
+        let v2 = tracker.stamp("v2");
+        assert!(v1.version() < v2.version());
+
+        let mut restarted = VersionTracker::new();
+        assert_eq!(restarted.load(v2.clone()).unwrap(), "v2");
+
+        let err = restarted
+            .load(v1.clone())
+            .expect_err("stale version was loaded");
+        assert_eq!(
+            err.to_string(),
+            "refusing to load stale version 0, version 1 has already been observed"
+        );
+
+        assert_eq!(restarted.load(v2).unwrap(), "v2");
+        assert!(VersionTracker::with_highest(1).load(v1).is_err());
+    }
+
+    /// Tests that stamps continue from the highest version loaded.
+    #[test]
+    fn test_stamp_after_load() {
+        let mut tracker = VersionTracker::new();
+        tracker.load(Versioned::new(5, ())).unwrap();
+
+        assert_eq!(tracker.stamp(()).version(), 6);
+        assert_eq!(tracker.highest(), Some(6));
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Abstractions over time.
+//!
+//! Algorithms which need to measure time, such as to set alarms, are generic over the [`Time`]
+//! trait and obtain the current time from a [`TimeSource`]. This allows a logical or mock clock
+//! to be used in place of the system clock.
+
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// A point in time.
+pub trait Time: Clone + Ord {
+    /// Returns the point in time which is `duration` after this one.
+    fn add(&self, duration: Duration) -> Self;
+
+    /// Returns the amount of time elapsed from `earlier` to this point in time, or a zero
+    /// duration if `earlier` is later than this point in time.
+    fn duration_since(&self, earlier: &Self) -> Duration;
+}
+
+/// A source of the current time.
+pub trait TimeSource {
+    /// The type of the points in time returned by the source.
+    type Time: Time;
+
+    /// Returns the current time.
+    fn now(&self) -> Self::Time;
+}
+
+impl Time for SystemTime {
+    fn add(&self, duration: Duration) -> Self {
+        *self + duration
+    }
+
+    fn duration_since(&self, earlier: &Self) -> Duration {
+        SystemTime::duration_since(self, *earlier).unwrap_or_else(|_| Duration::from_secs(0))
+    }
+}
+
+/// A [`TimeSource`] backed by the system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemTimeSource;
+
+impl SystemTimeSource {
+    /// Constructs a new `SystemTimeSource`, which reads the current time from the system clock.
+    pub fn new() -> Self {
+        SystemTimeSource
+    }
+}
+
+impl TimeSource for SystemTimeSource {
+    type Time = SystemTime;
+
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A [`TimeSource`] which only moves when told to, for deterministic tests.
+///
+/// The clock starts at the Unix epoch. Clones share the same underlying time, so a clock can be
+/// given to an algorithm while the test retains a clone to advance it.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+pub(crate) struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub(crate) fn new() -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(SystemTime::UNIX_EPOCH)),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub(crate) fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().expect("mock clock lock poisoned");
+        *now += duration;
+    }
+
+    /// Sets the clock to `instant`, which may be earlier than the current time.
+    pub(crate) fn set(&self, instant: SystemTime) {
+        *self.now.lock().expect("mock clock lock poisoned") = instant;
+    }
+}
+
+#[cfg(test)]
+impl TimeSource for MockClock {
+    type Time = SystemTime;
+
+    fn now(&self) -> SystemTime {
+        *self.now.lock().expect("mock clock lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that adding a duration to a `SystemTime` produces a later time, and that
+    /// `duration_since` measures the difference in either direction.
+    #[test]
+    fn test_system_time_add() {
+        let now = SystemTimeSource::new().now();
+        let later = Time::add(&now, Duration::from_secs(5));
+
+        assert!(later > now);
+        assert_eq!(Time::duration_since(&later, &now), Duration::from_secs(5));
+        assert_eq!(Time::duration_since(&now, &later), Duration::from_secs(0));
+    }
+
+    /// Tests that a `MockClock` only moves when advanced or set, and that clones share the same
+    /// time.
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new();
+        let clone = clock.clone();
+        let start = clock.now();
+
+        assert_eq!(clock.now(), start);
+
+        clone.advance(Duration::from_secs(3));
+        assert_eq!(
+            Time::duration_since(&clock.now(), &start),
+            Duration::from_secs(3)
+        );
+
+        clock.set(start);
+        assert_eq!(clone.now(), start);
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::{ContextUpdate, TraceId};
+
+use super::super::{Epoch, TwoPhaseCommitMessage};
+use super::CoordinatorContext;
+
+/// A notification of a change in the progress of the coordinator, for observability.
+///
+/// Notifications are returned after the messages which carry out the change:
+///
+/// - `Committed` once the coordinator enters the `Commit` state, when every participant has voted
+///   to commit.
+/// - `Aborted` once the coordinator enters the `Abort` state, when a participant has voted to
+///   abort or the alarm has expired while voting.
+/// - `RequestForStart` after either of them, since the coordinator is then waiting for a `Start`
+///   event to begin the next epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CoordinatorActionNotification {
+    /// The epoch was aborted, along with the epoch's trace id.
+    Aborted(Epoch, Option<TraceId>),
+    /// The epoch was committed, along with the epoch's trace id.
+    Committed(Epoch, Option<TraceId>),
+    /// The coordinator is ready to start the next epoch.
+    RequestForStart,
+}
+
+/// An action returned by the two-phase commit coordinator, to be performed by the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CoordinatorAction<P, V, T> {
+    /// Report the notification to the application.
+    Notify(CoordinatorActionNotification),
+    /// Send the message to the process.
+    SendMessage(P, TwoPhaseCommitMessage<V>),
+    /// Replace the stored context with this one.
+    UpdateContext(CoordinatorContext<P, T>),
+}
+
+impl<P, V, T> ContextUpdate for CoordinatorAction<P, V, T> {
+    fn is_context_update(&self) -> bool {
+        matches!(self, CoordinatorAction::UpdateContext(_))
+    }
+}
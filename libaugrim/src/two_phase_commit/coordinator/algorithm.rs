@@ -0,0 +1,1003 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use crate::algorithm::{normalize_actions, Algorithm, TraceId, Value};
+use crate::error::InternalError;
+use crate::process::Process;
+use crate::time::TimeSource;
+
+use super::super::{Epoch, TwoPhaseCommitMessage};
+use super::{CoordinatorAction, CoordinatorActionNotification, CoordinatorContext};
+use super::{CoordinatorEvent, CoordinatorMessage, CoordinatorState, Participant};
+
+/// The two-phase commit coordinator algorithm.
+///
+/// A `Start` event begins voting on a value: the coordinator requests a vote from every
+/// participant and waits for their responses. The epoch is committed if every participant votes
+/// to commit, and aborted as soon as any participant votes to abort. Either way, the decision is
+/// sent to every participant. A `Start` event received after a decision begins the next epoch.
+///
+/// The caller may set an alarm in the context, and deliver an `Alarm` event once it expires, as
+/// measured by the `time_source`; an `Alarm` event delivered before then is ignored. If the
+/// coordinator is still voting, the epoch is aborted. A vote received for an epoch which has
+/// already been decided is answered with the decision, so a participant which missed the decision
+/// can ask for it again. For an epoch before the current one, the decision is taken from the last
+/// committed epoch: a participant still waiting on an earlier epoch cannot have voted since, so
+/// no later epoch committed without it.
+///
+/// After a restart, the coordinator is resumed by delivering a `Recover` event along with its last
+/// persisted context. Since the context does not hold the value, the event carries it. A
+/// coordinator which was voting requests a vote again from each participant which has not yet
+/// responded, and one which had decided sends its decision to every participant again, as the
+/// decision may not have been sent before the restart.
+///
+/// The trace id given with a `Start` event is kept in the context for the epoch, and carried on
+/// every vote request and decision sent in it, as well as on the decision's notification. A
+/// decision sent again for an earlier epoch carries the trace id of the vote which asked for it.
+///
+/// Each decision is also reported with a [`CoordinatorActionNotification`]; see its
+/// documentation for which changes of state produce which notifications.
+pub struct CoordinatorAlgorithm<P, V, S> {
+    time_source: S,
+    _process: PhantomData<P>,
+    _value: PhantomData<V>,
+}
+
+impl<P, V, S> CoordinatorAlgorithm<P, V, S>
+where
+    P: Process,
+    V: Value,
+    S: TimeSource,
+{
+    /// Constructs a new `CoordinatorAlgorithm` which checks alarms against `time_source`.
+    pub fn new(time_source: S) -> Self {
+        CoordinatorAlgorithm {
+            time_source,
+            _process: PhantomData,
+            _value: PhantomData,
+        }
+    }
+
+    fn handle_start(
+        &self,
+        value: V,
+        trace_id: Option<TraceId>,
+        mut context: CoordinatorContext<P, S::Time>,
+    ) -> Result<Vec<CoordinatorAction<P, V, S::Time>>, InternalError> {
+        match context.state() {
+            CoordinatorState::WaitingForStart => (),
+            CoordinatorState::Commit | CoordinatorState::Abort => {
+                let epoch = *context.epoch() + 1;
+                context.set_epoch(epoch);
+            }
+            CoordinatorState::Voting => {
+                return Err(InternalError::with_message(format!(
+                    "unable to start, epoch {} is still voting",
+                    context.epoch()
+                )))
+            }
+        }
+
+        context
+            .try_transition(CoordinatorState::Voting)
+            .map_err(|err| InternalError::from_source(Box::new(err)))?;
+        for participant in context.participants_mut().iter_mut() {
+            participant.set_vote(None);
+        }
+        context.set_trace_id(trace_id);
+
+        let epoch = *context.epoch();
+        let mut actions = send_to_all(
+            context.participants(),
+            TwoPhaseCommitMessage::VoteRequest(epoch, value, context.trace_id().clone()),
+        );
+
+        actions.insert(0, CoordinatorAction::UpdateContext(context));
+        Ok(actions)
+    }
+
+    fn handle_recover(
+        &self,
+        value: V,
+        context: CoordinatorContext<P, S::Time>,
+    ) -> Result<Vec<CoordinatorAction<P, V, S::Time>>, InternalError> {
+        let epoch = *context.epoch();
+        let trace_id = context.trace_id().clone();
+        let actions = match context.state() {
+            CoordinatorState::Voting => context
+                .participants()
+                .iter()
+                .filter(|participant| participant.vote().is_none())
+                .map(|participant| {
+                    CoordinatorAction::SendMessage(
+                        *participant.process(),
+                        TwoPhaseCommitMessage::VoteRequest(epoch, value.clone(), trace_id.clone()),
+                    )
+                })
+                .collect(),
+            CoordinatorState::Commit => send_to_all(
+                context.participants(),
+                TwoPhaseCommitMessage::Commit(epoch, trace_id),
+            ),
+            CoordinatorState::Abort => send_to_all(
+                context.participants(),
+                TwoPhaseCommitMessage::Abort(epoch, trace_id),
+            ),
+            CoordinatorState::WaitingForStart => vec![],
+        };
+
+        Ok(actions)
+    }
+
+    fn handle_vote_response(
+        &self,
+        process: P,
+        epoch: Epoch,
+        vote: bool,
+        trace_id: Option<TraceId>,
+        mut context: CoordinatorContext<P, S::Time>,
+    ) -> Result<Vec<CoordinatorAction<P, V, S::Time>>, InternalError> {
+        match context.state() {
+            CoordinatorState::Voting if epoch == *context.epoch() => (),
+            CoordinatorState::Commit | CoordinatorState::Abort if epoch == *context.epoch() => {
+                // The participant did not receive the decision; send it again
+                let commit = context.state() == &CoordinatorState::Commit;
+                let trace_id = context.trace_id().clone();
+                return Ok(resend_decision(process, epoch, commit, trace_id, &context));
+            }
+            _ if epoch < *context.epoch() => {
+                // The participant did not receive the decision of an earlier epoch
+                let commit = matches!(context.last_commit_epoch(), Some(last) if epoch <= *last);
+                return Ok(resend_decision(process, epoch, commit, trace_id, &context));
+            }
+            _ => {
+                debug!(
+                    "ignoring vote response for epoch {} (current epoch is {})",
+                    epoch,
+                    context.epoch()
+                );
+                return Ok(vec![]);
+            }
+        }
+
+        match context
+            .participants_mut()
+            .iter_mut()
+            .find(|participant| participant.process() == &process)
+        {
+            Some(participant) => participant.set_vote(Some(vote)),
+            None => {
+                debug!("ignoring vote response from a process which is not a participant");
+                return Ok(vec![]);
+            }
+        }
+
+        let decision = if !vote {
+            Some(false)
+        } else if context
+            .participants()
+            .iter()
+            .all(|participant| participant.vote() == &Some(true))
+        {
+            Some(true)
+        } else {
+            None
+        };
+
+        match decision {
+            Some(commit) => self.decide(commit, context),
+            None => Ok(vec![CoordinatorAction::UpdateContext(context)]),
+        }
+    }
+
+    fn handle_alarm(
+        &self,
+        mut context: CoordinatorContext<P, S::Time>,
+    ) -> Result<Vec<CoordinatorAction<P, V, S::Time>>, InternalError> {
+        match context.alarm() {
+            Some(alarm) if self.time_source.now() < *alarm => {
+                debug!("ignoring alarm, it has not expired");
+                return Ok(vec![]);
+            }
+            Some(_) => (),
+            None => {
+                debug!("ignoring alarm, no alarm is set");
+                return Ok(vec![]);
+            }
+        }
+
+        context.set_alarm(None);
+        match context.state() {
+            CoordinatorState::Voting => {
+                debug!(
+                    "aborting epoch {}, the alarm expired before every vote was received",
+                    context.epoch()
+                );
+                self.decide(false, context)
+            }
+            _ => Ok(vec![CoordinatorAction::UpdateContext(context)]),
+        }
+    }
+
+    /// Commits or aborts the current epoch, sending the decision to every participant.
+    fn decide(
+        &self,
+        commit: bool,
+        mut context: CoordinatorContext<P, S::Time>,
+    ) -> Result<Vec<CoordinatorAction<P, V, S::Time>>, InternalError> {
+        let epoch = *context.epoch();
+        context.set_alarm(None);
+        context
+            .try_transition(if commit {
+                CoordinatorState::Commit
+            } else {
+                CoordinatorState::Abort
+            })
+            .map_err(|err| InternalError::from_source(Box::new(err)))?;
+
+        let trace_id = context.trace_id().clone();
+        let mut actions = if commit {
+            context.set_last_commit_epoch(Some(epoch));
+            let mut actions = send_to_all(
+                context.participants(),
+                TwoPhaseCommitMessage::Commit(epoch, trace_id.clone()),
+            );
+            actions.push(CoordinatorAction::Notify(
+                CoordinatorActionNotification::Committed(epoch, trace_id),
+            ));
+            actions
+        } else {
+            let mut actions = send_to_all(
+                context.participants(),
+                TwoPhaseCommitMessage::Abort(epoch, trace_id.clone()),
+            );
+            actions.push(CoordinatorAction::Notify(
+                CoordinatorActionNotification::Aborted(epoch, trace_id),
+            ));
+            actions
+        };
+        actions.push(CoordinatorAction::Notify(
+            CoordinatorActionNotification::RequestForStart,
+        ));
+
+        actions.insert(0, CoordinatorAction::UpdateContext(context));
+        Ok(actions)
+    }
+}
+
+impl<P, V, S> Default for CoordinatorAlgorithm<P, V, S>
+where
+    P: Process,
+    V: Value,
+    S: TimeSource + Default,
+{
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<P, V, S> Algorithm<P> for CoordinatorAlgorithm<P, V, S>
+where
+    P: Process,
+    V: Value,
+    S: TimeSource,
+{
+    type Event = CoordinatorEvent<P, V>;
+    type Action = CoordinatorAction<P, V, S::Time>;
+    type Context = CoordinatorContext<P, S::Time>;
+
+    fn event(
+        &self,
+        event: Self::Event,
+        context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError> {
+        let actions = match event {
+            CoordinatorEvent::Alarm => self.handle_alarm(context),
+            CoordinatorEvent::Deliver(
+                process,
+                CoordinatorMessage::VoteResponse(epoch, vote, trace_id),
+            ) => self.handle_vote_response(process, epoch, vote, trace_id, context),
+            CoordinatorEvent::Recover(value) => self.handle_recover(value, context),
+            CoordinatorEvent::Start(value, trace_id) => self.handle_start(value, trace_id, context),
+        }?;
+
+        Ok(normalize_actions(actions))
+    }
+}
+
+/// Sends the decision of `epoch` to `process` again, with `trace_id`, if it is a participant.
+fn resend_decision<P, V, T>(
+    process: P,
+    epoch: Epoch,
+    commit: bool,
+    trace_id: Option<TraceId>,
+    context: &CoordinatorContext<P, T>,
+) -> Vec<CoordinatorAction<P, V, T>>
+where
+    P: Process,
+{
+    if !context
+        .participants()
+        .iter()
+        .any(|participant| participant.process() == &process)
+    {
+        debug!("ignoring vote response from a process which is not a participant");
+        return vec![];
+    }
+
+    let message = if commit {
+        TwoPhaseCommitMessage::Commit(epoch, trace_id)
+    } else {
+        TwoPhaseCommitMessage::Abort(epoch, trace_id)
+    };
+    vec![CoordinatorAction::SendMessage(process, message)]
+}
+
+fn send_to_all<P, V, T>(
+    participants: &[Participant<P>],
+    message: TwoPhaseCommitMessage<V>,
+) -> Vec<CoordinatorAction<P, V, T>>
+where
+    P: Process,
+    V: Clone,
+{
+    participants
+        .iter()
+        .map(|participant| CoordinatorAction::SendMessage(*participant.process(), message.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+    use std::time::SystemTime;
+
+    use crate::time::MockClock;
+    use crate::two_phase_commit::TwoPhaseCommitContextBuilder;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestValue(&'static str);
+
+    impl Value for TestValue {}
+
+    type TestAction = CoordinatorAction<TestProcess, TestValue, SystemTime>;
+
+    fn new_context(
+        coordinator: TestProcess,
+        participants: &[TestProcess],
+    ) -> CoordinatorContext<TestProcess, SystemTime> {
+        CoordinatorContext::try_from(
+            TwoPhaseCommitContextBuilder::new()
+                .with_coordinator(coordinator)
+                .with_this_process(coordinator)
+                .with_participants(participants.iter().copied().map(Participant::new).collect())
+                .with_require_coordinator_in_participants(false)
+                .build()
+                .expect("failed to build context"),
+        )
+        .expect("failed to convert context")
+    }
+
+    /// Returns the context from the `UpdateContext` action, which must be the first action.
+    fn updated_context(actions: &[TestAction]) -> CoordinatorContext<TestProcess, SystemTime> {
+        match actions.first() {
+            Some(CoordinatorAction::UpdateContext(context)) => context.clone(),
+            _ => panic!("first action was not UpdateContext: {:?}", actions),
+        }
+    }
+
+    /// Starts voting on a value with a coordinator and two participants, returning the updated
+    /// context.
+    fn start(
+        algorithm: &CoordinatorAlgorithm<TestProcess, TestValue, MockClock>,
+    ) -> CoordinatorContext<TestProcess, SystemTime> {
+        let coordinator = TestProcess { id: 0 };
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Start(TestValue("value"), None),
+                new_context(coordinator, &[p1, p2]),
+            )
+            .expect("failed to start");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                CoordinatorAction::SendMessage(
+                    p1,
+                    TwoPhaseCommitMessage::VoteRequest(0, TestValue("value"), None)
+                ),
+                CoordinatorAction::SendMessage(
+                    p2,
+                    TwoPhaseCommitMessage::VoteRequest(0, TestValue("value"), None)
+                ),
+            ]
+        );
+
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Voting);
+        context
+    }
+
+    /// Tests that when every participant votes to commit, the coordinator commits the epoch and
+    /// sends the commit to every participant.
+    #[test]
+    fn test_all_participants_vote_yes() {
+        let algorithm = CoordinatorAlgorithm::new(MockClock::new());
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let context = start(&algorithm);
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p1, CoordinatorMessage::VoteResponse(0, true, None)),
+                context,
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(actions.len(), 1);
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Voting);
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p2, CoordinatorMessage::VoteResponse(0, true, None)),
+                context,
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                CoordinatorAction::SendMessage(p1, TwoPhaseCommitMessage::Commit(0, None)),
+                CoordinatorAction::SendMessage(p2, TwoPhaseCommitMessage::Commit(0, None)),
+                CoordinatorAction::Notify(CoordinatorActionNotification::Committed(0, None)),
+                CoordinatorAction::Notify(CoordinatorActionNotification::RequestForStart),
+            ]
+        );
+
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Commit);
+        assert_eq!(context.last_commit_epoch(), &Some(0));
+    }
+
+    /// Tests that the trace id given with `Start` is carried on the vote requests, the commit
+    /// messages and the `Committed` notification, is replaced when the next epoch starts, and
+    /// that a decision sent again for an earlier epoch carries the trace id of the vote.
+    #[test]
+    fn test_trace_id() {
+        let algorithm = CoordinatorAlgorithm::new(MockClock::new());
+        let coordinator = TestProcess { id: 0 };
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let trace_id = Some(TraceId::new("trace-1"));
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Start(TestValue("value"), trace_id.clone()),
+                new_context(coordinator, &[p1, p2]),
+            )
+            .expect("failed to start");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                CoordinatorAction::SendMessage(
+                    p1,
+                    TwoPhaseCommitMessage::VoteRequest(0, TestValue("value"), trace_id.clone())
+                ),
+                CoordinatorAction::SendMessage(
+                    p2,
+                    TwoPhaseCommitMessage::VoteRequest(0, TestValue("value"), trace_id.clone())
+                ),
+            ]
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.trace_id(), &trace_id);
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(
+                    p1,
+                    CoordinatorMessage::VoteResponse(0, true, trace_id.clone()),
+                ),
+                context,
+            )
+            .expect("failed to deliver vote");
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(
+                    p2,
+                    CoordinatorMessage::VoteResponse(0, true, trace_id.clone()),
+                ),
+                updated_context(&actions),
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                CoordinatorAction::SendMessage(
+                    p1,
+                    TwoPhaseCommitMessage::Commit(0, trace_id.clone())
+                ),
+                CoordinatorAction::SendMessage(
+                    p2,
+                    TwoPhaseCommitMessage::Commit(0, trace_id.clone())
+                ),
+                CoordinatorAction::Notify(CoordinatorActionNotification::Committed(
+                    0,
+                    trace_id.clone()
+                )),
+                CoordinatorAction::Notify(CoordinatorActionNotification::RequestForStart),
+            ]
+        );
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Start(TestValue("value"), None),
+                updated_context(&actions),
+            )
+            .expect("failed to start");
+        let context = updated_context(&actions);
+        assert_eq!(context.trace_id(), &None);
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(
+                    p1,
+                    CoordinatorMessage::VoteResponse(0, true, trace_id.clone()),
+                ),
+                context,
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(
+            actions,
+            vec![CoordinatorAction::SendMessage(
+                p1,
+                TwoPhaseCommitMessage::Commit(0, trace_id)
+            )]
+        );
+    }
+
+    /// Tests that when a participant votes to abort, the coordinator aborts the epoch without
+    /// waiting for the remaining votes.
+    #[test]
+    fn test_one_participant_votes_no() {
+        let algorithm = CoordinatorAlgorithm::new(MockClock::new());
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let context = start(&algorithm);
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p2, CoordinatorMessage::VoteResponse(0, false, None)),
+                context,
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                CoordinatorAction::SendMessage(p1, TwoPhaseCommitMessage::Abort(0, None)),
+                CoordinatorAction::SendMessage(p2, TwoPhaseCommitMessage::Abort(0, None)),
+                CoordinatorAction::Notify(CoordinatorActionNotification::Aborted(0, None)),
+                CoordinatorAction::Notify(CoordinatorActionNotification::RequestForStart),
+            ]
+        );
+
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Abort);
+        assert_eq!(context.last_commit_epoch(), &None);
+
+        // A late vote for the aborted epoch is answered with the decision
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p1, CoordinatorMessage::VoteResponse(0, true, None)),
+                context.clone(),
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(
+            actions,
+            vec![CoordinatorAction::SendMessage(
+                p1,
+                TwoPhaseCommitMessage::Abort(0, None)
+            )]
+        );
+
+        // Starting again moves to the next epoch
+        let actions = algorithm
+            .event(CoordinatorEvent::Start(TestValue("next"), None), context)
+            .expect("failed to start");
+        assert_eq!(updated_context(&actions).epoch(), &1);
+    }
+
+    /// Tests that running the coordinator to a commit, including a duplicate vote after the
+    /// decision, reports the `Committed` notification exactly once.
+    #[test]
+    fn test_committed_notified_once() {
+        let algorithm = CoordinatorAlgorithm::new(MockClock::new());
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let mut context = start(&algorithm);
+        let mut all_actions = Vec::new();
+        for process in [p1, p2, p2] {
+            let actions = algorithm
+                .event(
+                    CoordinatorEvent::Deliver(
+                        process,
+                        CoordinatorMessage::VoteResponse(0, true, None),
+                    ),
+                    context.clone(),
+                )
+                .expect("failed to deliver vote");
+            if let Some(CoordinatorAction::UpdateContext(updated)) = actions.first() {
+                context = updated.clone();
+            }
+            all_actions.extend(actions);
+        }
+
+        let committed = all_actions
+            .iter()
+            .filter(|action| {
+                **action
+                    == CoordinatorAction::Notify(CoordinatorActionNotification::Committed(0, None))
+            })
+            .count();
+        assert_eq!(committed, 1);
+        assert!(!all_actions.iter().any(|action| matches!(
+            action,
+            CoordinatorAction::Notify(CoordinatorActionNotification::Aborted(_, None))
+        )));
+    }
+
+    /// Tests that a coordinator which is still voting ignores its alarm until it expires, as
+    /// measured by a mock clock, and then aborts the epoch and clears the alarm.
+    #[test]
+    fn test_alarm_aborts_voting() {
+        use std::time::Duration;
+
+        use crate::time::TimeSource;
+
+        let clock = MockClock::new();
+        let algorithm = CoordinatorAlgorithm::new(clock.clone());
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let mut context = start(&algorithm);
+        let alarm = clock.now() + Duration::from_secs(10);
+        context.set_alarm(Some(alarm));
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p1, CoordinatorMessage::VoteResponse(0, true, None)),
+                context,
+            )
+            .expect("failed to deliver vote");
+        let context = updated_context(&actions);
+        assert_eq!(context.alarm(), &Some(alarm));
+
+        // An alarm delivered before it expires is ignored
+        clock.advance(Duration::from_secs(5));
+        let actions = algorithm
+            .event(CoordinatorEvent::Alarm, context.clone())
+            .expect("failed to handle alarm");
+        assert!(actions.is_empty());
+
+        clock.advance(Duration::from_secs(6));
+        let actions = algorithm
+            .event(CoordinatorEvent::Alarm, context)
+            .expect("failed to handle alarm");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                CoordinatorAction::SendMessage(p1, TwoPhaseCommitMessage::Abort(0, None)),
+                CoordinatorAction::SendMessage(p2, TwoPhaseCommitMessage::Abort(0, None)),
+                CoordinatorAction::Notify(CoordinatorActionNotification::Aborted(0, None)),
+                CoordinatorAction::Notify(CoordinatorActionNotification::RequestForStart),
+            ]
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Abort);
+        assert_eq!(context.alarm(), &None);
+
+        // The vote arriving after the timeout is answered with the abort
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p2, CoordinatorMessage::VoteResponse(0, true, None)),
+                context.clone(),
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(
+            actions,
+            vec![CoordinatorAction::SendMessage(
+                p2,
+                TwoPhaseCommitMessage::Abort(0, None)
+            )]
+        );
+
+        // Without an alarm set, an alarm event is ignored
+        let actions = algorithm
+            .event(CoordinatorEvent::Alarm, context)
+            .expect("failed to handle alarm");
+        assert!(actions.is_empty());
+    }
+
+    /// Tests that an alarm which expires after the epoch was committed leaves the decision
+    /// unchanged.
+    #[test]
+    fn test_alarm_after_commit() {
+        let algorithm = CoordinatorAlgorithm::new(MockClock::new());
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let mut context = start(&algorithm);
+        context.set_alarm(Some(SystemTime::UNIX_EPOCH));
+        for process in [p1, p2] {
+            let actions = algorithm
+                .event(
+                    CoordinatorEvent::Deliver(
+                        process,
+                        CoordinatorMessage::VoteResponse(0, true, None),
+                    ),
+                    context,
+                )
+                .expect("failed to deliver vote");
+            context = updated_context(&actions);
+        }
+        assert_eq!(context.state(), &CoordinatorState::Commit);
+        assert_eq!(context.alarm(), &None);
+
+        context.set_alarm(Some(SystemTime::UNIX_EPOCH));
+        let actions = algorithm
+            .event(CoordinatorEvent::Alarm, context)
+            .expect("failed to handle alarm");
+        assert_eq!(actions.len(), 1);
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Commit);
+        assert_eq!(context.alarm(), &None);
+    }
+
+    /// Tests that a coordinator recovered in the voting state with one missing vote requests a
+    /// vote again from exactly that participant.
+    #[test]
+    fn test_recover_voting() {
+        let algorithm = CoordinatorAlgorithm::new(MockClock::new());
+        let coordinator = TestProcess { id: 0 };
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+
+        let mut voted = Participant::new(p1);
+        voted.set_vote(Some(true));
+        let mut also_voted = Participant::new(p3);
+        also_voted.set_vote(Some(true));
+        let context = CoordinatorContext::try_from(
+            TwoPhaseCommitContextBuilder::new()
+                .with_coordinator(coordinator)
+                .with_this_process(coordinator)
+                .with_participants(vec![voted, Participant::new(p2), also_voted])
+                .with_require_coordinator_in_participants(false)
+                .with_epoch(3)
+                .with_state(CoordinatorState::Voting.into())
+                .build()
+                .expect("failed to build context"),
+        )
+        .expect("failed to convert context");
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Recover(TestValue("value")),
+                context.clone(),
+            )
+            .expect("failed to recover");
+        assert_eq!(
+            actions,
+            vec![CoordinatorAction::SendMessage(
+                p2,
+                TwoPhaseCommitMessage::VoteRequest(3, TestValue("value"), None)
+            )]
+        );
+
+        // The missing vote completes the epoch
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p2, CoordinatorMessage::VoteResponse(3, true, None)),
+                context,
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(updated_context(&actions).state(), &CoordinatorState::Commit);
+    }
+
+    /// Tests that a coordinator recovered after aborting sends the abort to every participant
+    /// again, and that one recovered while waiting for a start does nothing.
+    #[test]
+    fn test_recover_decided() {
+        let algorithm = CoordinatorAlgorithm::new(MockClock::new());
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p1, CoordinatorMessage::VoteResponse(0, false, None)),
+                start(&algorithm),
+            )
+            .expect("failed to deliver vote");
+        let context = updated_context(&actions);
+
+        let actions = algorithm
+            .event(CoordinatorEvent::Recover(TestValue("value")), context)
+            .expect("failed to recover");
+        assert_eq!(
+            actions,
+            vec![
+                CoordinatorAction::SendMessage(p1, TwoPhaseCommitMessage::Abort(0, None)),
+                CoordinatorAction::SendMessage(p2, TwoPhaseCommitMessage::Abort(0, None)),
+            ]
+        );
+
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Recover(TestValue("value")),
+                new_context(TestProcess { id: 0 }, &[p1, p2]),
+            )
+            .expect("failed to recover");
+        assert!(actions.is_empty());
+    }
+
+    /// Tests that a participant whose commit was lost, and which therefore ignores the vote
+    /// request of the next epoch, learns the commit by sending its vote again, and that the epoch
+    /// started after it has learned the commit commits.
+    #[test]
+    fn test_lost_commit_recovered_in_later_epoch() {
+        use crate::two_phase_commit::{
+            ParticipantAction, ParticipantAlgorithm, ParticipantContext, ParticipantEvent,
+            ParticipantMessage, ParticipantState,
+        };
+
+        let coordinator = TestProcess { id: 0 };
+        let p1 = TestProcess { id: 1 };
+        let algorithm = CoordinatorAlgorithm::new(MockClock::new());
+        let participant = ParticipantAlgorithm::new(|_: &TestValue| Ok(true), MockClock::new());
+
+        let participant_context = |actions: &[ParticipantAction<_, _, _>]| match actions.first() {
+            Some(ParticipantAction::UpdateContext(context)) => context.clone(),
+            _ => panic!("first action was not UpdateContext: {:?}", actions),
+        };
+        let vote = |epoch, context| {
+            participant
+                .event(
+                    ParticipantEvent::Deliver(
+                        coordinator,
+                        ParticipantMessage::VoteRequest(epoch, TestValue("value"), None),
+                    ),
+                    context,
+                )
+                .expect("failed to request vote")
+        };
+
+        let mut p1_context: ParticipantContext<TestProcess, SystemTime> =
+            ParticipantContext::try_from(
+                TwoPhaseCommitContextBuilder::new()
+                    .with_coordinator(coordinator)
+                    .with_this_process(p1)
+                    .with_participant_processes(vec![p1])
+                    .build()
+                    .expect("failed to build context"),
+            )
+            .expect("failed to convert context");
+
+        // Epoch 0 commits, but the commit sent to p1 is lost
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Start(TestValue("value"), None),
+                new_context(coordinator, &[p1]),
+            )
+            .expect("failed to start");
+        let context = updated_context(&actions);
+        p1_context = participant_context(&vote(0, p1_context));
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p1, CoordinatorMessage::VoteResponse(0, true, None)),
+                context,
+            )
+            .expect("failed to deliver vote");
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Commit);
+
+        // p1 is still waiting on epoch 0, so it ignores the vote request of epoch 1
+        let actions = algorithm
+            .event(CoordinatorEvent::Start(TestValue("value"), None), context)
+            .expect("failed to start");
+        let mut context = updated_context(&actions);
+        assert!(vote(1, p1_context.clone()).is_empty());
+
+        // Its alarm sends its vote again, which the coordinator answers with the commit
+        p1_context.set_alarm(Some(SystemTime::UNIX_EPOCH));
+        let actions = participant
+            .event(ParticipantEvent::Alarm, p1_context)
+            .expect("failed to handle alarm");
+        assert_eq!(
+            actions[1],
+            ParticipantAction::SendMessage(
+                coordinator,
+                TwoPhaseCommitMessage::VoteResponse(0, true, None)
+            )
+        );
+        p1_context = participant_context(&actions);
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p1, CoordinatorMessage::VoteResponse(0, true, None)),
+                context.clone(),
+            )
+            .expect("failed to deliver vote");
+        assert_eq!(
+            actions,
+            vec![CoordinatorAction::SendMessage(
+                p1,
+                TwoPhaseCommitMessage::Commit(0, None)
+            )]
+        );
+        let actions = participant
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Commit(0, None)),
+                p1_context,
+            )
+            .expect("failed to deliver commit");
+        p1_context = participant_context(&actions);
+        assert_eq!(p1_context.state(), &ParticipantState::Commit);
+
+        // Epoch 1 times out without p1's vote, and epoch 2 commits
+        context.set_alarm(Some(SystemTime::UNIX_EPOCH));
+        let actions = algorithm
+            .event(CoordinatorEvent::Alarm, context)
+            .expect("failed to handle alarm");
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Abort);
+
+        let actions = algorithm
+            .event(CoordinatorEvent::Start(TestValue("value"), None), context)
+            .expect("failed to start");
+        let context = updated_context(&actions);
+        assert_eq!(context.epoch(), &2);
+        let actions = vote(2, p1_context);
+        assert_eq!(
+            actions[1],
+            ParticipantAction::SendMessage(
+                coordinator,
+                TwoPhaseCommitMessage::VoteResponse(2, true, None)
+            )
+        );
+        let actions = algorithm
+            .event(
+                CoordinatorEvent::Deliver(p1, CoordinatorMessage::VoteResponse(2, true, None)),
+                context,
+            )
+            .expect("failed to deliver vote");
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &CoordinatorState::Commit);
+        assert_eq!(context.last_commit_epoch(), &Some(2));
+    }
+}
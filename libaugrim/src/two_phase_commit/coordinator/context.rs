@@ -0,0 +1,458 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The context of a two-phase commit coordinator.
+
+use crate::algorithm::TraceId;
+use crate::error::InvalidStateError;
+use crate::process::Process;
+
+use super::super::{check_last_commit_epoch, Epoch};
+use super::CoordinatorState;
+
+/// A participant as tracked by the coordinator, along with its vote for the current epoch.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Participant<P> {
+    process: P,
+    vote: Option<bool>,
+}
+
+impl<P> Participant<P>
+where
+    P: Process,
+{
+    /// Constructs a new `Participant` which has not yet voted.
+    pub fn new(process: P) -> Self {
+        Participant {
+            process,
+            vote: None,
+        }
+    }
+
+    /// Returns the process of the participant.
+    pub fn process(&self) -> &P {
+        &self.process
+    }
+
+    /// Returns the vote of the participant for the current epoch, or `None` if it has not voted.
+    pub fn vote(&self) -> &Option<bool> {
+        &self.vote
+    }
+
+    /// Sets the vote of the participant for the current epoch.
+    pub fn set_vote(&mut self, vote: Option<bool>) {
+        self.vote = vote
+    }
+}
+
+/// The context of a process acting as the two-phase commit coordinator.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoordinatorContext<P, T> {
+    pub(in crate::two_phase_commit) alarm: Option<T>,
+    pub(in crate::two_phase_commit) coordinator: P,
+    pub(in crate::two_phase_commit) epoch: Epoch,
+    pub(in crate::two_phase_commit) last_commit_epoch: Option<Epoch>,
+    pub(in crate::two_phase_commit) participants: Vec<Participant<P>>,
+    pub(in crate::two_phase_commit) state: CoordinatorState,
+    pub(in crate::two_phase_commit) this_process: P,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(in crate::two_phase_commit) trace_id: Option<TraceId>,
+}
+
+impl<P, T> CoordinatorContext<P, T>
+where
+    P: Process,
+{
+    /// Returns the time at which the current epoch is aborted if it is still being voted on.
+    pub fn alarm(&self) -> &Option<T> {
+        &self.alarm
+    }
+
+    /// Sets or clears the alarm.
+    pub fn set_alarm(&mut self, alarm: Option<T>) {
+        self.alarm = alarm
+    }
+
+    /// Returns the coordinator process.
+    pub fn coordinator(&self) -> &P {
+        &self.coordinator
+    }
+
+    /// Returns the current epoch.
+    pub fn epoch(&self) -> &Epoch {
+        &self.epoch
+    }
+
+    /// Sets the current epoch.
+    pub fn set_epoch(&mut self, epoch: Epoch) {
+        self.epoch = epoch
+    }
+
+    /// Returns the last epoch which was committed, if any.
+    pub fn last_commit_epoch(&self) -> &Option<Epoch> {
+        &self.last_commit_epoch
+    }
+
+    /// Sets the last epoch which was committed.
+    pub fn set_last_commit_epoch(&mut self, epoch: Option<Epoch>) {
+        self.last_commit_epoch = epoch
+    }
+
+    /// Returns the participants, along with their votes for the current epoch.
+    pub fn participants(&self) -> &Vec<Participant<P>> {
+        &self.participants
+    }
+
+    /// Returns the participants, for recording their votes.
+    pub fn participants_mut(&mut self) -> &mut Vec<Participant<P>> {
+        &mut self.participants
+    }
+
+    /// Returns the state of the coordinator.
+    pub fn state(&self) -> &CoordinatorState {
+        &self.state
+    }
+
+    /// Moves the coordinator to the state `to`, if the transition is legal.
+    ///
+    /// The coordinator moves from `WaitingForStart` to `Voting`, from `Voting` to either `Commit`
+    /// or `Abort`, and from a decision back to `Voting` for the next epoch. This is the only way
+    /// to change the state once the context is built.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` naming both states if the transition is not legal; the
+    /// state is left unchanged.
+    pub fn try_transition(&mut self, to: CoordinatorState) -> Result<(), InvalidStateError> {
+        let legal = matches!(
+            (&self.state, &to),
+            (CoordinatorState::WaitingForStart, CoordinatorState::Voting)
+                | (CoordinatorState::Voting, CoordinatorState::Commit)
+                | (CoordinatorState::Voting, CoordinatorState::Abort)
+                | (CoordinatorState::Commit, CoordinatorState::Voting)
+                | (CoordinatorState::Abort, CoordinatorState::Voting)
+        );
+
+        if !legal {
+            return Err(InvalidStateError::with_message(format!(
+                "illegal coordinator state transition from {:?} to {:?}",
+                self.state, to
+            )));
+        }
+
+        self.state = to;
+        Ok(())
+    }
+
+    /// Returns the process this context belongs to, which is the coordinator.
+    pub fn this_process(&self) -> &P {
+        &self.this_process
+    }
+
+    /// Returns the trace id of the current epoch, if it was started with one.
+    pub fn trace_id(&self) -> &Option<TraceId> {
+        &self.trace_id
+    }
+
+    /// Sets the trace id of the current epoch.
+    pub fn set_trace_id(&mut self, trace_id: Option<TraceId>) {
+        self.trace_id = trace_id
+    }
+}
+
+/// Builds a [`CoordinatorContext`].
+///
+/// Unlike [`TwoPhaseCommitContextBuilder`](crate::two_phase_commit::TwoPhaseCommitContextBuilder),
+/// only the fields of a coordinator can be set, so the built context always has the coordinator
+/// role.
+pub struct CoordinatorContextBuilder<P, T> {
+    alarm: Option<T>,
+    coordinator: Option<P>,
+    epoch: Option<Epoch>,
+    last_commit_epoch: Option<Epoch>,
+    participants: Option<Vec<Participant<P>>>,
+    require_coordinator_in_participants: bool,
+    state: Option<CoordinatorState>,
+    this_process: Option<P>,
+}
+
+impl<P, T> CoordinatorContextBuilder<P, T>
+where
+    P: Process,
+{
+    /// Constructs a new builder with no fields set.
+    pub fn new() -> Self {
+        CoordinatorContextBuilder {
+            alarm: None,
+            coordinator: None,
+            epoch: None,
+            last_commit_epoch: None,
+            participants: None,
+            require_coordinator_in_participants: true,
+            state: None,
+            this_process: None,
+        }
+    }
+
+    /// Sets the time at which the current epoch is aborted if it is still being voted on.
+    pub fn with_alarm(mut self, alarm: T) -> Self {
+        self.alarm = Some(alarm);
+        self
+    }
+
+    /// Sets the coordinator process.
+    pub fn with_coordinator(mut self, coordinator: P) -> Self {
+        self.coordinator = Some(coordinator);
+        self
+    }
+
+    /// Sets the current epoch.
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Sets the last epoch which was committed.
+    pub fn with_last_commit_epoch(mut self, epoch: Epoch) -> Self {
+        self.last_commit_epoch = Some(epoch);
+        self
+    }
+
+    /// Sets the participants.
+    pub fn with_participants(mut self, participants: Vec<Participant<P>>) -> Self {
+        self.participants = Some(participants);
+        self
+    }
+
+    /// Sets whether the coordinator must be one of the participants.
+    ///
+    /// This is required by default; it can be relaxed for a coordinator which does not vote.
+    pub fn with_require_coordinator_in_participants(mut self, require: bool) -> Self {
+        self.require_coordinator_in_participants = require;
+        self
+    }
+
+    /// Sets the initial state of the coordinator.
+    pub fn with_state(mut self, state: CoordinatorState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Sets the process the context belongs to.
+    pub fn with_this_process(mut self, this_process: P) -> Self {
+        self.this_process = Some(this_process);
+        self
+    }
+
+    /// Builds the context.
+    ///
+    /// The epoch defaults to 0 and the state to `WaitingForStart`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `coordinator`, `participants` or `this_process` is
+    /// missing, if `this_process` is not the coordinator, if the coordinator is required to be a
+    /// participant and is not, or if `last_commit_epoch` is after `epoch`.
+    pub fn build(self) -> Result<CoordinatorContext<P, T>, InvalidStateError> {
+        let coordinator = self.coordinator.ok_or_else(|| {
+            InvalidStateError::with_message("unable to build, missing field: `coordinator`".into())
+        })?;
+
+        let participants = self.participants.ok_or_else(|| {
+            InvalidStateError::with_message("unable to build, missing field: `participants`".into())
+        })?;
+
+        let this_process = self.this_process.ok_or_else(|| {
+            InvalidStateError::with_message("unable to build, missing field: `this_process`".into())
+        })?;
+
+        if this_process != coordinator {
+            return Err(InvalidStateError::with_message(
+                "unable to build, `this_process` must be the coordinator".into(),
+            ));
+        }
+
+        if self.require_coordinator_in_participants
+            && !participants
+                .iter()
+                .any(|participant| participant.process() == &coordinator)
+        {
+            return Err(InvalidStateError::with_message(
+                "unable to build, `coordinator` must be one of the participants".into(),
+            ));
+        }
+
+        let epoch = self.epoch.unwrap_or(0);
+        check_last_commit_epoch(epoch, self.last_commit_epoch)?;
+
+        Ok(CoordinatorContext {
+            alarm: self.alarm,
+            coordinator,
+            epoch,
+            last_commit_epoch: self.last_commit_epoch,
+            participants,
+            state: self.state.unwrap_or(CoordinatorState::WaitingForStart),
+            this_process,
+            trace_id: None,
+        })
+    }
+}
+
+impl<P, T> Default for CoordinatorContextBuilder<P, T>
+where
+    P: Process,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::SystemTime;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Tests that the coordinator builder produces a coordinator context with default epoch and
+    /// state, and that it requires the participants.
+    #[test]
+    fn test_build() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let context: CoordinatorContext<TestProcess, SystemTime> = CoordinatorContextBuilder::new()
+            .with_coordinator(p1)
+            .with_this_process(p1)
+            .with_participants(vec![Participant::new(p2)])
+            .with_require_coordinator_in_participants(false)
+            .build()
+            .expect("failed to build context");
+
+        assert_eq!(context.epoch(), &0);
+        assert_eq!(context.state(), &CoordinatorState::WaitingForStart);
+        assert_eq!(context.participants(), &vec![Participant::new(p2)]);
+
+        let err = CoordinatorContextBuilder::<TestProcess, SystemTime>::new()
+            .with_coordinator(p1)
+            .with_this_process(p1)
+            .build()
+            .expect_err("built a context without participants");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, missing field: `participants`"
+        );
+    }
+
+    /// Tests that by default the coordinator must be one of the participants, and that the
+    /// requirement can be relaxed.
+    #[test]
+    fn test_build_coordinator_in_participants() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let builder = || {
+            CoordinatorContextBuilder::<TestProcess, SystemTime>::new()
+                .with_coordinator(p1)
+                .with_this_process(p1)
+                .with_participants(vec![Participant::new(p2)])
+        };
+
+        let err = builder()
+            .build()
+            .expect_err("built a context without the coordinator as a participant");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, `coordinator` must be one of the participants"
+        );
+
+        builder()
+            .with_participants(vec![Participant::new(p1), Participant::new(p2)])
+            .build()
+            .expect("failed to build context with the coordinator as a participant");
+
+        builder()
+            .with_require_coordinator_in_participants(false)
+            .build()
+            .expect("failed to build relaxed context");
+    }
+
+    /// Tests that a context whose last committed epoch is after its current epoch cannot be
+    /// built.
+    #[test]
+    fn test_build_last_commit_epoch_after_epoch() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let result = CoordinatorContextBuilder::<TestProcess, SystemTime>::new()
+            .with_coordinator(p1)
+            .with_this_process(p1)
+            .with_participants(vec![Participant::new(p2)])
+            .with_epoch(1)
+            .with_last_commit_epoch(2)
+            .build();
+        assert!(result.is_err());
+    }
+
+    /// Tests that a legal transition changes the state, and that an illegal one returns an error
+    /// naming both states and leaves the state unchanged.
+    #[test]
+    fn test_try_transition() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let mut context: CoordinatorContext<TestProcess, SystemTime> =
+            CoordinatorContextBuilder::new()
+                .with_coordinator(p1)
+                .with_this_process(p1)
+                .with_participants(vec![Participant::new(p2)])
+                .with_require_coordinator_in_participants(false)
+                .build()
+                .expect("failed to build context");
+
+        let err = context
+            .try_transition(CoordinatorState::Commit)
+            .expect_err("moved from WaitingForStart to Commit");
+        assert_eq!(
+            err.to_string(),
+            "illegal coordinator state transition from WaitingForStart to Commit"
+        );
+        assert_eq!(context.state(), &CoordinatorState::WaitingForStart);
+
+        context
+            .try_transition(CoordinatorState::Voting)
+            .expect("failed to move to Voting");
+        assert_eq!(context.state(), &CoordinatorState::Voting);
+
+        assert!(context.try_transition(CoordinatorState::Voting).is_err());
+        assert!(context
+            .try_transition(CoordinatorState::WaitingForStart)
+            .is_err());
+
+        context
+            .try_transition(CoordinatorState::Abort)
+            .expect("failed to move to Abort");
+        assert!(context.try_transition(CoordinatorState::Commit).is_err());
+        context
+            .try_transition(CoordinatorState::Voting)
+            .expect("failed to move to Voting for the next epoch");
+    }
+}
@@ -0,0 +1,32 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::TraceId;
+
+use super::CoordinatorMessage;
+
+/// An event handled by the two-phase commit coordinator.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CoordinatorEvent<P, V> {
+    /// The alarm set in the context has expired.
+    Alarm,
+    /// A message from the participant was delivered.
+    Deliver(P, CoordinatorMessage),
+    /// Resume from a context restored after a restart; the value is the one being voted on in
+    /// the context's epoch.
+    Recover(V),
+    /// Start an epoch which attempts to commit the value. The trace id, if any, is carried on
+    /// every message and decision of the epoch.
+    Start(V, Option<TraceId>),
+}
@@ -0,0 +1,44 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::TraceId;
+use crate::message::Message;
+
+use super::super::Epoch;
+
+/// A message delivered to the two-phase commit coordinator.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoordinatorMessage {
+    /// A participant's vote for the epoch; `true` is a vote to commit. The trace id is the one
+    /// the epoch's vote request carried.
+    VoteResponse(Epoch, bool, Option<TraceId>),
+}
+
+impl Message for CoordinatorMessage {}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    /// Tests that each variant of `CoordinatorMessage` survives a round trip through JSON.
+    #[test]
+    fn test_serde_round_trip() {
+        let message = CoordinatorMessage::VoteResponse(3, false, Some(TraceId::new("trace-1")));
+        let json = serde_json::to_string(&message).expect("failed to serialize");
+        let decoded: CoordinatorMessage =
+            serde_json::from_str(&json).expect("failed to deserialize");
+        assert_eq!(decoded, message);
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The coordinator role of two-phase commit.
+
+mod action;
+mod algorithm;
+mod context;
+mod event;
+mod message;
+mod state;
+
+pub use action::{CoordinatorAction, CoordinatorActionNotification};
+pub use algorithm::CoordinatorAlgorithm;
+pub use context::{CoordinatorContext, CoordinatorContextBuilder, Participant};
+pub use event::CoordinatorEvent;
+pub use message::CoordinatorMessage;
+pub use state::CoordinatorState;
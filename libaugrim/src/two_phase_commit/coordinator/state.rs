@@ -0,0 +1,50 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! States of the two-phase commit coordinator.
+
+/// The state of a two-phase commit coordinator.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoordinatorState {
+    /// The coordinator has decided to abort the current epoch.
+    Abort,
+    /// The coordinator has decided to commit the current epoch.
+    Commit,
+    /// The coordinator has requested votes and is waiting for the participants to respond.
+    Voting,
+    /// The coordinator is waiting for a value to be proposed.
+    WaitingForStart,
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    /// Tests that each variant of `CoordinatorState` survives a round trip through JSON.
+    #[test]
+    fn test_serde_round_trip() {
+        for state in [
+            CoordinatorState::Abort,
+            CoordinatorState::Commit,
+            CoordinatorState::Voting,
+            CoordinatorState::WaitingForStart,
+        ] {
+            let json = serde_json::to_string(&state).expect("failed to serialize");
+            let decoded: CoordinatorState =
+                serde_json::from_str(&json).expect("failed to deserialize");
+            assert_eq!(decoded, state);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Selection of the coordinator for each new two-phase commit transaction.
+
+use crate::error::InvalidStateError;
+use crate::process::Process;
+
+/// Selects the coordinator for each new transaction using smooth weighted round-robin.
+///
+/// Each process is given a weight, and over any window of transactions whose length is the sum
+/// of the weights, each process coordinates exactly as many transactions as its weight. Selections
+/// for a given process are spread through the window rather than being grouped together, so
+/// consecutive transactions are coordinated by different processes whenever the weights allow.
+pub struct CoordinatorSelector<P> {
+    processes: Vec<WeightedProcess<P>>,
+    total_weight: i64,
+}
+
+struct WeightedProcess<P> {
+    process: P,
+    weight: i64,
+    current: i64,
+}
+
+impl<P> CoordinatorSelector<P>
+where
+    P: Process,
+{
+    /// Constructs a new `CoordinatorSelector` from a list of processes and their weights.
+    ///
+    /// A process with a weight of zero is never selected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if no process has a non-zero weight.
+    pub fn new(weights: Vec<(P, u32)>) -> Result<Self, InvalidStateError> {
+        let processes: Vec<WeightedProcess<P>> = weights
+            .into_iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(process, weight)| WeightedProcess {
+                process,
+                weight: i64::from(weight),
+                current: 0,
+            })
+            .collect();
+
+        if processes.is_empty() {
+            return Err(InvalidStateError::with_message(
+                "at least one process must have a non-zero weight".into(),
+            ));
+        }
+
+        let total_weight = processes.iter().map(|p| p.weight).sum();
+
+        Ok(CoordinatorSelector {
+            processes,
+            total_weight,
+        })
+    }
+
+    /// Returns the coordinator for the next transaction.
+    pub fn next_coordinator(&mut self) -> P {
+        for process in self.processes.iter_mut() {
+            process.current += process.weight;
+        }
+
+        let mut selected = 0;
+        for (index, process) in self.processes.iter().enumerate() {
+            if process.current > self.processes[selected].current {
+                selected = index;
+            }
+        }
+
+        self.processes[selected].current -= self.total_weight;
+        self.processes[selected].process
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Tests that over 100 transactions, coordinators are assigned in proportion to their
+    /// weights, and that a process never coordinates two consecutive transactions when a
+    /// lighter-weighted process is still owed a turn.
+    #[test]
+    fn test_weighted_distribution() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let p3 = TestProcess { id: 3 };
+        let p4 = TestProcess { id: 4 };
+
+        let mut selector = CoordinatorSelector::new(vec![(p1, 1), (p2, 2), (p3, 2), (p4, 0)])
+            .expect("failed to create selector");
+
+        let coordinators: Vec<TestProcess> =
+            (0..100).map(|_| selector.next_coordinator()).collect();
+        let count = |p: TestProcess| coordinators.iter().filter(|c| **c == p).count();
+
+        assert_eq!(count(p1), 20);
+        assert_eq!(count(p2), 40);
+        assert_eq!(count(p3), 40);
+        assert_eq!(count(p4), 0);
+
+        assert!(coordinators.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    /// Tests that a selector cannot be constructed without a process with a non-zero weight.
+    #[test]
+    fn test_no_weighted_process() {
+        assert!(CoordinatorSelector::<TestProcess>::new(vec![]).is_err());
+        assert!(CoordinatorSelector::new(vec![(TestProcess { id: 1 }, 0)]).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Messages exchanged between the two-phase commit coordinator and participants.
+
+use std::convert::TryFrom;
+
+use crate::algorithm::TraceId;
+use crate::error::InvalidStateError;
+use crate::message::Message;
+
+use super::coordinator::CoordinatorMessage;
+use super::participant::ParticipantMessage;
+use super::Epoch;
+
+/// A message sent between processes running two-phase commit, in either direction.
+///
+/// Every message carries the trace id of the epoch it belongs to, if the epoch was started with
+/// one.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TwoPhaseCommitMessage<V> {
+    /// Sent by the coordinator to request a vote on committing the value in the epoch.
+    VoteRequest(Epoch, V, Option<TraceId>),
+    /// Sent by a participant with its vote for the epoch; `true` is a vote to commit.
+    VoteResponse(Epoch, bool, Option<TraceId>),
+    /// Sent by the coordinator when the epoch has been committed.
+    Commit(Epoch, Option<TraceId>),
+    /// Sent by the coordinator when the epoch has been aborted.
+    Abort(Epoch, Option<TraceId>),
+}
+
+impl<V> Message for TwoPhaseCommitMessage<V> {}
+
+impl<V> From<CoordinatorMessage> for TwoPhaseCommitMessage<V> {
+    fn from(message: CoordinatorMessage) -> Self {
+        match message {
+            CoordinatorMessage::VoteResponse(epoch, vote, trace_id) => {
+                TwoPhaseCommitMessage::VoteResponse(epoch, vote, trace_id)
+            }
+        }
+    }
+}
+
+impl<V> TryFrom<TwoPhaseCommitMessage<V>> for CoordinatorMessage {
+    type Error = InvalidStateError;
+
+    fn try_from(message: TwoPhaseCommitMessage<V>) -> Result<Self, Self::Error> {
+        match message {
+            TwoPhaseCommitMessage::VoteResponse(epoch, vote, trace_id) => {
+                Ok(CoordinatorMessage::VoteResponse(epoch, vote, trace_id))
+            }
+            _ => Err(InvalidStateError::with_message(
+                "message is not delivered to a coordinator".into(),
+            )),
+        }
+    }
+}
+
+impl<V> From<ParticipantMessage<V>> for TwoPhaseCommitMessage<V> {
+    fn from(message: ParticipantMessage<V>) -> Self {
+        match message {
+            ParticipantMessage::VoteRequest(epoch, value, trace_id) => {
+                TwoPhaseCommitMessage::VoteRequest(epoch, value, trace_id)
+            }
+            ParticipantMessage::Commit(epoch, trace_id) => {
+                TwoPhaseCommitMessage::Commit(epoch, trace_id)
+            }
+            ParticipantMessage::Abort(epoch, trace_id) => {
+                TwoPhaseCommitMessage::Abort(epoch, trace_id)
+            }
+        }
+    }
+}
+
+impl<V> TryFrom<TwoPhaseCommitMessage<V>> for ParticipantMessage<V> {
+    type Error = InvalidStateError;
+
+    fn try_from(message: TwoPhaseCommitMessage<V>) -> Result<Self, Self::Error> {
+        match message {
+            TwoPhaseCommitMessage::VoteRequest(epoch, value, trace_id) => {
+                Ok(ParticipantMessage::VoteRequest(epoch, value, trace_id))
+            }
+            TwoPhaseCommitMessage::Commit(epoch, trace_id) => {
+                Ok(ParticipantMessage::Commit(epoch, trace_id))
+            }
+            TwoPhaseCommitMessage::Abort(epoch, trace_id) => {
+                Ok(ParticipantMessage::Abort(epoch, trace_id))
+            }
+            _ => Err(InvalidStateError::with_message(
+                "message is not delivered to a participant".into(),
+            )),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    /// Tests that each variant of `TwoPhaseCommitMessage` survives a round trip through JSON.
+    #[test]
+    fn test_serde_round_trip() {
+        for message in [
+            TwoPhaseCommitMessage::VoteRequest(1, "value".to_string(), None),
+            TwoPhaseCommitMessage::VoteResponse(1, true, Some(TraceId::new("trace-1"))),
+            TwoPhaseCommitMessage::Commit(1, None),
+            TwoPhaseCommitMessage::Abort(1, Some(TraceId::new("trace-1"))),
+        ] {
+            let json = serde_json::to_string(&message).expect("failed to serialize");
+            let decoded: TwoPhaseCommitMessage<String> =
+                serde_json::from_str(&json).expect("failed to deserialize");
+            assert_eq!(decoded, message);
+        }
+    }
+}
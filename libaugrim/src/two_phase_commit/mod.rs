@@ -0,0 +1,66 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Two-phase commit atomic commitment protocol.
+//!
+//! One process acts as the coordinator, which requests votes from the participants and decides
+//! to commit only if every participant voted to commit.
+
+use crate::error::InvalidStateError;
+
+pub mod coordinator;
+mod coordinator_selector;
+mod message;
+pub mod participant;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+mod state;
+mod unified_context;
+
+pub use coordinator::{
+    CoordinatorAction, CoordinatorActionNotification, CoordinatorAlgorithm, CoordinatorContext,
+    CoordinatorContextBuilder, CoordinatorEvent, CoordinatorMessage, CoordinatorState, Participant,
+};
+pub use coordinator_selector::CoordinatorSelector;
+pub use message::TwoPhaseCommitMessage;
+pub use participant::{
+    ParticipantAction, ParticipantActionNotification, ParticipantAlgorithm, ParticipantContext,
+    ParticipantContextBuilder, ParticipantEvent, ParticipantMessage, ParticipantState,
+};
+#[cfg(feature = "protobuf")]
+pub use protobuf::BytesValue;
+pub use state::TwoPhaseCommitState;
+pub use unified_context::{
+    TwoPhaseCommitContext, TwoPhaseCommitContextBuilder, TwoPhaseCommitSnapshot,
+};
+
+/// An epoch of two-phase commit; each epoch commits or aborts a single value.
+pub type Epoch = u64;
+
+/// Checks that the last committed epoch is not after the current epoch, which a correct run never
+/// produces; a context which violates this was recovered from corrupted state.
+fn check_last_commit_epoch(
+    epoch: Epoch,
+    last_commit_epoch: Option<Epoch>,
+) -> Result<(), InvalidStateError> {
+    match last_commit_epoch {
+        Some(last_commit_epoch) if last_commit_epoch > epoch => {
+            Err(InvalidStateError::with_message(format!(
+                "unable to build, `last_commit_epoch` {} is after `epoch` {}",
+                last_commit_epoch, epoch
+            )))
+        }
+        _ => Ok(()),
+    }
+}
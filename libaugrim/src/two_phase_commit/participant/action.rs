@@ -0,0 +1,56 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::{ContextUpdate, TraceId};
+
+use super::super::{Epoch, TwoPhaseCommitMessage};
+use super::ParticipantContext;
+
+/// A notification of a change in the progress of a participant, for observability.
+///
+/// Notifications are returned after the messages which carry out the change:
+///
+/// - `Voted` once the participant enters the `Voted` state, after its vote is sent to the
+///   coordinator. A vote which is sent again, because the request was repeated, is not notified
+///   again.
+/// - `Committed` once the participant enters the `Commit` state, when the coordinator's commit is
+///   delivered.
+/// - `Aborted` once the participant enters the `Abort` state, when the coordinator's abort is
+///   delivered or, if the participant voted to abort, when its alarm expires.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParticipantActionNotification {
+    /// The epoch was aborted, along with the epoch's trace id.
+    Aborted(Epoch, Option<TraceId>),
+    /// The epoch was committed, along with the epoch's trace id.
+    Committed(Epoch, Option<TraceId>),
+    /// The participant voted in the epoch; `true` is a vote to commit.
+    Voted(Epoch, bool),
+}
+
+/// An action returned by a two-phase commit participant, to be performed by the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParticipantAction<P, V, T> {
+    /// Report the notification to the application.
+    Notify(ParticipantActionNotification),
+    /// Send the message to the process.
+    SendMessage(P, TwoPhaseCommitMessage<V>),
+    /// Replace the stored context with this one.
+    UpdateContext(ParticipantContext<P, T>),
+}
+
+impl<P, V, T> ContextUpdate for ParticipantAction<P, V, T> {
+    fn is_context_update(&self) -> bool {
+        matches!(self, ParticipantAction::UpdateContext(_))
+    }
+}
@@ -0,0 +1,693 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use crate::algorithm::{normalize_actions, Algorithm, TraceId, Value};
+use crate::error::InternalError;
+use crate::process::Process;
+use crate::time::TimeSource;
+
+use super::super::{Epoch, TwoPhaseCommitMessage};
+use super::ParticipantState;
+use super::{ParticipantAction, ParticipantActionNotification, ParticipantContext};
+use super::{ParticipantEvent, ParticipantMessage};
+
+/// The two-phase commit participant algorithm.
+///
+/// When the coordinator requests a vote, the participant decides its vote using `vote_func` and
+/// replies to the coordinator, then waits for the coordinator's decision. Messages which do not
+/// fit the participant's current state, such as a decision which arrives before the vote
+/// request, are ignored. A participant waiting for a vote request accepts one for a later epoch,
+/// since the request for its current epoch may have been lost.
+///
+/// The `time_source` is used to record when the participant becomes uncertain, which is when it
+/// has voted to commit but has not yet learned the decision, and to check whether an alarm has
+/// expired.
+///
+/// The caller may set an alarm in the context, and deliver an `Alarm` event once it expires; an
+/// `Alarm` event delivered before then is ignored. A participant which voted to abort and is still
+/// waiting for the decision aborts on its own, since the coordinator cannot commit without its
+/// vote. A participant which voted to commit is uncertain and cannot decide on its own, so it
+/// queries the coordinator by sending its vote again; the coordinator answers with the decision
+/// once it is made.
+///
+/// The trace id carried by the vote request is kept in the context for the epoch, and carried on
+/// every vote sent in it, as well as on the decision's notification.
+///
+/// Each vote and decision is also reported with a [`ParticipantActionNotification`]; see its
+/// documentation for which changes of state produce which notifications.
+pub struct ParticipantAlgorithm<P, V, F, S> {
+    vote_func: F,
+    time_source: S,
+    _process: PhantomData<P>,
+    _value: PhantomData<V>,
+}
+
+impl<P, V, F, S> ParticipantAlgorithm<P, V, F, S>
+where
+    P: Process,
+    V: Value,
+    F: Fn(&V) -> Result<bool, InternalError>,
+    S: TimeSource,
+{
+    /// Constructs a new `ParticipantAlgorithm`; `vote_func` returns `true` to vote to commit the
+    /// given value, and `time_source` gives the current time.
+    pub fn new(vote_func: F, time_source: S) -> Self {
+        ParticipantAlgorithm {
+            vote_func,
+            time_source,
+            _process: PhantomData,
+            _value: PhantomData,
+        }
+    }
+
+    fn handle_vote_request(
+        &self,
+        process: P,
+        epoch: Epoch,
+        value: V,
+        trace_id: Option<TraceId>,
+        mut context: ParticipantContext<P, S::Time>,
+    ) -> Result<Vec<ParticipantAction<P, V, S::Time>>, InternalError> {
+        if &process != context.coordinator() {
+            debug!("ignoring vote request from a process which is not the coordinator");
+            return Ok(vec![]);
+        }
+
+        match context.state() {
+            ParticipantState::WaitingForVoteRequest if epoch >= *context.epoch() => {
+                // A later epoch means the request for the current one was lost, or the current
+                // epoch was aborted before this participant voted
+                context.set_epoch(epoch)
+            }
+            ParticipantState::Commit | ParticipantState::Abort if epoch > *context.epoch() => {
+                context.set_epoch(epoch)
+            }
+            ParticipantState::Voted { vote } if epoch == *context.epoch() => {
+                // The coordinator did not receive the vote; send it again
+                return Ok(vec![ParticipantAction::SendMessage(
+                    process,
+                    TwoPhaseCommitMessage::VoteResponse(epoch, *vote, context.trace_id().clone()),
+                )]);
+            }
+            _ => {
+                debug!(
+                    "ignoring vote request for epoch {} (current epoch is {})",
+                    epoch,
+                    context.epoch()
+                );
+                return Ok(vec![]);
+            }
+        }
+
+        let vote = (self.vote_func)(&value)?;
+        context
+            .try_transition(ParticipantState::Voted { vote })
+            .map_err(|err| InternalError::from_source(Box::new(err)))?;
+        if vote {
+            context.set_uncertain_since(Some(self.time_source.now()));
+        }
+        context.set_trace_id(trace_id.clone());
+
+        Ok(vec![
+            ParticipantAction::UpdateContext(context),
+            ParticipantAction::SendMessage(
+                process,
+                TwoPhaseCommitMessage::VoteResponse(epoch, vote, trace_id),
+            ),
+            ParticipantAction::Notify(ParticipantActionNotification::Voted(epoch, vote)),
+        ])
+    }
+
+    fn handle_decision(
+        &self,
+        process: P,
+        epoch: Epoch,
+        commit: bool,
+        mut context: ParticipantContext<P, S::Time>,
+    ) -> Result<Vec<ParticipantAction<P, V, S::Time>>, InternalError> {
+        if &process != context.coordinator() {
+            debug!("ignoring decision from a process which is not the coordinator");
+            return Ok(vec![]);
+        }
+
+        match context.state() {
+            ParticipantState::Voted { vote } if epoch == *context.epoch() => {
+                if commit && !vote {
+                    return Err(InternalError::with_message(format!(
+                        "coordinator committed epoch {} which this participant voted to abort",
+                        epoch
+                    )));
+                }
+            }
+            _ => {
+                debug!(
+                    "ignoring decision for epoch {} received in state {:?}",
+                    epoch,
+                    context.state()
+                );
+                return Ok(vec![]);
+            }
+        }
+
+        context.set_alarm(None);
+        context.set_uncertain_since(None);
+        let trace_id = context.trace_id().clone();
+        let notification = if commit {
+            context
+                .try_transition(ParticipantState::Commit)
+                .map_err(|err| InternalError::from_source(Box::new(err)))?;
+            context.set_last_commit_epoch(Some(epoch));
+            ParticipantActionNotification::Committed(epoch, trace_id)
+        } else {
+            context
+                .try_transition(ParticipantState::Abort)
+                .map_err(|err| InternalError::from_source(Box::new(err)))?;
+            ParticipantActionNotification::Aborted(epoch, trace_id)
+        };
+
+        Ok(vec![
+            ParticipantAction::UpdateContext(context),
+            ParticipantAction::Notify(notification),
+        ])
+    }
+
+    fn handle_alarm(
+        &self,
+        mut context: ParticipantContext<P, S::Time>,
+    ) -> Result<Vec<ParticipantAction<P, V, S::Time>>, InternalError> {
+        match context.alarm() {
+            Some(alarm) if self.time_source.now() < *alarm => {
+                debug!("ignoring alarm, it has not expired");
+                return Ok(vec![]);
+            }
+            Some(_) => (),
+            None => {
+                debug!("ignoring alarm, no alarm is set");
+                return Ok(vec![]);
+            }
+        }
+
+        context.set_alarm(None);
+        let epoch = *context.epoch();
+        let trace_id = context.trace_id().clone();
+        match context.state() {
+            ParticipantState::Voted { vote: false } => {
+                debug!(
+                    "aborting epoch {}, the alarm expired before the decision was received",
+                    epoch
+                );
+                context
+                    .try_transition(ParticipantState::Abort)
+                    .map_err(|err| InternalError::from_source(Box::new(err)))?;
+                Ok(vec![
+                    ParticipantAction::UpdateContext(context),
+                    ParticipantAction::Notify(ParticipantActionNotification::Aborted(
+                        epoch, trace_id,
+                    )),
+                ])
+            }
+            ParticipantState::Voted { vote: true } => {
+                let coordinator = *context.coordinator();
+                Ok(vec![
+                    ParticipantAction::UpdateContext(context),
+                    ParticipantAction::SendMessage(
+                        coordinator,
+                        TwoPhaseCommitMessage::VoteResponse(epoch, true, trace_id),
+                    ),
+                ])
+            }
+            _ => Ok(vec![ParticipantAction::UpdateContext(context)]),
+        }
+    }
+}
+
+impl<P, V, F, S> Algorithm<P> for ParticipantAlgorithm<P, V, F, S>
+where
+    P: Process,
+    V: Value,
+    F: Fn(&V) -> Result<bool, InternalError>,
+    S: TimeSource,
+{
+    type Event = ParticipantEvent<P, V>;
+    type Action = ParticipantAction<P, V, S::Time>;
+    type Context = ParticipantContext<P, S::Time>;
+
+    fn event(
+        &self,
+        event: Self::Event,
+        context: Self::Context,
+    ) -> Result<Vec<Self::Action>, InternalError> {
+        let actions = match event {
+            ParticipantEvent::Alarm => self.handle_alarm(context),
+            ParticipantEvent::Deliver(
+                process,
+                ParticipantMessage::VoteRequest(epoch, value, trace_id),
+            ) => self.handle_vote_request(process, epoch, value, trace_id, context),
+            ParticipantEvent::Deliver(process, ParticipantMessage::Commit(epoch, _)) => {
+                self.handle_decision(process, epoch, true, context)
+            }
+            ParticipantEvent::Deliver(process, ParticipantMessage::Abort(epoch, _)) => {
+                self.handle_decision(process, epoch, false, context)
+            }
+        }?;
+
+        Ok(normalize_actions(actions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+    use std::time::{Duration, SystemTime};
+
+    use crate::time::{MockClock, SystemTimeSource};
+    use crate::two_phase_commit::TwoPhaseCommitContextBuilder;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestValue(bool);
+
+    impl Value for TestValue {}
+
+    type TestAction = ParticipantAction<TestProcess, TestValue, SystemTime>;
+
+    fn vote(value: &TestValue) -> Result<bool, InternalError> {
+        Ok(value.0)
+    }
+
+    fn new_context(
+        coordinator: TestProcess,
+        this_process: TestProcess,
+    ) -> ParticipantContext<TestProcess, SystemTime> {
+        ParticipantContext::try_from(
+            TwoPhaseCommitContextBuilder::new()
+                .with_coordinator(coordinator)
+                .with_this_process(this_process)
+                .with_participant_processes(vec![this_process])
+                .build()
+                .expect("failed to build context"),
+        )
+        .expect("failed to convert context")
+    }
+
+    /// Returns the context from the `UpdateContext` action, which must be the first action.
+    fn updated_context(actions: &[TestAction]) -> ParticipantContext<TestProcess, SystemTime> {
+        match actions.first() {
+            Some(ParticipantAction::UpdateContext(context)) => context.clone(),
+            _ => panic!("first action was not UpdateContext: {:?}", actions),
+        }
+    }
+
+    /// Tests that a participant replies to a vote request with its vote and then commits when
+    /// the coordinator's commit is delivered.
+    #[test]
+    fn test_commit() {
+        let coordinator = TestProcess { id: 0 };
+        let this_process = TestProcess { id: 1 };
+        let algorithm = ParticipantAlgorithm::new(vote, SystemTimeSource::new());
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(
+                    coordinator,
+                    ParticipantMessage::VoteRequest(0, TestValue(true), None),
+                ),
+                new_context(coordinator, this_process),
+            )
+            .expect("failed to deliver vote request");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                ParticipantAction::SendMessage(
+                    coordinator,
+                    TwoPhaseCommitMessage::VoteResponse(0, true, None)
+                ),
+                ParticipantAction::Notify(ParticipantActionNotification::Voted(0, true)),
+            ]
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &ParticipantState::Voted { vote: true });
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Commit(0, None)),
+                context,
+            )
+            .expect("failed to deliver commit");
+
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![ParticipantAction::Notify(
+                ParticipantActionNotification::Committed(0, None)
+            )]
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &ParticipantState::Commit);
+        assert_eq!(context.last_commit_epoch(), &Some(0));
+    }
+
+    /// Tests that the trace id carried by the vote request is carried on the vote and on the
+    /// `Committed` notification.
+    #[test]
+    fn test_trace_id() {
+        let coordinator = TestProcess { id: 0 };
+        let this_process = TestProcess { id: 1 };
+        let algorithm = ParticipantAlgorithm::new(vote, MockClock::new());
+        let trace_id = Some(TraceId::new("trace-1"));
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(
+                    coordinator,
+                    ParticipantMessage::VoteRequest(0, TestValue(true), trace_id.clone()),
+                ),
+                new_context(coordinator, this_process),
+            )
+            .expect("failed to deliver vote request");
+        assert_eq!(
+            actions[1],
+            ParticipantAction::SendMessage(
+                coordinator,
+                TwoPhaseCommitMessage::VoteResponse(0, true, trace_id.clone())
+            )
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.trace_id(), &trace_id);
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Commit(0, None)),
+                context,
+            )
+            .expect("failed to deliver commit");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![ParticipantAction::Notify(
+                ParticipantActionNotification::Committed(0, trace_id)
+            )]
+        );
+    }
+
+    /// Tests that a decision delivered before the vote request is ignored without changing the
+    /// participant's state, and that the participant can still vote afterwards.
+    #[test]
+    fn test_decision_before_vote_request() {
+        let coordinator = TestProcess { id: 0 };
+        let this_process = TestProcess { id: 1 };
+        let algorithm = ParticipantAlgorithm::new(vote, SystemTimeSource::new());
+        let context = new_context(coordinator, this_process);
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Commit(0, None)),
+                context.clone(),
+            )
+            .expect("failed to deliver commit");
+        assert!(actions.is_empty());
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Abort(0, None)),
+                context.clone(),
+            )
+            .expect("failed to deliver abort");
+        assert!(actions.is_empty());
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(
+                    coordinator,
+                    ParticipantMessage::VoteRequest(0, TestValue(false), None),
+                ),
+                context,
+            )
+            .expect("failed to deliver vote request");
+        assert_eq!(
+            updated_context(&actions).state(),
+            &ParticipantState::Voted { vote: false }
+        );
+    }
+
+    /// Tests that a participant which never received the vote request for epoch 0 still votes in
+    /// epoch 1 and commits it.
+    #[test]
+    fn test_vote_request_for_later_epoch() {
+        let coordinator = TestProcess { id: 0 };
+        let this_process = TestProcess { id: 1 };
+        let algorithm = ParticipantAlgorithm::new(vote, SystemTimeSource::new());
+
+        // The request for epoch 0 is dropped, and the coordinator aborts it
+        let context = new_context(coordinator, this_process);
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Abort(0, None)),
+                context.clone(),
+            )
+            .expect("failed to deliver abort");
+        assert!(actions.is_empty());
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(
+                    coordinator,
+                    ParticipantMessage::VoteRequest(1, TestValue(true), None),
+                ),
+                context,
+            )
+            .expect("failed to deliver vote request");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![
+                ParticipantAction::SendMessage(
+                    coordinator,
+                    TwoPhaseCommitMessage::VoteResponse(1, true, None)
+                ),
+                ParticipantAction::Notify(ParticipantActionNotification::Voted(1, true)),
+            ]
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.epoch(), &1);
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Commit(1, None)),
+                context,
+            )
+            .expect("failed to deliver commit");
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &ParticipantState::Commit);
+        assert_eq!(context.last_commit_epoch(), &Some(1));
+    }
+
+    /// Tests that a participant records the time at which it voted to commit as the start of its
+    /// uncertain state, and clears it once the decision is delivered.
+    #[test]
+    fn test_uncertain_since() {
+        let coordinator = TestProcess { id: 0 };
+        let this_process = TestProcess { id: 1 };
+        let voted_at = SystemTime::UNIX_EPOCH + Duration::from_secs(42);
+        let clock = MockClock::new();
+        clock.set(voted_at);
+        let algorithm = ParticipantAlgorithm::new(vote, clock);
+
+        let context = new_context(coordinator, this_process);
+        assert_eq!(context.uncertain_since(), &None);
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(
+                    coordinator,
+                    ParticipantMessage::VoteRequest(0, TestValue(true), None),
+                ),
+                context,
+            )
+            .expect("failed to deliver vote request");
+        let context = updated_context(&actions);
+        assert_eq!(context.uncertain_since(), &Some(voted_at));
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Abort(0, None)),
+                context,
+            )
+            .expect("failed to deliver abort");
+        assert_eq!(updated_context(&actions).uncertain_since(), &None);
+
+        // A participant which votes to abort is never uncertain
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(
+                    coordinator,
+                    ParticipantMessage::VoteRequest(0, TestValue(false), None),
+                ),
+                new_context(coordinator, this_process),
+            )
+            .expect("failed to deliver vote request");
+        assert_eq!(updated_context(&actions).uncertain_since(), &None);
+    }
+
+    /// Tests that in a clean run, the participant notifies its vote to commit before it notifies
+    /// the commit, and notifies each once.
+    #[test]
+    fn test_voted_notified_before_committed() {
+        let coordinator = TestProcess { id: 0 };
+        let this_process = TestProcess { id: 1 };
+        let algorithm = ParticipantAlgorithm::new(vote, SystemTimeSource::new());
+
+        let mut context = new_context(coordinator, this_process);
+        let mut notifications = Vec::new();
+        for message in [
+            ParticipantMessage::VoteRequest(0, TestValue(true), None),
+            ParticipantMessage::VoteRequest(0, TestValue(true), None),
+            ParticipantMessage::Commit(0, None),
+        ] {
+            let actions = algorithm
+                .event(
+                    ParticipantEvent::Deliver(coordinator, message),
+                    context.clone(),
+                )
+                .expect("failed to deliver message");
+            for action in actions {
+                match action {
+                    ParticipantAction::UpdateContext(updated) => context = updated,
+                    ParticipantAction::Notify(notification) => notifications.push(notification),
+                    ParticipantAction::SendMessage(..) => (),
+                }
+            }
+        }
+
+        assert_eq!(
+            notifications,
+            vec![
+                ParticipantActionNotification::Voted(0, true),
+                ParticipantActionNotification::Committed(0, None),
+            ]
+        );
+    }
+
+    /// Tests that a participant which voted to abort ignores its alarm until it expires, as
+    /// measured by a mock clock, and then aborts on its own.
+    #[test]
+    fn test_alarm_aborts_after_abort_vote() {
+        let coordinator = TestProcess { id: 0 };
+        let this_process = TestProcess { id: 1 };
+        let clock = MockClock::new();
+        let algorithm = ParticipantAlgorithm::new(vote, clock.clone());
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(
+                    coordinator,
+                    ParticipantMessage::VoteRequest(0, TestValue(false), None),
+                ),
+                new_context(coordinator, this_process),
+            )
+            .expect("failed to deliver vote request");
+        let mut context = updated_context(&actions);
+        let alarm = clock.now() + Duration::from_secs(10);
+        context.set_alarm(Some(alarm));
+
+        // An alarm delivered before it expires is ignored
+        clock.advance(Duration::from_secs(5));
+        let actions = algorithm
+            .event(ParticipantEvent::Alarm, context.clone())
+            .expect("failed to handle alarm");
+        assert!(actions.is_empty());
+
+        clock.advance(Duration::from_secs(6));
+        let actions = algorithm
+            .event(ParticipantEvent::Alarm, context)
+            .expect("failed to handle alarm");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![ParticipantAction::Notify(
+                ParticipantActionNotification::Aborted(0, None)
+            )]
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &ParticipantState::Abort);
+        assert_eq!(context.alarm(), &None);
+
+        // The coordinator's abort arriving afterwards is ignored
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Abort(0, None)),
+                context,
+            )
+            .expect("failed to deliver abort");
+        assert!(actions.is_empty());
+    }
+
+    /// Tests that a participant which voted to commit queries the coordinator once its alarm
+    /// expires, as measured by a mock clock, and remains uncertain until the decision arrives.
+    #[test]
+    fn test_alarm_queries_coordinator_after_commit_vote() {
+        let coordinator = TestProcess { id: 0 };
+        let this_process = TestProcess { id: 1 };
+        let clock = MockClock::new();
+        let algorithm = ParticipantAlgorithm::new(vote, clock.clone());
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(
+                    coordinator,
+                    ParticipantMessage::VoteRequest(0, TestValue(true), None),
+                ),
+                new_context(coordinator, this_process),
+            )
+            .expect("failed to deliver vote request");
+        let mut context = updated_context(&actions);
+        let alarm = clock.now() + Duration::from_secs(10);
+        context.set_alarm(Some(alarm));
+
+        clock.advance(Duration::from_secs(11));
+        assert!(clock.now() >= alarm);
+
+        let actions = algorithm
+            .event(ParticipantEvent::Alarm, context)
+            .expect("failed to handle alarm");
+        assert_eq!(
+            actions[1..].to_vec(),
+            vec![ParticipantAction::SendMessage(
+                coordinator,
+                TwoPhaseCommitMessage::VoteResponse(0, true, None)
+            )]
+        );
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &ParticipantState::Voted { vote: true });
+        assert_eq!(context.uncertain_since(), &Some(SystemTime::UNIX_EPOCH));
+        assert_eq!(context.alarm(), &None);
+
+        let actions = algorithm
+            .event(
+                ParticipantEvent::Deliver(coordinator, ParticipantMessage::Commit(0, None)),
+                context,
+            )
+            .expect("failed to deliver commit");
+        let context = updated_context(&actions);
+        assert_eq!(context.state(), &ParticipantState::Commit);
+        assert_eq!(context.uncertain_since(), &None);
+    }
+}
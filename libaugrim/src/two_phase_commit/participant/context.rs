@@ -0,0 +1,445 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The context of a two-phase commit participant.
+
+use crate::algorithm::TraceId;
+use crate::error::InvalidStateError;
+use crate::process::Process;
+
+use super::super::{check_last_commit_epoch, Epoch};
+use super::ParticipantState;
+
+/// The context of a process acting as a two-phase commit participant.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParticipantContext<P, T> {
+    pub(in crate::two_phase_commit) alarm: Option<T>,
+    pub(in crate::two_phase_commit) coordinator: P,
+    pub(in crate::two_phase_commit) epoch: Epoch,
+    pub(in crate::two_phase_commit) last_commit_epoch: Option<Epoch>,
+    pub(in crate::two_phase_commit) participant_processes: Vec<P>,
+    pub(in crate::two_phase_commit) state: ParticipantState,
+    pub(in crate::two_phase_commit) this_process: P,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(in crate::two_phase_commit) trace_id: Option<TraceId>,
+    pub(in crate::two_phase_commit) uncertain_since: Option<T>,
+}
+
+impl<P, T> ParticipantContext<P, T>
+where
+    P: Process,
+{
+    /// Returns the time at which the alarm expires, if one is set.
+    pub fn alarm(&self) -> &Option<T> {
+        &self.alarm
+    }
+
+    /// Sets or clears the alarm.
+    pub fn set_alarm(&mut self, alarm: Option<T>) {
+        self.alarm = alarm
+    }
+
+    /// Returns the coordinator process.
+    pub fn coordinator(&self) -> &P {
+        &self.coordinator
+    }
+
+    /// Returns the current epoch.
+    pub fn epoch(&self) -> &Epoch {
+        &self.epoch
+    }
+
+    /// Sets the current epoch.
+    pub fn set_epoch(&mut self, epoch: Epoch) {
+        self.epoch = epoch
+    }
+
+    /// Returns the last epoch which was committed, if any.
+    pub fn last_commit_epoch(&self) -> &Option<Epoch> {
+        &self.last_commit_epoch
+    }
+
+    /// Sets the last epoch which was committed.
+    pub fn set_last_commit_epoch(&mut self, epoch: Option<Epoch>) {
+        self.last_commit_epoch = epoch
+    }
+
+    /// Returns the processes which vote in each epoch.
+    pub fn participant_processes(&self) -> &Vec<P> {
+        &self.participant_processes
+    }
+
+    /// Returns the state of the participant.
+    pub fn state(&self) -> &ParticipantState {
+        &self.state
+    }
+
+    /// Moves the participant to the state `to`, if the transition is legal.
+    ///
+    /// The participant moves from `WaitingForVoteRequest` to `Voted`, and from `Voted` to `Abort`
+    /// or, if it voted to commit, to `Commit`. From a decision, it moves to `Voted` when it votes
+    /// in the next epoch. This is the only way to change the state once the context is built.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` naming both states if the transition is not legal; the
+    /// state is left unchanged.
+    pub fn try_transition(&mut self, to: ParticipantState) -> Result<(), InvalidStateError> {
+        let legal = matches!(
+            (&self.state, &to),
+            (
+                ParticipantState::WaitingForVoteRequest,
+                ParticipantState::Voted { .. }
+            ) | (
+                ParticipantState::Voted { vote: true },
+                ParticipantState::Commit
+            ) | (ParticipantState::Voted { .. }, ParticipantState::Abort)
+                | (ParticipantState::Commit, ParticipantState::Voted { .. })
+                | (ParticipantState::Abort, ParticipantState::Voted { .. })
+        );
+
+        if !legal {
+            return Err(InvalidStateError::with_message(format!(
+                "illegal participant state transition from {:?} to {:?}",
+                self.state, to
+            )));
+        }
+
+        self.state = to;
+        Ok(())
+    }
+
+    /// Returns the process this context belongs to.
+    pub fn this_process(&self) -> &P {
+        &self.this_process
+    }
+
+    /// Returns the trace id of the current epoch, if it was started with one.
+    pub fn trace_id(&self) -> &Option<TraceId> {
+        &self.trace_id
+    }
+
+    /// Sets the trace id of the current epoch.
+    pub fn set_trace_id(&mut self, trace_id: Option<TraceId>) {
+        self.trace_id = trace_id
+    }
+
+    /// Returns the time at which this participant voted to commit in the current epoch, if it
+    /// has not yet learned the decision.
+    ///
+    /// While uncertain, the participant cannot decide on its own, so this can be used to alert on
+    /// transactions which have been blocked for too long.
+    pub fn uncertain_since(&self) -> &Option<T> {
+        &self.uncertain_since
+    }
+
+    /// Sets or clears the time at which this participant became uncertain.
+    pub fn set_uncertain_since(&mut self, uncertain_since: Option<T>) {
+        self.uncertain_since = uncertain_since
+    }
+}
+
+/// Builds a [`ParticipantContext`].
+///
+/// Unlike [`TwoPhaseCommitContextBuilder`](crate::two_phase_commit::TwoPhaseCommitContextBuilder),
+/// only the fields of a participant can be set, so the built context always has the participant
+/// role.
+pub struct ParticipantContextBuilder<P, T> {
+    alarm: Option<T>,
+    coordinator: Option<P>,
+    epoch: Option<Epoch>,
+    last_commit_epoch: Option<Epoch>,
+    participant_processes: Option<Vec<P>>,
+    require_coordinator_in_participants: bool,
+    state: Option<ParticipantState>,
+    this_process: Option<P>,
+    uncertain_since: Option<T>,
+}
+
+impl<P, T> ParticipantContextBuilder<P, T>
+where
+    P: Process,
+{
+    /// Constructs a new builder with no fields set.
+    pub fn new() -> Self {
+        ParticipantContextBuilder {
+            alarm: None,
+            coordinator: None,
+            epoch: None,
+            last_commit_epoch: None,
+            participant_processes: None,
+            require_coordinator_in_participants: false,
+            state: None,
+            this_process: None,
+            uncertain_since: None,
+        }
+    }
+
+    /// Sets the time at which the alarm expires.
+    pub fn with_alarm(mut self, alarm: T) -> Self {
+        self.alarm = Some(alarm);
+        self
+    }
+
+    /// Sets the coordinator process.
+    pub fn with_coordinator(mut self, coordinator: P) -> Self {
+        self.coordinator = Some(coordinator);
+        self
+    }
+
+    /// Sets the current epoch.
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Sets the last epoch which was committed.
+    pub fn with_last_commit_epoch(mut self, epoch: Epoch) -> Self {
+        self.last_commit_epoch = Some(epoch);
+        self
+    }
+
+    /// Sets the processes which vote in each epoch.
+    pub fn with_participant_processes(mut self, participant_processes: Vec<P>) -> Self {
+        self.participant_processes = Some(participant_processes);
+        self
+    }
+
+    /// Sets whether the coordinator must be one of the participant processes.
+    ///
+    /// This is not required by default, since the coordinator need not vote.
+    pub fn with_require_coordinator_in_participants(mut self, require: bool) -> Self {
+        self.require_coordinator_in_participants = require;
+        self
+    }
+
+    /// Sets the initial state of the participant.
+    pub fn with_state(mut self, state: ParticipantState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Sets the process the context belongs to.
+    pub fn with_this_process(mut self, this_process: P) -> Self {
+        self.this_process = Some(this_process);
+        self
+    }
+
+    /// Sets the time at which this participant became uncertain.
+    pub fn with_uncertain_since(mut self, uncertain_since: T) -> Self {
+        self.uncertain_since = Some(uncertain_since);
+        self
+    }
+
+    /// Builds the context.
+    ///
+    /// The epoch defaults to 0 and the state to `WaitingForVoteRequest`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if `coordinator`, `participant_processes` or
+    /// `this_process` is missing, if `this_process` is not one of the participant processes, if
+    /// the coordinator is required to be a participant process and is not, or if
+    /// `last_commit_epoch` is after `epoch`.
+    pub fn build(self) -> Result<ParticipantContext<P, T>, InvalidStateError> {
+        let coordinator = self.coordinator.ok_or_else(|| {
+            InvalidStateError::with_message("unable to build, missing field: `coordinator`".into())
+        })?;
+
+        let participant_processes = self.participant_processes.ok_or_else(|| {
+            InvalidStateError::with_message(
+                "unable to build, missing field: `participant_processes`".into(),
+            )
+        })?;
+
+        let this_process = self.this_process.ok_or_else(|| {
+            InvalidStateError::with_message("unable to build, missing field: `this_process`".into())
+        })?;
+
+        if !participant_processes.contains(&this_process) {
+            return Err(InvalidStateError::with_message(
+                "unable to build, `this_process` must be one of `participant_processes`".into(),
+            ));
+        }
+
+        if self.require_coordinator_in_participants && !participant_processes.contains(&coordinator)
+        {
+            return Err(InvalidStateError::with_message(
+                "unable to build, `coordinator` must be one of the participants".into(),
+            ));
+        }
+
+        let epoch = self.epoch.unwrap_or(0);
+        check_last_commit_epoch(epoch, self.last_commit_epoch)?;
+
+        Ok(ParticipantContext {
+            alarm: self.alarm,
+            coordinator,
+            epoch,
+            last_commit_epoch: self.last_commit_epoch,
+            participant_processes,
+            state: self
+                .state
+                .unwrap_or(ParticipantState::WaitingForVoteRequest),
+            this_process,
+            trace_id: None,
+            uncertain_since: self.uncertain_since,
+        })
+    }
+}
+
+impl<P, T> Default for ParticipantContextBuilder<P, T>
+where
+    P: Process,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::SystemTime;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    /// Tests that the participant builder produces a participant context with default epoch and
+    /// state, and that it requires the participant processes.
+    #[test]
+    fn test_build() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let context: ParticipantContext<TestProcess, SystemTime> = ParticipantContextBuilder::new()
+            .with_coordinator(p1)
+            .with_this_process(p2)
+            .with_participant_processes(vec![p2])
+            .with_epoch(3)
+            .build()
+            .expect("failed to build context");
+
+        assert_eq!(context.epoch(), &3);
+        assert_eq!(context.state(), &ParticipantState::WaitingForVoteRequest);
+        assert_eq!(context.participant_processes(), &vec![p2]);
+        assert_eq!(context.uncertain_since(), &None);
+
+        let err = ParticipantContextBuilder::<TestProcess, SystemTime>::new()
+            .with_coordinator(p1)
+            .with_this_process(p2)
+            .build()
+            .expect_err("built a context without participant processes");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, missing field: `participant_processes`"
+        );
+    }
+
+    /// Tests that by default the coordinator need not be one of the participant processes, but
+    /// can be required to be.
+    #[test]
+    fn test_build_coordinator_in_participants() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+        let builder = |participant_processes| {
+            ParticipantContextBuilder::<TestProcess, SystemTime>::new()
+                .with_coordinator(p1)
+                .with_this_process(p2)
+                .with_participant_processes(participant_processes)
+        };
+
+        builder(vec![p2]).build().expect("failed to build context");
+
+        let err = builder(vec![p2])
+            .with_require_coordinator_in_participants(true)
+            .build()
+            .expect_err("built a context without the coordinator as a participant");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, `coordinator` must be one of the participants"
+        );
+
+        builder(vec![p1, p2])
+            .with_require_coordinator_in_participants(true)
+            .build()
+            .expect("failed to build context with the coordinator as a participant");
+    }
+
+    /// Tests that a context whose last committed epoch is after its current epoch cannot be
+    /// built.
+    #[test]
+    fn test_build_last_commit_epoch_after_epoch() {
+        let p1 = TestProcess { id: 1 };
+
+        let result = ParticipantContextBuilder::<TestProcess, SystemTime>::new()
+            .with_coordinator(p1)
+            .with_this_process(p1)
+            .with_participant_processes(vec![p1])
+            .with_epoch(1)
+            .with_last_commit_epoch(2)
+            .build();
+        assert!(result.is_err());
+    }
+
+    /// Tests that a participant waiting for a vote request cannot move straight to `Commit`, and
+    /// that it can once it has voted to commit.
+    #[test]
+    fn test_try_transition() {
+        let p1 = TestProcess { id: 1 };
+        let p2 = TestProcess { id: 2 };
+
+        let mut context: ParticipantContext<TestProcess, SystemTime> =
+            ParticipantContextBuilder::new()
+                .with_coordinator(p1)
+                .with_this_process(p2)
+                .with_participant_processes(vec![p2])
+                .build()
+                .expect("failed to build context");
+
+        let err = context
+            .try_transition(ParticipantState::Commit)
+            .expect_err("moved from WaitingForVoteRequest to Commit");
+        assert_eq!(
+            err.to_string(),
+            "illegal participant state transition from WaitingForVoteRequest to Commit"
+        );
+        assert_eq!(context.state(), &ParticipantState::WaitingForVoteRequest);
+
+        context
+            .try_transition(ParticipantState::Voted { vote: false })
+            .expect("failed to vote");
+        assert!(context.try_transition(ParticipantState::Commit).is_err());
+
+        let mut context: ParticipantContext<TestProcess, SystemTime> =
+            ParticipantContextBuilder::new()
+                .with_coordinator(p1)
+                .with_this_process(p2)
+                .with_participant_processes(vec![p2])
+                .with_state(ParticipantState::Voted { vote: true })
+                .build()
+                .expect("failed to build context");
+        context
+            .try_transition(ParticipantState::Commit)
+            .expect("failed to commit");
+        assert_eq!(context.state(), &ParticipantState::Commit);
+        assert!(context.try_transition(ParticipantState::Abort).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ParticipantMessage;
+
+/// An event handled by a two-phase commit participant.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParticipantEvent<P, V> {
+    /// The alarm set in the context has expired.
+    Alarm,
+    /// A message from the coordinator was delivered.
+    Deliver(P, ParticipantMessage<V>),
+}
@@ -0,0 +1,53 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::algorithm::TraceId;
+use crate::message::Message;
+
+use super::super::Epoch;
+
+/// A message delivered to a two-phase commit participant.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParticipantMessage<V> {
+    /// The coordinator requests a vote on committing the value in the epoch, along with the trace
+    /// id the epoch was started with.
+    VoteRequest(Epoch, V, Option<TraceId>),
+    /// The coordinator committed the epoch.
+    Commit(Epoch, Option<TraceId>),
+    /// The coordinator aborted the epoch.
+    Abort(Epoch, Option<TraceId>),
+}
+
+impl<V> Message for ParticipantMessage<V> {}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    /// Tests that each variant of `ParticipantMessage` survives a round trip through JSON.
+    #[test]
+    fn test_serde_round_trip() {
+        for message in [
+            ParticipantMessage::VoteRequest(1, "value".to_string(), Some(TraceId::new("trace-1"))),
+            ParticipantMessage::Commit(1, None),
+            ParticipantMessage::Abort(1, Some(TraceId::new("trace-1"))),
+        ] {
+            let json = serde_json::to_string(&message).expect("failed to serialize");
+            let decoded: ParticipantMessage<String> =
+                serde_json::from_str(&json).expect("failed to deserialize");
+            assert_eq!(decoded, message);
+        }
+    }
+}
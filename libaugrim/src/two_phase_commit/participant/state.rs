@@ -0,0 +1,54 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! States of a two-phase commit participant.
+
+/// The state of a two-phase commit participant.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParticipantState {
+    /// The participant has learned that the current epoch was aborted.
+    Abort,
+    /// The participant has learned that the current epoch was committed.
+    Commit,
+    /// The participant has voted and is waiting for the coordinator's decision.
+    Voted {
+        /// Whether the participant voted to commit.
+        vote: bool,
+    },
+    /// The participant is waiting for the coordinator to request a vote.
+    WaitingForVoteRequest,
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    /// Tests that each variant of `ParticipantState` survives a round trip through JSON.
+    #[test]
+    fn test_serde_round_trip() {
+        for state in [
+            ParticipantState::Abort,
+            ParticipantState::Commit,
+            ParticipantState::Voted { vote: true },
+            ParticipantState::Voted { vote: false },
+            ParticipantState::WaitingForVoteRequest,
+        ] {
+            let json = serde_json::to_string(&state).expect("failed to serialize");
+            let decoded: ParticipantState =
+                serde_json::from_str(&json).expect("failed to deserialize");
+            assert_eq!(decoded, state);
+        }
+    }
+}
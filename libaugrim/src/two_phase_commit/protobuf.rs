@@ -0,0 +1,522 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encoding of two-phase commit messages as bytes.
+//!
+//! Messages are encoded in the protobuf wire format described by
+//! `protos/two_phase_commit.proto`, so that processes written in other languages can exchange
+//! messages with this implementation. The field numbers of the `oneof` in
+//! `TwoPhaseCommitMessage` are the stable tags of the message variants.
+//!
+//! Every variant carries its epoch as field 1. A vote request carries its value as field 2, and a
+//! vote response its vote as field 2. Field 3 of every variant is the trace id, which is optional
+//! and omitted when the message has no trace id. Unknown fields are skipped when decoding.
+
+use std::convert::TryFrom;
+
+use crate::algorithm::TraceId;
+use crate::error::InternalError;
+
+use super::coordinator::CoordinatorMessage;
+use super::participant::ParticipantMessage;
+use super::{Epoch, TwoPhaseCommitMessage};
+
+const WIRE_TYPE_VARINT: u64 = 0;
+const WIRE_TYPE_FIXED_64: u64 = 1;
+const WIRE_TYPE_LENGTH_DELIMITED: u64 = 2;
+const WIRE_TYPE_FIXED_32: u64 = 5;
+
+const TAG_VOTE_REQUEST: u64 = 1;
+const TAG_VOTE_RESPONSE: u64 = 2;
+const TAG_COMMIT: u64 = 3;
+const TAG_ABORT: u64 = 4;
+
+const FIELD_EPOCH: u64 = 1;
+const FIELD_VALUE: u64 = 2;
+const FIELD_VOTE: u64 = 2;
+const FIELD_TRACE_ID: u64 = 3;
+
+/// A value which can be carried in an encoded vote request.
+pub trait BytesValue: Sized {
+    /// Returns the encoded value.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Decodes a value previously encoded with [`BytesValue::to_bytes`].
+    fn from_bytes(bytes: &[u8]) -> Result<Self, InternalError>;
+}
+
+impl BytesValue for Vec<u8> {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, InternalError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl BytesValue for String {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, InternalError> {
+        String::from_utf8(bytes.to_vec()).map_err(|err| {
+            InternalError::from_source_with_prefix(Box::new(err), "invalid string value".into())
+        })
+    }
+}
+
+impl<V> TwoPhaseCommitMessage<V>
+where
+    V: BytesValue,
+{
+    /// Encodes the message as bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, InternalError> {
+        let mut inner = Vec::new();
+        let (tag, trace_id) = match self {
+            TwoPhaseCommitMessage::VoteRequest(epoch, value, trace_id) => {
+                write_varint_field(&mut inner, FIELD_EPOCH, *epoch);
+                write_bytes_field(&mut inner, FIELD_VALUE, &value.to_bytes());
+                (TAG_VOTE_REQUEST, trace_id)
+            }
+            TwoPhaseCommitMessage::VoteResponse(epoch, vote, trace_id) => {
+                write_varint_field(&mut inner, FIELD_EPOCH, *epoch);
+                write_varint_field(&mut inner, FIELD_VOTE, u64::from(*vote));
+                (TAG_VOTE_RESPONSE, trace_id)
+            }
+            TwoPhaseCommitMessage::Commit(epoch, trace_id) => {
+                write_varint_field(&mut inner, FIELD_EPOCH, *epoch);
+                (TAG_COMMIT, trace_id)
+            }
+            TwoPhaseCommitMessage::Abort(epoch, trace_id) => {
+                write_varint_field(&mut inner, FIELD_EPOCH, *epoch);
+                (TAG_ABORT, trace_id)
+            }
+        };
+        if let Some(trace_id) = trace_id {
+            write_bytes_field(&mut inner, FIELD_TRACE_ID, trace_id.as_str().as_bytes());
+        }
+
+        let mut bytes = Vec::with_capacity(inner.len() + 4);
+        write_bytes_field(&mut bytes, tag, &inner);
+        Ok(bytes)
+    }
+
+    /// Decodes a message previously encoded with [`TwoPhaseCommitMessage::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InternalError` if the bytes are truncated or otherwise malformed, or if the
+    /// variant tag is unknown.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InternalError> {
+        let mut reader = Reader::new(bytes);
+
+        let (tag, inner) = match reader.read_field()? {
+            (tag, Field::Bytes(inner)) => (tag, inner),
+            (tag, _) => {
+                return Err(InternalError::with_message(format!(
+                    "unknown two-phase commit message variant tag {}",
+                    tag
+                )))
+            }
+        };
+
+        if !reader.is_empty() {
+            return Err(InternalError::with_message(
+                "unexpected data after two-phase commit message".into(),
+            ));
+        }
+
+        let fields = InnerFields::read(tag, inner)?;
+        let trace_id = fields
+            .trace_id
+            .map(|bytes| String::from_bytes(bytes).map(TraceId::new))
+            .transpose()?;
+
+        match tag {
+            TAG_VOTE_REQUEST => Ok(TwoPhaseCommitMessage::VoteRequest(
+                fields.epoch,
+                V::from_bytes(fields.value.unwrap_or(&[]))?,
+                trace_id,
+            )),
+            TAG_VOTE_RESPONSE => Ok(TwoPhaseCommitMessage::VoteResponse(
+                fields.epoch,
+                fields.vote.unwrap_or(0) != 0,
+                trace_id,
+            )),
+            TAG_COMMIT => Ok(TwoPhaseCommitMessage::Commit(fields.epoch, trace_id)),
+            TAG_ABORT => Ok(TwoPhaseCommitMessage::Abort(fields.epoch, trace_id)),
+            _ => Err(InternalError::with_message(format!(
+                "unknown two-phase commit message variant tag {}",
+                tag
+            ))),
+        }
+    }
+}
+
+impl CoordinatorMessage {
+    /// Encodes the message as bytes, in the same format as [`TwoPhaseCommitMessage`].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, InternalError> {
+        TwoPhaseCommitMessage::<Vec<u8>>::from(self.clone()).to_bytes()
+    }
+
+    /// Decodes a message encoded as a [`TwoPhaseCommitMessage`], returning an error if the
+    /// message is not one which is delivered to the coordinator.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InternalError> {
+        CoordinatorMessage::try_from(TwoPhaseCommitMessage::<Vec<u8>>::from_bytes(bytes)?)
+            .map_err(|err| InternalError::from_source(Box::new(err)))
+    }
+}
+
+impl<V> ParticipantMessage<V>
+where
+    V: BytesValue + Clone,
+{
+    /// Encodes the message as bytes, in the same format as [`TwoPhaseCommitMessage`].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, InternalError> {
+        TwoPhaseCommitMessage::from(self.clone()).to_bytes()
+    }
+
+    /// Decodes a message encoded as a [`TwoPhaseCommitMessage`], returning an error if the
+    /// message is not one which is delivered to a participant.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InternalError> {
+        ParticipantMessage::try_from(TwoPhaseCommitMessage::from_bytes(bytes)?)
+            .map_err(|err| InternalError::from_source(Box::new(err)))
+    }
+}
+
+/// The fields of the message nested within the `oneof`; every variant has an epoch and an
+/// optional trace id, a vote request also has a value, and a vote response also has a vote.
+struct InnerFields<'a> {
+    epoch: Epoch,
+    value: Option<&'a [u8]>,
+    vote: Option<u64>,
+    trace_id: Option<&'a [u8]>,
+}
+
+impl<'a> InnerFields<'a> {
+    /// Reads the fields of the variant with the given tag, skipping fields it does not define.
+    fn read(tag: u64, bytes: &'a [u8]) -> Result<Self, InternalError> {
+        let mut reader = Reader::new(bytes);
+        let mut fields = InnerFields {
+            epoch: 0,
+            value: None,
+            vote: None,
+            trace_id: None,
+        };
+
+        while !reader.is_empty() {
+            match reader.read_field()? {
+                (FIELD_EPOCH, Field::Varint(epoch)) => fields.epoch = epoch,
+                (FIELD_EPOCH, _) => {
+                    return Err(InternalError::with_message(
+                        "epoch field has the wrong wire type".into(),
+                    ))
+                }
+                (FIELD_VALUE, Field::Bytes(value)) if tag == TAG_VOTE_REQUEST => {
+                    fields.value = Some(value)
+                }
+                (FIELD_VALUE, _) if tag == TAG_VOTE_REQUEST => {
+                    return Err(InternalError::with_message(
+                        "value field has the wrong wire type".into(),
+                    ))
+                }
+                (FIELD_VOTE, Field::Varint(vote)) if tag == TAG_VOTE_RESPONSE => {
+                    fields.vote = Some(vote)
+                }
+                (FIELD_VOTE, _) if tag == TAG_VOTE_RESPONSE => {
+                    return Err(InternalError::with_message(
+                        "vote field has the wrong wire type".into(),
+                    ))
+                }
+                (FIELD_TRACE_ID, Field::Bytes(bytes)) => fields.trace_id = Some(bytes),
+                (FIELD_TRACE_ID, _) => {
+                    return Err(InternalError::with_message(
+                        "trace id field has the wrong wire type".into(),
+                    ))
+                }
+                // Unknown fields are skipped for forward compatibility
+                _ => (),
+            }
+        }
+
+        Ok(fields)
+    }
+}
+
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn read_field(&mut self) -> Result<(u64, Field<'a>), InternalError> {
+        let key = self.read_varint()?;
+        let field = key >> 3;
+
+        match key & 0x7 {
+            WIRE_TYPE_VARINT => Ok((field, Field::Varint(self.read_varint()?))),
+            WIRE_TYPE_LENGTH_DELIMITED => {
+                let len = usize::try_from(self.read_varint()?).map_err(|_| {
+                    InternalError::with_message("length-delimited field is too long".into())
+                })?;
+                Ok((field, Field::Bytes(self.take(len)?)))
+            }
+            WIRE_TYPE_FIXED_64 => {
+                self.take(8)?;
+                Ok((field, Field::Fixed))
+            }
+            WIRE_TYPE_FIXED_32 => {
+                self.take(4)?;
+                Ok((field, Field::Fixed))
+            }
+            wire_type => Err(InternalError::with_message(format!(
+                "unsupported wire type {}",
+                wire_type
+            ))),
+        }
+    }
+
+    fn read_varint(&mut self) -> Result<u64, InternalError> {
+        let mut value: u64 = 0;
+
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = self
+                .bytes
+                .split_first()
+                .ok_or_else(|| InternalError::with_message("truncated varint".into()))?;
+            self.bytes = rest;
+
+            // The tenth byte holds only the most significant bit of a 64-bit value
+            if shift == 63 && *byte > 1 {
+                return Err(InternalError::with_message(
+                    "varint overflows 64 bits".into(),
+                ));
+            }
+
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(InternalError::with_message("varint is too long".into()))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], InternalError> {
+        if self.bytes.len() < len {
+            return Err(InternalError::with_message(format!(
+                "truncated field: expected {} bytes, found {}",
+                len,
+                self.bytes.len()
+            )));
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_varint_field(bytes: &mut Vec<u8>, field: u64, value: u64) {
+    write_varint(bytes, (field << 3) | WIRE_TYPE_VARINT);
+    write_varint(bytes, value);
+}
+
+fn write_bytes_field(bytes: &mut Vec<u8>, field: u64, value: &[u8]) {
+    write_varint(bytes, (field << 3) | WIRE_TYPE_LENGTH_DELIMITED);
+    write_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that every variant of `TwoPhaseCommitMessage`, including large epochs, survives an
+    /// encode/decode round trip.
+    #[test]
+    fn test_round_trip() {
+        for message in [
+            TwoPhaseCommitMessage::VoteRequest(1, "value".to_string(), None),
+            TwoPhaseCommitMessage::VoteRequest(0, String::new(), Some(TraceId::new("trace-1"))),
+            TwoPhaseCommitMessage::VoteResponse(u64::MAX, true, None),
+            TwoPhaseCommitMessage::VoteResponse(2, false, Some(TraceId::new("trace-1"))),
+            TwoPhaseCommitMessage::Commit(300, Some(TraceId::new(""))),
+            TwoPhaseCommitMessage::Abort(4, None),
+        ] {
+            let bytes = message.to_bytes().expect("failed to encode");
+            let decoded =
+                TwoPhaseCommitMessage::<String>::from_bytes(&bytes).expect("failed to decode");
+            assert_eq!(decoded, message);
+        }
+    }
+
+    /// Tests the exact encoding of a message, which must remain stable.
+    #[test]
+    fn test_wire_format() {
+        let bytes = TwoPhaseCommitMessage::<Vec<u8>>::Commit(1, None)
+            .to_bytes()
+            .expect("failed to encode");
+        assert_eq!(bytes, vec![0x1a, 0x02, 0x08, 0x01]);
+
+        let bytes = TwoPhaseCommitMessage::VoteRequest(2, vec![0xff], None)
+            .to_bytes()
+            .expect("failed to encode");
+        assert_eq!(bytes, vec![0x0a, 0x05, 0x08, 0x02, 0x12, 0x01, 0xff]);
+
+        let bytes = TwoPhaseCommitMessage::<Vec<u8>>::Commit(1, Some(TraceId::new("t")))
+            .to_bytes()
+            .expect("failed to encode");
+        assert_eq!(bytes, vec![0x1a, 0x05, 0x08, 0x01, 0x1a, 0x01, b't']);
+    }
+
+    /// Tests that a trace id is only added to the end of the nested message, after the fields a
+    /// decoder which predates it reads, so that such a decoder skips it as an unknown field.
+    #[test]
+    fn test_trace_id_appended() {
+        let without = TwoPhaseCommitMessage::VoteRequest(7, "value".to_string(), None)
+            .to_bytes()
+            .expect("failed to encode");
+        let with = TwoPhaseCommitMessage::VoteRequest(
+            7,
+            "value".to_string(),
+            Some(TraceId::new("trace-1")),
+        )
+        .to_bytes()
+        .expect("failed to encode");
+
+        // The variant tag and length of `without` each take one byte
+        let mut inner = without[2..].to_vec();
+        write_bytes_field(&mut inner, FIELD_TRACE_ID, b"trace-1");
+        let mut expected = Vec::new();
+        write_bytes_field(&mut expected, TAG_VOTE_REQUEST, &inner);
+        assert_eq!(with, expected);
+    }
+
+    /// Tests that the coordinator and participant messages encode as the equivalent
+    /// `TwoPhaseCommitMessage`, and that each refuses to decode a message meant for the other
+    /// role.
+    #[test]
+    fn test_role_messages() {
+        let message = CoordinatorMessage::VoteResponse(5, true, None);
+        let bytes = message.to_bytes().expect("failed to encode");
+        assert_eq!(
+            CoordinatorMessage::from_bytes(&bytes).expect("failed to decode"),
+            message
+        );
+        assert!(ParticipantMessage::<String>::from_bytes(&bytes).is_err());
+
+        let message = ParticipantMessage::VoteRequest(5, "value".to_string(), None);
+        let bytes = message.to_bytes().expect("failed to encode");
+        assert_eq!(
+            ParticipantMessage::from_bytes(&bytes).expect("failed to decode"),
+            message
+        );
+        assert!(CoordinatorMessage::from_bytes(&bytes).is_err());
+    }
+
+    /// Tests that malformed input returns an error rather than panicking: an unknown variant tag,
+    /// every possible truncation of a valid message, trailing data, and empty input.
+    #[test]
+    fn test_malformed_input() {
+        let err = TwoPhaseCommitMessage::<String>::from_bytes(&[0x4a, 0x02, 0x08, 0x01])
+            .expect_err("unknown tag was decoded");
+        assert_eq!(
+            err.to_string(),
+            "unknown two-phase commit message variant tag 9"
+        );
+
+        let bytes = TwoPhaseCommitMessage::VoteRequest(300, "value".to_string(), None)
+            .to_bytes()
+            .expect("failed to encode");
+        for len in 0..bytes.len() {
+            assert!(
+                TwoPhaseCommitMessage::<String>::from_bytes(&bytes[..len]).is_err(),
+                "truncated message of length {} was decoded",
+                len
+            );
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        assert!(TwoPhaseCommitMessage::<String>::from_bytes(&trailing).is_err());
+
+        assert!(TwoPhaseCommitMessage::<String>::from_bytes(&[0x80; 11]).is_err());
+    }
+
+    /// Tests that a value or vote field with the wrong wire type is rejected, while the same
+    /// field number is skipped in a variant which does not define it.
+    #[test]
+    fn test_wrong_field_wire_type() {
+        // A vote request whose value is a varint
+        let err =
+            TwoPhaseCommitMessage::<String>::from_bytes(&[0x0a, 0x04, 0x08, 0x01, 0x10, 0x01])
+                .expect_err("varint value was decoded");
+        assert_eq!(err.to_string(), "value field has the wrong wire type");
+
+        // A vote response whose vote is length-delimited
+        let err = TwoPhaseCommitMessage::<String>::from_bytes(&[
+            0x12, 0x05, 0x08, 0x01, 0x12, 0x01, 0x01,
+        ])
+        .expect_err("length-delimited vote was decoded");
+        assert_eq!(err.to_string(), "vote field has the wrong wire type");
+
+        // A commit with a field numbered like the value
+        let decoded = TwoPhaseCommitMessage::<String>::from_bytes(&[
+            0x1a, 0x05, 0x08, 0x01, 0x12, 0x01, 0x01,
+        ])
+        .expect("failed to decode");
+        assert_eq!(decoded, TwoPhaseCommitMessage::Commit(1, None));
+    }
+
+    /// Tests that a ten-byte varint whose last byte sets bits beyond the 64th is rejected, and
+    /// that the largest valid ten-byte varint is accepted.
+    #[test]
+    fn test_varint_overflow() {
+        let mut bytes = vec![0x1a, 0x0b, 0x08];
+        bytes.extend_from_slice(&[0xff; 9]);
+        bytes.push(0x02);
+        let err =
+            TwoPhaseCommitMessage::<String>::from_bytes(&bytes).expect_err("overflow was decoded");
+        assert_eq!(err.to_string(), "varint overflows 64 bits");
+
+        bytes[12] = 0x01;
+        let decoded =
+            TwoPhaseCommitMessage::<String>::from_bytes(&bytes).expect("failed to decode");
+        assert_eq!(decoded, TwoPhaseCommitMessage::Commit(u64::MAX, None));
+    }
+}
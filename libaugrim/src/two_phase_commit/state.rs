@@ -0,0 +1,92 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A state which may belong to either two-phase commit role.
+
+use std::convert::TryFrom;
+
+use crate::error::InvalidStateError;
+
+use super::coordinator::CoordinatorState;
+use super::participant::ParticipantState;
+
+/// The state of a process in two-phase commit, regardless of its role.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TwoPhaseCommitState {
+    /// The state of a process in the coordinator role.
+    Coordinator(CoordinatorState),
+    /// The state of a process in the participant role.
+    Participant(ParticipantState),
+}
+
+impl From<CoordinatorState> for TwoPhaseCommitState {
+    fn from(state: CoordinatorState) -> Self {
+        TwoPhaseCommitState::Coordinator(state)
+    }
+}
+
+impl From<ParticipantState> for TwoPhaseCommitState {
+    fn from(state: ParticipantState) -> Self {
+        TwoPhaseCommitState::Participant(state)
+    }
+}
+
+impl TryFrom<TwoPhaseCommitState> for CoordinatorState {
+    type Error = InvalidStateError;
+
+    fn try_from(state: TwoPhaseCommitState) -> Result<Self, Self::Error> {
+        match state {
+            TwoPhaseCommitState::Coordinator(state) => Ok(state),
+            TwoPhaseCommitState::Participant(_) => Err(InvalidStateError::with_message(
+                "cannot convert a participant state into a coordinator state".into(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<TwoPhaseCommitState> for ParticipantState {
+    type Error = InvalidStateError;
+
+    fn try_from(state: TwoPhaseCommitState) -> Result<Self, Self::Error> {
+        match state {
+            TwoPhaseCommitState::Participant(state) => Ok(state),
+            TwoPhaseCommitState::Coordinator(_) => Err(InvalidStateError::with_message(
+                "cannot convert a coordinator state into a participant state".into(),
+            )),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    /// Tests that each variant of `TwoPhaseCommitState` survives a round trip through JSON.
+    #[test]
+    fn test_serde_round_trip() {
+        let states: [TwoPhaseCommitState; 4] = [
+            CoordinatorState::Voting.into(),
+            CoordinatorState::Commit.into(),
+            ParticipantState::Voted { vote: true }.into(),
+            ParticipantState::Abort.into(),
+        ];
+        for state in states {
+            let json = serde_json::to_string(&state).expect("failed to serialize");
+            let decoded: TwoPhaseCommitState =
+                serde_json::from_str(&json).expect("failed to deserialize");
+            assert_eq!(decoded, state);
+        }
+    }
+}
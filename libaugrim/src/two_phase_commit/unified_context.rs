@@ -0,0 +1,803 @@
+// Copyright 2021 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A two-phase commit context which may represent either role.
+
+use std::convert::TryFrom;
+
+use crate::algorithm::TraceId;
+use crate::error::InvalidStateError;
+use crate::process::Process;
+
+use super::coordinator::{CoordinatorContext, CoordinatorState, Participant};
+use super::participant::{ParticipantContext, ParticipantState};
+use super::{check_last_commit_epoch, Epoch, TwoPhaseCommitState};
+
+/// The context of a process participating in two-phase commit, in either the coordinator or the
+/// participant role.
+///
+/// A coordinator context has `participants` (which track votes), while a participant context has
+/// `participant_processes`; exactly one of the two is set.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TwoPhaseCommitContext<P, T> {
+    alarm: Option<T>,
+    coordinator: P,
+    epoch: Epoch,
+    last_commit_epoch: Option<Epoch>,
+    participants: Option<Vec<Participant<P>>>,
+    participant_processes: Option<Vec<P>>,
+    state: TwoPhaseCommitState,
+    this_process: P,
+    #[cfg_attr(feature = "serde", serde(default))]
+    trace_id: Option<TraceId>,
+    uncertain_since: Option<T>,
+}
+
+impl<P, T> TwoPhaseCommitContext<P, T>
+where
+    P: Process,
+{
+    /// Returns the time at which the alarm expires, if one is set.
+    pub fn alarm(&self) -> &Option<T> {
+        &self.alarm
+    }
+
+    /// Sets or clears the alarm.
+    pub fn set_alarm(&mut self, alarm: Option<T>) {
+        self.alarm = alarm
+    }
+
+    /// Returns the coordinator process.
+    pub fn coordinator(&self) -> &P {
+        &self.coordinator
+    }
+
+    /// Returns the current epoch.
+    pub fn epoch(&self) -> &Epoch {
+        &self.epoch
+    }
+
+    /// Returns the last epoch which was committed, if any.
+    pub fn last_commit_epoch(&self) -> &Option<Epoch> {
+        &self.last_commit_epoch
+    }
+
+    /// Returns the participants and their votes, if this is a coordinator context.
+    pub fn participants(&self) -> &Option<Vec<Participant<P>>> {
+        &self.participants
+    }
+
+    /// Returns the processes which vote in each epoch, if this is a participant context.
+    pub fn participant_processes(&self) -> &Option<Vec<P>> {
+        &self.participant_processes
+    }
+
+    /// Returns the state of the process, which also gives its role.
+    pub fn state(&self) -> &TwoPhaseCommitState {
+        &self.state
+    }
+
+    /// Returns the process this context belongs to.
+    pub fn this_process(&self) -> &P {
+        &self.this_process
+    }
+
+    /// Returns the trace id of the current epoch, if it was started with one.
+    pub fn trace_id(&self) -> &Option<TraceId> {
+        &self.trace_id
+    }
+
+    /// Returns the time at which a participant became uncertain; always `None` for a
+    /// coordinator.
+    pub fn uncertain_since(&self) -> &Option<T> {
+        &self.uncertain_since
+    }
+
+    /// Returns a snapshot of the hard state of this context.
+    ///
+    /// The alarm is soft state and is excluded, so a restored context has no alarm until the
+    /// algorithm sets one again. The time a participant became uncertain cannot be recomputed
+    /// after a restart, so it is included. Only the snapshot needs to be persisted.
+    pub fn durable_snapshot(&self) -> TwoPhaseCommitSnapshot<P, T>
+    where
+        T: Clone,
+    {
+        TwoPhaseCommitSnapshot {
+            coordinator: self.coordinator,
+            epoch: self.epoch,
+            last_commit_epoch: self.last_commit_epoch,
+            participants: self.participants.clone(),
+            participant_processes: self.participant_processes.clone(),
+            state: self.state.clone(),
+            this_process: self.this_process,
+            trace_id: self.trace_id.clone(),
+            uncertain_since: self.uncertain_since.clone(),
+        }
+    }
+}
+
+/// The hard state of a [`TwoPhaseCommitContext`], which must be persisted to survive a restart.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TwoPhaseCommitSnapshot<P, T> {
+    coordinator: P,
+    epoch: Epoch,
+    last_commit_epoch: Option<Epoch>,
+    participants: Option<Vec<Participant<P>>>,
+    participant_processes: Option<Vec<P>>,
+    state: TwoPhaseCommitState,
+    this_process: P,
+    #[cfg_attr(feature = "serde", serde(default))]
+    trace_id: Option<TraceId>,
+    uncertain_since: Option<T>,
+}
+
+impl<P, T> TwoPhaseCommitSnapshot<P, T>
+where
+    P: Process,
+{
+    /// Returns the coordinator process.
+    pub fn coordinator(&self) -> &P {
+        &self.coordinator
+    }
+
+    /// Returns the current epoch.
+    pub fn epoch(&self) -> &Epoch {
+        &self.epoch
+    }
+
+    /// Returns the last epoch which was committed, if any.
+    pub fn last_commit_epoch(&self) -> &Option<Epoch> {
+        &self.last_commit_epoch
+    }
+
+    /// Returns the participants and their votes, if this is a snapshot of a coordinator context.
+    pub fn participants(&self) -> &Option<Vec<Participant<P>>> {
+        &self.participants
+    }
+
+    /// Returns the processes which vote in each epoch, if this is a snapshot of a participant
+    /// context.
+    pub fn participant_processes(&self) -> &Option<Vec<P>> {
+        &self.participant_processes
+    }
+
+    /// Returns the state of the process, which also gives its role.
+    pub fn state(&self) -> &TwoPhaseCommitState {
+        &self.state
+    }
+
+    /// Returns the process the snapshot belongs to.
+    pub fn this_process(&self) -> &P {
+        &self.this_process
+    }
+
+    /// Returns the trace id of the current epoch, if it was started with one.
+    pub fn trace_id(&self) -> &Option<TraceId> {
+        &self.trace_id
+    }
+
+    /// Returns the time at which a participant became uncertain; always `None` for a
+    /// coordinator.
+    pub fn uncertain_since(&self) -> &Option<T> {
+        &self.uncertain_since
+    }
+}
+
+impl<P, T> From<TwoPhaseCommitSnapshot<P, T>> for TwoPhaseCommitContext<P, T> {
+    /// Restores a context from its snapshot; the restored context has no alarm set.
+    fn from(snapshot: TwoPhaseCommitSnapshot<P, T>) -> Self {
+        TwoPhaseCommitContext {
+            alarm: None,
+            coordinator: snapshot.coordinator,
+            epoch: snapshot.epoch,
+            last_commit_epoch: snapshot.last_commit_epoch,
+            participants: snapshot.participants,
+            participant_processes: snapshot.participant_processes,
+            state: snapshot.state,
+            this_process: snapshot.this_process,
+            trace_id: snapshot.trace_id,
+            uncertain_since: snapshot.uncertain_since,
+        }
+    }
+}
+
+impl<P, T> From<CoordinatorContext<P, T>> for TwoPhaseCommitContext<P, T> {
+    fn from(context: CoordinatorContext<P, T>) -> Self {
+        TwoPhaseCommitContext {
+            alarm: context.alarm,
+            coordinator: context.coordinator,
+            epoch: context.epoch,
+            last_commit_epoch: context.last_commit_epoch,
+            participants: Some(context.participants),
+            participant_processes: None,
+            state: context.state.into(),
+            this_process: context.this_process,
+            trace_id: context.trace_id,
+            uncertain_since: None,
+        }
+    }
+}
+
+impl<P, T> From<ParticipantContext<P, T>> for TwoPhaseCommitContext<P, T> {
+    fn from(context: ParticipantContext<P, T>) -> Self {
+        TwoPhaseCommitContext {
+            alarm: context.alarm,
+            coordinator: context.coordinator,
+            epoch: context.epoch,
+            last_commit_epoch: context.last_commit_epoch,
+            participants: None,
+            participant_processes: Some(context.participant_processes),
+            state: context.state.into(),
+            this_process: context.this_process,
+            trace_id: context.trace_id,
+            uncertain_since: context.uncertain_since,
+        }
+    }
+}
+
+impl<P, T> TryFrom<TwoPhaseCommitContext<P, T>> for CoordinatorContext<P, T> {
+    type Error = InvalidStateError;
+
+    fn try_from(context: TwoPhaseCommitContext<P, T>) -> Result<Self, Self::Error> {
+        let participants = context.participants.ok_or_else(|| {
+            InvalidStateError::with_message(
+                "cannot convert to a coordinator context without participants".into(),
+            )
+        })?;
+
+        Ok(CoordinatorContext {
+            alarm: context.alarm,
+            coordinator: context.coordinator,
+            epoch: context.epoch,
+            last_commit_epoch: context.last_commit_epoch,
+            participants,
+            state: CoordinatorState::try_from(context.state)?,
+            this_process: context.this_process,
+            trace_id: context.trace_id,
+        })
+    }
+}
+
+impl<P, T> TryFrom<TwoPhaseCommitContext<P, T>> for ParticipantContext<P, T> {
+    type Error = InvalidStateError;
+
+    fn try_from(context: TwoPhaseCommitContext<P, T>) -> Result<Self, Self::Error> {
+        let participant_processes = context.participant_processes.ok_or_else(|| {
+            InvalidStateError::with_message(
+                "cannot convert to a participant context without participant processes".into(),
+            )
+        })?;
+
+        Ok(ParticipantContext {
+            alarm: context.alarm,
+            coordinator: context.coordinator,
+            epoch: context.epoch,
+            last_commit_epoch: context.last_commit_epoch,
+            participant_processes,
+            state: ParticipantState::try_from(context.state)?,
+            this_process: context.this_process,
+            trace_id: context.trace_id,
+            uncertain_since: context.uncertain_since,
+        })
+    }
+}
+
+/// Builds a [`TwoPhaseCommitContext`].
+///
+/// This builder is for when the role is only known at runtime. When it is known statically,
+/// [`CoordinatorContextBuilder`](super::CoordinatorContextBuilder) and
+/// [`ParticipantContextBuilder`](super::ParticipantContextBuilder) only accept the fields of that
+/// role.
+pub struct TwoPhaseCommitContextBuilder<P, T> {
+    alarm: Option<T>,
+    coordinator: Option<P>,
+    epoch: Option<Epoch>,
+    last_commit_epoch: Option<Epoch>,
+    participants: Option<Vec<Participant<P>>>,
+    participant_processes: Option<Vec<P>>,
+    require_coordinator_in_participants: Option<bool>,
+    state: Option<TwoPhaseCommitState>,
+    this_process: Option<P>,
+    uncertain_since: Option<T>,
+}
+
+impl<P, T> TwoPhaseCommitContextBuilder<P, T>
+where
+    P: Process,
+{
+    /// Constructs a new builder with no fields set.
+    pub fn new() -> Self {
+        TwoPhaseCommitContextBuilder {
+            alarm: None,
+            coordinator: None,
+            epoch: None,
+            last_commit_epoch: None,
+            participants: None,
+            participant_processes: None,
+            require_coordinator_in_participants: None,
+            state: None,
+            this_process: None,
+            uncertain_since: None,
+        }
+    }
+
+    /// Sets the time at which the alarm expires.
+    pub fn with_alarm(mut self, alarm: T) -> Self {
+        self.alarm = Some(alarm);
+        self
+    }
+
+    /// Sets the coordinator process.
+    pub fn with_coordinator(mut self, coordinator: P) -> Self {
+        self.coordinator = Some(coordinator);
+        self
+    }
+
+    /// Sets the current epoch.
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Sets the last epoch which was committed.
+    pub fn with_last_commit_epoch(mut self, epoch: Epoch) -> Self {
+        self.last_commit_epoch = Some(epoch);
+        self
+    }
+
+    /// Sets the participants, which makes the context a coordinator context.
+    pub fn with_participants(mut self, participants: Vec<Participant<P>>) -> Self {
+        self.participants = Some(participants);
+        self
+    }
+
+    /// Sets the processes which vote in each epoch, which makes the context a participant context.
+    pub fn with_participant_processes(mut self, participant_processes: Vec<P>) -> Self {
+        self.participant_processes = Some(participant_processes);
+        self
+    }
+
+    /// Sets whether the coordinator must be one of the participants, or of the participant
+    /// processes.
+    ///
+    /// By default this is required of a coordinator context, and not of a participant context.
+    pub fn with_require_coordinator_in_participants(mut self, require: bool) -> Self {
+        self.require_coordinator_in_participants = Some(require);
+        self
+    }
+
+    /// Sets the initial state of the process.
+    pub fn with_state(mut self, state: TwoPhaseCommitState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Sets the process the context belongs to.
+    pub fn with_this_process(mut self, this_process: P) -> Self {
+        self.this_process = Some(this_process);
+        self
+    }
+
+    /// Sets the time at which a participant became uncertain.
+    pub fn with_uncertain_since(mut self, uncertain_since: T) -> Self {
+        self.uncertain_since = Some(uncertain_since);
+        self
+    }
+
+    /// Builds the context.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidStateError` if a required field is missing, if both or neither of
+    /// `participants` and `participant_processes` are set, or if the state does not belong to the
+    /// role implied by the participant field which was set, if `this_process` is not the
+    /// coordinator of a coordinator context or not one of the participant processes of a
+    /// participant context, if the coordinator is required to be a participant and is not, if
+    /// `uncertain_since` is set for a coordinator, or if `last_commit_epoch` is after `epoch`.
+    pub fn build(self) -> Result<TwoPhaseCommitContext<P, T>, InvalidStateError> {
+        let coordinator = self.coordinator.ok_or_else(|| {
+            InvalidStateError::with_message("unable to build, missing field: `coordinator`".into())
+        })?;
+
+        let this_process = self.this_process.ok_or_else(|| {
+            InvalidStateError::with_message("unable to build, missing field: `this_process`".into())
+        })?;
+
+        let epoch = self.epoch.unwrap_or(0);
+        check_last_commit_epoch(epoch, self.last_commit_epoch)?;
+
+        let state =
+            match (&self.participants, &self.participant_processes, self.state) {
+                (Some(_), Some(_), _) => {
+                    return Err(InvalidStateError::with_message(
+                        "unable to build, only one of `participants` and `participant_processes` \
+                     may be set"
+                            .into(),
+                    ))
+                }
+                (None, None, _) => return Err(InvalidStateError::with_message(
+                    "unable to build, one of `participants` and `participant_processes` must be \
+                     set"
+                    .into(),
+                )),
+                (Some(_), None, None) => CoordinatorState::WaitingForStart.into(),
+                (None, Some(_), None) => ParticipantState::WaitingForVoteRequest.into(),
+                (Some(_), None, Some(state @ TwoPhaseCommitState::Coordinator(_))) => state,
+                (None, Some(_), Some(state @ TwoPhaseCommitState::Participant(_))) => state,
+                (_, _, Some(_)) => {
+                    return Err(InvalidStateError::with_message(
+                        "unable to build, `state` does not match the role implied by the \
+                     participant fields"
+                            .into(),
+                    ))
+                }
+            };
+
+        if self.participants.is_some() && this_process != coordinator {
+            return Err(InvalidStateError::with_message(
+                "unable to build, `this_process` must be the coordinator".into(),
+            ));
+        }
+
+        if let Some(participant_processes) = &self.participant_processes {
+            if !participant_processes.contains(&this_process) {
+                return Err(InvalidStateError::with_message(
+                    "unable to build, `this_process` must be one of `participant_processes`".into(),
+                ));
+            }
+        }
+
+        let is_coordinator = self.participants.is_some();
+        if self
+            .require_coordinator_in_participants
+            .unwrap_or(is_coordinator)
+        {
+            let is_participant = match (&self.participants, &self.participant_processes) {
+                (Some(participants), _) => participants
+                    .iter()
+                    .any(|participant| participant.process() == &coordinator),
+                (None, Some(participant_processes)) => participant_processes.contains(&coordinator),
+                (None, None) => false,
+            };
+            if !is_participant {
+                return Err(InvalidStateError::with_message(
+                    "unable to build, `coordinator` must be one of the participants".into(),
+                ));
+            }
+        }
+
+        if self.participants.is_some() && self.uncertain_since.is_some() {
+            return Err(InvalidStateError::with_message(
+                "unable to build, `uncertain_since` may only be set for a participant".into(),
+            ));
+        }
+
+        Ok(TwoPhaseCommitContext {
+            alarm: self.alarm,
+            coordinator,
+            epoch,
+            last_commit_epoch: self.last_commit_epoch,
+            participants: self.participants,
+            participant_processes: self.participant_processes,
+            state,
+            this_process,
+            trace_id: None,
+            uncertain_since: self.uncertain_since,
+        })
+    }
+}
+
+impl<P, T> Default for TwoPhaseCommitContextBuilder<P, T>
+where
+    P: Process,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, SystemTime};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestProcess {
+        id: u64,
+    }
+
+    impl Process for TestProcess {}
+
+    fn processes() -> (TestProcess, TestProcess, TestProcess) {
+        (
+            TestProcess { id: 1 },
+            TestProcess { id: 2 },
+            TestProcess { id: 3 },
+        )
+    }
+
+    /// Tests that building a coordinator context without a state defaults to
+    /// `CoordinatorState::WaitingForStart` and converts into a `CoordinatorContext`.
+    #[test]
+    fn test_build_coordinator_context() {
+        let (p1, p2, p3) = processes();
+
+        let context: TwoPhaseCommitContext<TestProcess, SystemTime> =
+            TwoPhaseCommitContextBuilder::new()
+                .with_coordinator(p1)
+                .with_this_process(p1)
+                .with_participants(vec![
+                    Participant::new(p1),
+                    Participant::new(p2),
+                    Participant::new(p3),
+                ])
+                .build()
+                .expect("failed to build context");
+
+        assert_eq!(
+            context.state(),
+            &TwoPhaseCommitState::Coordinator(CoordinatorState::WaitingForStart)
+        );
+
+        let context =
+            CoordinatorContext::try_from(context).expect("failed to convert to coordinator");
+        assert_eq!(context.participants().len(), 3);
+        assert_eq!(context.epoch(), &0);
+    }
+
+    /// Tests that building a participant context without a state defaults to
+    /// `ParticipantState::WaitingForVoteRequest` and converts into a `ParticipantContext`.
+    #[test]
+    fn test_build_participant_context() {
+        let (p1, p2, p3) = processes();
+
+        let context: TwoPhaseCommitContext<TestProcess, SystemTime> =
+            TwoPhaseCommitContextBuilder::new()
+                .with_coordinator(p1)
+                .with_this_process(p2)
+                .with_participant_processes(vec![p2, p3])
+                .build()
+                .expect("failed to build context");
+
+        assert_eq!(
+            context.state(),
+            &TwoPhaseCommitState::Participant(ParticipantState::WaitingForVoteRequest)
+        );
+        assert!(CoordinatorContext::try_from(context.clone()).is_err());
+        assert!(ParticipantContext::try_from(context).is_ok());
+    }
+
+    /// Tests that setting both `participants` and `participant_processes` is an error.
+    #[test]
+    fn test_build_both_participant_fields() {
+        let (p1, p2, _) = processes();
+
+        let result: Result<TwoPhaseCommitContext<TestProcess, SystemTime>, _> =
+            TwoPhaseCommitContextBuilder::new()
+                .with_coordinator(p1)
+                .with_this_process(p1)
+                .with_participants(vec![Participant::new(p2)])
+                .with_participant_processes(vec![p2])
+                .build();
+
+        assert!(result.is_err());
+    }
+
+    /// Tests that a state which does not match the role is an error.
+    #[test]
+    fn test_build_mismatched_state() {
+        let (p1, p2, _) = processes();
+
+        let result: Result<TwoPhaseCommitContext<TestProcess, SystemTime>, _> =
+            TwoPhaseCommitContextBuilder::new()
+                .with_coordinator(p1)
+                .with_this_process(p1)
+                .with_participants(vec![Participant::new(p2)])
+                .with_state(ParticipantState::WaitingForVoteRequest.into())
+                .build();
+
+        assert!(result.is_err());
+    }
+
+    /// Tests that a coordinator context in which `this_process` is not the coordinator is an
+    /// error.
+    #[test]
+    fn test_build_coordinator_not_this_process() {
+        let (p1, p2, p3) = processes();
+
+        let err = TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+            .with_coordinator(p1)
+            .with_this_process(p2)
+            .with_participants(vec![Participant::new(p2), Participant::new(p3)])
+            .build()
+            .expect_err("built a coordinator context for another process");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, `this_process` must be the coordinator"
+        );
+    }
+
+    /// Tests that a participant context may be built for any of the participant processes,
+    /// whether or not it is the coordinator, but not for a process which is not a participant.
+    #[test]
+    fn test_build_participant_this_process() {
+        let (p1, p2, p3) = processes();
+
+        for this_process in [p1, p2] {
+            TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+                .with_coordinator(p1)
+                .with_this_process(this_process)
+                .with_participant_processes(vec![p1, p2])
+                .build()
+                .expect("failed to build context");
+        }
+
+        let err = TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+            .with_coordinator(p1)
+            .with_this_process(p3)
+            .with_participant_processes(vec![p1, p2])
+            .build()
+            .expect_err("built a participant context for a non-participant");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, `this_process` must be one of `participant_processes`"
+        );
+    }
+
+    /// Tests that a context cannot be built for a dangling process, which is neither the
+    /// coordinator nor one of the participants, in either role.
+    #[test]
+    fn test_build_dangling_this_process() {
+        let (p1, p2, p3) = processes();
+        let dangling = TestProcess { id: 4 };
+
+        assert!(
+            TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+                .with_coordinator(p1)
+                .with_this_process(dangling)
+                .with_participants(vec![Participant::new(p2), Participant::new(p3)])
+                .build()
+                .is_err()
+        );
+
+        assert!(
+            TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+                .with_coordinator(p1)
+                .with_this_process(dangling)
+                .with_participant_processes(vec![p1, p2, p3])
+                .build()
+                .is_err()
+        );
+
+        TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+            .with_coordinator(p1)
+            .with_this_process(p1)
+            .with_participants(vec![Participant::new(p1), Participant::new(p2)])
+            .build()
+            .expect("failed to build coordinator context");
+    }
+
+    /// Tests that by default a coordinator context requires the coordinator to be one of the
+    /// participants, and that the requirement can be relaxed.
+    #[test]
+    fn test_build_coordinator_in_participants() {
+        let (p1, p2, p3) = processes();
+        let builder = || {
+            TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+                .with_coordinator(p1)
+                .with_this_process(p1)
+                .with_participants(vec![Participant::new(p2), Participant::new(p3)])
+        };
+
+        let err = builder()
+            .build()
+            .expect_err("built a coordinator context without the coordinator as a participant");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, `coordinator` must be one of the participants"
+        );
+
+        builder()
+            .with_require_coordinator_in_participants(false)
+            .build()
+            .expect("failed to build relaxed coordinator context");
+    }
+
+    /// Tests that by default a participant context does not require the coordinator to be one of
+    /// the participant processes, but can be made to.
+    #[test]
+    fn test_build_participant_coordinator_in_participants() {
+        let (p1, p2, p3) = processes();
+        let builder = || {
+            TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+                .with_coordinator(p1)
+                .with_this_process(p2)
+                .with_participant_processes(vec![p2, p3])
+        };
+
+        builder()
+            .build()
+            .expect("failed to build participant context");
+
+        assert!(builder()
+            .with_require_coordinator_in_participants(true)
+            .build()
+            .is_err());
+    }
+
+    /// Tests that a context whose last committed epoch is after its current epoch, which only
+    /// corrupted recovery state can produce, cannot be built, while an earlier one can.
+    #[test]
+    fn test_build_last_commit_epoch_after_epoch() {
+        let (p1, p2, _) = processes();
+        let builder = |epoch, last_commit_epoch| {
+            TwoPhaseCommitContextBuilder::<TestProcess, SystemTime>::new()
+                .with_coordinator(p1)
+                .with_this_process(p2)
+                .with_participant_processes(vec![p2])
+                .with_epoch(epoch)
+                .with_last_commit_epoch(last_commit_epoch)
+                .build()
+        };
+
+        let err = builder(1, 2).expect_err("built a context with a future last commit epoch");
+        assert_eq!(
+            err.to_string(),
+            "unable to build, `last_commit_epoch` 2 is after `epoch` 1"
+        );
+
+        let context = builder(2, 1).expect("failed to build context");
+        assert_eq!(context.epoch(), &2);
+        assert_eq!(context.last_commit_epoch(), &Some(1));
+    }
+
+    /// Tests that the durable snapshot of a context omits the alarm but includes the epoch, the
+    /// state and the time the participant became uncertain, and that a context restored from it
+    /// has no alarm.
+    #[test]
+    fn test_durable_snapshot() {
+        let (p1, p2, p3) = processes();
+
+        let context: TwoPhaseCommitContext<TestProcess, SystemTime> =
+            TwoPhaseCommitContextBuilder::new()
+                .with_alarm(SystemTime::now() + Duration::from_secs(10))
+                .with_coordinator(p1)
+                .with_epoch(3)
+                .with_last_commit_epoch(2)
+                .with_this_process(p2)
+                .with_participant_processes(vec![p2, p3])
+                .with_state(ParticipantState::Voted { vote: true }.into())
+                .with_uncertain_since(SystemTime::UNIX_EPOCH)
+                .build()
+                .expect("failed to build context");
+
+        let snapshot = context.durable_snapshot();
+        assert_eq!(snapshot.epoch(), &3);
+        assert_eq!(snapshot.last_commit_epoch(), &Some(2));
+        assert_eq!(
+            snapshot.state(),
+            &TwoPhaseCommitState::Participant(ParticipantState::Voted { vote: true })
+        );
+        assert_eq!(snapshot.uncertain_since(), &Some(SystemTime::UNIX_EPOCH));
+
+        let restored: TwoPhaseCommitContext<TestProcess, SystemTime> = snapshot.into();
+        assert!(restored.alarm().is_none());
+        assert_eq!(restored.epoch(), context.epoch());
+        assert_eq!(restored.state(), context.state());
+        assert_eq!(restored.uncertain_since(), context.uncertain_since());
+    }
+}